@@ -0,0 +1,1158 @@
+//! Encoding and decoding of CBOR data items.
+//!
+//! Only the subset of CBOR that OC-Wasm uses for method call parameters and results is supported,
+//! without an allocator: a [`Writer`] encodes into a caller-provided buffer and a [`Reader`]
+//! decodes from a borrowed slice.
+//!
+//! # Examples
+//! Encoding the parameters of a `read` call on descriptor 3 and decoding them again:
+//! ```
+//! use oc_wasm_bios::cbor::{self, MajorType};
+//!
+//! let mut buffer = [0_u8; 16];
+//! let mut writer = cbor::Writer::new(&mut buffer);
+//! writer.write_array_header(3).unwrap();
+//! writer.write_string("read").unwrap();
+//! writer.write_tag(39).unwrap();
+//! writer.write_uint(3).unwrap();
+//! writer.write_int(-2048).unwrap();
+//! let len = writer.position();
+//! assert_eq!(
+//!     buffer[..len],
+//!     [0x83, 0x64, b'r', b'e', b'a', b'd', 0xD8, 0x27, 0x03, 0x39, 0x07, 0xFF]
+//! );
+//!
+//! let mut reader = cbor::Reader::new(&buffer[..len]);
+//! reader.expect_array(3).unwrap();
+//! assert_eq!(reader.read_header(), Ok((MajorType::String, 4)));
+//! assert_eq!(reader.read_payload(4), Ok(&b"read"[..]));
+//! assert_eq!(reader.read_descriptor(), Ok(3));
+//! let (value, rest) = cbor::read_int(reader.remaining()).unwrap();
+//! assert_eq!(value, -2048);
+//! assert!(rest.is_empty());
+//! ```
+//!
+//! Decoding a call result and skipping the parts that are not needed:
+//! ```
+//! use oc_wasm_bios::cbor::{self, MajorType};
+//! use oc_wasm_safe::error::Error;
+//!
+//! // [h'0061736D', null, 1.5, {"eof": true}]
+//! let result = [
+//!     0x84, 0x44, 0x00, 0x61, 0x73, 0x6D, 0xF6, 0xF9, 0x3E, 0x00, 0xA1, 0x63, b'e', b'o', b'f',
+//!     0xF5,
+//! ];
+//! let mut reader = cbor::Reader::new(&result);
+//! assert_eq!(reader.read_header(), Ok((MajorType::Array, 4)));
+//! assert_eq!(reader.read_bytes(), Ok(&b"\0asm"[..]));
+//! assert_eq!(reader.read_special(), Ok(22));
+//! let (value, rest) = cbor::read_float(reader.remaining()).unwrap();
+//! assert_eq!(value, 1.5);
+//! let mut reader = cbor::Reader::new(rest);
+//! reader.skip().unwrap();
+//! assert_eq!(reader.read_header(), Err(Error::BufferTooShort));
+//!
+//! // Truncated input is malformed, not merely short.
+//! assert_eq!(cbor::skip_item(&[0x65, b'a']), Err(Error::CborDecode));
+//! ```
+
+use oc_wasm_safe::{descriptor, error};
+
+/// The CBOR major types.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum MajorType {
+	/// The data item is an unsigned integer whose value is equal to the count. There is no
+	/// payload.
+	UnsignedInteger,
+
+	/// The data item is a negative integer whose value is −1−count. There is no payload.
+	NegativeInteger,
+
+	/// The data item is a byte array. The count is the number of bytes, and they are stored in the
+	/// payload.
+	Bytes,
+
+	/// The data item is a string. The count is the number of bytes in the UTF-8 encoding, and that
+	/// encoding is stored in the payload.
+	String,
+
+	/// The data item is an array of data items. The count is the number of items in the array, and
+	/// they are stored in the payload.
+	Array,
+
+	/// The data item is an array of key/value pairs of data items. The count is the number of
+	/// pairs in the array, and they are stored in the payload.
+	Map,
+
+	/// The data item is a semantic tag. The count is the identity of the tag. The tagged item is
+	/// stored in the payload.
+	Tag,
+
+	/// The data item is a special value. The count is the value of the data item. There is no
+	/// payload.
+	Special,
+
+	/// The data item is a floating-point number. The count is the value of the data item. There is
+	/// no payload.
+	Float,
+
+	/// The data item is the break code that terminates an indefinite-length item. The count is
+	/// zero. There is no payload.
+	Break,
+}
+
+/// Reads a CBOR data item header from a byte slice.
+///
+/// The `slice` parameter is the byte slice to read from. On success, the major type, raw count
+/// value (prior to interpretation according to major type), and a slice containing the rest of the
+/// input slice starting immediately following the header (i.e. at the payload, if any, otherwise
+/// at the next date item) are returned.
+///
+/// # Errors
+/// * [`BufferTooShort`](error::Error::BufferTooShort) is returned if `slice` is empty.
+/// * [`CborDecode`](error::Error::CborDecode) is returned if `slice` is nonempty but does not
+///   contain a whole header, or if the header is invalid.
+pub fn decode_header(slice: &[u8]) -> error::Result<(MajorType, u64, &[u8])> {
+	// Grab the first byte.
+	let first_byte = slice.first().ok_or(error::Error::BufferTooShort)?;
+	let slice = &slice[1..];
+
+	// Decode the major type from the upper three bits.
+	let major_type = match first_byte >> 5 {
+		0 => MajorType::UnsignedInteger,
+		1 => MajorType::NegativeInteger,
+		2 => MajorType::Bytes,
+		3 => MajorType::String,
+		4 => MajorType::Array,
+		5 => MajorType::Map,
+		6 => MajorType::Tag,
+		7 => match first_byte & 31 {
+			25..=27 => MajorType::Float,
+			31 => return Ok((MajorType::Break, 0, slice)),
+			_ => MajorType::Special,
+		},
+		_ => unreachable!(), // Impossible; u8>>5 can only be 0..=7.
+	};
+
+	// Decode the count.
+	let count_bits = first_byte & 31;
+	let (count, slice): (u64, &[u8]) = if count_bits <= 23 {
+		(count_bits.into(), slice)
+	} else {
+		let count_bytes = match count_bits {
+			24 => 1,
+			25 => 2,
+			26 => 4,
+			27 => 8,
+			_ => return Err(error::Error::CborDecode),
+		};
+		if slice.len() < count_bytes {
+			return Err(error::Error::CborDecode);
+		}
+		let (count_bytes, slice) = slice.split_at(count_bytes);
+		let mut count_value: u64 = 0;
+		for &byte in count_bytes {
+			count_value = (count_value << 8) | Into::<u64>::into(byte);
+		}
+		(count_value, slice)
+	};
+
+	// Return everything.
+	Ok((major_type, count, slice))
+}
+
+/// Skips a whole CBOR data item at the start of a byte slice.
+///
+/// The `slice` parameter is the byte slice to read from. The data item’s payload, including any
+/// data items contained in an array, map, or tag, is skipped along with its header. An
+/// indefinite-length item is skipped up to and including the break that ends it. On success, a
+/// slice containing the rest of the input slice starting immediately following the data item is
+/// returned.
+///
+/// # Errors
+/// * [`BufferTooShort`](error::Error::BufferTooShort) is returned if `slice` is empty.
+/// * [`CborDecode`](error::Error::CborDecode) is returned if `slice` is nonempty but does not
+///   contain a whole data item, or if the data item is invalid or is a break.
+pub fn skip_item(slice: &[u8]) -> error::Result<&[u8]> {
+	// An indefinite-length byte array, string, array, or map (additional information 31) holds
+	// data items up to a break instead of a count.
+	if let Some((&(0x5F | 0x7F | 0x9F | 0xBF), mut slice)) = slice.split_first() {
+		loop {
+			if let Ok((MajorType::Break, _, rest)) = decode_header(slice) {
+				return Ok(rest);
+			}
+			slice = skip_item(slice).map_err(|_| error::Error::CborDecode)?;
+		}
+	}
+	let (major_type, count, mut slice) = decode_header(slice)?;
+	// The number of contained data items to skip.
+	let items = match major_type {
+		MajorType::Bytes | MajorType::String => {
+			return usize::try_from(count)
+				.ok()
+				.and_then(|len| slice.get(len..))
+				.ok_or(error::Error::CborDecode);
+		}
+		MajorType::Array => count,
+		MajorType::Map => count.saturating_mul(2),
+		MajorType::Tag => 1,
+		MajorType::Break => return Err(error::Error::CborDecode),
+		_ => 0,
+	};
+	// Each contained data item takes at least one byte, so this stops soon even if the count is
+	// huge.
+	for _ in 0..items {
+		slice = skip_item(slice).map_err(|_| error::Error::CborDecode)?;
+	}
+	Ok(slice)
+}
+
+/// Reads a CBOR integer, which may be unsigned or negative, from the start of a byte slice.
+///
+/// The `slice` parameter is the byte slice to read from. On success, the value of the integer and
+/// a slice containing the rest of the input slice starting immediately following the integer are
+/// returned.
+///
+/// # Errors
+/// * [`BufferTooShort`](error::Error::BufferTooShort) is returned if `slice` is empty.
+/// * [`CborDecode`](error::Error::CborDecode) is returned if `slice` is nonempty but does not
+///   start with a whole integer, or if the integer does not fit in an `i64`.
+pub fn read_int(slice: &[u8]) -> error::Result<(i64, &[u8])> {
+	let (major_type, count, slice) = decode_header(slice)?;
+	// A count that fits in an i64 is at most i64::MAX, so −1−count is at least i64::MIN.
+	let count = i64::try_from(count).map_err(|_| error::Error::CborDecode)?;
+	match major_type {
+		MajorType::UnsignedInteger => Ok((count, slice)),
+		MajorType::NegativeInteger => Ok((-1 - count, slice)),
+		_ => Err(error::Error::CborDecode),
+	}
+}
+
+/// Reads a CBOR floating-point number, which may be half, single, or double precision, from the
+/// start of a byte slice.
+///
+/// The `slice` parameter is the byte slice to read from. On success, the value of the number,
+/// widened to an `f64` (which every half- and single-precision value fits in exactly), and a slice
+/// containing the rest of the input slice starting immediately following the number are returned.
+///
+/// # Errors
+/// * [`BufferTooShort`](error::Error::BufferTooShort) is returned if `slice` is empty.
+/// * [`CborDecode`](error::Error::CborDecode) is returned if `slice` is nonempty but does not
+///   start with a whole floating-point number.
+pub fn read_float(slice: &[u8]) -> error::Result<(f64, &[u8])> {
+	let first_byte = slice.first().copied();
+	let (major_type, count, slice) = decode_header(slice)?;
+	if major_type != MajorType::Float {
+		return Err(error::Error::CborDecode);
+	}
+	// Casts from u64 are sound because the header holds only as many bits as the width.
+	#[allow(clippy::cast_possible_truncation)]
+	let value = match first_byte.map(|byte| byte & 31) {
+		Some(25) => half_to_f64(count as u16),
+		Some(26) => f64::from(f32::from_bits(count as u32)),
+		_ => f64::from_bits(count),
+	};
+	Ok((value, slice))
+}
+
+/// Converts the bits of a half-precision floating-point number to an `f64`.
+fn half_to_f64(half: u16) -> f64 {
+	let sign = u64::from(half & 0x8000) << 48;
+	let exponent = u64::from((half >> 10) & 0x1F);
+	let mantissa = half & 0x3FF;
+	let bits = match exponent {
+		// Zero or subnormal: the value is the mantissa times 2^−24, which is exact in an f64.
+		0 => (f64::from(mantissa) / 16_777_216.0).to_bits(),
+		// Infinity or NaN, keeping any NaN payload.
+		31 => (0x7FF << 52) | (u64::from(mantissa) << 42),
+		// Normal: rebias the exponent from 15 to 1023.
+		_ => ((exponent + 1008) << 52) | (u64::from(mantissa) << 42),
+	};
+	f64::from_bits(sign | bits)
+}
+
+/// A reader that decodes CBOR data items from a byte slice.
+///
+/// Each method decodes one data item (or, for arrays and tags, the header that precedes the
+/// contained data items) from the start of the remaining input and advances past it. Each fails
+/// with [`CborDecode`](error::Error::CborDecode) if the input does not hold the expected item,
+/// after which the reader should not be used further.
+#[derive(Debug)]
+pub struct Reader<'a> {
+	/// The input that has not been decoded yet.
+	remaining: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+	/// Creates a reader that reads from the start of `input`.
+	#[must_use]
+	pub fn new(input: &'a [u8]) -> Self {
+		Self { remaining: input }
+	}
+
+	/// Returns the input that has not been decoded yet.
+	#[must_use]
+	pub fn remaining(&self) -> &'a [u8] {
+		self.remaining
+	}
+
+	/// Reads a data item header, returning its major type and count.
+	///
+	/// # Errors
+	/// * [`BufferTooShort`](error::Error::BufferTooShort) is returned if there is no input left.
+	/// * [`CborDecode`](error::Error::CborDecode) is returned if the input does not start with a
+	///   whole, valid header.
+	pub fn read_header(&mut self) -> error::Result<(MajorType, u64)> {
+		let (major, count, rest) = decode_header(self.remaining)?;
+		self.remaining = rest;
+		Ok((major, count))
+	}
+
+	/// Reads `len` bytes of payload.
+	///
+	/// # Errors
+	/// * [`CborDecode`](error::Error::CborDecode) is returned if there are fewer than `len` bytes
+	///   left.
+	pub fn read_payload(&mut self, len: u64) -> error::Result<&'a [u8]> {
+		if len > self.remaining.len() as u64 {
+			return Err(error::Error::CborDecode);
+		}
+		// Cast from u64 to usize is sound because len ≤ remaining.len().
+		#[allow(clippy::cast_possible_truncation)]
+		let (payload, rest) = self.remaining.split_at(len as usize);
+		self.remaining = rest;
+		Ok(payload)
+	}
+
+	/// Skips a whole data item, including any data items it contains.
+	///
+	/// # Errors
+	/// * [`BufferTooShort`](error::Error::BufferTooShort) is returned if there is no input left.
+	/// * [`CborDecode`](error::Error::CborDecode) is returned if the input does not start with a
+	///   whole, valid data item.
+	pub fn skip(&mut self) -> error::Result<()> {
+		self.remaining = skip_item(self.remaining)?;
+		Ok(())
+	}
+
+	/// Consumes the next byte of input if it is `byte`.
+	///
+	/// This is useful for raw markers, such as the start of an indefinite-length item or a break.
+	/// `true` is returned if the byte was consumed, or `false` if the input is empty or starts with
+	/// something else.
+	pub fn consume_byte(&mut self, byte: u8) -> bool {
+		if let Some((&first, rest)) = self.remaining.split_first() {
+			if first == byte {
+				self.remaining = rest;
+				return true;
+			}
+		}
+		false
+	}
+
+	/// Reads the header of an array of exactly `len` data items.
+	///
+	/// The data items must be read afterwards.
+	///
+	/// # Errors
+	/// * [`CborDecode`](error::Error::CborDecode) is returned if the input does not start with
+	///   such a header.
+	pub fn expect_array(&mut self, len: u64) -> error::Result<()> {
+		self.expect(MajorType::Array, len)
+	}
+
+	/// Reads a semantic tag with identity `id`.
+	///
+	/// The tagged data item must be read afterwards.
+	///
+	/// # Errors
+	/// * [`CborDecode`](error::Error::CborDecode) is returned if the input does not start with
+	///   such a tag.
+	pub fn expect_tag(&mut self, id: u64) -> error::Result<()> {
+		self.expect(MajorType::Tag, id)
+	}
+
+	/// Reads an unsigned integer.
+	///
+	/// # Errors
+	/// * [`CborDecode`](error::Error::CborDecode) is returned if the input does not start with an
+	///   unsigned integer.
+	pub fn read_uint(&mut self) -> error::Result<u64> {
+		match self.read_header()? {
+			(MajorType::UnsignedInteger, value) => Ok(value),
+			_ => Err(error::Error::CborDecode),
+		}
+	}
+
+	/// Reads a byte array.
+	///
+	/// # Errors
+	/// * [`CborDecode`](error::Error::CborDecode) is returned if the input does not start with a
+	///   whole byte array.
+	pub fn read_bytes(&mut self) -> error::Result<&'a [u8]> {
+		match self.read_header()? {
+			(MajorType::Bytes, len) => self.read_payload(len),
+			_ => Err(error::Error::CborDecode),
+		}
+	}
+
+	/// Reads a special value, returning its value.
+	///
+	/// # Errors
+	/// * [`CborDecode`](error::Error::CborDecode) is returned if the input does not start with a
+	///   special value.
+	pub fn read_special(&mut self) -> error::Result<u64> {
+		match self.read_header()? {
+			(MajorType::Special, value) => Ok(value),
+			_ => Err(error::Error::CborDecode),
+		}
+	}
+
+	/// Reads an opaque value descriptor, encoded as an Identifier tagged integer, returning its raw
+	/// value.
+	///
+	/// OC-Wasm only hands over a descriptor this way when the receiver becomes its owner, so the
+	/// caller should take ownership of it at once.
+	///
+	/// # Errors
+	/// * [`CborDecode`](error::Error::CborDecode) is returned if the input does not start with
+	///   such an integer.
+	pub fn read_descriptor(&mut self) -> error::Result<u32> {
+		self.expect_tag(IDENTIFIER_TAG)?;
+		// Cast from u64 to u32 is sound because descriptors are always small.
+		#[allow(clippy::cast_possible_truncation)]
+		Ok(self.read_uint()? as u32)
+	}
+
+	/// Reads a header with a particular major type and count.
+	fn expect(&mut self, major: MajorType, count: u64) -> error::Result<()> {
+		if self.read_header()? == (major, count) {
+			Ok(())
+		} else {
+			Err(error::Error::CborDecode)
+		}
+	}
+}
+
+/// The semantic tag that OC-Wasm places on an integer to mark it as an opaque value descriptor.
+const IDENTIFIER_TAG: u64 = 39;
+
+/// The major type of an unsigned integer.
+const MAJOR_UNSIGNED_INTEGER: u8 = 0;
+
+/// The major type of a negative integer.
+const MAJOR_NEGATIVE_INTEGER: u8 = 1;
+
+/// The major type of a byte array.
+const MAJOR_BYTES: u8 = 2;
+
+/// The major type of a string.
+const MAJOR_STRING: u8 = 3;
+
+/// The major type of an array.
+const MAJOR_ARRAY: u8 = 4;
+
+/// The major type of a map.
+const MAJOR_MAP: u8 = 5;
+
+/// The major type of a semantic tag.
+const MAJOR_TAG: u8 = 6;
+
+/// A writer that encodes CBOR data items into a byte slice.
+///
+/// Each method appends one data item (or, for arrays and tags, the header that precedes the
+/// contained data items) at the current position and advances past it. Counts are always encoded
+/// in the shortest possible form.
+#[derive(Debug)]
+pub struct Writer<'a> {
+	/// The buffer to write into.
+	buffer: &'a mut [u8],
+
+	/// The number of bytes written so far.
+	position: usize,
+}
+
+// The writer’s methods are marked #[inline] because they are in a separate crate from the BIOS,
+// and rustc would otherwise not inline them into their callers, costing over a hundred bytes of
+// EEPROM space.
+impl<'a> Writer<'a> {
+	/// Creates a writer that writes to the start of `buffer`.
+	#[inline]
+	pub fn new(buffer: &'a mut [u8]) -> Self {
+		Self {
+			buffer,
+			position: 0,
+		}
+	}
+
+	/// Writes the header of an array of `len` data items.
+	///
+	/// The data items must be written afterwards.
+	///
+	/// # Errors
+	/// * [`BufferTooShort`](error::Error::BufferTooShort) is returned if the buffer is full.
+	#[inline]
+	pub fn write_array_header(&mut self, len: usize) -> error::Result<()> {
+		self.write_header(MAJOR_ARRAY, len as u64)
+	}
+
+	/// Writes the header of a map of `len` key/value pairs.
+	///
+	/// The keys and values must be written afterwards, alternating.
+	///
+	/// # Errors
+	/// * [`BufferTooShort`](error::Error::BufferTooShort) is returned if the buffer is full.
+	#[inline]
+	pub fn write_map_header(&mut self, len: usize) -> error::Result<()> {
+		self.write_header(MAJOR_MAP, len as u64)
+	}
+
+	/// Writes a string.
+	///
+	/// # Errors
+	/// * [`BufferTooShort`](error::Error::BufferTooShort) is returned if the buffer is full.
+	#[inline]
+	pub fn write_string(&mut self, value: &str) -> error::Result<()> {
+		self.write_header(MAJOR_STRING, value.len() as u64)?;
+		self.write_raw(value.as_bytes())
+	}
+
+	/// Writes a byte array.
+	///
+	/// # Errors
+	/// * [`BufferTooShort`](error::Error::BufferTooShort) is returned if the buffer is full.
+	#[inline]
+	pub fn write_bytes(&mut self, value: &[u8]) -> error::Result<()> {
+		self.write_header(MAJOR_BYTES, value.len() as u64)?;
+		self.write_raw(value)
+	}
+
+	/// Writes an unsigned integer.
+	///
+	/// # Errors
+	/// * [`BufferTooShort`](error::Error::BufferTooShort) is returned if the buffer is full.
+	#[inline]
+	pub fn write_uint(&mut self, value: u64) -> error::Result<()> {
+		self.write_header(MAJOR_UNSIGNED_INTEGER, value)
+	}
+
+	/// Writes a signed integer.
+	///
+	/// # Errors
+	/// * [`BufferTooShort`](error::Error::BufferTooShort) is returned if the buffer is full.
+	#[inline]
+	pub fn write_int(&mut self, value: i64) -> error::Result<()> {
+		if value < 0 {
+			// A negative integer is encoded as −1−value, which is |value|−1.
+			self.write_header(MAJOR_NEGATIVE_INTEGER, value.unsigned_abs() - 1)
+		} else {
+			self.write_header(MAJOR_UNSIGNED_INTEGER, value.unsigned_abs())
+		}
+	}
+
+	/// Writes a semantic tag.
+	///
+	/// The tagged data item must be written afterwards.
+	///
+	/// # Errors
+	/// * [`BufferTooShort`](error::Error::BufferTooShort) is returned if the buffer is full.
+	#[inline]
+	pub fn write_tag(&mut self, tag: u64) -> error::Result<()> {
+		self.write_header(MAJOR_TAG, tag)
+	}
+
+	/// Writes an opaque value descriptor, encoded as an Identifier tagged integer.
+	///
+	/// # Errors
+	/// * [`BufferTooShort`](error::Error::BufferTooShort) is returned if the buffer is full.
+	#[inline]
+	pub fn write_descriptor(&mut self, descriptor: &impl descriptor::AsRaw) -> error::Result<()> {
+		self.write_tag(IDENTIFIER_TAG)?;
+		self.write_uint(descriptor.as_raw().into())
+	}
+
+	/// Writes a data item header with a major type and count.
+	#[inline]
+	fn write_header(&mut self, major: u8, count: u64) -> error::Result<()> {
+		let (count_bits, count_bytes) = if count <= 23 {
+			// Cast is sound because count ≤ 23.
+			#[allow(clippy::cast_possible_truncation)]
+			let count_bits = count as u8;
+			(count_bits, 0)
+		} else if count <= 0xFF {
+			(24, 1)
+		} else if count <= 0xFFFF {
+			(25, 2)
+		} else if count <= 0xFFFF_FFFF {
+			(26, 4)
+		} else {
+			(27, 8)
+		};
+		self.write_raw(&[(major << 5) | count_bits])?;
+		for index in (0..count_bytes).rev() {
+			// Cast is sound because only the low 8 bits are wanted.
+			#[allow(clippy::cast_possible_truncation)]
+			self.write_raw(&[(count >> (8 * index)) as u8])?;
+		}
+		Ok(())
+	}
+
+	/// Returns the number of bytes written so far.
+	#[must_use]
+	#[inline]
+	pub fn position(&self) -> usize {
+		self.position
+	}
+
+	/// Writes raw bytes, which must be the encoding of zero or more whole data items.
+	///
+	/// # Errors
+	/// * [`BufferTooShort`](error::Error::BufferTooShort) is returned if the buffer is full.
+	#[inline]
+	pub fn write_raw(&mut self, data: &[u8]) -> error::Result<()> {
+		// Write one byte at a time. Copying the whole slice at once compiles into a call to
+		// memcpy, which is much larger than this loop.
+		for &byte in data {
+			*self
+				.buffer
+				.get_mut(self.position)
+				.ok_or(error::Error::BufferTooShort)? = byte;
+			self.position += 1;
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn read_int_reads_unsigned_integers() {
+		for (data, value) in [
+			(&[0x00][..], 0),
+			(&[0x17], 23),
+			(&[0x18, 0x18], 24),
+			(&[0x18, 0xFF], 255),
+			(&[0x19, 0x01, 0x00], 256),
+			(&[0x1A, 0x00, 0x01, 0x00, 0x00], 65536),
+			(
+				&[0x1B, 0x7F, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF],
+				i64::MAX,
+			),
+		] {
+			assert_eq!(read_int(data), Ok((value, &[][..])));
+		}
+	}
+
+	#[test]
+	fn read_int_reads_negative_integers() {
+		for (data, value) in [
+			(&[0x20][..], -1),
+			(&[0x37], -24),
+			(&[0x38, 0x18], -25),
+			(&[0x38, 0xFF], -256),
+			(&[0x39, 0x01, 0x00], -257),
+			(&[0x3A, 0xFF, 0xFF, 0xFF, 0xFF], -4_294_967_296),
+			(
+				&[0x3B, 0x7F, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF],
+				i64::MIN,
+			),
+		] {
+			assert_eq!(read_int(data), Ok((value, &[][..])));
+		}
+	}
+
+	#[test]
+	fn read_int_returns_rest() {
+		assert_eq!(
+			read_int(&[0x38, 0x63, 0xAA, 0xBB]),
+			Ok((-100, &[0xAA, 0xBB][..]))
+		);
+	}
+
+	#[test]
+	fn read_int_rejects_overflow() {
+		// 2⁶³, 2⁶⁴−1, −1−2⁶³, and −1−(2⁶⁴−1), which is −2⁶⁴.
+		for data in [
+			[0x1B, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+			[0x1B, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF],
+			[0x3B, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+			[0x3B, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF],
+		] {
+			assert_eq!(read_int(&data), Err(error::Error::CborDecode));
+		}
+	}
+
+	#[test]
+	fn read_int_rejects_other_items() {
+		assert_eq!(read_int(&[]), Err(error::Error::BufferTooShort));
+		// A truncated count, a string, a special value, and a float.
+		for data in [
+			&[0x39, 0x01][..],
+			&[0x61, b'1'],
+			&[0xF5],
+			&[0xF9, 0x3C, 0x00],
+		] {
+			assert_eq!(read_int(data), Err(error::Error::CborDecode));
+		}
+	}
+
+	#[test]
+	fn read_float_reads_half_precision() {
+		for (data, value) in [
+			([0xF9, 0x00, 0x00], 0.0),
+			([0xF9, 0x3C, 0x00], 1.0),
+			([0xF9, 0xC4, 0x00], -4.0),
+			([0xF9, 0x7B, 0xFF], 65504.0),
+			// The smallest subnormal and the smallest normal numbers.
+			([0xF9, 0x00, 0x01], 5.960_464_477_539_063e-8),
+			([0xF9, 0x04, 0x00], 6.103_515_625e-5),
+			([0xF9, 0x7C, 0x00], f64::INFINITY),
+			([0xF9, 0xFC, 0x00], f64::NEG_INFINITY),
+		] {
+			assert_eq!(read_float(&data), Ok((value, &[][..])));
+		}
+		// Negative zero keeps its sign.
+		let (value, _) = read_float(&[0xF9, 0x80, 0x00]).unwrap();
+		assert!(value == 0.0 && value.is_sign_negative());
+	}
+
+	#[test]
+	fn read_float_reads_nan() {
+		for data in [
+			&[0xF9, 0x7E, 0x00][..],
+			&[0xFA, 0x7F, 0xC0, 0x00, 0x00],
+			&[0xFB, 0x7F, 0xF8, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+		] {
+			let (value, rest) = read_float(data).unwrap();
+			assert!(value.is_nan());
+			assert!(rest.is_empty());
+		}
+	}
+
+	#[test]
+	fn read_float_reads_single_and_double_precision() {
+		assert_eq!(
+			read_float(&[0xFA, 0x47, 0xC3, 0x50, 0x00]),
+			Ok((100_000.0, &[][..]))
+		);
+		assert_eq!(
+			read_float(&[0xFB, 0x3F, 0xF1, 0x99, 0x99, 0x99, 0x99, 0x99, 0x9A, 0xAA]),
+			Ok((1.1, &[0xAA][..]))
+		);
+	}
+
+	#[test]
+	fn read_float_rejects_other_items() {
+		assert_eq!(read_float(&[]), Err(error::Error::BufferTooShort));
+		// A truncated float, an integer, and a special value.
+		for data in [&[0xFA, 0x47, 0xC3][..], &[0x01], &[0xF5]] {
+			assert_eq!(read_float(data), Err(error::Error::CborDecode));
+		}
+	}
+
+	#[test]
+	fn skip_item_skips_scalars() {
+		// 0, 23, 24 (one-byte count), 1000 (two-byte count), −1, false, and a half-precision 1.0.
+		let data = [
+			0x00, 0x17, 0x18, 0x18, 0x19, 0x03, 0xE8, 0x20, 0xF4, 0xF9, 0x3C, 0x00, 0xAA,
+		];
+		let mut slice = &data[..];
+		for _ in 0..7 {
+			slice = skip_item(slice).unwrap();
+		}
+		assert_eq!(slice, [0xAA]);
+	}
+
+	#[test]
+	fn skip_item_skips_strings() {
+		// The byte array h'0102', the string "abc", and the empty string.
+		let data = [0x42, 0x01, 0x02, 0x63, b'a', b'b', b'c', 0x60, 0xAA];
+		let slice = skip_item(&data).unwrap();
+		assert_eq!(slice, [0x63, b'a', b'b', b'c', 0x60, 0xAA]);
+		let slice = skip_item(slice).unwrap();
+		assert_eq!(slice, [0x60, 0xAA]);
+		assert_eq!(skip_item(slice).unwrap(), [0xAA]);
+	}
+
+	#[test]
+	fn skip_item_skips_nested_items() {
+		// {"a": [1, {"b": h'00'}], "c": 39([2, "d"])}
+		let data = [
+			0xA2, 0x61, b'a', 0x82, 0x01, 0xA1, 0x61, b'b', 0x41, 0x00, 0x61, b'c', 0xD8, 0x27,
+			0x82, 0x02, 0x61, b'd', 0xAA,
+		];
+		assert_eq!(skip_item(&data).unwrap(), [0xAA]);
+	}
+
+	#[test]
+	fn skip_item_rejects_empty_input() {
+		assert_eq!(skip_item(&[]), Err(error::Error::BufferTooShort));
+	}
+
+	#[test]
+	fn skip_item_rejects_truncated_items() {
+		// A string missing its last byte, a two-byte count missing its second byte, an array
+		// missing its second item, a map missing a value, and a tag missing its tagged item.
+		for data in [
+			&[0x63, b'a', b'b'][..],
+			&[0x19, 0x03],
+			&[0x82, 0x01],
+			&[0xA1, 0x61, b'a'],
+			&[0xC1],
+		] {
+			assert_eq!(skip_item(data), Err(error::Error::CborDecode));
+		}
+	}
+
+	#[test]
+	fn skip_item_rejects_breaks_and_bad_counts() {
+		// A break, an array holding a break, and a reserved count encoding.
+		for data in [&[0xFF][..], &[0x81, 0xFF], &[0x1C]] {
+			assert_eq!(skip_item(data), Err(error::Error::CborDecode));
+		}
+	}
+
+	#[test]
+	fn skip_item_rejects_huge_counts_quickly() {
+		// An array claiming 2⁶⁴−1 items, holding only one.
+		let data = [0x9B, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x00];
+		assert_eq!(skip_item(&data), Err(error::Error::CborDecode));
+		// A string claiming 2⁶⁴−1 bytes.
+		let data = [0x7B, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, b'a'];
+		assert_eq!(skip_item(&data), Err(error::Error::CborDecode));
+	}
+
+	/// Runs `write` on a writer with a large buffer and returns what it wrote.
+	fn written(write: impl FnOnce(&mut Writer<'_>) -> error::Result<()>) -> Vec<u8> {
+		let mut buffer = [0_u8; 512];
+		let mut writer = Writer::new(&mut buffer);
+		write(&mut writer).unwrap();
+		let len = writer.position();
+		buffer[..len].to_vec()
+	}
+
+	#[test]
+	fn write_uint_uses_shortest_head() {
+		for (value, expected) in [
+			(0, &[0x00][..]),
+			(23, &[0x17]),
+			(24, &[0x18, 0x18]),
+			(255, &[0x18, 0xFF]),
+			(256, &[0x19, 0x01, 0x00]),
+			(65535, &[0x19, 0xFF, 0xFF]),
+			(65536, &[0x1A, 0x00, 0x01, 0x00, 0x00]),
+			(0xFFFF_FFFF, &[0x1A, 0xFF, 0xFF, 0xFF, 0xFF]),
+			(
+				0x1_0000_0000,
+				&[0x1B, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00],
+			),
+			(
+				u64::MAX,
+				&[0x1B, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF],
+			),
+		] {
+			assert_eq!(written(|w| w.write_uint(value)), expected);
+		}
+	}
+
+	#[test]
+	fn write_int_encodes_both_signs() {
+		for (value, expected) in [
+			(0, &[0x00][..]),
+			(24, &[0x18, 0x18]),
+			(-1, &[0x20]),
+			(-24, &[0x37]),
+			(-25, &[0x38, 0x18]),
+			(-256, &[0x38, 0xFF]),
+			(-257, &[0x39, 0x01, 0x00]),
+			(-65536, &[0x39, 0xFF, 0xFF]),
+			(-65537, &[0x3A, 0x00, 0x01, 0x00, 0x00]),
+			(
+				i64::MIN,
+				&[0x3B, 0x7F, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF],
+			),
+		] {
+			assert_eq!(written(|w| w.write_int(value)), expected);
+		}
+	}
+
+	#[test]
+	fn write_string_writes_head_and_text() {
+		assert_eq!(written(|w| w.write_string("")), [0x60]);
+		assert_eq!(
+			written(|w| w.write_string("open")),
+			[0x64, b'o', b'p', b'e', b'n']
+		);
+		// 23 bytes fit in the initial byte; 24 need a one-byte count.
+		let text = "abcdefghijklmnopqrstuvwx";
+		let mut expected = vec![0x77];
+		expected.extend_from_slice(&text.as_bytes()[..23]);
+		assert_eq!(written(|w| w.write_string(&text[..23])), expected);
+		let mut expected = vec![0x78, 0x18];
+		expected.extend_from_slice(text.as_bytes());
+		assert_eq!(written(|w| w.write_string(text)), expected);
+		// Non-ASCII text is counted in bytes, not characters.
+		assert_eq!(written(|w| w.write_string("é")), [0x62, 0xC3, 0xA9]);
+	}
+
+	#[test]
+	fn write_bytes_writes_head_and_bytes() {
+		assert_eq!(written(|w| w.write_bytes(&[])), [0x40]);
+		assert_eq!(written(|w| w.write_bytes(&[0, 0xFF])), [0x42, 0x00, 0xFF]);
+		let data = [0xAA; 256];
+		let mut expected = vec![0x59, 0x01, 0x00];
+		expected.extend_from_slice(&data);
+		assert_eq!(written(|w| w.write_bytes(&data)), expected);
+	}
+
+	#[test]
+	fn write_array_and_map_headers() {
+		for (len, array, map) in [
+			(0, &[0x80][..], &[0xA0][..]),
+			(23, &[0x97], &[0xB7]),
+			(24, &[0x98, 0x18], &[0xB8, 0x18]),
+			(255, &[0x98, 0xFF], &[0xB8, 0xFF]),
+			(256, &[0x99, 0x01, 0x00], &[0xB9, 0x01, 0x00]),
+			(65535, &[0x99, 0xFF, 0xFF], &[0xB9, 0xFF, 0xFF]),
+			(
+				65536,
+				&[0x9A, 0x00, 0x01, 0x00, 0x00],
+				&[0xBA, 0x00, 0x01, 0x00, 0x00],
+			),
+		] {
+			assert_eq!(written(|w| w.write_array_header(len)), array);
+			assert_eq!(written(|w| w.write_map_header(len)), map);
+		}
+	}
+
+	#[test]
+	fn write_tag_writes_head() {
+		assert_eq!(written(|w| w.write_tag(1)), [0xC1]);
+		assert_eq!(written(|w| w.write_tag(39)), [0xD8, 0x27]);
+		assert_eq!(written(|w| w.write_tag(256)), [0xD9, 0x01, 0x00]);
+	}
+
+	#[test]
+	fn writer_writes_items_in_sequence() {
+		// ["seek", 39(5), "set", 0], as for a seek call.
+		assert_eq!(
+			written(|w| {
+				w.write_array_header(4)?;
+				w.write_string("seek")?;
+				w.write_tag(39)?;
+				w.write_uint(5)?;
+				w.write_string("set")?;
+				w.write_int(0)
+			}),
+			[0x84, 0x64, b's', b'e', b'e', b'k', 0xD8, 0x27, 0x05, 0x63, b's', b'e', b't', 0x00]
+		);
+	}
+
+	#[test]
+	fn writer_rejects_overflow() {
+		let mut buffer = [0_u8; 3];
+		let mut writer = Writer::new(&mut buffer);
+		assert_eq!(writer.write_uint(256), Ok(()));
+		assert_eq!(writer.write_uint(0), Err(error::Error::BufferTooShort));
+		assert_eq!(writer.position(), 3);
+		let mut writer = Writer::new(&mut buffer);
+		assert_eq!(
+			writer.write_string("abc"),
+			Err(error::Error::BufferTooShort)
+		);
+	}
+
+	#[test]
+	fn reader_decodes_open_results() {
+		// [39(3)], as filesystem.open returns on success.
+		let mut reader = Reader::new(&[0x81, 0xD8, 0x27, 0x03]);
+		assert_eq!(reader.expect_array(1), Ok(()));
+		assert_eq!(reader.read_descriptor(), Ok(3));
+		assert!(reader.remaining().is_empty());
+
+		// [null, "/init.wasm"], as filesystem.open returns for a missing file.
+		let result = [
+			0x82, 0xF6, 0x6A, b'/', b'i', b'n', b'i', b't', b'.', b'w', b'a', b's', b'm',
+		];
+		let mut reader = Reader::new(&result);
+		assert_eq!(reader.read_header(), Ok((MajorType::Array, 2)));
+		assert!(!reader.consume_byte(0xF4));
+		assert!(reader.consume_byte(0xF6));
+		assert_eq!(reader.read_header(), Ok((MajorType::String, 10)));
+		assert_eq!(reader.read_payload(10), Ok(&b"/init.wasm"[..]));
+		assert!(!reader.consume_byte(0xF6));
+	}
+
+	#[test]
+	fn reader_decodes_read_results() {
+		// [h'0061736D01000000'], as filesystem.read returns with data.
+		let result = [0x81, 0x48, 0x00, 0x61, 0x73, 0x6D, 0x01, 0x00, 0x00, 0x00];
+		let mut reader = Reader::new(&result);
+		assert_eq!(reader.expect_array(1), Ok(()));
+		assert_eq!(reader.read_bytes(), Ok(&b"\0asm\x01\0\0\0"[..]));
+		assert!(reader.remaining().is_empty());
+
+		// [null], as filesystem.read returns at end of file.
+		let mut reader = Reader::new(&[0x81, 0xF6]);
+		assert_eq!(reader.expect_array(1), Ok(()));
+		assert_eq!(reader.read_special(), Ok(22));
+	}
+
+	#[test]
+	fn reader_reads_what_writer_wrote() {
+		let values = [0, 23, 24, 255, 256, 65535, 65536, u64::MAX];
+		let mut buffer = [0_u8; 128];
+		let mut writer = Writer::new(&mut buffer);
+		writer.write_array_header(values.len() + 3).unwrap();
+		for value in values {
+			writer.write_uint(value).unwrap();
+		}
+		writer.write_bytes(&[1, 2, 3]).unwrap();
+		writer.write_tag(1000).unwrap();
+		writer.write_map_header(0).unwrap();
+		writer.write_uint(7).unwrap();
+		let len = writer.position();
+
+		let mut reader = Reader::new(&buffer[..len]);
+		assert_eq!(reader.read_header(), Ok((MajorType::Array, 11)));
+		for value in values {
+			assert_eq!(reader.read_uint(), Ok(value));
+		}
+		assert_eq!(reader.read_bytes(), Ok(&[1, 2, 3][..]));
+		assert_eq!(reader.expect_tag(1000), Ok(()));
+		assert_eq!(reader.read_header(), Ok((MajorType::Map, 0)));
+		reader.skip().unwrap();
+		assert!(reader.remaining().is_empty());
+	}
+
+	#[test]
+	fn reader_rejects_truncated_input() {
+		assert_eq!(
+			Reader::new(&[]).read_header(),
+			Err(error::Error::BufferTooShort)
+		);
+		// A two-byte count missing its second byte.
+		assert_eq!(
+			Reader::new(&[0x19, 0x01]).read_header(),
+			Err(error::Error::CborDecode)
+		);
+		assert_eq!(
+			Reader::new(&[0x19, 0x01]).read_uint(),
+			Err(error::Error::CborDecode)
+		);
+		// A byte array missing its last byte.
+		assert_eq!(
+			Reader::new(&[0x43, 0x01, 0x02]).read_bytes(),
+			Err(error::Error::CborDecode)
+		);
+		// A payload longer than the input leaves the input alone.
+		let mut reader = Reader::new(&[0x01, 0x02]);
+		assert_eq!(reader.read_payload(3), Err(error::Error::CborDecode));
+		assert_eq!(reader.remaining(), [0x01, 0x02]);
+		// A tag with no tagged item.
+		let mut reader = Reader::new(&[0xD8, 0x27]);
+		assert_eq!(reader.read_descriptor(), Err(error::Error::BufferTooShort));
+		assert!(!reader.consume_byte(0xF6));
+	}
+
+	#[test]
+	fn reader_rejects_unexpected_items() {
+		// An array of the wrong length, and something other than an array.
+		assert_eq!(
+			Reader::new(&[0x82, 0x01, 0x02]).expect_array(1),
+			Err(error::Error::CborDecode)
+		);
+		assert_eq!(
+			Reader::new(&[0xA1, 0x01, 0x02]).expect_array(1),
+			Err(error::Error::CborDecode)
+		);
+		// The wrong tag, and an untagged integer where a descriptor belongs.
+		assert_eq!(
+			Reader::new(&[0xC1, 0x00]).expect_tag(39),
+			Err(error::Error::CborDecode)
+		);
+		assert_eq!(
+			Reader::new(&[0x03]).read_descriptor(),
+			Err(error::Error::CborDecode)
+		);
+		// A negative integer, a string, and a special value are not unsigned integers or bytes.
+		for data in [&[0x20][..], &[0x61, b'a'], &[0xF6]] {
+			assert_eq!(Reader::new(data).read_uint(), Err(error::Error::CborDecode));
+			assert_eq!(
+				Reader::new(data).read_bytes(),
+				Err(error::Error::CborDecode)
+			);
+		}
+		assert_eq!(
+			Reader::new(&[0x00]).read_special(),
+			Err(error::Error::CborDecode)
+		);
+	}
+
+	/// A raw descriptor value, standing in for a real descriptor.
+	struct RawDescriptor(u32);
+
+	impl descriptor::AsRaw for RawDescriptor {
+		fn as_raw(&self) -> u32 {
+			self.0
+		}
+	}
+
+	#[test]
+	fn descriptor_round_trips() {
+		assert_eq!(
+			written(|w| w.write_descriptor(&RawDescriptor(3))),
+			[0xD8, 0x27, 0x03]
+		);
+		assert_eq!(
+			written(|w| w.write_descriptor(&RawDescriptor(300))),
+			[0xD8, 0x27, 0x19, 0x01, 0x2C]
+		);
+		for raw in [0, 23, 24, 255, 256, 65535, 65536, u32::MAX] {
+			let data = written(|w| w.write_descriptor(&RawDescriptor(raw)));
+			let mut reader = Reader::new(&data);
+			assert_eq!(reader.read_descriptor(), Ok(raw));
+			assert!(reader.remaining().is_empty());
+		}
+	}
+
+	#[test]
+	fn decode_header_recognizes_break() {
+		assert_eq!(
+			decode_header(&[0xFF, 0xAA]),
+			Ok((MajorType::Break, 0, &[0xAA][..]))
+		);
+		// Null, false, and true are special values, not breaks.
+		for (byte, value) in [(0xF6, 22), (0xF4, 20), (0xF5, 21)] {
+			assert_eq!(
+				decode_header(&[byte]),
+				Ok((MajorType::Special, value, &[][..]))
+			);
+		}
+	}
+
+	#[test]
+	fn skip_item_skips_indefinite_length_items() {
+		// (_ h'01', h'0203'), (_ "a"), [_ 1, [_ ]], and {_ "a": 2}.
+		let data = [
+			0x5F, 0x41, 0x01, 0x42, 0x02, 0x03, 0xFF, 0x7F, 0x61, b'a', 0xFF, 0x9F, 0x01, 0x9F,
+			0xFF, 0xFF, 0xBF, 0x61, b'a', 0x02, 0xFF, 0xAA,
+		];
+		let slice = skip_item(&data).unwrap();
+		assert_eq!(slice[0], 0x7F);
+		let slice = skip_item(slice).unwrap();
+		assert_eq!(slice[0], 0x9F);
+		let slice = skip_item(slice).unwrap();
+		assert_eq!(slice[0], 0xBF);
+		assert_eq!(skip_item(slice).unwrap(), [0xAA]);
+	}
+
+	#[test]
+	fn skip_item_rejects_unterminated_indefinite_length_items() {
+		for data in [&[0x5F, 0x41, 0x01][..], &[0x9F], &[0x9F, 0x9F, 0xFF]] {
+			assert_eq!(skip_item(data), Err(error::Error::CborDecode));
+		}
+	}
+}
@@ -0,0 +1,1141 @@
+//! Parsing of the boot configuration stored in the EEPROM data area.
+//!
+//! The data area is laid out as follows:
+//! * Bytes 0–15 hold the binary UUID of the designated boot device. If the data area is shorter
+//!   than 16 bytes, there is no designated boot device.
+//! * Byte 16, if present, holds a set of flags. If absent, all flags are clear.
+//! * Byte 17, if present and nonzero, holds the boot slot: the position (counting from 1) in the
+//!   list of filesystem components of the filesystem to boot from if there is no usable
+//!   designated boot device. This byte is only used with the `boot-slot` feature.
+//! * Bytes 18 onward, if present, hold the path of the image file to boot, encoded as UTF-8. If
+//!   absent, or if they are not valid UTF-8, `/init.wasm` is booted. The path is normalized
+//!   first, and if that fails, `/init.wasm` is booted too. These bytes are only used with the
+//!   `config-path` feature.
+//!
+//! With the `checksum` feature, if the `CHECKSUM` flag is set, the last four bytes of the data
+//! area instead hold the CRC-32 checksum of the image, most significant byte first, and the fields
+//! above end before them.
+//!
+//! With the `http-boot` feature, the data area may instead hold an `http://` or `https://` URL,
+//! encoded as UTF-8, optionally surrounded by whitespace. The image is then downloaded from that
+//! URL using an Internet card.
+//!
+//! With the `text-uuid` feature, the data area may instead hold the UUID of the designated boot
+//! device as text, in the canonical hyphenated hexadecimal form, optionally surrounded by
+//! whitespace. A data area of exactly 16 bytes is always taken to be a binary UUID.
+//!
+//! With the `boot-list` feature, the data area may instead hold a CBOR array of byte arrays, each
+//! holding the binary UUID of a boot device to try, in order. Byte arrays that are not 16 bytes
+//! long are skipped. The data area is only taken to be a boot list if the whole of it is a
+//! well-formed array of byte arrays.
+//!
+//! With the `map-config` feature, the data area may instead hold a CBOR map whose keys are strings
+//! naming the fields: `uuid` (a 16-byte byte array), `flags` (an integer), `slot` (an integer),
+//! `path` (a string), `url` (a string), `checksum` (an integer), and `order` (a boot list, as
+//! above). Each is only used with the same features as its counterpart above. With the
+//! `prefer-label` feature, the map may also hold `label` (a string), the prefix of the labels of the
+//! filesystems to try first in a scan. With the `scan-limit` feature, it may also hold `scan-limit`
+//! (an integer from 1 to 255), the most filesystems to try in a scan. With the `reboot-on-failure`
+//! feature, it may also hold `reboot` (`true` or `false`), whether to reboot rather than halt if
+//! booting fails. With the `boot-delay` feature, it may also hold `delay` (an integer from 1 to
+//! 65535), the number of milliseconds to wait for a key press that shows the boot menu. With the
+//! `boot-menu` feature, it may also hold `timeout` (an integer from 1 to 255), the number of seconds
+//! to wait for a key to be pressed in the boot menu. With the `kernel-args` feature, it may also
+//! hold `args` (a string of up to 64 bytes), arguments to pass to the booted image. With the
+//! `seek-first` feature, it may also hold `seek` (`true` or `false`), whether to seek to the start
+//! of each image file before reading it. With the `split-image` feature, it may also hold `split`
+//! (`true` or `false`), whether to look for an image split into numbered parts. With the `skip-list`
+//! feature, it may also hold `skip` (an array of 16-byte byte arrays), the UUIDs of filesystems that
+//! a scan skips without trying to open anything on them; only the first 8 are used. None of these
+//! has a counterpart in the layout above. Entries with other keys are skipped, whatever their values
+//! hold. The data area is only taken to be a map if the whole of it is a well-formed one.
+//!
+//! With the `eeprom-image` feature, the data area may instead hold `EMBEDDED_IMAGE_MARKER`
+//! followed by a Wasm module, recognized by its magic number, to boot if no medium is bootable.
+//! Such a data area holds no boot configuration.
+
+#[cfg(any(feature = "boot-list", feature = "map-config"))]
+use crate::cbor;
+use oc_wasm_safe::Address;
+
+/// The byte that marks a data area holding an embedded image rather than a boot configuration.
+///
+/// This byte cannot start any of the other forms of boot configuration, other than a binary UUID.
+#[cfg(feature = "eeprom-image")]
+pub const EMBEDDED_IMAGE_MARKER: u8 = 0xFF;
+
+/// The flag that causes the designated boot device to be ignored, so that a scan for bootable
+/// media is always performed.
+pub const ALWAYS_SCAN: u8 = 0x01;
+
+/// The flag that indicates that the last four bytes of the data area hold a checksum of the image.
+#[cfg(feature = "checksum")]
+pub const CHECKSUM: u8 = 0x02;
+
+/// The flag that causes each major step of booting, and each filesystem examined by a scan, to be
+/// reported on the screen or to a debug component.
+#[cfg(feature = "verbose")]
+pub const VERBOSE: u8 = 0x04;
+
+/// The flag that causes the image to be received from a network boot server over a modem, if
+/// there is one, rather than loaded from a boot device.
+#[cfg(feature = "net-boot")]
+pub const NET_BOOT: u8 = 0x08;
+
+/// The flag that causes a second-stage loader to be booted in place of the image file on each
+/// filesystem that has one.
+#[cfg(feature = "stage2")]
+pub const STAGE2: u8 = 0x10;
+
+/// The flag that causes a scan to try filesystems that look like removable media first.
+#[cfg(feature = "removable-first")]
+pub const REMOVABLE_FIRST: u8 = 0x20;
+
+/// The flag that causes the image to be read from a tape in a tape drive, if there is one holding
+/// an image, rather than loaded from a boot device.
+#[cfg(feature = "tape-boot")]
+pub const TAPE_BOOT: u8 = 0x40;
+
+/// The flag that causes a scan to try filesystems in order of UUID rather than in listing order.
+#[cfg(feature = "sorted-scan")]
+pub const SORTED_SCAN: u8 = 0x80;
+
+/// The offset of the boot path within the data area.
+#[cfg(feature = "config-path")]
+const PATH_OFFSET: usize = 18;
+
+/// The length of the longest boot path, which fills an EEPROM’s 256-byte data area.
+#[cfg(feature = "config-path")]
+pub const MAX_PATH_LEN: usize = 256 - PATH_OFFSET;
+
+/// The length of the longest URL, which fills an EEPROM’s 256-byte data area.
+#[cfg(feature = "http-boot")]
+pub const MAX_URL_LEN: usize = 256;
+
+/// The length of the longest label prefix.
+#[cfg(feature = "prefer-label")]
+pub const MAX_LABEL_PREFIX_LEN: usize = 64;
+
+/// The length of the longest kernel arguments string.
+#[cfg(feature = "kernel-args")]
+pub const MAX_ARGS_LEN: usize = 64;
+
+/// A string of up to `N` bytes taken from the data area.
+#[cfg(any(
+	feature = "config-path",
+	feature = "http-boot",
+	feature = "kernel-args",
+	feature = "prefer-label"
+))]
+#[derive(Clone, Copy)]
+pub struct Text<const N: usize> {
+	/// The string, encoded as UTF-8, in the first `len` bytes.
+	bytes: [u8; N],
+
+	/// The length of the string, in bytes.
+	len: usize,
+}
+
+#[cfg(any(
+	feature = "config-path",
+	feature = "http-boot",
+	feature = "kernel-args",
+	feature = "prefer-label"
+))]
+impl<const N: usize> Text<N> {
+	/// Copies a string from a byte slice.
+	///
+	/// `None` is returned if `bytes` is empty, too long, or not valid UTF-8.
+	fn new(bytes: &[u8]) -> Option<Self> {
+		if bytes.is_empty() || bytes.len() > N || core::str::from_utf8(bytes).is_err() {
+			return None;
+		}
+		let mut text = Self {
+			bytes: [0; N],
+			len: bytes.len(),
+		};
+		text.bytes[..bytes.len()].copy_from_slice(bytes);
+		Some(text)
+	}
+
+	/// Returns the string.
+	pub fn as_str(&self) -> &str {
+		// SAFETY: The first len bytes were checked to be valid UTF-8 when the string was created.
+		unsafe { core::str::from_utf8_unchecked(self.bytes.get_unchecked(..self.len)) }
+	}
+}
+
+/// A path of an image file to boot.
+#[cfg(feature = "config-path")]
+pub type Path = Text<MAX_PATH_LEN>;
+
+/// A URL of an image file to download and boot.
+#[cfg(feature = "http-boot")]
+pub type Url = Text<MAX_URL_LEN>;
+
+/// A prefix of the labels of the filesystems to try first in a scan.
+#[cfg(feature = "prefer-label")]
+pub type LabelPrefix = Text<MAX_LABEL_PREFIX_LEN>;
+
+/// Arguments to pass to the booted image, like a kernel command line.
+#[cfg(feature = "kernel-args")]
+pub type Args = Text<MAX_ARGS_LEN>;
+
+/// The most boot devices that a boot list can hold, which fill an EEPROM’s 256-byte data area.
+#[cfg(feature = "boot-list")]
+const MAX_BOOT_LIST_LEN: usize = 15;
+
+/// An ordered list of boot devices to try in turn.
+///
+/// Iterating over the list yields the boot devices that have not been tried yet.
+#[cfg(feature = "boot-list")]
+#[derive(Clone)]
+pub struct BootList {
+	/// The UUIDs of the boot devices, in the first `len` elements.
+	uuids: [Address; MAX_BOOT_LIST_LEN],
+
+	/// The number of valid elements in `uuids`.
+	len: usize,
+
+	/// The number of boot devices yielded so far.
+	position: usize,
+}
+
+#[cfg(feature = "boot-list")]
+impl Iterator for BootList {
+	type Item = Address;
+
+	fn next(&mut self) -> Option<Address> {
+		let uuid = *self.uuids.get(self.position..self.len)?.first()?;
+		self.position += 1;
+		Some(uuid)
+	}
+}
+
+/// The most filesystems that a skip list can hold.
+#[cfg(feature = "skip-list")]
+const MAX_SKIP_LIST_LEN: usize = 8;
+
+/// A set of filesystems that a scan skips.
+#[cfg(feature = "skip-list")]
+#[derive(Clone, Copy)]
+pub struct SkipList {
+	/// The UUIDs of the filesystems, in the first `len` elements.
+	uuids: [Address; MAX_SKIP_LIST_LEN],
+
+	/// The number of valid elements in `uuids`.
+	len: usize,
+}
+
+#[cfg(feature = "skip-list")]
+impl SkipList {
+	/// Returns whether the list holds a filesystem.
+	pub fn contains(&self, address: &Address) -> bool {
+		self.uuids[..self.len].contains(address)
+	}
+}
+
+/// A boot configuration.
+pub struct Config {
+	/// The designated boot device, if any.
+	pub boot_device: Option<Address>,
+
+	/// The boot devices to try in turn, if the data area holds a boot list.
+	#[cfg(feature = "boot-list")]
+	pub boot_list: Option<BootList>,
+
+	/// The flags.
+	pub flags: u8,
+
+	/// The boot slot, if any.
+	#[cfg(feature = "boot-slot")]
+	pub boot_slot: Option<u8>,
+
+	/// The path of the image file to boot, if not `/init.wasm`.
+	#[cfg(feature = "config-path")]
+	pub path: Option<Path>,
+
+	/// The URL of the image file to download and boot, if any.
+	#[cfg(feature = "http-boot")]
+	pub url: Option<Url>,
+
+	/// The CRC-32 checksum that the image must have, if any.
+	#[cfg(feature = "checksum")]
+	pub checksum: Option<u32>,
+
+	/// The prefix of the labels of the filesystems to try first in a scan, if any.
+	#[cfg(feature = "prefer-label")]
+	pub label_prefix: Option<LabelPrefix>,
+
+	/// The most filesystems to try in a scan, if not the default.
+	#[cfg(feature = "scan-limit")]
+	pub scan_limit: Option<u8>,
+
+	/// The filesystems that a scan skips, if any.
+	#[cfg(feature = "skip-list")]
+	pub skip: Option<SkipList>,
+
+	/// Whether to reboot, rather than halt, if booting fails.
+	#[cfg(feature = "reboot-on-failure")]
+	pub reboot: bool,
+
+	/// Whether to seek to the start of each image file before reading it.
+	#[cfg(feature = "seek-first")]
+	pub seek: bool,
+
+	/// Whether to look for an image split into numbered parts on a filesystem that lacks the
+	/// image file.
+	#[cfg(feature = "split-image")]
+	pub split: bool,
+
+	/// The number of milliseconds to wait for a key press, which shows the boot menu, before
+	/// booting, if any.
+	#[cfg(feature = "boot-delay")]
+	pub delay: Option<u16>,
+
+	/// The number of seconds to wait for a key to be pressed in the boot menu, if not the default.
+	#[cfg(feature = "boot-menu")]
+	pub menu_timeout: Option<u8>,
+
+	/// The arguments to pass to the booted image, if any.
+	#[cfg(feature = "kernel-args")]
+	pub args: Option<Args>,
+}
+
+impl Config {
+	/// Returns a configuration with no designated boot device, no flags set, and nothing else
+	/// given, to which the fields that a data area does give can be added.
+	const fn empty() -> Self {
+		Self {
+			boot_device: None,
+			#[cfg(feature = "boot-list")]
+			boot_list: None,
+			flags: 0,
+			#[cfg(feature = "boot-slot")]
+			boot_slot: None,
+			#[cfg(feature = "config-path")]
+			path: None,
+			#[cfg(feature = "http-boot")]
+			url: None,
+			#[cfg(feature = "checksum")]
+			checksum: None,
+			#[cfg(feature = "prefer-label")]
+			label_prefix: None,
+			#[cfg(feature = "scan-limit")]
+			scan_limit: None,
+			#[cfg(feature = "skip-list")]
+			skip: None,
+			#[cfg(feature = "reboot-on-failure")]
+			reboot: false,
+			#[cfg(feature = "seek-first")]
+			seek: false,
+			#[cfg(feature = "split-image")]
+			split: false,
+			#[cfg(feature = "boot-delay")]
+			delay: None,
+			#[cfg(feature = "boot-menu")]
+			menu_timeout: None,
+			#[cfg(feature = "kernel-args")]
+			args: None,
+		}
+	}
+
+	/// Parses a boot configuration from the contents of the EEPROM data area.
+	///
+	/// Parsing never fails; a data area with no usable contents yields a configuration with no
+	/// designated boot device and no flags set.
+	pub fn parse(data: &[u8]) -> Self {
+		#[cfg(feature = "eeprom-image")]
+		let data = if embedded_image(data).is_some() {
+			&[]
+		} else {
+			data
+		};
+		#[cfg(feature = "map-config")]
+		if let Some(config) = parse_map(data) {
+			return config;
+		}
+		#[cfg(feature = "boot-list")]
+		if let Some(boot_list) = parse_boot_list(data) {
+			return Self {
+				boot_list: Some(boot_list),
+				..Self::empty()
+			};
+		}
+		#[cfg(feature = "http-boot")]
+		if let Some(url) = parse_url(data) {
+			return Self {
+				url: Some(url),
+				..Self::empty()
+			};
+		}
+		#[cfg(feature = "text-uuid")]
+		if data.len() != 16 {
+			if let Some(boot_device) = parse_text_uuid(data) {
+				return Self {
+					boot_device: Some(boot_device),
+					..Self::empty()
+				};
+			}
+		}
+		let boot_device = data
+			.get(0..16)
+			.and_then(|uuid| uuid.try_into().ok())
+			.map(Address::from_bytes);
+		let flags = data.get(16).copied().unwrap_or(0);
+		// The checksum is not part of the fields that follow the flags.
+		#[cfg(feature = "checksum")]
+		#[cfg_attr(
+			not(any(feature = "boot-slot", feature = "config-path")),
+			allow(unused_variables)
+		)]
+		let (data, checksum) = match data.len().checked_sub(4) {
+			Some(len) if flags & CHECKSUM != 0 && len > 16 => {
+				let (data, checksum) = data.split_at(len);
+				(data, checksum.try_into().ok().map(u32::from_be_bytes))
+			}
+			_ => (data, None),
+		};
+		// Without the features that add fields, the binary layout gives every field itself.
+		#[allow(clippy::needless_update)]
+		Self {
+			boot_device,
+			flags,
+			#[cfg(feature = "boot-slot")]
+			boot_slot: data.get(17).copied().filter(|&slot| slot != 0),
+			#[cfg(feature = "config-path")]
+			path: data.get(PATH_OFFSET..).and_then(parse_path),
+			#[cfg(feature = "checksum")]
+			checksum,
+			..Self::empty()
+		}
+	}
+
+	/// Returns whether the configuration says what to boot from.
+	///
+	/// This is the case if it designates a boot device or, with the features that honour them,
+	/// gives a boot slot or a URL.
+	#[cfg(feature = "multi-eeprom")]
+	pub fn is_usable(&self) -> bool {
+		let usable = self.boot_device.is_some();
+		#[cfg(feature = "boot-list")]
+		let usable = usable || self.boot_list.is_some();
+		#[cfg(feature = "boot-slot")]
+		let usable = usable || self.boot_slot.is_some();
+		#[cfg(feature = "http-boot")]
+		let usable = usable || self.url.is_some();
+		usable
+	}
+
+	/// Returns whether a flag is set.
+	pub fn flag(&self, flag: u8) -> bool {
+		self.flags & flag != 0
+	}
+}
+
+/// Returns whether a data area holds a boot configuration written as a CBOR map.
+///
+/// Without the `map-config` feature, this is never the case.
+#[cfg(feature = "remember-device")]
+#[cfg_attr(not(feature = "map-config"), allow(unused_variables))]
+pub fn is_map(data: &[u8]) -> bool {
+	#[cfg(feature = "map-config")]
+	{
+		parse_map(data).is_some()
+	}
+	#[cfg(not(feature = "map-config"))]
+	false
+}
+
+/// Returns whether a data area holds an embedded image.
+///
+/// Without the `eeprom-image` feature, this is never the case.
+#[cfg(feature = "remember-device")]
+#[cfg_attr(not(feature = "eeprom-image"), allow(unused_variables))]
+pub fn is_embedded_image(data: &[u8]) -> bool {
+	#[cfg(feature = "eeprom-image")]
+	{
+		embedded_image(data).is_some()
+	}
+	#[cfg(not(feature = "eeprom-image"))]
+	false
+}
+
+/// Returns the image embedded in a data area, if it holds one.
+#[cfg(feature = "eeprom-image")]
+pub fn embedded_image(data: &[u8]) -> Option<&[u8]> {
+	match data.split_first() {
+		Some((&EMBEDDED_IMAGE_MARKER, image)) if image.starts_with(b"\0asm") => Some(image),
+		_ => None,
+	}
+}
+
+/// Parses a boot configuration written as a CBOR map.
+///
+/// `None` is returned if the data area does not hold exactly one well-formed map with string keys,
+/// or if a known key has a value of the wrong type.
+#[cfg(feature = "map-config")]
+fn parse_map(data: &[u8]) -> Option<Config> {
+	let mut reader = cbor::Reader::new(data);
+	let Ok((cbor::MajorType::Map, entries)) = reader.read_header() else {
+		return None;
+	};
+	let mut config = Config::empty();
+	for _ in 0..entries {
+		let Ok((cbor::MajorType::String, len)) = reader.read_header() else {
+			return None;
+		};
+		match reader.read_payload(len).ok()? {
+			b"uuid" => {
+				let uuid = reader.read_bytes().ok()?.try_into().ok()?;
+				config.boot_device = Some(Address::from_bytes(uuid));
+			}
+			b"flags" => config.flags = reader.read_uint().ok()?.try_into().ok()?,
+			#[cfg(feature = "boot-list")]
+			b"order" => config.boot_list = Some(read_boot_list(&mut reader)?),
+			#[cfg(feature = "boot-slot")]
+			b"slot" => {
+				let slot = reader.read_uint().ok()?.try_into().ok()?;
+				config.boot_slot = Some(slot).filter(|&slot| slot != 0);
+			}
+			#[cfg(feature = "config-path")]
+			b"path" => config.path = Some(parse_path(read_string(&mut reader)?)?),
+			#[cfg(feature = "http-boot")]
+			b"url" => config.url = Some(Url::new(read_string(&mut reader)?)?),
+			#[cfg(feature = "checksum")]
+			b"checksum" => config.checksum = Some(reader.read_uint().ok()?.try_into().ok()?),
+			#[cfg(feature = "prefer-label")]
+			b"label" => config.label_prefix = Some(LabelPrefix::new(read_string(&mut reader)?)?),
+			#[cfg(feature = "scan-limit")]
+			b"scan-limit" => {
+				let limit = reader.read_uint().ok()?.try_into().ok()?;
+				config.scan_limit = Some(limit).filter(|&limit| limit != 0);
+			}
+			#[cfg(feature = "skip-list")]
+			b"skip" => config.skip = Some(parse_skip_list(&mut reader)?),
+			#[cfg(feature = "boot-delay")]
+			b"delay" => {
+				let delay = reader.read_uint().ok()?.try_into().ok()?;
+				config.delay = Some(delay).filter(|&delay| delay != 0);
+			}
+			#[cfg(feature = "boot-menu")]
+			b"timeout" => {
+				let timeout = reader.read_uint().ok()?.try_into().ok()?;
+				config.menu_timeout = Some(timeout).filter(|&timeout| timeout != 0);
+			}
+			#[cfg(feature = "kernel-args")]
+			b"args" => config.args = Some(Args::new(read_string(&mut reader)?)?),
+			#[cfg(feature = "reboot-on-failure")]
+			b"reboot" => {
+				config.reboot = match reader.read_special().ok()? {
+					20 => false,
+					21 => true,
+					_ => return None,
+				};
+			}
+			#[cfg(feature = "seek-first")]
+			b"seek" => {
+				config.seek = match reader.read_special().ok()? {
+					20 => false,
+					21 => true,
+					_ => return None,
+				};
+			}
+			#[cfg(feature = "split-image")]
+			b"split" => {
+				config.split = match reader.read_special().ok()? {
+					20 => false,
+					21 => true,
+					_ => return None,
+				};
+			}
+			_ => reader.skip().ok()?,
+		}
+	}
+	reader.remaining().is_empty().then_some(config)
+}
+
+/// Parses a boot list.
+///
+/// `None` is returned if the data area does not hold exactly one well-formed array of byte arrays.
+#[cfg(feature = "boot-list")]
+fn parse_boot_list(data: &[u8]) -> Option<BootList> {
+	let mut reader = cbor::Reader::new(data);
+	let boot_list = read_boot_list(&mut reader)?;
+	reader.remaining().is_empty().then_some(boot_list)
+}
+
+/// Reads a boot list, either the whole of a data area or the value of an `order` entry in a map.
+///
+/// Byte arrays that are not 16 bytes long are skipped. `None` is returned if the input does not
+/// start with a well-formed array of byte arrays.
+#[cfg(feature = "boot-list")]
+fn read_boot_list(reader: &mut cbor::Reader<'_>) -> Option<BootList> {
+	let Ok((cbor::MajorType::Array, entries)) = reader.read_header() else {
+		return None;
+	};
+	let mut boot_list = BootList {
+		uuids: [Address::default(); MAX_BOOT_LIST_LEN],
+		len: 0,
+		position: 0,
+	};
+	for _ in 0..entries {
+		if let Ok(uuid) = reader.read_bytes().ok()?.try_into() {
+			// A list that fits in the data area cannot hold more UUIDs than this, but check anyway.
+			if boot_list.len < MAX_BOOT_LIST_LEN {
+				boot_list.uuids[boot_list.len] = Address::from_bytes(uuid);
+				boot_list.len += 1;
+			}
+		}
+	}
+	Some(boot_list)
+}
+
+/// Parses a skip list, the value of a `skip` entry in a map.
+///
+/// Byte arrays that are not 16 bytes long are ignored, as are any beyond the first
+/// [`MAX_SKIP_LIST_LEN`]. `None` is returned if the value is not a well-formed array of byte
+/// arrays.
+#[cfg(feature = "skip-list")]
+fn parse_skip_list(reader: &mut cbor::Reader<'_>) -> Option<SkipList> {
+	let Ok((cbor::MajorType::Array, entries)) = reader.read_header() else {
+		return None;
+	};
+	let mut skip_list = SkipList {
+		uuids: [Address::default(); MAX_SKIP_LIST_LEN],
+		len: 0,
+	};
+	for _ in 0..entries {
+		if let Ok(uuid) = reader.read_bytes().ok()?.try_into() {
+			if skip_list.len < MAX_SKIP_LIST_LEN {
+				skip_list.uuids[skip_list.len] = Address::from_bytes(uuid);
+				skip_list.len += 1;
+			}
+		}
+	}
+	Some(skip_list)
+}
+
+/// Normalizes a boot path.
+///
+/// A slash is added at the start if missing, and repeated slashes and slashes at the end are
+/// removed, so that, for example, `//boot//init.wasm/` becomes `/boot/init.wasm`. On success, the
+/// normalized path is written to the start of `out` and returned. `None` is returned if the path
+/// has a `..` component, which could reach files outside the directories named, or has no
+/// components at all, or if the normalized path does not fit in `out`.
+#[cfg(feature = "config-path")]
+fn normalize_path<'out>(input: &[u8], out: &'out mut [u8]) -> Option<&'out [u8]> {
+	let mut len = 0;
+	for component in input.split(|&byte| byte == b'/') {
+		if component == b".." {
+			return None;
+		}
+		if !component.is_empty() {
+			let end = len + 1 + component.len();
+			let (slash, name) = out.get_mut(len..end)?.split_first_mut()?;
+			*slash = b'/';
+			name.copy_from_slice(component);
+			len = end;
+		}
+	}
+	if len == 0 {
+		None
+	} else {
+		Some(&out[..len])
+	}
+}
+
+/// Parses a boot path, [normalizing](normalize_path) it.
+///
+/// `None` is returned if the path cannot be normalized or is not valid UTF-8.
+#[cfg(feature = "config-path")]
+fn parse_path(bytes: &[u8]) -> Option<Path> {
+	Path::new(normalize_path(bytes, &mut [0; MAX_PATH_LEN])?)
+}
+
+/// Reads a string from a CBOR map value, returning its encoding.
+#[cfg(all(
+	feature = "map-config",
+	any(
+		feature = "config-path",
+		feature = "http-boot",
+		feature = "kernel-args",
+		feature = "prefer-label"
+	)
+))]
+fn read_string<'data>(reader: &mut cbor::Reader<'data>) -> Option<&'data [u8]> {
+	match reader.read_header().ok()? {
+		(cbor::MajorType::String, len) => reader.read_payload(len).ok(),
+		_ => None,
+	}
+}
+
+/// Parses a UUID written as text.
+///
+/// The text must be in the canonical hyphenated form of 32 hexadecimal digits (in either letter
+/// case) in groups of 8, 4, 4, 4, and 12, optionally surrounded by whitespace. `None` is returned
+/// if it is not.
+#[cfg(any(
+	feature = "boot-prompt",
+	feature = "text-uuid",
+	all(feature = "boot-record", feature = "net-boot")
+))]
+pub fn parse_text_uuid(text: &[u8]) -> Option<Address> {
+	let text = text.trim_ascii();
+	if text.len() != 36 {
+		return None;
+	}
+	let mut bytes = [0_u8; 16];
+	let mut index = 0;
+	for byte in &mut bytes {
+		if matches!(index, 8 | 13 | 18 | 23) {
+			if text[index] != b'-' {
+				return None;
+			}
+			index += 1;
+		}
+		*byte = hex_byte(text[index], text[index + 1])?;
+		index += 2;
+	}
+	Some(Address::from_bytes(bytes))
+}
+
+/// Returns the value of a hexadecimal digit, in either letter case, or `None` if `ch` is not one.
+#[cfg(any(
+	feature = "boot-prompt",
+	feature = "text-uuid",
+	all(feature = "boot-record", feature = "net-boot")
+))]
+fn hex_nibble(ch: u8) -> Option<u8> {
+	// Cast is sound because a hexadecimal digit is less than 16.
+	#[allow(clippy::cast_possible_truncation)]
+	char::from(ch).to_digit(16).map(|digit| digit as u8)
+}
+
+/// Returns the byte written as two hexadecimal digits, the most significant first, or `None` if
+/// either `high` or `low` is not one.
+#[cfg(any(
+	feature = "boot-prompt",
+	feature = "text-uuid",
+	all(feature = "boot-record", feature = "net-boot")
+))]
+fn hex_byte(high: u8, low: u8) -> Option<u8> {
+	Some((hex_nibble(high)? << 4) | hex_nibble(low)?)
+}
+
+/// Parses a URL of an image file to download.
+///
+/// The text must start with `http://` or `https://` (in lowercase) and be valid UTF-8, optionally
+/// surrounded by whitespace. `None` is returned if it is not.
+#[cfg(feature = "http-boot")]
+fn parse_url(text: &[u8]) -> Option<Url> {
+	let text = text.trim_ascii();
+	if text.starts_with(b"http://") || text.starts_with(b"https://") {
+		Url::new(text)
+	} else {
+		None
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// The UUID used in the tests, in binary.
+	const UUID: [u8; 16] = [
+		0x01, 0x23, 0x45, 0x67, 0x89, 0xAB, 0xCD, 0xEF, 0x10, 0x32, 0x54, 0x76, 0x98, 0xBA, 0xDC,
+		0xFE,
+	];
+
+	#[test]
+	fn parse_binary_layout() {
+		let mut data = UUID.to_vec();
+		data.push(ALWAYS_SCAN);
+		let config = Config::parse(&data);
+		assert_eq!(config.boot_device, Some(Address::from_bytes(UUID)));
+		assert!(config.flag(ALWAYS_SCAN));
+	}
+
+	#[test]
+	fn parse_short_data_area() {
+		let config = Config::parse(&UUID[..15]);
+		assert_eq!(config.boot_device, None);
+		assert_eq!(config.flags, 0);
+	}
+
+	#[cfg(feature = "verbose")]
+	#[test]
+	fn parse_verbose_flag() {
+		let mut data = UUID.to_vec();
+		data.push(VERBOSE);
+		let config = Config::parse(&data);
+		// The flag comes after the UUID, so it leaves the UUID intact.
+		assert_eq!(config.boot_device, Some(Address::from_bytes(UUID)));
+		assert!(config.flag(VERBOSE));
+		assert!(!config.flag(ALWAYS_SCAN));
+		data[16] = 0;
+		assert!(!Config::parse(&data).flag(VERBOSE));
+	}
+
+	#[cfg(feature = "boot-list")]
+	#[test]
+	fn parse_boot_list_skips_bad_entries() {
+		// [UUID, h'00', UUID reversed]
+		let mut data = vec![0x83, 0x50];
+		data.extend_from_slice(&UUID);
+		data.extend_from_slice(&[0x41, 0x00, 0x50]);
+		let mut reversed = UUID;
+		reversed.reverse();
+		data.extend_from_slice(&reversed);
+		let config = Config::parse(&data);
+		assert_eq!(
+			config.boot_list.unwrap().collect::<Vec<_>>(),
+			[Address::from_bytes(UUID), Address::from_bytes(reversed)]
+		);
+		assert_eq!(config.boot_device, None);
+
+		// An empty list is still a list, and leaves nothing to try.
+		assert_eq!(Config::parse(&[0x80]).boot_list.unwrap().count(), 0);
+
+		// Trailing data after the list means the data area holds something else.
+		data.push(0x00);
+		assert!(Config::parse(&data).boot_list.is_none());
+	}
+
+	#[cfg(feature = "checksum")]
+	#[test]
+	fn parse_trailing_checksum() {
+		let mut data = UUID.to_vec();
+		data.push(CHECKSUM);
+		data.extend_from_slice(&[0xDE, 0xAD, 0xBE, 0xEF]);
+		let config = Config::parse(&data);
+		assert_eq!(config.boot_device, Some(Address::from_bytes(UUID)));
+		assert_eq!(config.checksum, Some(0xDEAD_BEEF));
+
+		// Without the flag, the trailing bytes are not a checksum.
+		data[16] = 0;
+		assert_eq!(Config::parse(&data).checksum, None);
+
+		// A data area with no room for a checksum after the flags has none.
+		assert_eq!(Config::parse(&data[..17]).checksum, None);
+	}
+
+	/// Returns the encoding of a map holding `uuid`, `flags` set to 5, and the given extra
+	/// entries.
+	#[cfg(feature = "map-config")]
+	fn map(extra_entries: u8, extra: &[u8]) -> Vec<u8> {
+		let mut data = vec![0xA2 + extra_entries, 0x64];
+		data.extend_from_slice(b"uuid");
+		data.push(0x50);
+		data.extend_from_slice(&UUID);
+		data.push(0x65);
+		data.extend_from_slice(b"flags");
+		data.push(0x05);
+		data.extend_from_slice(extra);
+		data
+	}
+
+	#[cfg(feature = "map-config")]
+	#[test]
+	fn parse_map_reads_known_keys() {
+		let config = parse_map(&map(0, &[])).unwrap();
+		assert_eq!(config.boot_device, Some(Address::from_bytes(UUID)));
+		assert_eq!(config.flags, 5);
+	}
+
+	#[cfg(feature = "map-config")]
+	#[test]
+	fn parse_map_skips_unknown_keys() {
+		// "extra": {"x": [1, h'00', 39(2)]}, "more": -1
+		let config = parse_map(&map(
+			2,
+			&[
+				0x65, b'e', b'x', b't', b'r', b'a', 0xA1, 0x61, b'x', 0x83, 0x01, 0x41, 0x00, 0xD8,
+				0x27, 0x02, 0x64, b'm', b'o', b'r', b'e', 0x20,
+			],
+		))
+		.unwrap();
+		assert_eq!(config.boot_device, Some(Address::from_bytes(UUID)));
+		assert_eq!(config.flags, 5);
+	}
+
+	#[cfg(feature = "map-config")]
+	#[test]
+	fn parse_map_rejects_malformed_maps() {
+		// Not a map at all.
+		assert!(parse_map(&[0x80]).is_none());
+		// Trailing data after the map.
+		assert!(parse_map(&map(0, &[0x00])).is_none());
+		// A missing entry.
+		assert!(parse_map(&map(1, &[])).is_none());
+		// A key that is not a string.
+		assert!(parse_map(&map(1, &[0x01, 0x02])).is_none());
+		// A UUID of the wrong length.
+		assert!(parse_map(&[0xA1, 0x64, b'u', b'u', b'i', b'd', 0x41, 0x00]).is_none());
+		// Flags that do not fit in a byte.
+		assert!(parse_map(&[0xA1, 0x65, b'f', b'l', b'a', b'g', b's', 0x19, 0x01, 0x00]).is_none());
+	}
+
+	#[cfg(all(feature = "map-config", feature = "config-path"))]
+	#[test]
+	fn parse_map_normalizes_path() {
+		let config = parse_map(&map(
+			1,
+			&[
+				0x64, b'p', b'a', b't', b'h', 0x6A, b'b', b'o', b'o', b't', b'/', b'/', b'o', b's',
+				b'/', b'/',
+			],
+		))
+		.unwrap();
+		assert_eq!(config.path.unwrap().as_str(), "/boot/os");
+	}
+
+	#[cfg(all(feature = "map-config", feature = "boot-list", feature = "config-path"))]
+	#[test]
+	fn parse_map_reads_path_and_boot_order() {
+		// As a boot configuration file might hold: {"path": "/os.wasm", "order": [UUID, h'00',
+		// UUID reversed]}.
+		let mut data = vec![0xA2, 0x64, b'p', b'a', b't', b'h', 0x68];
+		data.extend_from_slice(b"/os.wasm");
+		data.extend_from_slice(&[0x65, b'o', b'r', b'd', b'e', b'r', 0x83, 0x50]);
+		data.extend_from_slice(&UUID);
+		data.extend_from_slice(&[0x41, 0x00, 0x50]);
+		let mut reversed = UUID;
+		reversed.reverse();
+		data.extend_from_slice(&reversed);
+		let config = parse_map(&data).unwrap();
+		assert_eq!(config.path.unwrap().as_str(), "/os.wasm");
+		assert_eq!(
+			config.boot_list.unwrap().collect::<Vec<_>>(),
+			[Address::from_bytes(UUID), Address::from_bytes(reversed)]
+		);
+		assert_eq!(config.boot_device, None);
+	}
+
+	#[cfg(all(feature = "map-config", feature = "boot-list"))]
+	#[test]
+	fn parse_map_rejects_bad_boot_order() {
+		// An order that is not an array, and one holding something other than byte arrays.
+		let order = [0x65, b'o', b'r', b'd', b'e', b'r'];
+		let mut extra = order.to_vec();
+		extra.extend_from_slice(&[0x41, 0x00]);
+		assert!(parse_map(&map(1, &extra)).is_none());
+		let mut extra = order.to_vec();
+		extra.extend_from_slice(&[0x81, 0x01]);
+		assert!(parse_map(&map(1, &extra)).is_none());
+	}
+
+	#[cfg(all(feature = "map-config", feature = "scan-limit"))]
+	#[test]
+	fn parse_map_reads_scan_limit() {
+		let scan_limit = [
+			0x6A, b's', b'c', b'a', b'n', b'-', b'l', b'i', b'm', b'i', b't',
+		];
+		let mut extra = scan_limit.to_vec();
+		extra.push(0x02);
+		assert_eq!(parse_map(&map(1, &extra)).unwrap().scan_limit, Some(2));
+		let mut extra = scan_limit.to_vec();
+		extra.push(0x00);
+		assert_eq!(parse_map(&map(1, &extra)).unwrap().scan_limit, None);
+		let mut extra = scan_limit.to_vec();
+		extra.extend_from_slice(&[0x19, 0x01, 0x00]);
+		assert!(parse_map(&map(1, &extra)).is_none());
+	}
+
+	#[cfg(feature = "kernel-args")]
+	#[test]
+	fn parse_map_reads_args() {
+		let args = [0x64, b'a', b'r', b'g', b's'];
+		let mut extra = args.to_vec();
+		extra.extend_from_slice(&[0x65, b'q', b'u', b'i', b'e', b't']);
+		assert_eq!(
+			parse_map(&map(1, &extra))
+				.unwrap()
+				.args
+				.as_ref()
+				.map(Args::as_str),
+			Some("quiet")
+		);
+		let mut extra = args.to_vec();
+		extra.extend_from_slice(&[0x78, 0x40]);
+		extra.extend_from_slice(&[b'x'; 64]);
+		assert_eq!(
+			parse_map(&map(1, &extra))
+				.unwrap()
+				.args
+				.map(|args| args.as_str().len()),
+			Some(64)
+		);
+		let mut extra = args.to_vec();
+		extra.extend_from_slice(&[0x78, 0x41]);
+		extra.extend_from_slice(&[b'x'; 65]);
+		assert!(parse_map(&map(1, &extra)).is_none());
+	}
+
+	#[cfg(feature = "skip-list")]
+	#[test]
+	fn parse_map_reads_skip_list() {
+		/// Returns a map whose `skip` entry holds `entries`, which are already encoded.
+		fn skip(count: u8, entries: &[u8]) -> Vec<u8> {
+			let mut extra = vec![0x64, b's', b'k', b'i', b'p', 0x80 + count];
+			extra.extend_from_slice(entries);
+			map(1, &extra)
+		}
+		/// Returns the encoding of a 16-byte byte array filled with `byte`.
+		fn uuid(byte: u8) -> Vec<u8> {
+			let mut entry = vec![0x50];
+			entry.extend_from_slice(&[byte; 16]);
+			entry
+		}
+		let address = |byte| Address::from_bytes([byte; 16]);
+
+		// Byte arrays of the wrong length are ignored.
+		let mut entries = uuid(1);
+		entries.extend_from_slice(&[0x43, 2, 2, 2]);
+		entries.extend(uuid(3));
+		let skip_list = parse_map(&skip(3, &entries)).unwrap().skip.unwrap();
+		assert!(skip_list.contains(&address(1)));
+		assert!(!skip_list.contains(&address(2)));
+		assert!(skip_list.contains(&address(3)));
+
+		// Only the first MAX_SKIP_LIST_LEN are used.
+		let count = u8::try_from(MAX_SKIP_LIST_LEN).unwrap() + 1;
+		let entries: Vec<u8> = (1..=count).flat_map(uuid).collect();
+		let skip_list = parse_map(&skip(count, &entries)).unwrap().skip.unwrap();
+		assert!((1..count).all(|byte| skip_list.contains(&address(byte))));
+		assert!(!skip_list.contains(&address(count)));
+
+		// Anything other than an array of byte arrays makes the map malformed.
+		assert!(parse_map(&skip(1, &[0x01])).is_none());
+		assert!(parse_map(&map(1, &[0x64, b's', b'k', b'i', b'p', 0x01])).is_none());
+	}
+
+	#[cfg(all(feature = "map-config", feature = "boot-menu"))]
+	#[test]
+	fn parse_map_reads_timeout() {
+		let timeout = [0x67, b't', b'i', b'm', b'e', b'o', b'u', b't'];
+		let mut extra = timeout.to_vec();
+		extra.push(0x0A);
+		assert_eq!(parse_map(&map(1, &extra)).unwrap().menu_timeout, Some(10));
+		let mut extra = timeout.to_vec();
+		extra.push(0x00);
+		assert_eq!(parse_map(&map(1, &extra)).unwrap().menu_timeout, None);
+		let mut extra = timeout.to_vec();
+		extra.extend_from_slice(&[0x19, 0x01, 0x00]);
+		assert!(parse_map(&map(1, &extra)).is_none());
+	}
+
+	#[cfg(feature = "config-path")]
+	#[test]
+	fn normalize_path_removes_extra_slashes() {
+		let mut out = [0; 32];
+		assert_eq!(
+			normalize_path(b"//boot//init.wasm/", &mut out),
+			Some(&b"/boot/init.wasm"[..])
+		);
+		assert_eq!(
+			normalize_path(b"init.wasm", &mut out),
+			Some(&b"/init.wasm"[..])
+		);
+		assert_eq!(normalize_path(b"/./a", &mut out), Some(&b"/./a"[..]));
+	}
+
+	#[cfg(feature = "config-path")]
+	#[test]
+	fn normalize_path_rejects_bad_paths() {
+		let mut out = [0; 8];
+		assert_eq!(normalize_path(b"", &mut out), None);
+		assert_eq!(normalize_path(b"///", &mut out), None);
+		assert_eq!(normalize_path(b"/boot/../init.wasm", &mut out), None);
+		assert_eq!(normalize_path(b"..", &mut out), None);
+		// The normalized path takes nine bytes.
+		assert_eq!(normalize_path(b"abcdefgh", &mut out), None);
+		assert_eq!(normalize_path(b"abcdefg", &mut out), Some(&b"/abcdefg"[..]));
+	}
+
+	#[cfg(any(
+		feature = "boot-prompt",
+		feature = "text-uuid",
+		all(feature = "boot-record", feature = "net-boot")
+	))]
+	#[test]
+	fn parse_text_uuid_accepts_canonical_form() {
+		let expected = Some(Address::from_bytes(UUID));
+		assert_eq!(
+			parse_text_uuid(b"01234567-89ab-cdef-1032-547698badcfe"),
+			expected
+		);
+		assert_eq!(
+			parse_text_uuid(b" 01234567-89AB-CDEF-1032-547698BADCFE\n"),
+			expected
+		);
+	}
+
+	#[cfg(any(
+		feature = "boot-prompt",
+		feature = "text-uuid",
+		all(feature = "boot-record", feature = "net-boot")
+	))]
+	#[test]
+	fn parse_text_uuid_rejects_other_forms() {
+		for text in [
+			&b""[..],
+			b"0123456789abcdef1032547698badcfe",
+			b"01234567-89ab-cdef-1032-547698badcf",
+			b"01234567-89ab-cdef-1032-547698badcfe0",
+			b"01234567+89ab-cdef-1032-547698badcfe",
+			b"0123456-789ab-cdef-1032-547698badcfe",
+			b"01234567-89ab-cdef-1032-547698badcfg",
+			b"{1234567-89ab-cdef-1032-547698badcfe}",
+			b"01234567-89ab-cdef-1032-5476-8badcfe",
+		] {
+			assert_eq!(parse_text_uuid(text), None);
+		}
+	}
+
+	#[cfg(any(
+		feature = "boot-prompt",
+		feature = "text-uuid",
+		all(feature = "boot-record", feature = "net-boot")
+	))]
+	#[test]
+	fn hex_nibble_accepts_only_hexadecimal_digits() {
+		for ch in 0..=255_u8 {
+			let expected = match ch {
+				b'0'..=b'9' | b'a'..=b'f' | b'A'..=b'F' => char::from(ch)
+					.to_digit(16)
+					.map(|digit| u8::try_from(digit).unwrap()),
+				_ => None,
+			};
+			assert_eq!(hex_nibble(ch), expected, "{ch:#04x}");
+		}
+		assert_eq!(hex_nibble(b'0'), Some(0));
+		assert_eq!(hex_nibble(b'9'), Some(9));
+		assert_eq!(hex_nibble(b'a'), Some(10));
+		assert_eq!(hex_nibble(b'F'), Some(15));
+		assert_eq!(hex_nibble(b'g'), None);
+		assert_eq!(hex_nibble(b'/'), None);
+		assert_eq!(hex_nibble(b':'), None);
+	}
+
+	#[cfg(any(
+		feature = "boot-prompt",
+		feature = "text-uuid",
+		all(feature = "boot-record", feature = "net-boot")
+	))]
+	#[test]
+	fn hex_byte_combines_digits() {
+		for high in 0..=255_u8 {
+			for low in 0..=255_u8 {
+				let expected = hex_nibble(high)
+					.zip(hex_nibble(low))
+					.map(|(high, low)| high * 16 + low);
+				assert_eq!(hex_byte(high, low), expected);
+			}
+		}
+		assert_eq!(hex_byte(b'0', b'0'), Some(0x00));
+		assert_eq!(hex_byte(b'f', b'F'), Some(0xFF));
+		assert_eq!(hex_byte(b'A', b'5'), Some(0xA5));
+		assert_eq!(hex_byte(b'-', b'5'), None);
+	}
+}
@@ -0,0 +1,59 @@
+//! Computation of CRC-32 checksums.
+//!
+//! This is the common CRC-32 used by zlib, PNG, and others (reflected polynomial `0xEDB88320`,
+//! initial value and final XOR of all ones). It is computed bit by bit, without a table, to keep
+//! the BIOS small.
+
+/// A CRC-32 checksum being computed over a sequence of bytes.
+#[derive(Clone, Copy)]
+pub struct Crc32(u32);
+
+impl Crc32 {
+	/// Starts computing a checksum.
+	pub const fn new() -> Self {
+		Self(0xFFFF_FFFF)
+	}
+
+	/// Adds some bytes to the checksum.
+	pub fn update(&mut self, data: &[u8]) {
+		for &byte in data {
+			self.0 ^= u32::from(byte);
+			for _ in 0..8 {
+				let mask = (self.0 & 1).wrapping_neg();
+				self.0 = (self.0 >> 1) ^ (0xEDB8_8320 & mask);
+			}
+		}
+	}
+
+	/// Returns the checksum of the bytes added so far.
+	pub fn finish(self) -> u32 {
+		!self.0
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn empty() {
+		assert_eq!(Crc32::new().finish(), 0);
+	}
+
+	#[test]
+	fn check_value() {
+		// The standard check value for this CRC.
+		let mut crc = Crc32::new();
+		crc.update(b"123456789");
+		assert_eq!(crc.finish(), 0xCBF4_3926);
+	}
+
+	#[test]
+	fn split_updates() {
+		let mut crc = Crc32::new();
+		crc.update(b"The quick brown fox ");
+		crc.update(b"");
+		crc.update(b"jumps over the lazy dog");
+		assert_eq!(crc.finish(), 0x414F_A339);
+	}
+}
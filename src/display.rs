@@ -0,0 +1,303 @@
+//! Display of boot progress on a screen attached to a GPU.
+//!
+//! All output is best-effort: if there is no GPU or no screen, or if a call to draw text cannot
+//! complete immediately, the text is simply not drawn.
+
+use super::{cbor, component, component_sys, internal_error, start_best_effort};
+use oc_wasm_safe::Address;
+use sync_unsafe_cell::SyncUnsafeCell;
+
+/// The GPU to draw with, once it has been bound to a screen.
+static GPU: SyncUnsafeCell<Option<Address>> = SyncUnsafeCell::new(None);
+
+/// The total number of bytes of image data loaded so far.
+static LOADED: SyncUnsafeCell<usize> = SyncUnsafeCell::new(0);
+
+/// The size of the image file being read, if known, and the value of [`LOADED`] when reading it
+/// started.
+#[cfg(feature = "size-check")]
+static SIZE: SyncUnsafeCell<Option<(usize, usize)>> = SyncUnsafeCell::new(None);
+
+/// Finds a GPU and a screen to display progress on.
+///
+/// The addresses of the first GPU and the first screen are returned, or `None` if there is no GPU
+/// or no screen.
+pub fn find_gpu(lister: &mut component::Lister) -> Option<(Address, Address)> {
+	let gpu = *lister.start(Some("gpu")).next()?.address();
+	let screen = *lister.start(Some("screen")).next()?.address();
+	Some((gpu, screen))
+}
+
+/// Starts binding a GPU to a screen.
+///
+/// `true` is returned if the call is complete now. `false` is returned if the call has started but
+/// will not be complete until the next timeslice.
+pub fn invoke_bind(gpu: &Address, screen: &Address) -> bool {
+	// Encode the parameters: an array holding the screen’s address as a string. The buffer has
+	// room for the array header, a string header with a 1-byte count, and the address, so this
+	// only fails if the BIOS is buggy.
+	let mut address_buffer = [0_u8; 36];
+	let mut buffer = [0_u8; 3 + 36];
+	let mut writer = cbor::Writer::new(&mut buffer);
+	if writer.write_array_header(1).is_err()
+		|| writer
+			.write_string(format_address(screen, &mut address_buffer))
+			.is_err()
+	{
+		internal_error();
+	}
+	invoke(gpu, "bind", &buffer)
+}
+
+/// Finishes binding a GPU to a screen and starts drawing with it.
+///
+/// The result of the bind call is discarded; if it failed, later drawing calls fail too, which is
+/// harmless.
+pub fn end_bind(gpu: Address) {
+	// SAFETY: invoke_cancel is unconditionally safe.
+	unsafe { component_sys::invoke_cancel() };
+	// SAFETY: Wasm is single-threaded, and GPU is only accessed in this module, without holding
+	// any references beyond a single statement.
+	unsafe { *GPU.get() = Some(gpu) };
+}
+
+/// Returns whether a GPU has been bound.
+pub fn bound() -> bool {
+	// SAFETY: Wasm is single-threaded, and GPU is only accessed in this module, without holding
+	// any references beyond a single statement.
+	unsafe { *GPU.get() }.is_some()
+}
+
+/// Draws text on the screen.
+///
+/// The `x` and `y` parameters are the 1-based column and row at which to start drawing. The
+/// `text` parameter is the text to draw, which must be at most 256 bytes long.
+pub fn gpu_set(x: u32, y: u32, text: &str) {
+	// SAFETY: Wasm is single-threaded, and GPU is only accessed in this module, without holding
+	// any references beyond a single statement.
+	let Some(gpu) = (unsafe { *GPU.get() }) else {
+		return;
+	};
+	// Encode the parameters: an array holding the column, row, and text. The buffer has room for
+	// the array header, two integers of up to 32 bits, a string header with a 2-byte count, and
+	// the text.
+	let mut buffer = [0_u8; 1 + 5 + 5 + 3 + 256];
+	let mut writer = cbor::Writer::new(&mut buffer);
+	if writer.write_array_header(3).is_err()
+		|| writer.write_uint(x.into()).is_err()
+		|| writer.write_uint(y.into()).is_err()
+		|| writer.write_string(text).is_err()
+	{
+		internal_error();
+	}
+	// If the call queue is full, which happens while a call whose result the state machine is
+	// waiting for is in progress, the text is lost. That call must not be cancelled.
+	if start_best_effort(&gpu, "set", Some(&buffer)).is_none() {
+		return;
+	}
+	// Drawing is a direct call, so it normally completes immediately. If it completed, this
+	// discards the result, which is of no interest. If it did not (because the timeslice’s call
+	// budget ran out), this abandons it, losing the text.
+	// SAFETY: invoke_cancel is unconditionally safe.
+	unsafe { component_sys::invoke_cancel() };
+}
+
+/// Shows that an image file is about to be read.
+pub fn reading(path: &str) {
+	gpu_set(1, 1, "Reading ");
+	gpu_set(9, 1, path);
+	// The size of the file is not known until it is fetched.
+	// SAFETY: Wasm is single-threaded, and SIZE is only accessed in this module, without holding
+	// any references beyond a single statement.
+	#[cfg(feature = "size-check")]
+	unsafe {
+		*SIZE.get() = None;
+	}
+}
+
+/// Notes the size of the image file about to be read, so that progress through it is shown as a
+/// percentage rather than a number of bytes.
+#[cfg(feature = "size-check")]
+pub fn sized(size: usize) {
+	// SAFETY: Wasm is single-threaded, and LOADED and SIZE are only accessed in this module,
+	// without holding any references beyond a single statement.
+	unsafe {
+		*SIZE.get() = Some((size, *LOADED.get()));
+	}
+	// Erase any byte count shown for an earlier file, which is longer than a percentage.
+	// SAFETY: The buffer holds only spaces, so it is valid UTF-8.
+	gpu_set(1, 2, unsafe {
+		core::str::from_utf8_unchecked(&[b' '; 10 + 6])
+	});
+}
+
+/// Shows that some image data has been loaded.
+///
+/// The `len` parameter is the number of bytes just loaded. With the `size-check` feature, if the
+/// size of the image file is known, the percentage of it loaded so far is shown; otherwise, the
+/// total number of bytes loaded is.
+pub fn loaded(len: usize) {
+	// SAFETY: Wasm is single-threaded, and LOADED is only accessed in this module, without holding
+	// any references beyond a single statement.
+	let loaded = unsafe {
+		let loaded = &mut *LOADED.get();
+		*loaded += len;
+		*loaded
+	};
+	// SAFETY: Wasm is single-threaded, and SIZE is only accessed in this module, without holding
+	// any references beyond a single statement.
+	#[cfg(feature = "size-check")]
+	let (mut value, suffix) = match unsafe { *SIZE.get() } {
+		// Casts are sound because usize is 32 bits on Wasm and the percentage is at most 100.
+		#[allow(clippy::cast_possible_truncation)]
+		Some((size, start)) if size != 0 => (
+			((loaded - start) as u64 * 100 / size as u64).min(100) as usize,
+			b"%     ",
+		),
+		_ => (loaded, b" bytes"),
+	};
+	#[cfg(not(feature = "size-check"))]
+	let (mut value, suffix) = (loaded, b" bytes");
+	let mut buffer = [0_u8; 10 + 6];
+	let mut start = 10;
+	loop {
+		start -= 1;
+		// Cast is sound because the digit is less than ten.
+		#[allow(clippy::cast_possible_truncation)]
+		{
+			buffer[start] = b'0' + (value % 10) as u8;
+		}
+		value /= 10;
+		if value == 0 {
+			break;
+		}
+	}
+	buffer[10..].copy_from_slice(suffix);
+	// SAFETY: Every byte written is an ASCII character, so the text is valid UTF-8.
+	gpu_set(1, 2, unsafe {
+		core::str::from_utf8_unchecked(&buffer[start..])
+	});
+}
+
+/// Shows an entry in the boot menu.
+///
+/// The `index` parameter is the position of the entry, counting from 0, which must be less than
+/// nine. The `label` parameter is the label of the filesystem the entry boots from.
+#[cfg(feature = "boot-menu")]
+pub fn menu_entry(index: usize, label: &str) {
+	// Casts are sound because the index is less than nine.
+	#[allow(clippy::cast_possible_truncation)]
+	let (row, digit) = (index as u32 + 3, b'1' + index as u8);
+	// SAFETY: The digit is an ASCII character, so the text is valid UTF-8.
+	gpu_set(1, row, unsafe { core::str::from_utf8_unchecked(&[digit]) });
+	gpu_set(3, row, label);
+}
+
+/// Shows the boot prompt.
+///
+/// The `text` parameter is the text typed so far, which must be ASCII and at most 36 bytes long, or
+/// `None` to erase the prompt.
+#[cfg(feature = "boot-prompt")]
+pub fn prompt(text: Option<&[u8]>) {
+	let mut buffer = [b' '; 11 + 36 + 1];
+	if let Some(text) = text {
+		buffer[..11].copy_from_slice(b"Boot UUID: ");
+		buffer[11..11 + text.len()].copy_from_slice(text);
+		buffer[11 + text.len()] = b'_';
+	}
+	// SAFETY: The text is ASCII and the rest of the buffer is ASCII characters, so the whole is
+	// valid UTF-8.
+	gpu_set(1, 1, unsafe { core::str::from_utf8_unchecked(&buffer) });
+}
+
+/// The notice shown while waiting for a key press before booting.
+#[cfg(feature = "boot-delay")]
+const DELAY_NOTICE: &str = "Press any key for boot menu";
+
+/// Shows or erases the notice shown while waiting for a key press before booting.
+#[cfg(feature = "boot-delay")]
+pub fn delay_notice(shown: bool) {
+	if shown {
+		gpu_set(1, 1, DELAY_NOTICE);
+	} else {
+		// SAFETY: The buffer holds only spaces, so it is valid UTF-8.
+		gpu_set(1, 1, unsafe {
+			core::str::from_utf8_unchecked(&[b' '; DELAY_NOTICE.len()])
+		});
+	}
+}
+
+/// Starts a method call on a component.
+///
+/// `true` is returned if the call is complete now. `false` is returned if the call has started but
+/// will not be complete until the next timeslice. A call that cannot be started because the call
+/// queue is full is dropped, since drawing is best-effort, and `true` is returned.
+fn invoke(address: &Address, method: &str, params: &[u8]) -> bool {
+	start_best_effort(address, method, Some(params)).unwrap_or(true)
+}
+
+/// Formats an address as a UUID string.
+///
+/// The `buffer` parameter is where to build the string.
+pub fn format_address<'buffer>(address: &Address, buffer: &'buffer mut [u8; 36]) -> &'buffer str {
+	const HEX_DIGITS: &[u8; 16] = b"0123456789abcdef";
+	let mut position = 0;
+	for (index, byte) in address.as_bytes().iter().enumerate() {
+		if matches!(index, 4 | 6 | 8 | 10) {
+			buffer[position] = b'-';
+			position += 1;
+		}
+		buffer[position] = HEX_DIGITS[usize::from(byte >> 4)];
+		buffer[position + 1] = HEX_DIGITS[usize::from(byte & 0x0F)];
+		position += 2;
+	}
+	// SAFETY: Every byte written is an ASCII character, so the string is valid UTF-8.
+	unsafe { core::str::from_utf8_unchecked(buffer) }
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// Formats `bytes` as a UUID string.
+	fn formatted(bytes: [u8; 16]) -> String {
+		let mut buffer = [0_u8; 36];
+		format_address(&Address::from_bytes(bytes), &mut buffer).to_owned()
+	}
+
+	#[test]
+	fn format_address_writes_known_uuids() {
+		assert_eq!(formatted([0; 16]), "00000000-0000-0000-0000-000000000000");
+		assert_eq!(
+			formatted([0xFF; 16]),
+			"ffffffff-ffff-ffff-ffff-ffffffffffff"
+		);
+		assert_eq!(
+			formatted([
+				0x01, 0x23, 0x45, 0x67, 0x89, 0xAB, 0xCD, 0xEF, 0x10, 0x32, 0x54, 0x76, 0x98, 0xBA,
+				0xDC, 0xFE,
+			]),
+			"01234567-89ab-cdef-1032-547698badcfe"
+		);
+	}
+
+	#[test]
+	fn format_address_writes_every_byte_value() {
+		for byte in 0..=255_u8 {
+			let digits = format!("{byte:02x}");
+			let group = |len| digits.repeat(len);
+			let expected = [group(4), group(2), group(2), group(2), group(6)].join("-");
+			assert_eq!(formatted([byte; 16]), expected);
+		}
+	}
+
+	#[test]
+	fn format_address_places_hyphens_between_groups() {
+		// Each byte is different, so a digit out of place would show.
+		let bytes = core::array::from_fn(|index| u8::try_from(index).unwrap() * 0x11);
+		let text = formatted(bytes);
+		let hyphens: Vec<_> = text.match_indices('-').map(|(index, _)| index).collect();
+		assert_eq!(hyphens, [8, 13, 18, 23]);
+		assert_eq!(text.replace('-', ""), "00112233445566778899aabbccddeeff");
+	}
+}
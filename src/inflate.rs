@@ -0,0 +1,854 @@
+//! Decompression of gzip-compressed images.
+//!
+//! This is a streaming decoder for the gzip file format (RFC 1952) and the DEFLATE compressed data
+//! format inside it (RFC 1951), supporting stored, fixed Huffman, and dynamic Huffman blocks.
+//! Compressed data is fed in as it is read, in chunks of any size, and decompressed data is passed
+//! on as it is produced. Like zlib’s `puff`, it decodes Huffman codes a bit at a time rather than
+//! using lookup tables, to keep the BIOS small.
+
+use super::BiosError;
+use crate::crc32::Crc32;
+
+/// The bytes at the start of a gzip file.
+pub const MAGIC: [u8; 2] = [0x1F, 0x8B];
+
+/// The gzip header flag indicating that the header has a CRC-16.
+const FHCRC: u8 = 0x02;
+
+/// The gzip header flag indicating that the header has an extra field.
+const FEXTRA: u8 = 0x04;
+
+/// The gzip header flag indicating that the header has an original file name.
+const FNAME: u8 = 0x08;
+
+/// The gzip header flag indicating that the header has a comment.
+const FCOMMENT: u8 = 0x10;
+
+/// The size of the DEFLATE sliding window, which is the farthest back a match can refer.
+const WINDOW_SIZE: usize = 32768;
+
+/// The length of the longest Huffman code.
+const MAX_CODE_LEN: usize = 15;
+
+/// The number of literal/length symbols, including the two that never appear in compressed data
+/// but are given lengths in the fixed code.
+const LITERAL_SYMBOLS: usize = 288;
+
+/// The number of distance symbols that a dynamic block can give lengths for.
+const DISTANCE_SYMBOLS: usize = 32;
+
+/// The order in which a dynamic block gives the lengths of the code length code.
+const CODE_LENGTH_ORDER: [u8; 19] = [
+	16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+/// The shortest match length for each length symbol, starting from symbol 257.
+const LENGTH_BASE: [u16; 29] = [
+	3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+	163, 195, 227, 258,
+];
+
+/// The number of extra bits following each length symbol, starting from symbol 257.
+const LENGTH_EXTRA: [u8; 29] = [
+	0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+
+/// The shortest match distance for each distance symbol.
+const DISTANCE_BASE: [u16; 30] = [
+	1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+	2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+
+/// The number of extra bits following each distance symbol.
+const DISTANCE_EXTRA: [u8; 30] = [
+	0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+	13,
+];
+
+/// Bits of compressed data that have been read but not yet consumed.
+///
+/// Bits are consumed from the least significant end. A step works on a copy and stores it back
+/// only once it has all the bits it needs, so that a step that runs out of bits part way through
+/// can be retried from the start when more data arrives.
+#[derive(Clone, Copy)]
+struct Bits {
+	/// The bits.
+	buffer: u64,
+
+	/// The number of bits in [`buffer`](Self::buffer).
+	count: u32,
+}
+
+impl Bits {
+	/// Consumes `n` bits, which must be at most 32, returning them as an integer whose least
+	/// significant bit is the first one consumed.
+	///
+	/// `None` is returned, and nothing is consumed, if there are not enough bits.
+	fn take(&mut self, n: u32) -> Option<u32> {
+		if self.count < n {
+			return None;
+		}
+		// Cast is sound because n ≤ 32.
+		#[allow(clippy::cast_possible_truncation)]
+		let value = (self.buffer & ((1 << n) - 1)) as u32;
+		self.buffer >>= n;
+		self.count -= n;
+		Some(value)
+	}
+
+	/// Discards bits up to the next byte boundary in the compressed data.
+	fn align(&mut self) {
+		// Whole bytes are added to the buffer, so the position in the compressed data is at a byte
+		// boundary whenever the number of bits left is a multiple of eight.
+		self.buffer >>= self.count % 8;
+		self.count -= self.count % 8;
+	}
+}
+
+/// A canonical Huffman code.
+///
+/// The code is stored as the number of codes of each length and the symbols ordered by code,
+/// which is enough to decode it a bit at a time.
+struct Huffman<const N: usize> {
+	/// The number of codes of each length. Element zero is unused.
+	counts: [u16; MAX_CODE_LEN + 1],
+
+	/// The symbols, in order of their codes.
+	symbols: [u16; N],
+}
+
+impl<const N: usize> Huffman<N> {
+	/// Returns an empty code.
+	const fn new() -> Self {
+		Self {
+			counts: [0; MAX_CODE_LEN + 1],
+			symbols: [0; N],
+		}
+	}
+
+	/// Builds the code from the code length of each symbol, zero meaning that the symbol is unused.
+	///
+	/// # Errors
+	/// [`ImageMalformed`](BiosError::ImageMalformed) is returned if the lengths describe more codes
+	/// than there are bit patterns. Incomplete codes are accepted; the unused patterns simply fail
+	/// to decode.
+	fn build(&mut self, lengths: &[u8]) -> Result<(), BiosError> {
+		self.counts = [0; MAX_CODE_LEN + 1];
+		for &len in lengths {
+			self.counts[usize::from(len)] += 1;
+		}
+		let mut left = 1_i32;
+		for &count in &self.counts[1..] {
+			left = (left << 1) - i32::from(count);
+			if left < 0 {
+				return Err(BiosError::ImageMalformed);
+			}
+		}
+		let mut offsets = [0_u16; MAX_CODE_LEN + 1];
+		for len in 1..MAX_CODE_LEN {
+			offsets[len + 1] = offsets[len] + self.counts[len];
+		}
+		for (symbol, &len) in (0..).zip(lengths) {
+			if len != 0 {
+				let offset = &mut offsets[usize::from(len)];
+				self.symbols[usize::from(*offset)] = symbol;
+				*offset += 1;
+			}
+		}
+		Ok(())
+	}
+
+	/// Decodes a symbol.
+	///
+	/// `Some` is returned with the symbol, or `None` if `bits` ran out first.
+	///
+	/// # Errors
+	/// [`ImageMalformed`](BiosError::ImageMalformed) is returned if the bits do not form any code.
+	fn decode(&self, bits: &mut Bits) -> Result<Option<u16>, BiosError> {
+		// Codes are packed starting from their most significant bit. Among codes of the same
+		// length, the numerically smallest is first, and each length’s first code follows on from
+		// the previous length’s last.
+		let mut code = 0_usize;
+		let mut first = 0_usize;
+		let mut index = 0_usize;
+		for &count in &self.counts[1..] {
+			let Some(bit) = bits.take(1) else {
+				return Ok(None);
+			};
+			code |= bit as usize;
+			let count = usize::from(count);
+			if code < first + count {
+				return Ok(Some(self.symbols[index + code - first]));
+			}
+			index += count;
+			first = (first + count) << 1;
+			code <<= 1;
+		}
+		Err(BiosError::ImageMalformed)
+	}
+}
+
+/// The part of the gzip file expected next.
+#[derive(Clone, Copy)]
+enum State {
+	/// The magic number, compression method, and flags at the start of the header.
+	Magic,
+
+	/// Some bytes of the header that are not needed; the number of them is given.
+	Skip(u16),
+
+	/// The length of the header’s extra field.
+	ExtraLength,
+
+	/// A zero-terminated string in the header.
+	String,
+
+	/// The header at the start of a DEFLATE block.
+	BlockHeader,
+
+	/// The data of a stored block; the number of bytes left is given.
+	Stored(u16),
+
+	/// The lengths of the code length code of a dynamic block.
+	CodeLengthCodes {
+		/// The number of lengths read so far.
+		index: u8,
+
+		/// The number of lengths given.
+		count: u8,
+
+		/// The number of literal/length code lengths given afterwards.
+		literals: u16,
+
+		/// The total number of code lengths given afterwards.
+		total: u16,
+	},
+
+	/// The code lengths of the literal/length and distance codes of a dynamic block.
+	CodeLengths {
+		/// The number of lengths read so far.
+		index: u16,
+
+		/// The number of literal/length code lengths given.
+		literals: u16,
+
+		/// The total number of code lengths given.
+		count: u16,
+	},
+
+	/// The compressed data of a fixed or dynamic block.
+	Codes,
+
+	/// The CRC-32 of the decompressed data, in the trailer.
+	Crc,
+
+	/// The size of the decompressed data, in the trailer.
+	Size,
+
+	/// Nothing; the whole file has been decompressed.
+	Done,
+}
+
+/// A decompressor for a gzip file.
+pub struct Inflater {
+	/// The part of the file expected next.
+	state: State,
+
+	/// The header flags for fields that have not been skipped yet.
+	flags: u8,
+
+	/// Whether the current block is the last one.
+	last: bool,
+
+	/// Compressed data that has been read but not yet consumed.
+	bits: Bits,
+
+	/// The most recently decompressed data, which matches can refer back to.
+	window: [u8; WINDOW_SIZE],
+
+	/// The position in [`window`](Self::window) of the next byte to decompress.
+	pos: usize,
+
+	/// The position in [`window`](Self::window) of the first byte not yet passed on.
+	flushed: usize,
+
+	/// Whether [`window`](Self::window) has been filled at least once.
+	full: bool,
+
+	/// The code lengths of a dynamic block being read.
+	lengths: [u8; LITERAL_SYMBOLS + DISTANCE_SYMBOLS],
+
+	/// The literal/length code of the current block.
+	literal: Huffman<LITERAL_SYMBOLS>,
+
+	/// The distance code of the current block, or the code length code while a dynamic block’s
+	/// code lengths are being read.
+	distance: Huffman<DISTANCE_SYMBOLS>,
+
+	/// The checksum of the data passed on so far.
+	crc: Crc32,
+
+	/// The number of bytes passed on so far, modulo 2³².
+	size: u32,
+}
+
+impl Inflater {
+	/// Returns a decompressor that has not been fed any data.
+	// This is only used to initialize a static, so the window is never on the stack.
+	#[allow(clippy::large_stack_arrays)]
+	pub const fn new() -> Self {
+		Self {
+			state: State::Magic,
+			flags: 0,
+			last: false,
+			bits: Bits {
+				buffer: 0,
+				count: 0,
+			},
+			window: [0; WINDOW_SIZE],
+			pos: 0,
+			flushed: 0,
+			full: false,
+			lengths: [0; LITERAL_SYMBOLS + DISTANCE_SYMBOLS],
+			literal: Huffman::new(),
+			distance: Huffman::new(),
+			crc: Crc32::new(),
+			size: 0,
+		}
+	}
+
+	/// Forgets any data fed so far, so that a new file can be decompressed.
+	#[cfg(feature = "call-timeout")]
+	pub fn reset(&mut self) {
+		self.state = State::Magic;
+		self.bits = Bits {
+			buffer: 0,
+			count: 0,
+		};
+		self.pos = 0;
+		self.flushed = 0;
+		self.full = false;
+		self.crc = Crc32::new();
+		self.size = 0;
+	}
+
+	/// Returns whether the whole file, including its trailer, has been decompressed.
+	pub fn finished(&self) -> bool {
+		matches!(self.state, State::Done)
+	}
+
+	/// Decompresses the next chunk of the file.
+	///
+	/// The decompressed data is passed to `sink`, in one or more pieces. Any data after the end of
+	/// the file is ignored.
+	///
+	/// # Errors
+	/// * [`ImageMalformed`](BiosError::ImageMalformed) is returned if the file is not a valid
+	///   gzip file or its checksum or size does not match the decompressed data.
+	/// * Any error returned by `sink` is passed on.
+	pub fn feed(
+		&mut self,
+		mut input: &[u8],
+		mut sink: impl FnMut(&[u8]) -> Result<(), BiosError>,
+	) -> Result<(), BiosError> {
+		loop {
+			// Top up the bit buffer. This leaves at least 57 bits unless the input has run out,
+			// which is more than any step needs.
+			while self.bits.count <= 56 {
+				let Some((&byte, rest)) = input.split_first() else {
+					break;
+				};
+				self.bits.buffer |= u64::from(byte) << self.bits.count;
+				self.bits.count += 8;
+				input = rest;
+			}
+			if !self.step(&mut sink)? {
+				break;
+			}
+		}
+		self.flush(&mut sink)
+	}
+
+	/// Performs one step of decompression.
+	///
+	/// `true` is returned if the step was done, or `false` if there are not enough bits to do it
+	/// (or the file has been decompressed), in which case nothing is consumed.
+	///
+	/// # Errors
+	/// As for [`feed`](Self::feed).
+	fn step(
+		&mut self,
+		sink: &mut impl FnMut(&[u8]) -> Result<(), BiosError>,
+	) -> Result<bool, BiosError> {
+		let mut bits = self.bits;
+		match self.state {
+			State::Magic => {
+				let Some(header) = bits.take(32) else {
+					return Ok(false);
+				};
+				// Check the magic number and that the compression method is DEFLATE (8). Reserved
+				// flags mean there is something in the header that cannot be skipped.
+				let [id1, id2, method, flags] = header.to_le_bytes();
+				if [id1, id2] != MAGIC || method != 8 || flags & 0xE0 != 0 {
+					return Err(BiosError::ImageMalformed);
+				}
+				self.flags = flags;
+				// Skip the modification time, extra flags, and operating system.
+				self.state = State::Skip(6);
+			}
+			State::Skip(count) => {
+				if bits.take(8).is_none() {
+					return Ok(false);
+				}
+				self.state = if count > 1 {
+					State::Skip(count - 1)
+				} else {
+					self.header_field()
+				};
+			}
+			State::ExtraLength => {
+				let Some(len) = bits.take(16) else {
+					return Ok(false);
+				};
+				// Cast is sound because only 16 bits were taken.
+				#[allow(clippy::cast_possible_truncation)]
+				let len = len as u16;
+				self.state = if len != 0 {
+					State::Skip(len)
+				} else {
+					self.header_field()
+				};
+			}
+			State::String => {
+				let Some(byte) = bits.take(8) else {
+					return Ok(false);
+				};
+				if byte == 0 {
+					self.state = self.header_field();
+				}
+			}
+			State::BlockHeader => {
+				let Some(header) = bits.take(3) else {
+					return Ok(false);
+				};
+				self.last = header & 1 != 0;
+				match header >> 1 {
+					0 => {
+						// A stored block starts at a byte boundary with its length and the length’s
+						// ones’ complement.
+						bits.align();
+						let Some(len) = bits.take(32) else {
+							return Ok(false);
+						};
+						// Casts are sound because each half is 16 bits.
+						#[allow(clippy::cast_possible_truncation)]
+						let (len, complement) = (len as u16, (len >> 16) as u16);
+						if len != !complement {
+							return Err(BiosError::ImageMalformed);
+						}
+						self.state = State::Stored(len);
+					}
+					1 => {
+						self.lengths[..144].fill(8);
+						self.lengths[144..256].fill(9);
+						self.lengths[256..280].fill(7);
+						self.lengths[280..LITERAL_SYMBOLS].fill(8);
+						self.literal.build(&self.lengths[..LITERAL_SYMBOLS])?;
+						self.distance.build(&[5; 30])?;
+						self.state = State::Codes;
+					}
+					2 => {
+						let Some(counts) = bits.take(14) else {
+							return Ok(false);
+						};
+						// Casts are sound because each field is at most 5 bits.
+						#[allow(clippy::cast_possible_truncation)]
+						let (literals, distances, code_lengths) = (
+							(counts & 0x1F) as u16 + 257,
+							((counts >> 5) & 0x1F) as u16 + 1,
+							(counts >> 10) as u8 + 4,
+						);
+						if literals > 286 || distances > 30 {
+							return Err(BiosError::ImageMalformed);
+						}
+						self.lengths[..CODE_LENGTH_ORDER.len()].fill(0);
+						self.state = State::CodeLengthCodes {
+							index: 0,
+							count: code_lengths,
+							literals,
+							total: literals + distances,
+						};
+					}
+					_ => return Err(BiosError::ImageMalformed),
+				}
+			}
+			State::Stored(remaining) => {
+				if remaining == 0 {
+					self.end_block(&mut bits);
+				} else {
+					let Some(byte) = bits.take(8) else {
+						return Ok(false);
+					};
+					self.bits = bits;
+					// Cast is sound because only 8 bits were taken.
+					#[allow(clippy::cast_possible_truncation)]
+					self.put(byte as u8, sink)?;
+					self.state = State::Stored(remaining - 1);
+				}
+			}
+			State::CodeLengthCodes {
+				index,
+				count,
+				literals,
+				total,
+			} => {
+				if index == count {
+					self.distance
+						.build(&self.lengths[..CODE_LENGTH_ORDER.len()])?;
+					self.state = State::CodeLengths {
+						index: 0,
+						literals,
+						count: total,
+					};
+				} else {
+					let Some(len) = bits.take(3) else {
+						return Ok(false);
+					};
+					// Cast is sound because only 3 bits were taken.
+					#[allow(clippy::cast_possible_truncation)]
+					let len = len as u8;
+					self.lengths[usize::from(CODE_LENGTH_ORDER[usize::from(index)])] = len;
+					self.state = State::CodeLengthCodes {
+						index: index + 1,
+						count,
+						literals,
+						total,
+					};
+				}
+			}
+			State::CodeLengths {
+				index,
+				literals,
+				count,
+			} => {
+				if index == count {
+					let literals = usize::from(literals);
+					let count = usize::from(count);
+					self.literal.build(&self.lengths[..literals])?;
+					self.distance.build(&self.lengths[literals..count])?;
+					self.state = State::Codes;
+				} else {
+					let Some(symbol) = self.distance.decode(&mut bits)? else {
+						return Ok(false);
+					};
+					// Symbols below 16 are lengths. The others repeat the previous length (16) or
+					// zero (17 and 18) a number of times given by some extra bits.
+					let (len, extra, base) = match symbol {
+						0..=15 => (symbol, 0, 1),
+						16 => {
+							if index == 0 {
+								return Err(BiosError::ImageMalformed);
+							}
+							(u16::from(self.lengths[usize::from(index) - 1]), 2, 3)
+						}
+						17 => (0, 3, 3),
+						_ => (0, 7, 11),
+					};
+					let Some(repeat) = bits.take(extra) else {
+						return Ok(false);
+					};
+					// Cast is sound because at most 7 bits were taken.
+					#[allow(clippy::cast_possible_truncation)]
+					let end = index + base + repeat as u16;
+					if end > count {
+						return Err(BiosError::ImageMalformed);
+					}
+					// Cast is sound because lengths are at most 15.
+					#[allow(clippy::cast_possible_truncation)]
+					self.lengths[usize::from(index)..usize::from(end)].fill(len as u8);
+					self.state = State::CodeLengths {
+						index: end,
+						literals,
+						count,
+					};
+				}
+			}
+			State::Codes => {
+				let Some(symbol) = self.literal.decode(&mut bits)? else {
+					return Ok(false);
+				};
+				match symbol {
+					0..=255 => {
+						self.bits = bits;
+						// Cast is sound because the symbol was just checked to fit.
+						#[allow(clippy::cast_possible_truncation)]
+						self.put(symbol as u8, sink)?;
+					}
+					256 => self.end_block(&mut bits),
+					_ => {
+						// A match: a length, then a distance back into the window.
+						let symbol = usize::from(symbol - 257);
+						if symbol >= LENGTH_BASE.len() {
+							return Err(BiosError::ImageMalformed);
+						}
+						let Some(extra) = bits.take(LENGTH_EXTRA[symbol].into()) else {
+							return Ok(false);
+						};
+						let len = usize::from(LENGTH_BASE[symbol]) + extra as usize;
+						let Some(symbol) = self.distance.decode(&mut bits)? else {
+							return Ok(false);
+						};
+						let symbol = usize::from(symbol);
+						if symbol >= DISTANCE_BASE.len() {
+							return Err(BiosError::ImageMalformed);
+						}
+						let Some(extra) = bits.take(DISTANCE_EXTRA[symbol].into()) else {
+							return Ok(false);
+						};
+						let distance = usize::from(DISTANCE_BASE[symbol]) + extra as usize;
+						if !self.full && distance > self.pos {
+							return Err(BiosError::ImageMalformed);
+						}
+						self.bits = bits;
+						for _ in 0..len {
+							let byte =
+								self.window[(self.pos + WINDOW_SIZE - distance) % WINDOW_SIZE];
+							self.put(byte, sink)?;
+						}
+					}
+				}
+			}
+			State::Crc => {
+				let Some(crc) = bits.take(32) else {
+					return Ok(false);
+				};
+				self.flush(sink)?;
+				if crc != self.crc.finish() {
+					return Err(BiosError::ImageMalformed);
+				}
+				self.state = State::Size;
+			}
+			State::Size => {
+				let Some(size) = bits.take(32) else {
+					return Ok(false);
+				};
+				if size != self.size {
+					return Err(BiosError::ImageMalformed);
+				}
+				self.state = State::Done;
+			}
+			State::Done => return Ok(false),
+		}
+		self.bits = bits;
+		Ok(true)
+	}
+
+	/// Returns the part of the header to expect after the fields read so far, and forgets about the
+	/// field it is the start of.
+	fn header_field(&mut self) -> State {
+		for (flag, state) in [
+			(FEXTRA, State::ExtraLength),
+			(FNAME, State::String),
+			(FCOMMENT, State::String),
+			(FHCRC, State::Skip(2)),
+		] {
+			if self.flags & flag != 0 {
+				self.flags &= !flag;
+				return state;
+			}
+		}
+		State::BlockHeader
+	}
+
+	/// Moves on from the end of a block to the next block or, after the last block, the trailer,
+	/// which starts at a byte boundary.
+	fn end_block(&mut self, bits: &mut Bits) {
+		self.state = if self.last {
+			bits.align();
+			State::Crc
+		} else {
+			State::BlockHeader
+		};
+	}
+
+	/// Adds a decompressed byte to the window, passing the window on to `sink` if it is full.
+	///
+	/// # Errors
+	/// Any error returned by `sink` is passed on.
+	fn put(
+		&mut self,
+		byte: u8,
+		sink: &mut impl FnMut(&[u8]) -> Result<(), BiosError>,
+	) -> Result<(), BiosError> {
+		self.window[self.pos] = byte;
+		self.pos += 1;
+		if self.pos == WINDOW_SIZE {
+			self.flush(sink)?;
+			self.pos = 0;
+			self.flushed = 0;
+			self.full = true;
+		}
+		Ok(())
+	}
+
+	/// Passes the decompressed data that has not yet been passed on to `sink`.
+	///
+	/// # Errors
+	/// Any error returned by `sink` is passed on.
+	fn flush(
+		&mut self,
+		sink: &mut impl FnMut(&[u8]) -> Result<(), BiosError>,
+	) -> Result<(), BiosError> {
+		let data = &self.window[self.flushed..self.pos];
+		self.crc.update(data);
+		// Cast is sound because usize and u32 are the same size on Wasm.
+		#[allow(clippy::cast_possible_truncation)]
+		{
+			self.size = self.size.wrapping_add(data.len() as u32);
+		}
+		self.flushed = self.pos;
+		sink(data)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// `hello, hello, hello!\n`, compressed with gzip -0, which uses a stored block.
+	const STORED: &[u8] = &[
+		0x1F, 0x8B, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x04, 0x03, 0x01, 0x15, 0x00, 0xEA, 0xFF,
+		0x68, 0x65, 0x6C, 0x6C, 0x6F, 0x2C, 0x20, 0x68, 0x65, 0x6C, 0x6C, 0x6F, 0x2C, 0x20, 0x68,
+		0x65, 0x6C, 0x6C, 0x6F, 0x21, 0x0A, 0x63, 0xE2, 0x9B, 0x7A, 0x15, 0x00, 0x00, 0x00,
+	];
+
+	/// `hello, hello, hello!\n`, compressed with gzip -9, which uses a fixed Huffman block.
+	const FIXED: &[u8] = &[
+		0x1F, 0x8B, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x03, 0xCB, 0x48, 0xCD, 0xC9, 0xC9,
+		0xD7, 0x51, 0xC8, 0x40, 0xA2, 0x14, 0xB9, 0x00, 0x63, 0xE2, 0x9B, 0x7A, 0x15, 0x00, 0x00,
+		0x00,
+	];
+
+	/// The output of [`lines`], compressed with gzip -9, which uses a dynamic Huffman block.
+	const DYNAMIC: &[u8] = &[
+		0x1F, 0x8B, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x03, 0x9D, 0xD2, 0x5D, 0x16, 0x42,
+		0x50, 0x18, 0x85, 0xE1, 0x7B, 0xA3, 0xF8, 0x86, 0x60, 0x4B, 0x3F, 0x9A, 0x8D, 0x38, 0x4A,
+		0x0E, 0x27, 0x0A, 0x65, 0xF4, 0x96, 0x66, 0xE0, 0xBD, 0xDE, 0xEB, 0xBD, 0xDA, 0x8F, 0xAF,
+		0x3B, 0x67, 0xF1, 0xD5, 0x3E, 0x0F, 0x67, 0xFD, 0x58, 0x17, 0x8D, 0xDD, 0x86, 0x30, 0x77,
+		0x56, 0x85, 0xAF, 0x3D, 0xC7, 0xF6, 0xF5, 0xB6, 0x30, 0xB9, 0xE1, 0x3F, 0xFB, 0x7C, 0xF9,
+		0x59, 0x19, 0xEE, 0x91, 0xDF, 0x1A, 0x81, 0x26, 0x01, 0xCD, 0x01, 0x34, 0x29, 0x68, 0x8E,
+		0xA0, 0x39, 0x81, 0xE6, 0x0C, 0x9A, 0x0B, 0x68, 0x32, 0xF2, 0x29, 0x82, 0x40, 0x24, 0x88,
+		0x50, 0x10, 0xB1, 0x20, 0x82, 0x41, 0x44, 0x83, 0x08, 0x07, 0x11, 0x0F, 0x22, 0x20, 0xB4,
+		0x53, 0xC4, 0x0A, 0x94, 0xA7, 0x05, 0x11, 0x1A, 0x04, 0x00, 0x00,
+	];
+
+	/// 40000 zero bytes, compressed with gzip -9, which refers back across the whole window.
+	const ZEROS: &[u8] = &[
+		0x1F, 0x8B, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x03, 0xED, 0xC1, 0x31, 0x01, 0x00,
+		0x00, 0x00, 0xC2, 0xA0, 0xF5, 0x4F, 0xED, 0x65, 0x0B, 0xA0, 0x00, 0x00, 0x00, 0x00, 0x00,
+		0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+		0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+		0x00, 0x00, 0x00, 0x80, 0x1B, 0x79, 0x44, 0xA9, 0xE6, 0x40, 0x9C, 0x00, 0x00,
+	];
+
+	/// Returns the text compressed in [`DYNAMIC`].
+	fn lines() -> Vec<u8> {
+		(0..20)
+			.flat_map(|i| {
+				format!("line {i}: the quick brown fox jumps over the lazy dog\n").into_bytes()
+			})
+			.collect()
+	}
+
+	/// Decompresses a gzip file, feeding it in chunks of `chunk` bytes.
+	///
+	/// The decompressed data is returned, along with whether the whole file was decompressed.
+	fn inflate(file: &[u8], chunk: usize) -> Result<(Vec<u8>, bool), BiosError> {
+		let mut inflater = Box::new(Inflater::new());
+		let mut output = Vec::new();
+		for piece in file.chunks(chunk) {
+			inflater.feed(piece, |data| {
+				output.extend_from_slice(data);
+				Ok(())
+			})?;
+		}
+		Ok((output, inflater.finished()))
+	}
+
+	/// Checks that a gzip file decompresses to `expected`, whether fed all at once or a byte at a
+	/// time.
+	fn check(file: &[u8], expected: &[u8]) {
+		for chunk in [file.len(), 1] {
+			let Ok((output, finished)) = inflate(file, chunk) else {
+				panic!("decompression failed with chunk size {chunk}");
+			};
+			assert!(output == expected, "wrong output with chunk size {chunk}");
+			assert!(finished);
+		}
+	}
+
+	#[test]
+	fn inflate_stored_block() {
+		check(STORED, b"hello, hello, hello!\n");
+	}
+
+	#[test]
+	fn inflate_fixed_block() {
+		check(FIXED, b"hello, hello, hello!\n");
+	}
+
+	#[test]
+	fn inflate_dynamic_block() {
+		check(DYNAMIC, &lines());
+	}
+
+	#[test]
+	fn inflate_across_window() {
+		check(ZEROS, &vec![0; 40000]);
+	}
+
+	#[test]
+	fn inflate_skips_header_fields() {
+		// Add an original file name and a comment to the header.
+		let mut file = FIXED[..10].to_vec();
+		file[3] = FNAME | FCOMMENT;
+		file.extend_from_slice(b"init.wasm\0a comment\0");
+		file.extend_from_slice(&FIXED[10..]);
+		check(&file, b"hello, hello, hello!\n");
+	}
+
+	#[test]
+	fn inflate_ignores_trailing_data() {
+		let mut file = FIXED.to_vec();
+		file.extend_from_slice(b"junk");
+		check(&file, b"hello, hello, hello!\n");
+	}
+
+	#[test]
+	fn inflate_stops_at_truncation() {
+		let Ok((_, finished)) = inflate(&DYNAMIC[..DYNAMIC.len() - 1], DYNAMIC.len()) else {
+			panic!("decompression failed");
+		};
+		assert!(!finished);
+	}
+
+	#[test]
+	fn inflate_rejects_bad_files() {
+		// A wrong magic number, a wrong CRC, and a wrong size.
+		for index in [0, FIXED.len() - 8, FIXED.len() - 4] {
+			let mut file = FIXED.to_vec();
+			file[index] ^= 1;
+			assert!(matches!(
+				inflate(&file, file.len()),
+				Err(BiosError::ImageMalformed)
+			));
+		}
+	}
+
+	#[test]
+	fn inflate_passes_on_sink_errors() {
+		let mut inflater = Box::new(Inflater::new());
+		let result = inflater.feed(DYNAMIC, |_| Err(BiosError::InternalError));
+		assert!(matches!(result, Err(BiosError::InternalError)));
+	}
+}
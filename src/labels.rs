@@ -0,0 +1,136 @@
+//! Collection of the labels of the filesystems examined by a scan, so that they can be listed if
+//! no bootable medium is found.
+
+use sync_unsafe_cell::SyncUnsafeCell;
+
+/// The message prefix used when no bootable medium is found.
+const PREFIX: &str = "BIOS: no bootable medium (";
+
+/// The maximum number of bytes of labels, including separators, that are listed.
+const MAX_LEN: usize = 160;
+
+/// The labels listed so far, separated by commas.
+struct Labels {
+	/// The labels, encoded as UTF-8, in the first `len` bytes.
+	bytes: [u8; MAX_LEN],
+
+	/// The number of valid bytes in `bytes`.
+	len: usize,
+
+	/// Whether any label has been left out for lack of room.
+	truncated: bool,
+}
+
+/// The labels of the filesystems examined so far.
+static LABELS: SyncUnsafeCell<Labels> = SyncUnsafeCell::new(Labels::new());
+
+impl Labels {
+	/// Returns an empty list.
+	const fn new() -> Self {
+		Self {
+			bytes: [0; MAX_LEN],
+			len: 0,
+			truncated: false,
+		}
+	}
+
+	/// Adds a label to the list, unless it is full.
+	fn record(&mut self, label: Option<&str>) {
+		let label = label.unwrap_or("unlabelled");
+		let separator: &[u8] = if self.len == 0 { b"" } else { b", " };
+		let start = self.len + separator.len();
+		let end = start + label.len();
+		if self.truncated || end > MAX_LEN {
+			self.truncated = true;
+			return;
+		}
+		self.bytes[self.len..start].copy_from_slice(separator);
+		self.bytes[start..end].copy_from_slice(label.as_bytes());
+		self.len = end;
+	}
+
+	/// Builds the message listing the labels in `buffer`.
+	fn message<'buffer>(&self, buffer: &'buffer mut [u8; MESSAGE_LEN]) -> &'buffer str {
+		let mut len = PREFIX.len();
+		buffer[..len].copy_from_slice(PREFIX.as_bytes());
+		buffer[len..len + self.len].copy_from_slice(&self.bytes[..self.len]);
+		len += self.len;
+		let suffix: &[u8] = if self.truncated { b", ...)" } else { b")" };
+		buffer[len..len + suffix.len()].copy_from_slice(suffix);
+		len += suffix.len();
+		// SAFETY: The prefix and suffix are strings, and the labels are whole strings joined by
+		// ASCII separators, so the message is valid UTF-8.
+		unsafe { core::str::from_utf8_unchecked(&buffer[..len]) }
+	}
+}
+
+/// Records the label of a filesystem examined by a scan.
+///
+/// The `label` parameter is the filesystem’s label, or `None` if it has none or it could not be
+/// fetched. Once the list is full, further labels are left out.
+pub fn record(label: Option<&str>) {
+	// SAFETY: Wasm is single-threaded, and LABELS is only accessed in this module, without holding
+	// any references across calls.
+	unsafe { &mut *LABELS.get() }.record(label);
+}
+
+/// The size of the buffer needed to build the message.
+pub const MESSAGE_LEN: usize = PREFIX.len() + MAX_LEN + 6;
+
+/// Builds the message reported when no bootable medium is found.
+///
+/// The message lists the labels recorded so far. The `buffer` parameter is where to build it.
+pub fn message(buffer: &mut [u8; MESSAGE_LEN]) -> &str {
+	// SAFETY: Wasm is single-threaded, and LABELS is only accessed in this module, without holding
+	// any references across calls.
+	unsafe { &*LABELS.get() }.message(buffer)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// Returns the message built from `labels`.
+	fn message_of(labels: &Labels) -> String {
+		labels.message(&mut [0; MESSAGE_LEN]).to_owned()
+	}
+
+	#[test]
+	fn empty_list() {
+		assert_eq!(message_of(&Labels::new()), "BIOS: no bootable medium ()");
+	}
+
+	#[test]
+	fn labels_and_missing_labels() {
+		let mut labels = Labels::new();
+		labels.record(Some("boot"));
+		assert_eq!(message_of(&labels), "BIOS: no bootable medium (boot)");
+		labels.record(None);
+		labels.record(Some("data"));
+		assert_eq!(
+			message_of(&labels),
+			"BIOS: no bootable medium (boot, unlabelled, data)"
+		);
+	}
+
+	#[test]
+	fn truncated_list() {
+		let mut labels = Labels::new();
+		// The first label takes 10 bytes, as does each later one with its separator, so 16 labels
+		// fill the list exactly.
+		labels.record(Some("0123456789"));
+		for _ in 0..15 {
+			labels.record(Some("abcdefgh"));
+		}
+		assert!(!labels.truncated);
+		assert_eq!(labels.len, MAX_LEN);
+		// Nothing more fits, and once a label is left out, no later one is listed either, even if
+		// it would fit.
+		labels.record(Some("x"));
+		labels.record(Some(""));
+		let message = message_of(&labels);
+		assert!(message.ends_with("abcdefgh, ...)"), "{message}");
+		assert_eq!(message.matches("abcdefgh").count(), 15);
+		assert_eq!(message.len(), MESSAGE_LEN);
+	}
+}
@@ -0,0 +1,33 @@
+//! The parts of the OC-Wasm BIOS that other crates can reuse.
+//!
+//! The BIOS itself is the binary in this package. The [`cbor`] module, which it uses to encode
+//! method call parameters and decode method call results, is also built as this library, so that
+//! other `no_std` crates can depend on it. It needs neither an allocator nor the BIOS’s state
+//! machine, and reports failures with OC-Wasm-safe’s [`Error`](oc_wasm_safe::error::Error).
+
+#![cfg_attr(not(test), no_std)]
+#![warn(
+	// Turn on extra language lints. The library uses only some of the package’s dependencies, so
+	// unused_crate_dependencies is left off.
+	future_incompatible,
+	missing_abi,
+	missing_debug_implementations,
+	missing_docs,
+	nonstandard_style,
+	rust_2018_idioms,
+	single_use_lifetimes,
+	trivial_casts,
+	trivial_numeric_casts,
+	unused,
+	unused_import_braces,
+	unused_lifetimes,
+	unused_qualifications,
+
+	// Turn on extra Clippy lints.
+	clippy::cargo,
+	clippy::pedantic,
+)]
+// Shadowing is useful when decoding a bunch of CBOR headers one after another.
+#![allow(clippy::shadow_unrelated)]
+
+pub mod cbor;
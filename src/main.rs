@@ -1,5 +1,4 @@
-#![no_main]
-#![no_std]
+#![cfg_attr(not(test), no_main, no_std)]
 #![warn(
 	// Turn on extra language lints.
 	future_incompatible,
@@ -26,135 +25,480 @@
 // Uninlining the state machine steps produces larger code.
 #![allow(clippy::too_many_lines)]
 
-use core::convert::TryInto;
+// The boot record is written to the EEPROM’s volatile data area, which would clobber a boot
+// configuration kept there.
+#[cfg(all(feature = "boot-record", feature = "volatile-config"))]
+compile_error!("the boot-record and volatile-config features cannot be used together");
+
+mod config;
+#[cfg(any(feature = "checksum", feature = "crc-report", feature = "gzip"))]
+mod crc32;
+#[cfg(feature = "display")]
+mod display;
+#[cfg(feature = "gzip")]
+mod inflate;
+#[cfg(feature = "scan-labels")]
+mod labels;
+mod scan;
+#[cfg(test)]
+mod sim;
+#[cfg(any(feature = "trace", feature = "verbose"))]
+mod trace;
+#[cfg(feature = "verbose")]
+mod verbose;
+
+use config::Config;
 use core::mem::replace;
+#[cfg(not(test))]
 use core::panic::PanicInfo;
 use core::ptr;
-use oc_wasm_safe::{
-	component, computer, descriptor, descriptor::AsDescriptor, error, execute, Address,
-};
+use descriptor::AsDescriptor;
+use oc_wasm_bios::cbor;
+#[cfg(not(test))]
+use oc_wasm_safe::{component, computer, descriptor, execute};
+use oc_wasm_safe::{error, Address};
+#[cfg(not(test))]
 use oc_wasm_sys::component as component_sys;
+// The tests run the BIOS on a simulated computer instead of OC-Wasm.
+#[cfg(test)]
+use oc_wasm_sys as _;
+use scan::Scan;
+#[cfg(test)]
+use sim::{component, component_sys, computer, descriptor, execute};
 use sync_unsafe_cell::SyncUnsafeCell;
 
 /// The panic handler used for the BIOS.
+///
+/// With the `panic-message` feature, the computer is halted with a message saying where the panic
+/// happened, which helps with debugging.
+#[cfg(not(test))]
 #[panic_handler]
-fn handle_panic(_: &PanicInfo<'_>) -> ! {
+#[cfg_attr(not(feature = "panic-message"), allow(unused_variables))]
+fn handle_panic(info: &PanicInfo<'_>) -> ! {
+	#[cfg(feature = "panic-message")]
+	report_panic(
+		info.location()
+			.map(|location| (location.file(), location.line())),
+	);
 	// Do the absolute bare minimum to stop execution.
+	#[cfg(not(feature = "panic-message"))]
 	core::arch::wasm32::unreachable();
 }
 
+/// The message reported when the BIOS panics.
+#[cfg(feature = "panic-message")]
+const PANIC_MESSAGE: &str = "BIOS: panic";
+
+/// The length of the longest panic message that includes a location.
+///
+/// This leaves room for [`PANIC_MESSAGE`], ` at `, a source file name of up to 16 bytes, a colon,
+/// and a line number of up to 10 digits.
+#[cfg(feature = "panic-message")]
+const PANIC_MESSAGE_LEN: usize = PANIC_MESSAGE.len() + 4 + 16 + 1 + 10;
+
+/// Halts the computer with the message reported when the BIOS panics.
+///
+/// The `location` parameter is the name of the source file and the line at which the panic
+/// happened, if they are known.
+#[cfg(feature = "panic-message")]
+fn report_panic(location: Option<(&str, u32)>) -> ! {
+	computer::error(panic_message(location, &mut [0; PANIC_MESSAGE_LEN]))
+}
+
+/// Builds the message reported when the BIOS panics.
+///
+/// The message is [`PANIC_MESSAGE`], followed by the name of the source file and the line at
+/// which the panic happened, if they are known and fit in `buffer`.
+#[cfg(feature = "panic-message")]
+fn panic_message<'buffer>(
+	location: Option<(&str, u32)>,
+	buffer: &'buffer mut [u8; PANIC_MESSAGE_LEN],
+) -> &'buffer str {
+	let mut len = PANIC_MESSAGE.len();
+	buffer[..len].copy_from_slice(PANIC_MESSAGE.as_bytes());
+	if let Some((file, line)) = location {
+		// Format the line number, filling the digits in from the end.
+		let mut digits = [0_u8; 10];
+		let mut start = digits.len();
+		let mut value = line;
+		loop {
+			start -= 1;
+			// Cast is sound because the digit is less than ten.
+			#[allow(clippy::cast_possible_truncation)]
+			{
+				digits[start] = b'0' + (value % 10) as u8;
+			}
+			value /= 10;
+			if value == 0 {
+				break;
+			}
+		}
+		let file = file.rsplit('/').next().unwrap_or_default();
+		let parts: [&[u8]; 4] = [b" at ", file.as_bytes(), b":", &digits[start..]];
+		if parts.iter().map(|part| part.len()).sum::<usize>() <= buffer.len() - len {
+			for part in parts {
+				buffer[len..len + part.len()].copy_from_slice(part);
+				len += part.len();
+			}
+		}
+	}
+	// SAFETY: The message and the file name are strings, and every other byte added is an ASCII
+	// character, so the result is valid UTF-8.
+	unsafe { core::str::from_utf8_unchecked(&buffer[..len]) }
+}
+
 /// Reports an internal error with no more detailed message.
+#[cold]
 fn internal_error() -> ! {
-	computer::error("BIOS: internal error")
+	BiosError::InternalError.fail()
+}
+
+/// Reports that fetching a method call’s result failed.
+///
+/// The `rc` parameter is the negative value returned by `invoke_end`. Such a failure indicates a
+/// bug in the BIOS, so it is reported as an internal error. With the `result-check` feature, a
+/// result that did not fit in the buffer is instead reported as such.
+#[cfg(feature = "result-check")]
+#[cold]
+fn call_failed(rc: isize) -> ! {
+	match error::Error::from_isize(rc) {
+		Err(error::Error::BufferTooShort) => BiosError::ResultTooLarge,
+		_ => BiosError::InternalError,
+	}
+	.fail()
+}
+#[cfg(not(feature = "result-check"))]
+#[inline]
+fn call_failed(_: isize) -> ! {
+	internal_error()
 }
 
-/// The CBOR major types.
+/// The ways in which booting can fail.
 #[derive(Clone, Copy, Eq, PartialEq)]
-enum CborMajorType {
-	/// The data item is an unsigned integer whose value is equal to the count. There is no
-	/// payload.
-	UnsignedInteger,
+enum BiosError {
+	/// There is no EEPROM.
+	NoEeprom,
+
+	/// No bootable medium was found.
+	NoBootMedium,
+
+	/// There are no storage devices to boot from at all.
+	NoStorage,
+
+	/// The EEPROM returned a malformed boot configuration.
+	EepromDataMalformed,
+
+	/// A filesystem returned a malformed result when opening a file.
+	OpenFailed,
+
+	/// A filesystem returned a malformed result when reading the image file.
+	///
+	/// With the skip-bad-media feature, only some configurations report this.
+	#[cfg_attr(feature = "skip-bad-media", allow(dead_code))]
+	ReadIoError,
+
+	/// A filesystem returned a malformed result when reading the boot configuration file.
+	#[cfg(feature = "file-config")]
+	ConfigIoError,
+
+	/// The boot configuration file is too large.
+	#[cfg(feature = "file-config")]
+	ConfigTooLarge,
 
-	/// The data item is a negative integer whose value is −1−count. There is no payload.
-	NegativeInteger,
+	/// A filesystem returned a malformed label.
+	#[cfg(any(
+		feature = "boot-menu",
+		feature = "install-once",
+		feature = "prefer-label",
+		feature = "scan-labels"
+	))]
+	LabelMalformed,
 
-	/// The data item is a byte array. The count is the number of bytes, and they are stored in the
-	/// payload.
-	Bytes,
+	/// A filesystem returned a malformed result when checking whether the image file exists.
+	#[cfg(any(feature = "boot-menu", feature = "check-exists"))]
+	ExistsMalformed,
 
-	/// The data item is a string. The count is the number of bytes in the UTF-8 encoding, and that
-	/// encoding is stored in the payload.
-	String,
+	/// An Internet card returned a malformed result when downloading the image file.
+	#[cfg(feature = "http-boot")]
+	HttpMalformed,
 
-	/// The data item is an array of data items. The count is the number of items in the array, and
-	/// they are stored in the payload.
-	Array,
+	/// The image is too large to fit in the execution buffer.
+	ImageTooLarge,
 
-	/// The data item is an array of key/value pairs of data items. The count is the number of
-	/// pairs in the array, and they are stored in the payload.
-	Map,
+	/// A filesystem returned a malformed result when fetching the size of the image file.
+	#[cfg(feature = "size-check")]
+	SizeMalformed,
 
-	/// The data item is a semantic tag. The count is the identity of the tag. The tagged item is
-	/// stored in the payload.
-	Tag,
+	/// The image does not have the checksum given by the boot configuration.
+	#[cfg(feature = "checksum")]
+	ChecksumMismatch,
 
-	/// The data item is a special value. The count is the value of the data item. There is no
-	/// payload.
-	Special,
+	/// The computer does not have enough free RAM to execute the image.
+	#[cfg(feature = "ram-check")]
+	NotEnoughRam,
 
-	/// The data item is a floating-point number. The count is the value of the data item. There is
-	/// no payload.
-	Float,
+	/// The image file starts like a gzip file but is not a valid one.
+	#[cfg(feature = "gzip")]
+	ImageMalformed,
+
+	/// A method call could not be started because the call queue stayed full.
+	#[cfg(feature = "busy-retry")]
+	ComponentBusy,
+
+	/// A network boot server stopped sending the image part of the way through.
+	#[cfg(feature = "net-boot")]
+	NetTimeout,
+
+	/// A signal too large to pop arrived while receiving the image over the network.
+	#[cfg(feature = "net-boot")]
+	NetSignalTooLarge,
+
+	/// A scan tried as many filesystems as it is allowed to without finding a bootable one.
+	#[cfg(feature = "scan-limit")]
+	ScanLimitReached,
+
+	/// Booting took longer than [`BOOT_TIMEOUT`] seconds.
+	#[cfg(feature = "boot-timeout")]
+	BootTimeout,
+
+	/// The image does not start with the header of a Wasm module.
+	#[cfg(feature = "magic-check")]
+	NotWasm,
+
+	/// A method call’s result did not fit in the buffer provided for it.
+	#[cfg(feature = "result-check")]
+	ResultTooLarge,
+
+	/// The halt key was being pressed at boot.
+	#[cfg(feature = "halt-key")]
+	HaltedByUser,
+
+	/// Something happened that indicates a bug in the BIOS.
+	InternalError,
 }
 
-/// Reads a CBOR data item header from a byte slice.
-///
-/// The `slice` parameter is the byte slice to read from. On success, the major type, raw count
-/// value (prior to interpretation according to major type), and a slice containing the rest of the
-/// input slice starting immediately following the header (i.e. at the payload, if any, otherwise
-/// at the next date item) are returned.
-///
-/// # Errors
-/// * [`BufferTooShort`](error::Error::BufferTooShort) is returned if `slice` is empty.
-/// * [`CborDecode`](error::Error::CborDecode) is returned if `slice` is nonempty but does not
-///   contain a whole header, or if the header is invalid.
-fn cbor_decode_header(slice: &[u8]) -> error::Result<(CborMajorType, u64, &[u8])> {
-	// Grab the first byte.
-	let first_byte = slice.first().ok_or(error::Error::BufferTooShort)?;
-	let slice = &slice[1..];
-
-	// Decode the major type from the upper three bits.
-	let major_type = match first_byte >> 5 {
-		0 => CborMajorType::UnsignedInteger,
-		1 => CborMajorType::NegativeInteger,
-		2 => CborMajorType::Bytes,
-		3 => CborMajorType::String,
-		4 => CborMajorType::Array,
-		5 => CborMajorType::Map,
-		6 => CborMajorType::Tag,
-		7 => match first_byte & 31 {
-			25..=27 => CborMajorType::Float,
-			_ => CborMajorType::Special,
-		},
-		_ => unreachable!(), // Impossible; u8>>5 can only be 0..=7.
-	};
+impl BiosError {
+	/// Returns the message to halt the computer with.
+	fn message(self) -> &'static str {
+		match self {
+			Self::NoEeprom => "BIOS: no EEPROM",
+			Self::NoBootMedium => "BIOS: no bootable medium",
+			Self::NoStorage => "BIOS: no storage devices",
+			Self::EepromDataMalformed => CONFIG_METHOD_BAD,
+			Self::OpenFailed => "BIOS: filesystem.open bad",
+			Self::ReadIoError => "BIOS: I/O error reading /init.wasm",
+			#[cfg(feature = "file-config")]
+			Self::ConfigIoError => "BIOS: I/O error reading bios.cfg",
+			#[cfg(feature = "file-config")]
+			Self::ConfigTooLarge => "BIOS: bios.cfg too large",
+			#[cfg(any(
+				feature = "boot-menu",
+				feature = "install-once",
+				feature = "prefer-label",
+				feature = "scan-labels"
+			))]
+			Self::LabelMalformed => "BIOS: filesystem.getLabel bad",
+			#[cfg(any(feature = "boot-menu", feature = "check-exists"))]
+			Self::ExistsMalformed => "BIOS: filesystem.exists bad",
+			#[cfg(feature = "http-boot")]
+			Self::HttpMalformed => "BIOS: internet.request bad",
+			Self::ImageTooLarge => "BIOS: image too large",
+			#[cfg(feature = "size-check")]
+			Self::SizeMalformed => "BIOS: filesystem.size bad",
+			#[cfg(feature = "checksum")]
+			Self::ChecksumMismatch => "BIOS: image checksum mismatch",
+			#[cfg(feature = "ram-check")]
+			Self::NotEnoughRam => "BIOS: kernel too large for RAM",
+			#[cfg(feature = "gzip")]
+			Self::ImageMalformed => "BIOS: bad gzip data in /init.wasm",
+			#[cfg(feature = "busy-retry")]
+			Self::ComponentBusy => "BIOS: component busy",
+			#[cfg(feature = "net-boot")]
+			Self::NetTimeout => "BIOS: network boot timed out",
+			#[cfg(feature = "net-boot")]
+			Self::NetSignalTooLarge => "BIOS: signal too large",
+			#[cfg(feature = "scan-limit")]
+			Self::ScanLimitReached => "BIOS: scan limit reached",
+			#[cfg(feature = "boot-timeout")]
+			Self::BootTimeout => "BIOS: boot timeout",
+			#[cfg(feature = "magic-check")]
+			Self::NotWasm => "BIOS: init.wasm is not a Wasm module",
+			#[cfg(feature = "result-check")]
+			Self::ResultTooLarge => "BIOS: call result too large",
+			#[cfg(feature = "halt-key")]
+			Self::HaltedByUser => "BIOS: halted by user",
+			Self::InternalError => "BIOS: internal error",
+		}
+	}
 
-	// Decode the count.
-	let count_bits = first_byte & 31;
-	let (count, slice): (u64, &[u8]) = if count_bits <= 23 {
-		(count_bits.into(), slice)
-	} else {
-		let count_bytes = match count_bits {
-			24 => 1,
-			25 => 2,
-			26 => 4,
-			27 => 8,
-			_ => return Err(error::Error::CborDecode),
-		};
-		if slice.len() < count_bytes {
-			return Err(error::Error::CborDecode);
+	/// Returns the code identifying the failure, if it has one.
+	///
+	/// With the `error-codes` feature, a failure with a code is reported by returning the code
+	/// from [`run`] rather than by halting the computer. With the `beep-codes` feature, the beep
+	/// pattern for the code is played when the failure is reported.
+	#[cfg_attr(
+		not(any(feature = "beep-codes", feature = "error-codes")),
+		allow(dead_code)
+	)]
+	fn code(self) -> Option<u8> {
+		match self {
+			Self::NoEeprom => Some(1),
+			Self::NoBootMedium | Self::NoStorage => Some(2),
+			Self::OpenFailed | Self::ReadIoError => Some(3),
+			#[cfg(feature = "file-config")]
+			Self::ConfigIoError => Some(3),
+			_ => None,
 		}
-		let (count_bytes, slice) = slice.split_at(count_bytes);
-		let mut count_value: u64 = 0;
-		for &byte in count_bytes {
-			count_value = (count_value << 8) | Into::<u64>::into(byte);
+	}
+
+	/// Halts the computer with the failure’s message.
+	///
+	/// With the `reboot-on-failure` feature, if the boot configuration asks for it, the computer
+	/// beeps and reboots instead, so that booting is tried again.
+	#[cold]
+	fn fail(self) -> ! {
+		#[cfg(feature = "beep-codes")]
+		if let Some(code) = self.code() {
+			beep_code(code);
 		}
-		(count_value, slice)
-	};
+		#[cfg(feature = "reboot-on-failure")]
+		// SAFETY: Wasm is single-threaded, and REBOOT is only accessed in single statements.
+		if unsafe { *REBOOT.get() } {
+			computer::beep(REBOOT_BEEP_FREQUENCY, REBOOT_BEEP_DURATION);
+			computer::reboot();
+		}
+		#[cfg(feature = "scan-labels")]
+		if self == Self::NoBootMedium {
+			computer::error(labels::message(&mut [0; labels::MESSAGE_LEN]));
+		}
+		#[cfg(feature = "error-uuid")]
+		if matches!(self, Self::OpenFailed | Self::ReadIoError) {
+			// SAFETY: Wasm is single-threaded, and DEVICE is only accessed in single statements.
+			if let Some(device) = unsafe { *DEVICE.get() } {
+				computer::error(message_with_device(
+					self.message(),
+					&device,
+					&mut [0; DEVICE_MESSAGE_LEN],
+				));
+			}
+		}
+		computer::error(self.message())
+	}
+}
+
+/// The number of seconds that booting may take, from the first time the BIOS runs (or the user last
+/// answered it) until the image is executed, before the BIOS gives up.
+#[cfg(feature = "boot-timeout")]
+const BOOT_TIMEOUT: f64 = 60.0;
+
+/// The uptime at which booting times out, or `None` if the BIOS has not run yet or has just been
+/// waiting for the user.
+#[cfg(feature = "boot-timeout")]
+static BOOT_DEADLINE: SyncUnsafeCell<Option<f64>> = SyncUnsafeCell::new(None);
+
+/// Whether to reboot, rather than halt, if booting fails.
+#[cfg(feature = "reboot-on-failure")]
+static REBOOT: SyncUnsafeCell<bool> = SyncUnsafeCell::new(false);
 
-	// Return everything.
-	Ok((major_type, count, slice))
+/// The frequency, in hertz, of the beep played before rebooting after a failure.
+#[cfg(feature = "reboot-on-failure")]
+const REBOOT_BEEP_FREQUENCY: u16 = 440;
+
+/// The length, in milliseconds, of the beep played before rebooting after a failure.
+#[cfg(feature = "reboot-on-failure")]
+const REBOOT_BEEP_DURATION: u16 = 200;
+
+/// The UUID of the component most recently accessed to open or read a file, which is reported
+/// along with failures to do so.
+#[cfg(feature = "error-uuid")]
+static DEVICE: SyncUnsafeCell<Option<Address>> = SyncUnsafeCell::new(None);
+
+/// Records the UUID of the component about to be accessed to open or read a file.
+#[cfg(feature = "error-uuid")]
+fn note_device(uuid: &Address) {
+	// SAFETY: Wasm is single-threaded, and DEVICE is only accessed in single statements.
+	unsafe { *DEVICE.get() = Some(*uuid) };
+}
+
+/// Writes the first four bytes of a UUID as eight lowercase hexadecimal digits.
+///
+/// The `buffer` parameter is where to write the digits.
+#[cfg(feature = "error-uuid")]
+fn fmt_uuid_prefix(address: &Address, buffer: &mut [u8; 8]) {
+	const HEX_DIGITS: &[u8; 16] = b"0123456789abcdef";
+	for (digits, byte) in buffer.chunks_exact_mut(2).zip(address.as_bytes()) {
+		digits[0] = HEX_DIGITS[usize::from(byte >> 4)];
+		digits[1] = HEX_DIGITS[usize::from(byte & 0x0F)];
+	}
+}
+
+/// The size of the buffer needed to build a failure message that names a component.
+#[cfg(feature = "error-uuid")]
+const DEVICE_MESSAGE_LEN: usize = 64;
+
+/// Builds a failure message followed by the first eight hexadecimal digits of a component’s UUID
+/// in parentheses.
+///
+/// The `message` parameter must be at most `DEVICE_MESSAGE_LEN - 11` bytes long. The `buffer`
+/// parameter is where to build the message.
+#[cfg(feature = "error-uuid")]
+fn message_with_device<'buffer>(
+	message: &str,
+	device: &Address,
+	buffer: &'buffer mut [u8; DEVICE_MESSAGE_LEN],
+) -> &'buffer str {
+	let len = message.len();
+	buffer[..len].copy_from_slice(message.as_bytes());
+	buffer[len..len + 2].copy_from_slice(b" (");
+	let mut digits = [0_u8; 8];
+	fmt_uuid_prefix(device, &mut digits);
+	buffer[len + 2..len + 10].copy_from_slice(&digits);
+	buffer[len + 10] = b')';
+	// SAFETY: The message is a string, and every byte added is an ASCII character, so the result
+	// is valid UTF-8.
+	unsafe { core::str::from_utf8_unchecked(&buffer[..len + 11]) }
+}
+
+impl From<error::Error> for BiosError {
+	/// Converts an error from a system call, such as a failure to decode CBOR returned by a
+	/// component, which the BIOS does not expect to happen.
+	fn from(_: error::Error) -> Self {
+		Self::InternalError
+	}
+}
+
+/// Plays the Morse code beep pattern for a failure code.
+///
+/// A code with no pattern plays nothing.
+#[cfg(feature = "beep-codes")]
+fn beep_code(code: u8) {
+	let pattern = match code {
+		// No EEPROM: one long beep.
+		1 => "-",
+		// No bootable medium: two short beeps.
+		2 => "..",
+		// A malformed result from a filesystem: three short beeps.
+		3 => "...",
+		_ => return,
+	};
+	computer::beep_pattern(pattern);
 }
 
 /// When opening a `/init.wasm` file, the two possible ways in which we could have found the UUID
 /// of the filesystem component we are accessing.
+// A scan may hold its candidate list inline, and there is no heap to box it on.
+#[allow(clippy::large_enum_variant)]
 enum UuidSource {
 	/// We read the UUID from the EEPROM, where it identifies the default boot device.
 	Eeprom,
 
 	/// We got the UUID from the list of all filesystem components and are scanning for any
 	/// bootable medium.
-	Scan(component::Listing<'static>),
+	Scan(Scan),
+
+	/// We got the UUID from a boot list in the EEPROM, which holds the boot devices not tried yet.
+	#[cfg(feature = "boot-list")]
+	List(config::BootList),
 }
 
 /// The information associated with the [`OpeningFile`](State::OpeningFile) state.
@@ -164,38 +508,466 @@ struct OpeningFileInfo {
 
 	/// Where the UUID came from.
 	pub source: UuidSource,
+
+	/// The index in [`FILENAMES`] of the image file being opened.
+	pub filename: usize,
+
+	/// The part of a split image being opened, or `None` if the image file itself is being opened.
+	#[cfg(feature = "split-image")]
+	pub part: Option<u16>,
 }
 
 /// The information associated with the [`ReadingFile`](State::ReadingFile) state.
-#[derive(Eq, PartialEq)]
 struct ReadingFileInfo {
 	/// The file descriptor.
 	pub descriptor: descriptor::Owned,
 
 	/// The UUID of the filesystem component.
 	pub uuid: Address,
+
+	/// Where the UUID came from.
+	#[cfg(any(
+		feature = "call-timeout",
+		feature = "remember-device",
+		feature = "seek-first",
+		feature = "skip-bad-media",
+		feature = "split-image"
+	))]
+	pub source: UuidSource,
+
+	/// The index in [`FILENAMES`] of the image file being read.
+	#[cfg(feature = "split-image")]
+	pub filename: usize,
+
+	/// The part of a split image being read, or `None` if `/init.wasm` itself is being read.
+	#[cfg(feature = "split-image")]
+	pub part: Option<u16>,
+
+	/// The number of reads in a row that have returned no data.
+	#[cfg(feature = "empty-read-limit")]
+	pub empty_reads: u8,
+
+	/// The size of the file, if the filesystem reported it.
+	#[cfg(feature = "size-check")]
+	pub size: Option<usize>,
+
+	/// The number of bytes read from the file so far.
+	#[cfg(feature = "size-check")]
+	pub read: usize,
+}
+
+/// The information associated with the [`OpeningDrive`](State::OpeningDrive) and
+/// [`ReadingSectors`](State::ReadingSectors) states.
+#[cfg(feature = "drive-boot")]
+struct DriveInfo {
+	/// The listing of unmanaged drives that have not been tried yet.
+	pub listing: component::Listing<'static>,
+
+	/// The UUID of the drive.
+	pub uuid: Address,
+
+	/// The number of the sector being read, counting from 1.
+	pub sector: u32,
+
+	/// The number of bytes of the image that have not been read yet.
+	pub remaining: usize,
+}
+
+/// The information associated with the [`ReadingTape`](State::ReadingTape) state.
+#[cfg(feature = "tape-boot")]
+struct TapeInfo {
+	/// The UUID of the tape drive.
+	pub uuid: Address,
+
+	/// The number of bytes of the image that have not been read yet.
+	pub remaining: usize,
+}
+
+/// The information associated with the [`Surveying`](State::Surveying),
+/// [`Labelling`](State::Labelling), and [`Menu`](State::Menu) states.
+#[cfg(feature = "boot-menu")]
+struct MenuInfo {
+	/// The listing of filesystem components that have not been surveyed yet.
+	pub listing: component::Listing<'static>,
+
+	/// The UUID of the filesystem component being surveyed.
+	pub current: Address,
+
+	/// The UUIDs of the bootable filesystem components found so far, in the first `len` elements.
+	pub candidates: [Address; MAX_MENU_ENTRIES],
+
+	/// The number of valid elements in `candidates`.
+	pub len: usize,
+
+	/// The uptime at which the first candidate is booted if no key has been pressed.
+	pub deadline: f64,
+}
+
+/// The information associated with the [`Prompt`](State::Prompt) state.
+#[cfg(feature = "boot-prompt")]
+struct PromptInfo {
+	/// The text typed so far, in the first `len` elements.
+	pub text: [u8; 36],
+
+	/// The number of valid elements in `text`.
+	pub len: usize,
+}
+
+/// The information associated with the [`NetReceive`](State::NetReceive) state.
+#[cfg(feature = "net-boot")]
+struct NetInfo {
+	/// The UUID of the server, as text, once it has replied.
+	pub server: Option<[u8; 36]>,
+
+	/// The uptime at which to give up waiting for the next message from the server.
+	pub deadline: f64,
+}
+
+/// The information associated with the [`ReadingBootDeviceUuid`](State::ReadingBootDeviceUuid)
+/// state.
+struct EepromInfo {
+	/// The UUID of the EEPROM being read.
+	#[cfg(feature = "remember-device")]
+	pub address: Address,
+
+	/// The listing of EEPROMs that have not been read yet.
+	#[cfg(feature = "multi-eeprom")]
+	pub listing: component::Listing<'static>,
+}
+
+/// The information associated with the [`SearchingConfig`](State::SearchingConfig) and
+/// [`OpeningConfig`](State::OpeningConfig) states.
+#[cfg(feature = "file-config")]
+struct ConfigSearchInfo {
+	/// The boot configuration read from the EEPROM, which is used if no filesystem has a boot
+	/// configuration file.
+	pub eeprom_config: Config,
+
+	/// The designated boot device, if it is a filesystem and has not been searched yet.
+	pub first: Option<Address>,
+
+	/// The listing of filesystem components that have not been searched yet.
+	pub listing: component::Listing<'static>,
+}
+
+/// The information associated with the [`BootDelay`](State::BootDelay) state.
+#[cfg(feature = "boot-delay")]
+struct BootDelayInfo {
+	/// The boot configuration to boot according to once the delay is over.
+	pub config: Config,
+
+	/// The uptime at which the delay is over.
+	pub deadline: f64,
+}
+
+/// The information associated with the [`WaitingForDevice`](State::WaitingForDevice) state.
+#[cfg(feature = "wait-device")]
+struct WaitingInfo {
+	/// The UUID of the designated boot device.
+	pub address: Address,
+
+	/// The uptime at which to stop waiting.
+	pub deadline: f64,
+
+	/// The boot slot to fall back to once the wait is over, if any.
+	#[cfg(feature = "boot-slot")]
+	pub slot: Option<u8>,
+}
+
+/// The information associated with the [`ReadingConfig`](State::ReadingConfig) state.
+#[cfg(feature = "file-config")]
+struct ReadingConfigInfo {
+	/// The file descriptor.
+	pub descriptor: descriptor::Owned,
+
+	/// The UUID of the filesystem component.
+	pub uuid: Address,
+
+	/// The contents of the file read so far.
+	pub data: [u8; CONFIG_FILE_MAX],
+
+	/// The number of bytes of `data` that have been read.
+	pub len: usize,
 }
 
 /// The state machine that the BIOS moves through while doing its work.
+// Some states hold buffers inline, and there is no heap to box them on.
+#[allow(clippy::large_enum_variant)]
 enum State {
 	/// The initial state when the BIOS starts running.
 	Init,
 
+	/// A method call has been made to bind the GPU with the contained UUID to a screen.
+	#[cfg(feature = "display")]
+	BindingGpu(Address),
+
 	/// The EEPROM’s boot device UUID is being read.
-	ReadingBootDeviceUuid,
+	ReadingBootDeviceUuid(EepromInfo),
+
+	/// A search for a filesystem with a boot configuration file is in progress.
+	#[cfg(feature = "file-config")]
+	SearchingConfig(ConfigSearchInfo),
+
+	/// A method call has been made to open the boot configuration file on the filesystem with the
+	/// contained UUID.
+	#[cfg(feature = "file-config")]
+	OpeningConfig(Address, ConfigSearchInfo),
+
+	/// A boot configuration file has been opened successfully. We are now reading it.
+	#[cfg(feature = "file-config")]
+	ReadingConfig(ReadingConfigInfo),
+
+	/// The boot configuration asks for a boot delay, and the BIOS is waiting for it to pass or for a
+	/// key to be pressed.
+	#[cfg(feature = "boot-delay")]
+	BootDelay(BootDelayInfo),
+
+	/// The next boot device in the contained boot list should be tried.
+	#[cfg(feature = "boot-list")]
+	TryingList(config::BootList),
+
+	/// The filesystem in the contained boot slot should be looked up and booted from.
+	#[cfg(feature = "boot-slot")]
+	FindingSlot(u8),
+
+	/// The designated boot device is not connected, and the BIOS is waiting for it to appear.
+	#[cfg(feature = "wait-device")]
+	WaitingForDevice(WaitingInfo),
+
+	/// A method call has been made to check whether `/init.wasm` exists on a filesystem, in order
+	/// to offer it in the boot menu.
+	#[cfg(feature = "boot-menu")]
+	Surveying(MenuInfo),
+
+	/// A method call has been made to fetch the label of a filesystem that has `/init.wasm`, in
+	/// order to show it in the boot menu.
+	#[cfg(feature = "boot-menu")]
+	Labelling(MenuInfo),
+
+	/// The boot menu is being shown, and the BIOS is waiting for a key to be pressed.
+	#[cfg(feature = "boot-menu")]
+	Menu(MenuInfo),
 
 	/// A component listing should be started.
 	StartScan,
 
-	/// A component listing is in progress.
-	Scanning(component::Listing<'static>),
+	/// A scan for bootable media is in progress.
+	Scanning(Scan),
+
+	/// A method call has been made to fetch the label of the filesystem with the contained UUID,
+	/// found by the contained scan, to check whether it is an installer.
+	#[cfg(feature = "install-once")]
+	CheckingLabel(Address, Scan),
+
+	/// A method call has been made to check whether `/init.wasm` exists on the designated boot
+	/// device, which has the contained UUID.
+	#[cfg(feature = "check-exists")]
+	CheckingExists(Address),
+
+	/// A method call has been made to an Internet card to start downloading the image file from
+	/// the URL in the boot configuration.
+	#[cfg(feature = "http-boot")]
+	StartingHttp,
+
+	/// A method call has been made to fetch the status of the contained HTTP request.
+	#[cfg(feature = "http-boot")]
+	AwaitingHttpStatus(descriptor::Owned),
+
+	/// The image file is being downloaded. A method call has been made to read from the body of the
+	/// contained HTTP request.
+	#[cfg(feature = "http-boot")]
+	ReadingHttpResponse(descriptor::Owned),
+
+	/// A method call has been made to open the network boot port on the modem with the contained
+	/// UUID.
+	#[cfg(feature = "net-boot")]
+	NetDiscover(Address),
+
+	/// A method call has been made to broadcast a message to discover a network boot server. We
+	/// are now receiving the image from the server in modem messages and storing it to the
+	/// execution buffer.
+	#[cfg(feature = "net-boot")]
+	NetReceive(NetInfo),
 
 	/// A method call has been made to open `/init.wasm` on a filesystem.
 	OpeningFile(OpeningFileInfo),
 
+	/// A `/init.wasm` file has been opened successfully, and a method call has been made to fetch
+	/// its size.
+	#[cfg(feature = "size-check")]
+	SizingFile(ReadingFileInfo),
+
+	/// A `/init.wasm` file has been opened successfully, and a method call has been made to seek
+	/// to its start.
+	#[cfg(feature = "seek-first")]
+	Seeking(ReadingFileInfo),
+
 	/// A `/init.wasm` file has been opened successfully. We are now reading data from the file and
 	/// storing it to the execution buffer.
 	ReadingFile(ReadingFileInfo),
+
+	/// No filesystem is bootable, and a method call has been made to read the first sector of an
+	/// unmanaged drive to check whether it holds an image.
+	#[cfg(feature = "drive-boot")]
+	OpeningDrive(DriveInfo),
+
+	/// An unmanaged drive holding an image has been found. We are now reading its sectors and
+	/// storing the image to the execution buffer.
+	#[cfg(feature = "drive-boot")]
+	ReadingSectors(DriveInfo),
+
+	/// The boot configuration asks for a tape boot, and a method call has been made to check
+	/// whether the contained tape drive holds a tape.
+	#[cfg(feature = "tape-boot")]
+	CheckingTape(Address),
+
+	/// The contained tape drive holds a tape, and a method call has been made to seek to its
+	/// start.
+	#[cfg(feature = "tape-boot")]
+	RewindingTape(Address),
+
+	/// A method call has been made to read the start of the tape in the contained tape drive to
+	/// check whether it holds an image.
+	#[cfg(feature = "tape-boot")]
+	OpeningTape(Address),
+
+	/// A tape holding an image has been found. We are now reading it and storing the image to the
+	/// execution buffer.
+	#[cfg(feature = "tape-boot")]
+	ReadingTape(TapeInfo),
+
+	/// No bootable medium has been found. If a keyboard and a screen are attached, the user
+	/// should be asked for the UUID of a filesystem to boot from.
+	#[cfg(feature = "boot-prompt")]
+	StartPrompt,
+
+	/// The user is being asked for the UUID of a filesystem to boot from, and the BIOS is waiting
+	/// for keys to be pressed.
+	#[cfg(feature = "boot-prompt")]
+	Prompt(PromptInfo),
+
+	/// The image has been loaded from a filesystem found by a scan, and a method call has been made
+	/// to designate that filesystem as the boot device in the EEPROM.
+	#[cfg(feature = "remember-device")]
+	RememberingDevice,
+
+	/// The image has been loaded, and a method call has been made to write the boot record to the
+	/// EEPROM.
+	#[cfg(feature = "boot-record")]
+	RecordingBoot,
+
+	/// The image has been loaded and is about to be executed.
+	ReadyToExecute,
+
+	/// The image has been loaded, and the BIOS is waiting until the contained uptime before
+	/// executing it, so that any messages on the screen can be read.
+	#[cfg(feature = "handoff-delay")]
+	Delaying(f64),
+
+	/// Booting has failed, and the contained code is returned from [`run`].
+	#[cfg(feature = "error-codes")]
+	Failed(i32),
+}
+
+#[cfg(any(feature = "trace", feature = "verbose"))]
+impl State {
+	/// Returns the name of the state.
+	fn name(&self) -> &'static str {
+		match self {
+			Self::Init => "Init",
+			#[cfg(feature = "display")]
+			Self::BindingGpu(..) => "BindingGpu",
+			Self::ReadingBootDeviceUuid(..) => "ReadingBootDeviceUuid",
+			#[cfg(feature = "file-config")]
+			Self::SearchingConfig(..) => "SearchingConfig",
+			#[cfg(feature = "file-config")]
+			Self::OpeningConfig(..) => "OpeningConfig",
+			#[cfg(feature = "file-config")]
+			Self::ReadingConfig(..) => "ReadingConfig",
+			#[cfg(feature = "boot-delay")]
+			Self::BootDelay(..) => "BootDelay",
+			#[cfg(feature = "boot-list")]
+			Self::TryingList(..) => "TryingList",
+			#[cfg(feature = "boot-slot")]
+			Self::FindingSlot(..) => "FindingSlot",
+			#[cfg(feature = "wait-device")]
+			Self::WaitingForDevice(..) => "WaitingForDevice",
+			#[cfg(feature = "boot-menu")]
+			Self::Surveying(..) => "Surveying",
+			#[cfg(feature = "boot-menu")]
+			Self::Labelling(..) => "Labelling",
+			#[cfg(feature = "boot-menu")]
+			Self::Menu(..) => "Menu",
+			Self::StartScan => "StartScan",
+			Self::Scanning(..) => "Scanning",
+			#[cfg(feature = "install-once")]
+			Self::CheckingLabel(..) => "CheckingLabel",
+			#[cfg(feature = "check-exists")]
+			Self::CheckingExists(..) => "CheckingExists",
+			#[cfg(feature = "http-boot")]
+			Self::StartingHttp => "StartingHttp",
+			#[cfg(feature = "http-boot")]
+			Self::AwaitingHttpStatus(..) => "AwaitingHttpStatus",
+			#[cfg(feature = "http-boot")]
+			Self::ReadingHttpResponse(..) => "ReadingHttpResponse",
+			#[cfg(feature = "net-boot")]
+			Self::NetDiscover(..) => "NetDiscover",
+			#[cfg(feature = "net-boot")]
+			Self::NetReceive(..) => "NetReceive",
+			Self::OpeningFile(..) => "OpeningFile",
+			#[cfg(feature = "size-check")]
+			Self::SizingFile(..) => "SizingFile",
+			#[cfg(feature = "seek-first")]
+			Self::Seeking(..) => "Seeking",
+			Self::ReadingFile(..) => "ReadingFile",
+			#[cfg(feature = "drive-boot")]
+			Self::OpeningDrive(..) => "OpeningDrive",
+			#[cfg(feature = "drive-boot")]
+			Self::ReadingSectors(..) => "ReadingSectors",
+			#[cfg(feature = "tape-boot")]
+			Self::CheckingTape(..) => "CheckingTape",
+			#[cfg(feature = "tape-boot")]
+			Self::RewindingTape(..) => "RewindingTape",
+			#[cfg(feature = "tape-boot")]
+			Self::OpeningTape(..) => "OpeningTape",
+			#[cfg(feature = "tape-boot")]
+			Self::ReadingTape(..) => "ReadingTape",
+			#[cfg(feature = "boot-prompt")]
+			Self::StartPrompt => "StartPrompt",
+			#[cfg(feature = "boot-prompt")]
+			Self::Prompt(..) => "Prompt",
+			#[cfg(feature = "remember-device")]
+			Self::RememberingDevice => "RememberingDevice",
+			#[cfg(feature = "boot-record")]
+			Self::RecordingBoot => "RecordingBoot",
+			Self::ReadyToExecute => "ReadyToExecute",
+			#[cfg(feature = "handoff-delay")]
+			Self::Delaying(..) => "Delaying",
+			#[cfg(feature = "error-codes")]
+			Self::Failed(..) => "Failed",
+		}
+	}
+}
+
+#[cfg(feature = "boot-timeout")]
+impl State {
+	/// Returns whether the state is waiting for the user, or has finished booting one way or
+	/// another, so that the time spent in it should not count towards [`BOOT_TIMEOUT`].
+	fn stops_boot_clock(&self) -> bool {
+		match self {
+			#[cfg(feature = "boot-delay")]
+			Self::BootDelay(..) => true,
+			#[cfg(feature = "boot-menu")]
+			Self::Menu(..) => true,
+			#[cfg(feature = "boot-prompt")]
+			Self::Prompt(..) => true,
+			#[cfg(feature = "handoff-delay")]
+			Self::Delaying(..) => true,
+			#[cfg(feature = "error-codes")]
+			Self::Failed(..) => true,
+			_ => false,
+		}
+	}
 }
 
 /// The possible values that a single successful run step can return.
@@ -209,364 +981,7307 @@ enum RunResult {
 	Return,
 }
 
-/// The filename of the file to open.
-const FILENAME: &[u8] = b"/init.wasm";
+/// The path of the image file to boot, unless the boot configuration specifies another one.
+///
+/// This is taken from the `BIOS_INIT_PATH` environment variable at build time, if set.
+const FILENAME: &str = match option_env!("BIOS_INIT_PATH") {
+	Some(path) => path,
+	None => "/init.wasm",
+};
 
-/// Starts opening `/init.wasm` on a filesystem component.
+/// The length of the longest path that [`FILENAME`] may have.
 ///
-/// The `address` parameter identifies the component by its UUID.
+/// This leaves room for the suffix of a part of a split image while still fitting in a string with
+/// a 1-byte count. Every other path that may be booted, including one from the boot configuration,
+/// is shorter.
+const MAX_FILENAME_LEN: usize = 251;
+
+// Reject an unusable path from the environment when building rather than when booting.
+const _: () = {
+	assert!(!FILENAME.is_empty(), "BIOS_INIT_PATH must not be empty");
+	assert!(
+		FILENAME.len() <= MAX_FILENAME_LEN,
+		"BIOS_INIT_PATH must be at most 251 bytes long"
+	);
+	assert!(FILENAME.is_ascii(), "BIOS_INIT_PATH must be ASCII");
+};
+
+/// The paths of the image files to try booting, in order, on each filesystem.
 ///
-/// `true` is returned if the call is complete now. `false` is returned if the call has started but
-/// will not be complete until the next timeslice.
-fn invoke_open_init(address: &Address) -> bool {
-	let mut buffer = [0_u8; 3 + FILENAME.len()];
-	// Write the array header.
-	buffer[0] = (4 << 5) | 1;
-	// Write the filename string.
-	buffer[1] = (3 << 5) | 24;
-	// Cast is sound because FILENAME is short.
-	#[allow(clippy::cast_possible_truncation)]
-	{
-		buffer[2] = FILENAME.len() as u8;
-	}
-	// SAFETY: buffer is of length (3 + FILENAME.len()). Therefore buffer[3..] is of length
-	// FILENAME.len(). FILENAME.as_ptr() returns *const u8, and u8 impl Copy.
-	unsafe {
-		ptr::copy_nonoverlapping(FILENAME.as_ptr(), buffer[3..].as_mut_ptr(), FILENAME.len());
+/// The first entry is replaced by the path given by the boot configuration, if any; see
+/// [`image_filename`]. With the `fallback-filenames` feature, the other entries are tried on a
+/// filesystem that lacks the first one before moving on to another filesystem.
+#[cfg(not(feature = "fallback-filenames"))]
+const FILENAMES: &[&str] = &[FILENAME];
+#[cfg(feature = "fallback-filenames")]
+const FILENAMES: &[&str] = &[FILENAME, "/boot/init.wasm", "/boot.wasm"];
+
+/// The length of the longest path of an image file to boot.
+const MAX_BOOT_PATH_LEN: usize = {
+	let mut len = 0;
+	let mut index = 0;
+	while index < FILENAMES.len() {
+		if FILENAMES[index].len() > len {
+			len = FILENAMES[index].len();
+		}
+		index += 1;
 	}
-	let method = "open";
-	let rc = unsafe {
-		component_sys::invoke_component_method(
-			address.as_bytes().as_ptr(),
-			method.as_ptr(),
-			method.len(),
-			buffer.as_ptr(),
-		)
+	#[cfg(feature = "rescue-key")]
+	let len = if RESCUE_FILENAME.len() > len {
+		RESCUE_FILENAME.len()
+	} else {
+		len
 	};
-	// If this fails, it indicates a bug in the BIOS, not a problem with the user’s configuration.
-	if rc < 0 {
-		internal_error();
-	}
-	rc != 0
-}
+	#[cfg(feature = "stage2")]
+	let len = if STAGE2_FILENAME.len() > len {
+		STAGE2_FILENAME.len()
+	} else {
+		len
+	};
+	#[cfg(feature = "config-path")]
+	let len = if config::MAX_PATH_LEN > len {
+		config::MAX_PATH_LEN
+	} else {
+		len
+	};
+	len
+};
 
-/// The number of bytes to ask to read from a file at a time.
-const CHUNK_SIZE: usize = 16384;
+/// The path of the image file to boot if the rescue key is pressed.
+#[cfg(feature = "rescue-key")]
+const RESCUE_FILENAME: &str = "/rescue.wasm";
 
-/// Starts reading from a file.
+/// The key code of the rescue key, which is R.
+#[cfg(feature = "rescue-key")]
+const RESCUE_KEY: u64 = 0x13;
+
+/// The key code of the halt key, which is Escape.
+#[cfg(feature = "halt-key")]
+const HALT_KEY: u64 = 0x01;
+
+/// The signal pushed to mark where the signal queue ended before [`examine_signals`] started, as
+/// pushed: an array holding just the signal name.
+#[cfg(any(feature = "boot-delay", feature = "halt-key", feature = "rescue-key"))]
+const MARKER_SIGNAL: &[u8] = b"\x81\x69bios_mark";
+
+/// The most bytes that re-encoding a popped signal to push it back can add, which is the size of
+/// the longest data item header.
+#[cfg(any(feature = "boot-delay", feature = "halt-key", feature = "rescue-key"))]
+const SIGNAL_HEADROOM: usize = 9;
+
+/// The buffer into which signals are popped while examining the signal queue.
 ///
-/// The `address` parameter identifies the filesystem component by UUID. The `descriptor` parameter
-/// is the file descriptor.
+/// It has room for a modem message with the largest payload (the default maximum network packet
+/// size of 8192 bytes), plus its other parameters and [`SIGNAL_HEADROOM`]. It is static, rather
+/// than on the stack, so that the stack frame of [`run_step`] does not grow by a whole message.
+#[cfg(any(feature = "boot-delay", feature = "halt-key", feature = "rescue-key"))]
+static SIGNAL_BUFFER: SyncUnsafeCell<[u8; SIGNAL_HEADROOM + 256 + 8192]> =
+	SyncUnsafeCell::new([0; SIGNAL_HEADROOM + 256 + 8192]);
+
+/// Whether [`MARKER_SIGNAL`] is in the signal queue, left there by an examination that could not
+/// reach it.
+#[cfg(any(feature = "boot-delay", feature = "halt-key", feature = "rescue-key"))]
+static MARKER_QUEUED: SyncUnsafeCell<bool> = SyncUnsafeCell::new(false);
+
+/// Whether the rescue key was pressed at boot.
+#[cfg(feature = "rescue-key")]
+static RESCUE: SyncUnsafeCell<bool> = SyncUnsafeCell::new(false);
+
+/// The path of the second-stage loader, which is booted in place of the image file if it exists
+/// and the boot configuration asks for it.
+#[cfg(feature = "stage2")]
+const STAGE2_FILENAME: &str = "/boot/stage2.wasm";
+
+/// Whether the second-stage loader is tried before the image files.
+#[cfg(feature = "stage2")]
+static STAGE2: SyncUnsafeCell<bool> = SyncUnsafeCell::new(false);
+
+/// The path of the image file to boot, if the boot configuration specifies one.
+#[cfg(feature = "config-path")]
+static BOOT_PATH: SyncUnsafeCell<Option<config::Path>> = SyncUnsafeCell::new(None);
+
+/// Returns the path of the image file to boot.
 ///
-/// `true` is returned if the call is complete now. `false` is returned if the call has started but
-/// will not be complete until the next timeslice.
-fn invoke_read(address: &Address, descriptor: descriptor::Borrowed<'_>) -> bool {
-	let mut buffer = [0_u8; 13];
-	// Write the array header.
-	buffer[0] = (4 << 5) | 2;
-	// Write the tag.
-	buffer[1] = (6 << 5) | 24;
-	buffer[2] = 39;
-	// Write the descriptor.
-	buffer[3] = 26;
-	// SAFETY: buffer[4..8] is of length 4. descriptor.to_be_bytes returns 4 bytes because
-	// descriptor is a u32. The array is of u8, which impl Copy.
-	unsafe {
-		let descriptor_bytes: [u8; 4] = descriptor.as_raw().to_be_bytes();
-		ptr::copy_nonoverlapping(descriptor_bytes.as_ptr(), buffer[4..8].as_mut_ptr(), 4);
+/// With the `config-path` feature, this is the path given by the boot configuration passed to
+/// [`boot`], if it gives one; otherwise, it is [`FILENAME`]. With the `rescue-key` feature, it is
+/// `RESCUE_FILENAME` instead if the rescue key was pressed at boot.
+fn boot_path() -> &'static str {
+	#[cfg(feature = "rescue-key")]
+	// SAFETY: Wasm is single-threaded, and RESCUE is only accessed in single statements.
+	if unsafe { *RESCUE.get() } {
+		return RESCUE_FILENAME;
 	}
-	// Write the requested byte count.
-	buffer[8] = 26;
-	// SAFETY: buffer[9..13] is of length 4. CHUNK_SIZE.to_be_bytes returns 4 bytes because
-	// CHUNK_SIZE is a usize and Wasm is a 32-bit platform. The array is of u8, which impl Copy.
-	unsafe {
-		let cs_bytes: [u8; 4] = CHUNK_SIZE.to_be_bytes();
-		ptr::copy_nonoverlapping(cs_bytes.as_ptr(), buffer[9..13].as_mut_ptr(), 4);
+	#[cfg(feature = "config-path")]
+	// SAFETY: Wasm is single-threaded. BOOT_PATH is only written by boot, which is called at most
+	// once per boot configuration and never while a string returned from here is still in use.
+	if let Some(path) = unsafe { &*BOOT_PATH.get() } {
+		return path.as_str();
 	}
-	let method = "read";
-	let rc = unsafe {
-		component_sys::invoke_component_method(
-			address.as_bytes().as_ptr(),
-			method.as_ptr(),
-			method.len(),
-			buffer.as_ptr(),
-		)
+	FILENAME
+}
+
+/// The highest part number of a split image.
+#[cfg(feature = "split-image")]
+const MAX_PART: u16 = 999;
+
+/// Returns the path of an image file to try booting, given its index in [`FILENAMES`].
+///
+/// Index 0 yields the [boot path](boot_path). `None` is returned if there are no more image files
+/// to try. With the `stage2` feature, if the second-stage loader is to be tried, index 0 yields
+/// `STAGE2_FILENAME` instead and the other image files each move up one index.
+fn image_filename(index: usize) -> Option<&'static str> {
+	#[cfg(feature = "stage2")]
+	// SAFETY: Wasm is single-threaded, and STAGE2 is only accessed in single statements.
+	let index = if unsafe { *STAGE2.get() } {
+		match index.checked_sub(1) {
+			Some(index) => index,
+			None => return Some(STAGE2_FILENAME),
+		}
+	} else {
+		index
 	};
-	// If this fails, it indicates a bug in the BIOS, not a problem with the user’s configuration.
-	if rc < 0 {
-		internal_error();
+	if index == 0 {
+		Some(boot_path())
+	} else {
+		FILENAMES.get(index).copied()
 	}
-	rc != 0
 }
 
-/// The type of a bootable medium.
-const BOOTABLE_COMPONENT_TYPE: &str = "filesystem";
+/// Returns the path of the first image file to try booting on each filesystem.
+///
+/// This is the [boot path](boot_path), unless the second-stage loader is to be tried first.
+fn first_image_filename() -> &'static str {
+	image_filename(0).unwrap_or_else(|| internal_error())
+}
 
-/// Runs one step of the state machine.
-fn run_step(state: State) -> error::Result<(RunResult, State)> {
-	// Hold a Lister.
-	static LISTER: SyncUnsafeCell<Option<component::Lister>> = SyncUnsafeCell::new(None);
-	// SAFETY: Wasm is single-threaded, so only one thread will be here touching LISTER at a time.
-	// This is the only place in which LISTER is touched, so the same thread also cannot make a
-	// second mutable reference.
-	let lister = unsafe { &mut *LISTER.get() };
-	let lister =
-		lister.get_or_insert_with(|| component::Lister::take().unwrap_or_else(|| internal_error()));
+/// The length of the longest filename of a part of a split image.
+#[cfg(feature = "split-image")]
+const PART_FILENAME_LEN: usize = MAX_BOOT_PATH_LEN + 4;
 
-	// Dispatch based on current state.
+/// Returns the filename of a part of a split image.
+///
+/// Part `n` of the image file at `base` is named `base.n` (for example, `/init.wasm.0`), where `n`
+/// is written in decimal without leading zeroes. The `base` parameter must be at most
+/// [`MAX_BOOT_PATH_LEN`] bytes long. The `part` parameter must be at most [`MAX_PART`]. The `name`
+/// parameter is the buffer in which to build the filename.
+#[cfg(feature = "split-image")]
+fn part_filename<'name>(
+	base: &str,
+	part: u16,
+	name: &'name mut [u8; PART_FILENAME_LEN],
+) -> &'name str {
+	let mut len = base.len();
+	name[..len].copy_from_slice(base.as_bytes());
+	name[len] = b'.';
+	len += 1;
+	// Write the digits that are needed, most significant first.
+	for divisor in [100, 10, 1] {
+		if part >= divisor || divisor == 1 {
+			// Cast is sound because each digit is less than ten.
+			#[allow(clippy::cast_possible_truncation)]
+			let digit = (part / divisor % 10) as u8;
+			name[len] = b'0' + digit;
+			len += 1;
+		}
+	}
+	// SAFETY: The base is a string, and every other byte written is an ASCII character, so
+	// the filename is valid UTF-8.
+	unsafe { core::str::from_utf8_unchecked(&name[..len]) }
+}
+
+/// The filename of the boot configuration file.
+#[cfg(feature = "file-config")]
+const CONFIG_FILENAME: &str = "/boot/bios.cfg";
+
+/// The maximum size of the boot configuration file, which is the size of an EEPROM’s data area.
+#[cfg(feature = "file-config")]
+const CONFIG_FILE_MAX: usize = 256;
+
+/// The length of the longest path that can be passed to [`invoke_open`].
+const MAX_PATH_LEN: usize = {
+	let len = MAX_BOOT_PATH_LEN;
+	#[cfg(feature = "split-image")]
+	let len = if PART_FILENAME_LEN > len {
+		PART_FILENAME_LEN
+	} else {
+		len
+	};
+	#[cfg(feature = "file-config")]
+	let len = if CONFIG_FILENAME.len() > len {
+		CONFIG_FILENAME.len()
+	} else {
+		len
+	};
+	len
+};
+
+// Every path, including those of the parts of a split image, must fit in a string with a 1-byte
+// count, as invoke_with_path assumes.
+const _: () = assert!(MAX_PATH_LEN <= 255, "paths must be at most 255 bytes long");
+
+/// The size of the largest parameters that a method call retried by [`retry_busy_call`] can have.
+///
+/// This is the size of the parameters built by [`invoke_with_path`] or, if every path is short,
+/// those built by [`invoke_read`], or of those built by `invoke_modem`, `record_boot`,
+/// `remember_device`, or `invoke_request` if they are larger. A call with larger parameters cannot
+/// be retried, so if the call queue is full when it is made, booting fails.
+#[cfg(feature = "busy-retry")]
+const MAX_PARAMS_LEN: usize = {
+	let len = if 3 + MAX_PATH_LEN > READ_PARAMS_LEN {
+		3 + MAX_PATH_LEN
+	} else {
+		READ_PARAMS_LEN
+	};
+	#[cfg(feature = "net-boot")]
+	let len = if MODEM_PARAMS_LEN > len {
+		MODEM_PARAMS_LEN
+	} else {
+		len
+	};
+	#[cfg(feature = "boot-record")]
+	let len = if BOOT_RECORD_PARAMS_LEN > len {
+		BOOT_RECORD_PARAMS_LEN
+	} else {
+		len
+	};
+	#[cfg(feature = "remember-device")]
+	let len = if REMEMBER_PARAMS_LEN > len {
+		REMEMBER_PARAMS_LEN
+	} else {
+		len
+	};
+	#[cfg(feature = "http-boot")]
+	let len = if REQUEST_PARAMS_LEN > len {
+		REQUEST_PARAMS_LEN
+	} else {
+		len
+	};
+	len
+};
+
+/// The number of times a method call that could not be started because the call queue was full is
+/// retried before booting fails.
+#[cfg(feature = "busy-retry")]
+const MAX_BUSY_RETRIES: u8 = 8;
+
+/// A method call that could not be started because the call queue was full.
+#[cfg(feature = "busy-retry")]
+struct BusyCall {
+	/// The UUID of the component.
+	address: Address,
+
+	/// The name of the method.
+	method: &'static str,
+
+	/// The CBOR-encoded parameters, in the first `len` bytes, or `None` if there are none.
+	params: Option<[u8; MAX_PARAMS_LEN]>,
+
+	/// The length of the parameters, in bytes.
+	len: usize,
+
+	/// The number of times the call has been retried so far.
+	retries: u8,
+}
+
+/// The method call waiting to be retried, if any.
+#[cfg(feature = "busy-retry")]
+static BUSY_CALL: SyncUnsafeCell<Option<BusyCall>> = SyncUnsafeCell::new(None);
+
+/// Starts a method call on a component, if the call queue has room for it.
+///
+/// The `address` parameter identifies the component by its UUID. The `method` parameter is the
+/// name of the method. The `params` parameter is the CBOR-encoded parameters, or `None` if there
+/// are none.
+///
+/// `Some(true)` is returned if the call is complete now. `Some(false)` is returned if the call has
+/// started but will not be complete until the next timeslice. With the `busy-retry` feature,
+/// `None` is returned if the call could not be started because the call queue was full; without
+/// it, that is treated like any other failure.
+///
+/// This suits calls whose results are only wanted if they arrive straight away. Calls whose
+/// results the state machine waits for should be made with [`invoke_method`] instead.
+#[cfg_attr(not(feature = "busy-retry"), allow(clippy::unnecessary_wraps))]
+fn start_method(address: &Address, method: &str, params: Option<&[u8]>) -> Option<bool> {
+	let rc = start_call(address, method, params);
+	#[cfg(feature = "busy-retry")]
+	if error::Error::from_i32(rc) == Err(error::Error::QueueFull) {
+		return None;
+	}
+	// If this fails, it indicates a bug in the BIOS, not a problem with the user’s configuration.
+	if rc < 0 {
+		internal_error();
+	}
+	Some(rc != 0)
+}
+
+/// Starts a method call on a component whose result is not needed, such as drawing or logging.
+///
+/// The parameters and return value are as for [`start_method`], except that `None` is returned
+/// whenever the call queue is full, with or without the `busy-retry` feature. That also happens
+/// while a call whose result the state machine is waiting for is in progress. The caller then
+/// drops the call.
+#[cfg(any(feature = "display", feature = "trace"))]
+fn start_best_effort(address: &Address, method: &str, params: Option<&[u8]>) -> Option<bool> {
+	let rc = start_call(address, method, params);
+	if error::Error::from_i32(rc) == Err(error::Error::QueueFull) {
+		return None;
+	}
+	// If this fails, it indicates a bug in the BIOS, not a problem with the user’s configuration.
+	if rc < 0 {
+		internal_error();
+	}
+	Some(rc != 0)
+}
+
+/// Starts a method call on a component, returning OC-Wasm’s return code.
+///
+/// The parameters are as for [`start_method`].
+fn start_call(address: &Address, method: &str, params: Option<&[u8]>) -> i32 {
+	unsafe {
+		component_sys::invoke_component_method(
+			address.as_bytes().as_ptr(),
+			method.as_ptr(),
+			method.len(),
+			params.map_or(ptr::null(), <[u8]>::as_ptr),
+		)
+	}
+}
+
+/// Fetches the result of the method call in progress into `buffer`, returning OC-Wasm’s return
+/// code.
+///
+/// With the `call-timeout` feature, the code of a failure already found by `call_status` is
+/// returned instead.
+fn end_call(buffer: &mut [u8]) -> isize {
+	// SAFETY: Wasm is single-threaded, and FAILED_CALL is only accessed in single statements.
+	#[cfg(feature = "call-timeout")]
+	if let Some(rc) = unsafe { (*FAILED_CALL.get()).take() } {
+		return rc;
+	}
+	unsafe { component_sys::invoke_end(buffer.as_mut_ptr(), buffer.len()) }
+}
+
+/// Starts a method call on a component.
+///
+/// The parameters are as for [`start_method`].
+///
+/// `true` is returned if the call is complete now. `false` is returned if the call has started but
+/// will not be complete until the next timeslice. With the `busy-retry` feature, `false` is also
+/// returned if the call could not be started because the call queue was full, in which case the
+/// call is retried at the start of the next timeslice; see `retry_busy_call`.
+fn invoke_method(address: &Address, method: &'static str, params: Option<&[u8]>) -> bool {
+	if let Some(done) = start_method(address, method, params) {
+		return done;
+	}
+	// The call queue is full. Keep the call to retry it later. A call whose parameters do not fit
+	// cannot be retried, so booting fails as if every retry had found the queue still full.
+	#[cfg(feature = "busy-retry")]
+	{
+		let len = params.map_or(0, <[u8]>::len);
+		let params = params.map(|params| {
+			let mut buffer = [0_u8; MAX_PARAMS_LEN];
+			buffer
+				.get_mut(..len)
+				.unwrap_or_else(|| BiosError::ComponentBusy.fail())
+				.copy_from_slice(params);
+			buffer
+		});
+		// SAFETY: Wasm is single-threaded, and BUSY_CALL is only accessed in single statements.
+		unsafe {
+			*BUSY_CALL.get() = Some(BusyCall {
+				address: *address,
+				method,
+				params,
+				len,
+				retries: 0,
+			});
+		}
+	}
+	false
+}
+
+/// Retries the method call that could not be started because the call queue was full, if any.
+///
+/// `true` is returned if the state machine can carry on, because there was no such call or it has
+/// now started and is complete. `false` is returned if the BIOS should wait for the next timeslice,
+/// because the call has started but is not complete or the call queue is still full.
+///
+/// If the call has been retried [`MAX_BUSY_RETRIES`] times without starting, the computer halts.
+#[cfg(feature = "busy-retry")]
+fn retry_busy_call() -> bool {
+	// SAFETY: Wasm is single-threaded, and BUSY_CALL is only accessed in single statements.
+	let Some(mut call) = (unsafe { (*BUSY_CALL.get()).take() }) else {
+		return true;
+	};
+	if call.retries == MAX_BUSY_RETRIES {
+		BiosError::ComponentBusy.fail();
+	}
+	let done = invoke_method(
+		&call.address,
+		call.method,
+		call.params.as_ref().map(|params| &params[..call.len]),
+	);
+	// SAFETY: Wasm is single-threaded, and BUSY_CALL is only accessed in single statements.
+	if let Some(retry) = unsafe { &mut *BUSY_CALL.get() } {
+		// The call queue is still full. Keep count of the attempts.
+		call.retries += 1;
+		retry.retries = call.retries;
+		return false;
+	}
+	done
+}
+
+/// Returns the part of a buffer that holds a method call’s result.
+///
+/// The `buffer` parameter is the buffer passed to `invoke_end`, and `rc` is what it returned,
+/// which the caller must already have checked to be nonnegative. OC-Wasm never returns a length
+/// longer than the buffer, but if it did, this would be a bug, so the computer halts with an
+/// internal error rather than reading past the end of the buffer.
+fn call_result(buffer: &[u8], rc: isize) -> &[u8] {
+	usize::try_from(rc)
+		.ok()
+		.and_then(|len| buffer.get(..len))
+		.unwrap_or_else(|| internal_error())
+}
+
+/// Starts a method call, taking a path as its only parameter, on a filesystem component.
+///
+/// The `address` parameter identifies the component by its UUID. The `method` parameter is the
+/// name of the method. The `path` parameter is the path, which must be at most [`MAX_PATH_LEN`]
+/// bytes long.
+///
+/// `true` is returned if the call is complete now. `false` is returned if the call has started but
+/// will not be complete until the next timeslice.
+fn invoke_with_path(address: &Address, method: &'static str, path: &str) -> bool {
+	// Encode the parameters: an array holding the path. The buffer has room for the array header,
+	// a string header with a 1-byte count, and a path of up to MAX_PATH_LEN bytes, so this only
+	// fails if the BIOS is buggy.
+	let mut buffer = [0_u8; 3 + MAX_PATH_LEN];
+	let mut writer = cbor::Writer::new(&mut buffer);
+	if writer.write_array_header(1).is_err() || writer.write_string(path).is_err() {
+		internal_error();
+	}
+	invoke_method(address, method, Some(&buffer))
+}
+
+/// Starts opening a file on a filesystem component.
+///
+/// The `address` parameter identifies the component by its UUID. The `path` parameter is the path
+/// to the file, which must be at most [`MAX_PATH_LEN`] bytes long.
+///
+/// `true` is returned if the call is complete now. `false` is returned if the call has started but
+/// will not be complete until the next timeslice.
+fn invoke_open(address: &Address, path: &str) -> bool {
+	invoke_with_path(address, "open", path)
+}
+
+/// The number of bytes to ask to read from a file at a time.
+const CHUNK_SIZE: usize = 16384;
+
+/// The buffer into which the result of a read from the image file is fetched.
+///
+/// It is static, rather than on the stack, so that the stack frame of [`run_step`] does not grow
+/// by a whole chunk.
+static READ_BUFFER: SyncUnsafeCell<[u8; 32 + CHUNK_SIZE]> =
+	SyncUnsafeCell::new([0; 32 + CHUNK_SIZE]);
+
+/// The number of reads in a row that may return no data, without reaching end of file, before a
+/// filesystem is considered broken.
+#[cfg(feature = "empty-read-limit")]
+const MAX_EMPTY_READS: u8 = 8;
+
+/// The number of bytes of image data added to the execution buffer so far.
+#[cfg(any(
+	feature = "crc-report",
+	feature = "fit-chunks",
+	feature = "magic-check",
+	feature = "progress-hook",
+	feature = "ram-check"
+))]
+static IMAGE_LEN: SyncUnsafeCell<usize> = SyncUnsafeCell::new(0);
+
+/// The free RAM, in bytes, below which the image file is read in smaller chunks.
+#[cfg(feature = "low-memory-chunks")]
+const LOW_MEMORY_THRESHOLD: usize = 65536;
+
+/// The number of bytes to ask to read from an image file at a time once free RAM runs low.
+#[cfg(feature = "low-memory-chunks")]
+const LOW_MEMORY_CHUNK_SIZE: usize = 4096;
+
+/// Returns the number of bytes to ask to read from an image file next.
+///
+/// This is normally [`CHUNK_SIZE`]. With the `low-memory-chunks` feature, it is
+/// `LOW_MEMORY_CHUNK_SIZE` instead while the computer has less than `LOW_MEMORY_THRESHOLD`
+/// bytes of free RAM. With the `fit-chunks` feature, it is limited to the space left in the
+/// execution buffer, which can hold as many bytes as the computer has RAM installed, but is always
+/// at least one byte so that the end of a file that exactly fills the buffer is still seen.
+fn image_chunk_size() -> usize {
+	// Ask for less at a time when free RAM runs low, so that each result needs less of it.
+	// Cast is sound because usize and u32 are the same size on Wasm.
+	#[cfg(feature = "low-memory-chunks")]
+	#[allow(clippy::cast_possible_truncation)]
+	let chunk_size = if (computer::free_ram() as usize) < LOW_MEMORY_THRESHOLD {
+		LOW_MEMORY_CHUNK_SIZE
+	} else {
+		CHUNK_SIZE
+	};
+	#[cfg(not(feature = "low-memory-chunks"))]
+	let chunk_size = CHUNK_SIZE;
+
+	#[cfg(feature = "fit-chunks")]
+	{
+		// SAFETY: Wasm is single-threaded, and IMAGE_LEN is only accessed in single statements.
+		let len = unsafe { *IMAGE_LEN.get() };
+		// Cast is sound because usize and u32 are the same size on Wasm.
+		#[allow(clippy::cast_possible_truncation)]
+		let capacity = computer::installed_ram() as usize;
+		chunk_size.min(capacity.saturating_sub(len)).max(1)
+	}
+	#[cfg(not(feature = "fit-chunks"))]
+	chunk_size
+}
+
+/// The checksum that the image must have, if the boot configuration gives one.
+#[cfg(feature = "checksum")]
+static EXPECTED_CHECKSUM: SyncUnsafeCell<Option<u32>> = SyncUnsafeCell::new(None);
+
+/// The checksum of the image data added to the execution buffer so far.
+#[cfg(any(feature = "checksum", feature = "crc-report"))]
+static CHECKSUM: SyncUnsafeCell<crc32::Crc32> = SyncUnsafeCell::new(crc32::Crc32::new());
+
+/// Discards any image data added to the execution buffer so far.
+#[cfg(any(
+	feature = "call-timeout",
+	feature = "defer-busy",
+	feature = "eeprom-image",
+	feature = "seek-first",
+	feature = "skip-bad-media"
+))]
+fn clear_image() {
+	execute::clear();
+	// SAFETY: Wasm is single-threaded, and IMAGE_LEN is only accessed in single statements.
+	#[cfg(any(
+		feature = "crc-report",
+		feature = "fit-chunks",
+		feature = "magic-check",
+		feature = "progress-hook",
+		feature = "ram-check"
+	))]
+	unsafe {
+		*IMAGE_LEN.get() = 0;
+	}
+	// SAFETY: Wasm is single-threaded, and CHECKSUM is only accessed in single statements.
+	#[cfg(any(feature = "checksum", feature = "crc-report"))]
+	unsafe {
+		*CHECKSUM.get() = crc32::Crc32::new();
+	}
+	// SAFETY: Wasm is single-threaded, and COMPRESSED and INFLATER are only accessed in single
+	// statements.
+	#[cfg(feature = "gzip")]
+	unsafe {
+		*COMPRESSED.get() = None;
+		(*INFLATER.get()).reset();
+	}
+}
+
+/// Checks that the loaded image has the checksum given by the boot configuration, if any.
+///
+/// If it does not, the computer halts.
+#[cfg(feature = "checksum")]
+fn check_image() {
+	// SAFETY: Wasm is single-threaded, and EXPECTED_CHECKSUM and CHECKSUM are only accessed in
+	// single statements.
+	if let Some(expected) = unsafe { *EXPECTED_CHECKSUM.get() } {
+		if unsafe { *CHECKSUM.get() }.finish() != expected {
+			BiosError::ChecksumMismatch.fail();
+		}
+	}
+}
+
+/// The number of bytes of free RAM needed to execute the image, beyond the size of the image
+/// itself.
+///
+/// This leaves room for the image’s linear memory and other state once it starts running.
+#[cfg(feature = "ram-check")]
+const RAM_MARGIN: usize = 65536;
+
+/// Checks that the computer has enough free RAM to execute the loaded image.
+///
+/// If it does not, the computer halts.
+#[cfg(feature = "ram-check")]
+fn check_ram() {
+	// SAFETY: Wasm is single-threaded, and IMAGE_LEN is only accessed in single statements.
+	let len = unsafe { *IMAGE_LEN.get() };
+	// Cast is sound because usize and u32 are the same size on Wasm.
+	#[allow(clippy::cast_possible_truncation)]
+	if len.saturating_add(RAM_MARGIN) > computer::free_ram() as usize {
+		BiosError::NotEnoughRam.fail();
+	}
+}
+
+/// Reports that some image data has been added to the execution buffer.
+///
+/// The `loaded` parameter is the number of bytes of image data added so far. For a compressed
+/// image, this counts decompressed bytes. It starts again from zero if a partly loaded image is
+/// discarded.
+///
+/// This does nothing. It is there to be filled in by anyone adapting the BIOS who wants to show
+/// progress in their own way without changing the state machine.
+#[cfg(feature = "progress-hook")]
+#[cfg_attr(not(test), allow(unused_variables))]
+fn on_progress(loaded: usize) {
+	// The tests record the reports, to check when they are made.
+	#[cfg(test)]
+	tests::PROGRESS.lock().unwrap().push(loaded);
+}
+
+/// The bytes at the start of every Wasm module: the magic number `\0asm`, then version 1 as a
+/// 32-bit integer with the least significant byte first.
+#[cfg(feature = "magic-check")]
+const WASM_HEADER: &[u8; 8] = b"\0asm\x01\0\0\0";
+
+/// Adds some image data to the execution buffer.
+///
+/// # Errors
+/// [`ImageTooLarge`](BiosError::ImageTooLarge) is returned if the execution buffer refuses the
+/// data. With the `fit-chunks` feature, it is also returned, and nothing is added, if the data does
+/// not fit in the space left in the execution buffer. With the `magic-check` feature,
+/// `NotWasm` is returned instead, and nothing is added, if the data covers part of `WASM_HEADER`
+/// and does not match it.
+fn add_image_data(data: &[u8]) -> Result<(), BiosError> {
+	// Check that the image starts with the Wasm header. The header may be split across several
+	// pieces of data, so each piece is checked against whatever part of it the piece covers.
+	#[cfg(feature = "magic-check")]
+	{
+		// SAFETY: Wasm is single-threaded, and IMAGE_LEN is only accessed in single statements.
+		let start = unsafe { *IMAGE_LEN.get() }.min(WASM_HEADER.len());
+		let header = &WASM_HEADER[start..];
+		let len = header.len().min(data.len());
+		if header[..len] != data[..len] {
+			return Err(BiosError::NotWasm);
+		}
+	}
+	#[cfg(any(
+		feature = "crc-report",
+		feature = "fit-chunks",
+		feature = "magic-check",
+		feature = "progress-hook",
+		feature = "ram-check"
+	))]
+	{
+		// SAFETY: Wasm is single-threaded, and IMAGE_LEN is only accessed in single statements.
+		let len = unsafe { *IMAGE_LEN.get() } + data.len();
+		// Cast is sound because usize and u32 are the same size on Wasm.
+		#[cfg(feature = "fit-chunks")]
+		#[allow(clippy::cast_possible_truncation)]
+		if len > computer::installed_ram() as usize {
+			return Err(BiosError::ImageTooLarge);
+		}
+		// SAFETY: Wasm is single-threaded, and IMAGE_LEN is only accessed in single statements.
+		unsafe { *IMAGE_LEN.get() = len };
+	}
+	#[cfg(any(feature = "checksum", feature = "crc-report"))]
+	// SAFETY: Wasm is single-threaded, and CHECKSUM is only accessed in single statements.
+	unsafe {
+		(*CHECKSUM.get()).update(data);
+	}
+	#[cfg(feature = "display")]
+	display::loaded(data.len());
+	match execute::add(data) {
+		Ok(()) => {
+			// SAFETY: Wasm is single-threaded, and IMAGE_LEN is only accessed in single statements.
+			#[cfg(feature = "progress-hook")]
+			on_progress(unsafe { *IMAGE_LEN.get() });
+			// Report the checksum so far, for power users.
+			// SAFETY: Wasm is single-threaded, and CHECKSUM is only accessed in single statements.
+			#[cfg(feature = "crc-report")]
+			verbose::checksum(unsafe { *CHECKSUM.get() }.finish());
+			Ok(())
+		}
+		// The execution buffer refuses data that would make it larger than the installed RAM.
+		Err(error::Error::Other) => Err(BiosError::ImageTooLarge),
+		Err(e) => Err(e.into()),
+	}
+}
+
+/// Whether the image file is gzip-compressed, or `None` if no data has been read from it yet.
+#[cfg(feature = "gzip")]
+static COMPRESSED: SyncUnsafeCell<Option<bool>> = SyncUnsafeCell::new(None);
+
+/// The decompressor for a gzip-compressed image file.
+#[cfg(feature = "gzip")]
+static INFLATER: SyncUnsafeCell<inflate::Inflater> = SyncUnsafeCell::new(inflate::Inflater::new());
+
+/// Adds some data read from the image file to the execution buffer.
+///
+/// With the `gzip` feature, if the first data read from the image file starts with the gzip magic
+/// number, the image file is decompressed on the way.
+///
+/// # Errors
+/// As for [`add_image_data`]. With the `gzip` feature, `ImageMalformed` is also returned if the
+/// image file is compressed but the data is not valid.
+fn add_file_data(data: &[u8]) -> Result<(), BiosError> {
+	#[cfg(feature = "gzip")]
+	{
+		// SAFETY: Wasm is single-threaded, and COMPRESSED is only accessed in single statements.
+		let compressed = match unsafe { *COMPRESSED.get() } {
+			Some(compressed) => compressed,
+			None if data.is_empty() => false,
+			None => {
+				let compressed = data.starts_with(&inflate::MAGIC);
+				// SAFETY: Wasm is single-threaded, and COMPRESSED is only accessed in single
+				// statements.
+				unsafe { *COMPRESSED.get() = Some(compressed) };
+				compressed
+			}
+		};
+		if compressed {
+			// SAFETY: Wasm is single-threaded, and INFLATER is only accessed in single statements.
+			return unsafe { (*INFLATER.get()).feed(data, add_image_data) };
+		}
+	}
+	add_image_data(data)
+}
+
+/// Checks that a gzip-compressed image file has been decompressed all the way to its end.
+///
+/// # Errors
+/// [`ImageMalformed`](BiosError::ImageMalformed) is returned if the image file is compressed but
+/// ended early.
+#[cfg(feature = "gzip")]
+fn check_decompressed() -> Result<(), BiosError> {
+	// SAFETY: Wasm is single-threaded, and COMPRESSED and INFLATER are only accessed in single
+	// statements.
+	if unsafe { *COMPRESSED.get() } == Some(true) && !unsafe { (*INFLATER.get()).finished() } {
+		return Err(BiosError::ImageMalformed);
+	}
+	Ok(())
+}
+
+/// The size of the parameters built by [`invoke_read`].
+const READ_PARAMS_LEN: usize = 13;
+
+/// Starts reading from a file.
+///
+/// The `address` parameter identifies the filesystem component by UUID. The `descriptor` parameter
+/// is the file descriptor. The `count` parameter is the maximum number of bytes to read.
+///
+/// `true` is returned if the call is complete now. `false` is returned if the call has started but
+/// will not be complete until the next timeslice.
+fn invoke_read(address: &Address, descriptor: descriptor::Borrowed<'_>, count: usize) -> bool {
+	// Encode the parameters: an array holding the descriptor and the byte count. The buffer has
+	// room for the array header, a tag with a 1-byte number, and two integers of up to 32 bits, so
+	// this only fails if the BIOS is buggy.
+	let mut buffer = [0_u8; READ_PARAMS_LEN];
+	let mut writer = cbor::Writer::new(&mut buffer);
+	if writer.write_array_header(2).is_err()
+		|| writer.write_descriptor(&descriptor).is_err()
+		|| writer.write_uint(count as u64).is_err()
+	{
+		internal_error();
+	}
+	invoke_method(address, "read", Some(&buffer))
+}
+
+/// Whether to look for an image split into numbered parts on a filesystem that lacks the image
+/// file.
+#[cfg(feature = "split-image")]
+static SPLIT_IMAGE: SyncUnsafeCell<bool> = SyncUnsafeCell::new(false);
+
+/// Whether to seek to the start of each image file before reading it.
+#[cfg(feature = "seek-first")]
+static SEEK_FIRST: SyncUnsafeCell<bool> = SyncUnsafeCell::new(false);
+
+/// The size of the parameters built by [`invoke_seek`].
+#[cfg(feature = "seek-first")]
+const SEEK_PARAMS_LEN: usize = 13;
+
+/// Starts seeking to the start of a file.
+///
+/// The `address` parameter identifies the filesystem component by UUID. The `descriptor` parameter
+/// is the file descriptor.
+///
+/// `true` is returned if the call is complete now. `false` is returned if the call has started but
+/// will not be complete until the next timeslice.
+#[cfg(feature = "seek-first")]
+fn invoke_seek(address: &Address, descriptor: descriptor::Borrowed<'_>) -> bool {
+	// Encode the parameters: an array holding the descriptor, the origin, and the offset. The
+	// buffer has room for the array header, a tag with a 1-byte number, an integer of up to 32
+	// bits, a 3-byte string, and a small integer, so this only fails if the BIOS is buggy.
+	let mut buffer = [0_u8; SEEK_PARAMS_LEN];
+	let mut writer = cbor::Writer::new(&mut buffer);
+	if writer.write_array_header(3).is_err()
+		|| writer.write_descriptor(&descriptor).is_err()
+		|| writer.write_string("set").is_err()
+		|| writer.write_uint(0).is_err()
+	{
+		internal_error();
+	}
+	invoke_method(address, "seek", Some(&buffer))
+}
+
+/// Fetches the result of a `filesystem.seek` call.
+///
+/// `true` is returned if the seek succeeded. `false` is returned if the call failed or returned
+/// `false` or null, optionally followed by a reason.
+#[cfg(feature = "seek-first")]
+fn end_seek() -> bool {
+	// A successful seek returns the new position, which is ignored. A failure reason that does
+	// not fit in the buffer makes the call fail, which is treated the same as any other failure.
+	let mut result_buffer = [0_u8; 16];
+	let rc = end_call(&mut result_buffer);
+	if rc < 0 {
+		return false;
+	}
+	let mut reader = cbor::Reader::new(call_result(&result_buffer, rc));
+	matches!(reader.read_header(), Ok((cbor::MajorType::Array, 1..)))
+		&& !reader.consume_byte(0xF4 /* false */)
+		&& !reader.consume_byte(0xF6 /* null */)
+}
+
+/// Starts either seeking to the start of an image file or reading it, depending on whether the
+/// boot configuration asks to seek first.
+///
+/// The `address` parameter identifies the filesystem component by UUID. The `descriptor` parameter
+/// is the file descriptor.
+///
+/// Whether the call is complete now, as for [`invoke_read`], and the state to wait for it in are
+/// returned.
+#[cfg(feature = "seek-first")]
+fn invoke_seek_or_read(
+	address: &Address,
+	descriptor: descriptor::Borrowed<'_>,
+) -> (bool, fn(ReadingFileInfo) -> State) {
+	// SAFETY: Wasm is single-threaded, and SEEK_FIRST is only accessed in single statements.
+	if unsafe { *SEEK_FIRST.get() } {
+		(invoke_seek(address, descriptor), State::Seeking)
+	} else {
+		(
+			invoke_read(address, descriptor, image_chunk_size()),
+			State::ReadingFile,
+		)
+	}
+}
+
+/// The number of times in a row that [`run`] may find a method call still in progress before the
+/// call is abandoned.
+#[cfg(feature = "call-timeout")]
+const MAX_PENDING_STEPS: u16 = 100;
+
+/// The number of times in a row that the method call in progress has been found still in
+/// progress.
+#[cfg(feature = "call-timeout")]
+static PENDING_STEPS: SyncUnsafeCell<u16> = SyncUnsafeCell::new(0);
+
+/// The state of the method call in progress.
+#[cfg(feature = "call-timeout")]
+enum CallStatus {
+	/// The call is complete, and its result can be fetched.
+	Done,
+
+	/// The call is still in progress.
+	Pending,
+
+	/// The call has been in progress for too long and has been abandoned.
+	TimedOut,
+}
+
+/// The return code of the method call that [`call_status`] found to have failed.
+///
+/// Asking for the length of the result of a call that failed finishes the call, so the code is
+/// kept here for [`end_call`] to return.
+#[cfg(feature = "call-timeout")]
+static FAILED_CALL: SyncUnsafeCell<Option<isize>> = SyncUnsafeCell::new(None);
+
+/// Checks whether the method call in progress is complete.
+///
+/// If it has been found still in progress [`MAX_PENDING_STEPS`] times in a row, it is cancelled.
+#[cfg(feature = "call-timeout")]
+fn call_status() -> CallStatus {
+	// Asking for the length of the result does not consume it, unless the call failed.
+	// SAFETY: invoke_end permits a null buffer with a length of zero.
+	let rc = unsafe { component_sys::invoke_end(ptr::null_mut(), 0) };
+	let pending = rc == -9;
+	if rc < 0 && !pending {
+		// SAFETY: Wasm is single-threaded, and FAILED_CALL is only accessed in single statements.
+		unsafe { *FAILED_CALL.get() = Some(rc) };
+	}
+	// SAFETY: Wasm is single-threaded, and PENDING_STEPS is only accessed in this function,
+	// without holding any references beyond a single statement.
+	let steps = unsafe { &mut *PENDING_STEPS.get() };
+	if !pending {
+		*steps = 0;
+		CallStatus::Done
+	} else if *steps < MAX_PENDING_STEPS {
+		*steps += 1;
+		CallStatus::Pending
+	} else {
+		*steps = 0;
+		// SAFETY: invoke_cancel is unconditionally safe.
+		unsafe { component_sys::invoke_cancel() };
+		CallStatus::TimedOut
+	}
+}
+
+/// Moves on from a filesystem that has been given up on, discarding any of its image already
+/// loaded.
+///
+/// The `source` parameter is where the filesystem’s UUID came from.
+#[cfg(any(
+	feature = "call-timeout",
+	feature = "defer-busy",
+	feature = "seek-first",
+	feature = "skip-bad-media"
+))]
+fn abandon_medium(source: UuidSource) -> (RunResult, State) {
+	clear_image();
+	(
+		RunResult::RunNext,
+		match source {
+			UuidSource::Eeprom => State::StartScan,
+			UuidSource::Scan(scan) => State::Scanning(scan),
+			#[cfg(feature = "boot-list")]
+			UuidSource::List(list) => State::TryingList(list),
+		},
+	)
+}
+
+/// The name of the EEPROM method that reads the data area holding the boot configuration.
+///
+/// An EEPROM has a persistent data area (read by `getData`) and may also have a volatile one (read
+/// by `getVolatileData`). The persistent area is used by default; the `volatile-config` feature
+/// selects the volatile area instead.
+#[cfg(not(feature = "volatile-config"))]
+const CONFIG_METHOD: &str = "getData";
+#[cfg(feature = "volatile-config")]
+const CONFIG_METHOD: &str = "getVolatileData";
+
+/// Starts reading the boot configuration from an EEPROM.
+///
+/// The `address` parameter identifies the EEPROM by its UUID. The step result to continue with is
+/// returned.
+fn invoke_read_config(address: &Address) -> RunResult {
+	if invoke_method(address, CONFIG_METHOD, None) {
+		RunResult::RunNext
+	} else {
+		RunResult::Return
+	}
+}
+
+/// The error message reported when the EEPROM returns a malformed boot configuration.
+#[cfg(not(feature = "volatile-config"))]
+const CONFIG_METHOD_BAD: &str = "BIOS: eeprom.getData bad";
+#[cfg(feature = "volatile-config")]
+const CONFIG_METHOD_BAD: &str = "BIOS: eeprom.getVolatileData bad";
+
+/// The buffer into which the boot configuration read from an EEPROM is fetched.
+///
+/// An EEPROM’s data area is 256 bytes so 300 should be plenty for the CBOR overhead. It is static,
+/// rather than on the stack, because zeroing a stack buffer this large compiles into a call to
+/// memset, which is larger than everything else that needs it put together.
+static CONFIG_RESULT_BUFFER: SyncUnsafeCell<[u8; 300]> = SyncUnsafeCell::new([0; 300]);
+
+/// The name of the EEPROM method that writes the data area holding the boot configuration.
+#[cfg(all(feature = "remember-device", not(feature = "volatile-config")))]
+const CONFIG_WRITE_METHOD: &str = "setData";
+#[cfg(all(feature = "remember-device", feature = "volatile-config"))]
+const CONFIG_WRITE_METHOD: &str = "setVolatileData";
+
+/// The size of an EEPROM’s data area.
+#[cfg(feature = "remember-device")]
+const EEPROM_DATA_MAX: usize = 256;
+
+/// The information needed to designate a new boot device in the EEPROM.
+#[cfg(feature = "remember-device")]
+struct EepromData {
+	/// The UUID of the EEPROM.
+	pub address: Address,
+
+	/// The contents of the data area as read, in the first `len` bytes.
+	pub data: [u8; EEPROM_DATA_MAX],
+
+	/// The length of the data area’s contents.
+	pub len: usize,
+}
+
+/// The EEPROM whose designated boot device should be updated after booting from a filesystem
+/// found by a scan, or `None` if it should not be updated.
+#[cfg(feature = "remember-device")]
+static EEPROM_DATA: SyncUnsafeCell<Option<EepromData>> = SyncUnsafeCell::new(None);
+
+/// The size of the largest image that can be embedded in an EEPROM’s data area: the whole data
+/// area, less the marker byte.
+#[cfg(feature = "eeprom-image")]
+const EMBEDDED_IMAGE_MAX: usize = 255;
+
+/// An image embedded in an EEPROM’s data area.
+#[cfg(feature = "eeprom-image")]
+struct EmbeddedImage {
+	/// The image, in the first `len` bytes.
+	pub data: [u8; EMBEDDED_IMAGE_MAX],
+
+	/// The length of the image.
+	pub len: usize,
+}
+
+/// The image embedded in the EEPROM, to boot if no medium is bootable, or `None` if there is none.
+#[cfg(feature = "eeprom-image")]
+static EMBEDDED_IMAGE: SyncUnsafeCell<Option<EmbeddedImage>> = SyncUnsafeCell::new(None);
+
+/// Starts booting the image embedded in the EEPROM, discarding any other image data loaded so far.
+///
+/// The step result to continue with, or the error adding the image to the execution buffer, is
+/// returned. If there is no embedded image, or it has already been tried, `None` is returned.
+#[cfg(feature = "eeprom-image")]
+fn boot_embedded_image() -> Option<Result<(RunResult, State), BiosError>> {
+	// SAFETY: Wasm is single-threaded, and EMBEDDED_IMAGE is only accessed in single statements.
+	let image = unsafe { (*EMBEDDED_IMAGE.get()).take() }?;
+	clear_image();
+	#[cfg(feature = "boot-record")]
+	note_source("eeprom", None, None);
+	Some(
+		add_image_data(&image.data[..image.len])
+			.map(|()| (RunResult::RunNext, State::ReadyToExecute)),
+	)
+}
+
+/// The size of the parameters built by [`remember_device`].
+#[cfg(feature = "remember-device")]
+const REMEMBER_PARAMS_LEN: usize = 1 + 3 + EEPROM_DATA_MAX;
+
+/// Starts designating a filesystem as the boot device in the EEPROM.
+///
+/// The `uuid` parameter is the UUID of the filesystem. The rest of the data area, beyond the UUID,
+/// is kept as it was.
+///
+/// If the EEPROM should be updated, the step result to continue with is returned. If not (because
+/// the data area could not be read, or asks for the designated boot device to be ignored, or the
+/// EEPROM has already been updated), `None` is returned.
+#[cfg(feature = "remember-device")]
+fn remember_device(uuid: &Address) -> Option<(RunResult, State)> {
+	// Don’t designate a filesystem whose image is corrupt.
+	#[cfg(feature = "checksum")]
+	check_image();
+
+	// SAFETY: Wasm is single-threaded, and EEPROM_DATA is only accessed in single statements.
+	let mut eeprom = unsafe { (*EEPROM_DATA.get()).take() }?;
+	eeprom.data[..16].copy_from_slice(uuid.as_bytes());
+	let len = eeprom.len.max(16);
+
+	// Encode the parameters: an array holding the new contents of the data area. The buffer has
+	// room for the array header, a byte array header with a 2-byte count, and the contents, so
+	// this only fails if the BIOS is buggy.
+	let mut buffer = [0_u8; REMEMBER_PARAMS_LEN];
+	let mut writer = cbor::Writer::new(&mut buffer);
+	if writer.write_array_header(1).is_err() || writer.write_bytes(&eeprom.data[..len]).is_err() {
+		internal_error();
+	}
+	let done = invoke_method(&eeprom.address, CONFIG_WRITE_METHOD, Some(&buffer));
+	Some((
+		if done {
+			RunResult::RunNext
+		} else {
+			RunResult::Return
+		},
+		State::RememberingDevice,
+	))
+}
+
+/// Where the image was loaded from, as told to the image in the boot record.
+#[cfg(feature = "boot-record")]
+#[derive(Clone, Copy)]
+struct BootSource {
+	/// The kind of source: `filesystem`, `drive`, `tape`, `http`, `net`, or `eeprom`.
+	pub kind: &'static str,
+
+	/// The UUID of the component the image was loaded from, if any.
+	pub uuid: Option<Address>,
+
+	/// The path of the image file the image was loaded from, if any.
+	///
+	/// For a split image, this is the path the parts’ paths are formed from.
+	pub path: Option<&'static str>,
+}
+
+/// The source the image is being loaded from, or `None` once the boot record has been written.
+#[cfg(feature = "boot-record")]
+static BOOT_SOURCE: SyncUnsafeCell<Option<BootSource>> = SyncUnsafeCell::new(None);
+
+/// Records the source the image is being loaded from.
+///
+/// If loading from the source fails and another is tried, the later call replaces the earlier one.
+#[cfg(feature = "boot-record")]
+fn note_source(kind: &'static str, uuid: Option<Address>, path: Option<&'static str>) {
+	// SAFETY: Wasm is single-threaded, and BOOT_SOURCE is only accessed in single statements.
+	unsafe { *BOOT_SOURCE.get() = Some(BootSource { kind, uuid, path }) };
+}
+
+/// The length of the longest boot record, which fills an EEPROM’s 256-byte volatile data area.
+#[cfg(feature = "boot-record")]
+const BOOT_RECORD_MAX: usize = 256;
+
+/// The length of the longest path recorded in the boot record.
+///
+/// This leaves room for a map header, the `source` key and the longest kind, the `uuid` key and a
+/// 16-byte byte array, and the `path` key and a string header with a 1-byte count. With the
+/// `kernel-args` feature, it also leaves room for the `args` key and the longest arguments string
+/// with its header.
+#[cfg(all(feature = "boot-record", not(feature = "kernel-args")))]
+const MAX_RECORDED_PATH_LEN: usize = BOOT_RECORD_MAX - (1 + 7 + 11 + 5 + 17 + 5 + 2);
+#[cfg(feature = "kernel-args")]
+const MAX_RECORDED_PATH_LEN: usize =
+	BOOT_RECORD_MAX - (1 + 7 + 11 + 5 + 17 + 5 + 2 + 5 + 2 + config::MAX_ARGS_LEN);
+
+/// The arguments to pass to the booted image in the boot record, if any.
+#[cfg(feature = "kernel-args")]
+static KERNEL_ARGS: SyncUnsafeCell<Option<config::Args>> = SyncUnsafeCell::new(None);
+
+/// The size of the parameters built by [`record_boot`].
+#[cfg(feature = "boot-record")]
+const BOOT_RECORD_PARAMS_LEN: usize = 1 + 3 + BOOT_RECORD_MAX;
+
+/// Starts writing the boot record to the EEPROM’s volatile data area.
+///
+/// The record is a CBOR map holding `source`, the kind of source as a string; `uuid`, the UUID of
+/// the component the image was loaded from as a 16-byte byte array, unless there is none; and
+/// `path`, the path of the image file as a string, unless there is none or it is longer than
+/// [`MAX_RECORDED_PATH_LEN`]. With the `kernel-args` feature, it also holds `args`, the arguments
+/// from the boot configuration as a string, unless there are none. If the EEPROM has no volatile
+/// data area, the call fails, which is harmless.
+///
+/// If the record should be written, the step result to continue with is returned. If not (because
+/// it has already been written, or there is no EEPROM), `None` is returned.
+#[cfg(feature = "boot-record")]
+fn record_boot(lister: &mut component::Lister) -> Option<(RunResult, State)> {
+	// SAFETY: Wasm is single-threaded, and BOOT_SOURCE is only accessed in single statements.
+	let source = unsafe { (*BOOT_SOURCE.get()).take() }?;
+	let eeprom = *lister.start(Some("eeprom")).next()?.address();
+
+	// Encode the record. The buffer has room for the longest record, so this only fails if the
+	// BIOS is buggy.
+	let path = source
+		.path
+		.filter(|path| path.len() <= MAX_RECORDED_PATH_LEN);
+	// SAFETY: Wasm is single-threaded, and KERNEL_ARGS is only accessed in single statements.
+	#[cfg(feature = "kernel-args")]
+	let args = unsafe { *KERNEL_ARGS.get() };
+	#[cfg(not(feature = "kernel-args"))]
+	let args: Option<&str> = None;
+	let entries = 1
+		+ usize::from(source.uuid.is_some())
+		+ usize::from(path.is_some())
+		+ usize::from(args.is_some());
+	let mut record = [0_u8; BOOT_RECORD_MAX];
+	let mut writer = cbor::Writer::new(&mut record);
+	if writer.write_map_header(entries).is_err()
+		|| writer.write_string("source").is_err()
+		|| writer.write_string(source.kind).is_err()
+		|| source.uuid.is_some_and(|uuid| {
+			writer.write_string("uuid").is_err() || writer.write_bytes(uuid.as_bytes()).is_err()
+		}) {
+		internal_error();
+	}
+	if path.is_some_and(|path| {
+		writer.write_string("path").is_err() || writer.write_string(path).is_err()
+	}) {
+		internal_error();
+	}
+	#[cfg(feature = "kernel-args")]
+	if args.is_some_and(|args| {
+		writer.write_string("args").is_err() || writer.write_string(args.as_str()).is_err()
+	}) {
+		internal_error();
+	}
+	let len = writer.position();
+
+	// Encode the parameters: an array holding the record. The buffer has room for the array
+	// header, a byte array header with a 2-byte count, and the record, so this only fails if the
+	// BIOS is buggy.
+	let mut buffer = [0_u8; BOOT_RECORD_PARAMS_LEN];
+	let mut writer = cbor::Writer::new(&mut buffer);
+	if writer.write_array_header(1).is_err() || writer.write_bytes(&record[..len]).is_err() {
+		internal_error();
+	}
+	let done = invoke_method(&eeprom, "setVolatileData", Some(&buffer));
+	Some((
+		if done {
+			RunResult::RunNext
+		} else {
+			RunResult::Return
+		},
+		State::RecordingBoot,
+	))
+}
+
+/// The number of seconds to wait before executing the loaded image, if a screen is attached.
+#[cfg(feature = "handoff-delay")]
+const HANDOFF_DELAY: f64 = 2.0;
+
+/// The type of a bootable medium.
+const BOOTABLE_COMPONENT_TYPE: &str = "filesystem";
+
+/// The size of the buffer used to fetch the type of the designated boot device.
+///
+/// With the `lenient-type` feature, there is room for some trailing whitespace after the type
+/// name.
+#[cfg(not(feature = "lenient-type"))]
+const TYPE_BUFFER_LEN: usize = BOOTABLE_COMPONENT_TYPE.len();
+#[cfg(feature = "lenient-type")]
+const TYPE_BUFFER_LEN: usize = BOOTABLE_COMPONENT_TYPE.len() + 8;
+
+/// Returns whether a component type is that of a bootable medium.
+///
+/// By default the type must match exactly. With the `lenient-type` feature, trailing whitespace is
+/// ignored and letter case is not significant.
+fn is_bootable_type(candidate: &str) -> bool {
+	#[cfg(not(feature = "lenient-type"))]
+	{
+		candidate == BOOTABLE_COMPONENT_TYPE
+	}
+	#[cfg(feature = "lenient-type")]
+	{
+		candidate
+			.trim_end()
+			.eq_ignore_ascii_case(BOOTABLE_COMPONENT_TYPE)
+	}
+}
+
+/// Returns whether a component exists and is a bootable medium.
+fn is_bootable(address: &Address) -> bool {
+	// component_type can fail for reasons BufferTooShort or NoSuchComponent. The buffer is long
+	// enough to hold the component type we care about, so either of those means the component is
+	// either not found or is not a bootable medium.
+	component::component_type(address, &mut [0; TYPE_BUFFER_LEN]).is_ok_and(|t| is_bootable_type(t))
+}
+
+/// Starts a new listing of all bootable media.
+///
+/// The lister only keeps track of one listing at a time, so starting a listing ends the one before
+/// it: an earlier listing that is still held carries on with the new listing’s entries rather than
+/// its own. The borrow checker cannot catch this, because each step of the state machine takes a
+/// fresh `'static` reference to the lister. The state machine therefore upholds the invariant that
+/// a listing held in a state is never used again once another listing has been started; every
+/// step that starts a listing either starts from a state that holds none or leaves behind the one
+/// it held.
+fn restart_scan(lister: &'static mut component::Lister) -> component::Listing<'static> {
+	lister.start(Some(BOOTABLE_COMPONENT_TYPE))
+}
+
+/// The label that identifies a filesystem as an installer.
+///
+/// This is taken from the `BIOS_INSTALLER_LABEL` environment variable at build time, if set.
+#[cfg(feature = "install-once")]
+const INSTALLER_LABEL: &str = match option_env!("BIOS_INSTALLER_LABEL") {
+	Some(label) => label,
+	None => "installer",
+};
+
+/// Starts fetching the label of a filesystem component.
+///
+/// The `address` parameter identifies the component by its UUID.
+///
+/// `true` is returned if the call is complete now. `false` is returned if the call has started but
+/// will not be complete until the next timeslice.
+#[cfg(any(feature = "boot-menu", feature = "install-once"))]
+fn invoke_get_label(address: &Address) -> bool {
+	invoke_method(address, "getLabel", None)
+}
+
+/// The size of the buffer used to fetch the label of a filesystem.
+///
+/// Labels are short, so this is plenty for the label and the CBOR overhead.
+#[cfg(any(
+	feature = "boot-menu",
+	feature = "install-once",
+	feature = "prefer-label",
+	feature = "scan-labels"
+))]
+const LABEL_BUFFER_LEN: usize = 128;
+
+/// Fetches the result of a `filesystem.getLabel` call.
+///
+/// The `buffer` parameter is where to store the call result. The label is returned, or `None` if
+/// the filesystem has no label.
+///
+/// # Errors
+/// [`LabelMalformed`](BiosError::LabelMalformed) is returned if the result is malformed.
+#[cfg(any(
+	feature = "boot-menu",
+	feature = "install-once",
+	feature = "prefer-label",
+	feature = "scan-labels"
+))]
+fn end_get_label(buffer: &mut [u8]) -> Result<Option<&str>, BiosError> {
+	let rc = unsafe { component_sys::invoke_end(buffer.as_mut_ptr(), buffer.len()) };
+	if rc < 0 {
+		call_failed(rc);
+	}
+	let result = call_result(buffer, rc);
+	// We expect a 1-element array holding either a string or null.
+	let mut reader = cbor::Reader::new(result);
+	let label = (|| {
+		reader.expect_array(1)?;
+		match reader.read_header()? {
+			(cbor::MajorType::String, len) => Ok(Some(reader.read_payload(len)?)),
+			(cbor::MajorType::Special, 22) => Ok(None),
+			_ => Err(error::Error::CborDecode),
+		}
+	})()
+	.map_err(|_| BiosError::LabelMalformed)?;
+	match label {
+		Some(label) => core::str::from_utf8(label)
+			.map(Some)
+			.map_err(|_| BiosError::LabelMalformed),
+		None => Ok(None),
+	}
+}
+
+/// Fetches the label of a filesystem component straight away.
+///
+/// The `address` parameter identifies the component by its UUID. The `buffer` parameter is where to
+/// store the call result, which should be [`LABEL_BUFFER_LEN`] bytes long.
+///
+/// The label is returned, or `None` if the filesystem has no label, the label is malformed, or the
+/// call could not be completed within this timeslice.
+#[cfg(any(feature = "prefer-label", feature = "scan-labels"))]
+fn read_label<'buffer>(address: &Address, buffer: &'buffer mut [u8]) -> Option<&'buffer str> {
+	// If the call queue is full, the label cannot be fetched straight away.
+	if start_method(address, "getLabel", None)? {
+		end_get_label(buffer).ok().flatten()
+	} else {
+		// getLabel is a direct call, so this only happens if the timeslice’s call budget has run
+		// out. Abandon the call rather than waiting for it.
+		// SAFETY: invoke_cancel is unconditionally safe.
+		unsafe { component_sys::invoke_cancel() };
+		None
+	}
+}
+
+/// The prefix of the labels of the filesystems that a scan tries first, if the boot configuration
+/// gives one.
+#[cfg(feature = "prefer-label")]
+static LABEL_PREFIX: SyncUnsafeCell<Option<config::LabelPrefix>> = SyncUnsafeCell::new(None);
+
+/// Returns whether a scan should try a filesystem before those whose labels lack the preferred
+/// prefix.
+///
+/// The `address` parameter identifies the filesystem by its UUID. A filesystem whose label cannot
+/// be fetched straight away is not preferred.
+#[cfg(feature = "prefer-label")]
+fn is_preferred(address: &Address) -> bool {
+	// SAFETY: Wasm is single-threaded, and LABEL_PREFIX is only accessed in single statements.
+	let Some(prefix) = (unsafe { *LABEL_PREFIX.get() }) else {
+		return false;
+	};
+	read_label(address, &mut [0; LABEL_BUFFER_LEN])
+		.is_some_and(|label| label.starts_with(prefix.as_str()))
+}
+
+/// Whether a scan tries filesystems in order of UUID.
+#[cfg(feature = "sorted-scan")]
+static SORTED_SCAN: SyncUnsafeCell<bool> = SyncUnsafeCell::new(false);
+
+/// Returns whether a scan should try filesystems in order of UUID rather than in listing order.
+#[cfg(feature = "sorted-scan")]
+fn sort_scan() -> bool {
+	// SAFETY: Wasm is single-threaded, and SORTED_SCAN is only accessed in single statements.
+	unsafe { *SORTED_SCAN.get() }
+}
+
+/// The largest total space, in bytes, of a filesystem that looks like removable media, which is
+/// the capacity of a floppy disk.
+#[cfg(feature = "removable-first")]
+const REMOVABLE_MAX_SPACE: u64 = 512 * 1024;
+
+/// Whether a scan tries filesystems that look like removable media first.
+#[cfg(feature = "removable-first")]
+static REMOVABLE_FIRST: SyncUnsafeCell<bool> = SyncUnsafeCell::new(false);
+
+/// The most filesystems that a scan tries, unless the boot configuration says otherwise.
+#[cfg(feature = "scan-limit")]
+const MAX_SCAN: usize = 64;
+
+/// The most filesystems that a scan tries.
+#[cfg(feature = "scan-limit")]
+static SCAN_LIMIT: SyncUnsafeCell<usize> = SyncUnsafeCell::new(MAX_SCAN);
+
+/// The filesystems that a scan skips, if the boot configuration lists any.
+#[cfg(feature = "skip-list")]
+static SKIP_LIST: SyncUnsafeCell<Option<config::SkipList>> = SyncUnsafeCell::new(None);
+
+/// Returns whether a scan should skip a filesystem without trying to boot from it.
+#[cfg(feature = "skip-list")]
+fn is_skipped(address: &Address) -> bool {
+	// SAFETY: Wasm is single-threaded, and SKIP_LIST is only accessed in single statements.
+	unsafe { *SKIP_LIST.get() }.is_some_and(|skip_list| skip_list.contains(address))
+}
+
+/// Returns the priority with which a scan should try a filesystem, higher priorities first.
+///
+/// The `address` parameter identifies the filesystem by its UUID. If the boot configuration asks
+/// for removable media to be tried first, a filesystem with a total space of at most
+/// [`REMOVABLE_MAX_SPACE`] looks like a floppy disk and has priority 1. Every other filesystem,
+/// including one whose total space cannot be fetched straight away, has priority 0.
+#[cfg(feature = "removable-first")]
+fn boot_priority(address: &Address) -> u8 {
+	// SAFETY: Wasm is single-threaded, and REMOVABLE_FIRST is only accessed in single statements.
+	if !unsafe { *REMOVABLE_FIRST.get() } {
+		return 0;
+	}
+	match start_method(address, "spaceTotal", None) {
+		Some(true) => (),
+		Some(false) => {
+			// spaceTotal is a direct call, so this only happens if the timeslice’s call budget has
+			// run out. Abandon the call rather than waiting for it.
+			// SAFETY: invoke_cancel is unconditionally safe.
+			unsafe { component_sys::invoke_cancel() };
+			return 0;
+		}
+		// The call queue is full, so the total space cannot be fetched straight away.
+		None => return 0,
+	}
+	// We expect a 1-element array holding a number, which is usually an integer but may be a
+	// floating-point number. Filesystems with unlimited space report a floating-point infinity,
+	// which does not look removable.
+	let mut result_buffer = [0_u8; 16];
+	let rc = unsafe { component_sys::invoke_end(result_buffer.as_mut_ptr(), result_buffer.len()) };
+	if rc < 0 {
+		return 0;
+	}
+	let mut reader = cbor::Reader::new(call_result(&result_buffer, rc));
+	if reader.expect_array(1).is_err() {
+		return 0;
+	}
+	let space = reader.remaining();
+	let removable = if let Ok((space, _)) = cbor::read_float(space) {
+		// Cast is sound because REMOVABLE_MAX_SPACE is far below 2⁵³.
+		#[allow(clippy::cast_precision_loss)]
+		(0.0..=REMOVABLE_MAX_SPACE as f64).contains(&space)
+	} else {
+		matches!(reader.read_uint(), Ok(space) if space <= REMOVABLE_MAX_SPACE)
+	};
+	u8::from(removable)
+}
+
+/// Starts checking whether a file exists on a filesystem component.
+///
+/// The `address` parameter identifies the component by its UUID. The `path` parameter is the path
+/// to the file, which must be at most [`MAX_PATH_LEN`] bytes long.
+///
+/// `true` is returned if the call is complete now. `false` is returned if the call has started but
+/// will not be complete until the next timeslice.
+#[cfg(any(feature = "boot-menu", feature = "check-exists"))]
+fn invoke_exists(address: &Address, path: &str) -> bool {
+	invoke_with_path(address, "exists", path)
+}
+
+/// Fetches the result of a `filesystem.exists` call.
+///
+/// `true` is returned if the file exists, or `false` if it does not.
+///
+/// # Errors
+/// [`ExistsMalformed`](BiosError::ExistsMalformed) is returned if the result is malformed.
+#[cfg(any(feature = "boot-menu", feature = "check-exists"))]
+fn end_exists() -> Result<bool, BiosError> {
+	// Fetch the call result, which is a 1-element array holding a boolean.
+	let mut result_buffer = [0_u8; 8];
+	let rc = unsafe { component_sys::invoke_end(result_buffer.as_mut_ptr(), result_buffer.len()) };
+	if rc < 0 {
+		call_failed(rc);
+	}
+	let result = call_result(&result_buffer, rc);
+	let mut reader = cbor::Reader::new(result);
+	let value = (|| {
+		reader.expect_array(1)?;
+		reader.read_special()
+	})()
+	.map_err(|_| BiosError::ExistsMalformed)?;
+	match value {
+		// false
+		20 => Ok(false),
+		// true
+		21 => Ok(true),
+		_ => Err(BiosError::ExistsMalformed),
+	}
+}
+
+/// The size of the largest image file that is booted.
+#[cfg(feature = "size-check")]
+const MAX_IMAGE_SIZE: usize = 4 * 1024 * 1024;
+
+/// Fetches the result of a `filesystem.size` call.
+///
+/// The size of the file is returned, or `None` if the filesystem did not report one.
+///
+/// # Errors
+/// [`SizeMalformed`](BiosError::SizeMalformed) is returned if the result is malformed.
+#[cfg(feature = "size-check")]
+fn end_size() -> Result<Option<u64>, BiosError> {
+	// Fetch the call result, which is a 1-element array holding an integer or null.
+	let mut result_buffer = [0_u8; 16];
+	let rc = unsafe { component_sys::invoke_end(result_buffer.as_mut_ptr(), result_buffer.len()) };
+	if rc < 0 {
+		call_failed(rc);
+	}
+	let result = call_result(&result_buffer, rc);
+	let mut reader = cbor::Reader::new(result);
+	(|| {
+		reader.expect_array(1)?;
+		match reader.read_header()? {
+			(cbor::MajorType::UnsignedInteger, size) => Ok(Some(size)),
+			(cbor::MajorType::Special, 22) => Ok(None),
+			_ => Err(error::Error::CborDecode),
+		}
+	})()
+	.map_err(|_| BiosError::SizeMalformed)
+}
+
+/// The buffer into which the result of a `filesystem.open` call is fetched.
+///
+/// An open call returns either a handle or else a null followed by the filename you tried to open,
+/// so it is large enough to hold either of those. It is static, rather than on the stack, for the
+/// same reason as [`CONFIG_RESULT_BUFFER`].
+static OPEN_RESULT_BUFFER: SyncUnsafeCell<[u8; 32 + MAX_PATH_LEN]> =
+	SyncUnsafeCell::new([0; 32 + MAX_PATH_LEN]);
+
+/// Fetches the result of a `filesystem.open` call.
+///
+/// If the file was opened, its descriptor is returned. If it could not be opened (most likely
+/// because it does not exist), `None` is returned. This is the case whether the call failed or
+/// returned `false` or null, optionally followed by a reason. With the `bare-results` feature, the
+/// descriptor may also be on its own rather than in an array.
+///
+/// # Errors
+/// [`OpenFailed`](BiosError::OpenFailed) is returned if the result is malformed.
+fn end_open() -> Result<Option<descriptor::Owned>, BiosError> {
+	// Fetch the call result.
+	// SAFETY: Wasm is single-threaded, so only one thread will be here touching OPEN_RESULT_BUFFER
+	// at a time. This is the only place in which OPEN_RESULT_BUFFER is touched, and nothing borrowed
+	// from it outlives the call, so the same thread also cannot make a second mutable reference.
+	let result_buffer = unsafe { &mut *OPEN_RESULT_BUFFER.get() };
+	let rc = end_call(result_buffer);
+	if rc >= 0 {
+		// Decode the first data item.
+		let result = call_result(result_buffer, rc);
+		// We expect a 1-element array holding a descriptor, or else an array starting with false
+		// or null if the file could not be opened.
+		let mut reader = cbor::Reader::new(result);
+		let descriptor = (|| {
+			// With the bare-results feature, the descriptor may also be on its own.
+			#[cfg(feature = "bare-results")]
+			if let Ok(raw) = cbor::Reader::new(result).read_descriptor() {
+				// SAFETY: OC-Wasm has just handed the descriptor over, so nothing else owns it.
+				return Ok(Some(unsafe { descriptor::Owned::new(raw) }));
+			}
+			match reader.read_header()? {
+				(cbor::MajorType::Array, count) if count >= 1 => {
+					if reader.consume_byte(0xF4 /* false */)
+						|| reader.consume_byte(0xF6 /* null */)
+					{
+						Ok(None)
+					} else if count == 1 {
+						reader.read_descriptor().map(|raw| {
+							// SAFETY: OC-Wasm has just handed the descriptor over, so nothing else
+							// owns it.
+							Some(unsafe { descriptor::Owned::new(raw) })
+						})
+					} else {
+						Err(error::Error::CborDecode)
+					}
+				}
+				_ => Err(error::Error::CborDecode),
+			}
+		})()
+		.map_err(|_| BiosError::OpenFailed)?;
+		let Some(descriptor) = descriptor else {
+			// The file could not be opened. Report the reason, if one was given, for power users.
+			#[cfg(feature = "verbose")]
+			if let Ok((cbor::MajorType::String, len)) = reader.read_header() {
+				if let Ok(reason) = reader.read_payload(len) {
+					verbose::open_failed(reason);
+				}
+			}
+			return Ok(None);
+		};
+		// We got a file descriptor.
+		Ok(Some(descriptor))
+	} else if rc == -12
+	/* Other error */
+	{
+		// This probably means open failed.
+		Ok(None)
+	} else {
+		Err(BiosError::OpenFailed)
+	}
+}
+
+/// The message of the exception a filesystem throws when asked to open a file while it already
+/// has as many files open as it allows.
+#[cfg(feature = "defer-busy")]
+const BUSY_MESSAGE: &[u8] = b"too many open handles";
+
+/// Returns whether the most recent call failed because the filesystem was busy.
+///
+/// This is only meaningful right after [`end_open`] returns `None`, in which case it distinguishes
+/// a filesystem that cannot open any more files (for example because another computer is writing
+/// to it) from one that does not have the file.
+#[cfg(feature = "defer-busy")]
+fn open_busy() -> bool {
+	let mut buffer = [0_u8; BUSY_MESSAGE.len()];
+	// SAFETY: The buffer pointer and length are valid.
+	let rc = unsafe { component_sys::last_exception_message(buffer.as_mut_ptr(), buffer.len()) };
+	usize::try_from(rc) == Ok(BUSY_MESSAGE.len()) && buffer == BUSY_MESSAGE
+}
+
+/// Fetches the result of a `filesystem.read` call.
+///
+/// The `buffer` parameter is where to store the call result; it must be large enough to hold the
+/// number of bytes requested plus some CBOR overhead. The `sink` parameter is called with the
+/// bytes read.
+///
+/// The result is normally a 1-element array holding either a byte array of file data or null at end
+/// of file. With the `chunked-read` feature, the array may instead hold any number of byte arrays,
+/// which are passed to `sink` in order, optionally followed by a null at end of file. A null
+/// followed by anything else, normally the reason a read failed, is not end of file. With the
+/// `indefinite-read` feature, any byte array may instead be of indefinite length, in which case
+/// each of its chunks is passed to `sink` in order. With the `map-read` feature, any element of the
+/// array may instead be a map holding `data`, a byte array of file data passed to `sink`, and
+/// `eof`, `true` at end of file; either may be absent, and entries with other keys are skipped.
+/// With the `bare-results` feature, the result may instead be a single element on its own rather
+/// than in an array.
+///
+/// `Some(true)` is returned at end of file, `Some(false)` if there may be more data to read, or
+/// `None` if the result is malformed.
+fn end_read(
+	buffer: &mut [u8],
+	mut sink: impl FnMut(&[u8]) -> Result<(), BiosError>,
+) -> Result<Option<bool>, BiosError> {
+	let rc = end_call(buffer);
+	if rc < 0 {
+		call_failed(rc);
+	}
+	let result = call_result(buffer, rc);
+	// Decode the first data item. Any decoding failure means the result is malformed.
+	let mut reader = cbor::Reader::new(result);
+	let count = match reader.read_header() {
+		#[cfg(not(feature = "chunked-read"))]
+		Ok((cbor::MajorType::Array, 1)) => 1,
+		#[cfg(feature = "chunked-read")]
+		Ok((cbor::MajorType::Array, count)) if count >= 1 => count,
+		#[cfg(feature = "bare-results")]
+		Ok((major, _)) if major != cbor::MajorType::Array => {
+			// We got a single value on its own. Decode it as if it were wrapped in an array.
+			reader = cbor::Reader::new(result);
+			1
+		}
+		_ => {
+			// We did not get an array of the expected length.
+			return Ok(None);
+		}
+	};
+	for _ in 0..count {
+		#[cfg(feature = "indefinite-read")]
+		if reader.consume_byte(0x5F) {
+			// We got an indefinite-length byte array (major type 2, additional information 31).
+			// It holds definite-length byte arrays of file data, terminated by a break (0xFF).
+			while !reader.consume_byte(0xFF) {
+				let Ok(data) = reader.read_bytes() else {
+					// We got something other than a byte array inside the byte array.
+					return Ok(None);
+				};
+				sink(data)?;
+			}
+			continue;
+		}
+		match reader.read_header() {
+			Ok((cbor::MajorType::Bytes, len)) => {
+				// We got some file data.
+				let Ok(data) = reader.read_payload(len) else {
+					return Ok(None);
+				};
+				sink(data)?;
+			}
+			Ok((cbor::MajorType::Special, 22)) => {
+				// We got null. On its own, it indicates EOF. Followed by a reason, it indicates
+				// that the read failed.
+				if reader.remaining().is_empty() {
+					return Ok(Some(true));
+				}
+				#[cfg(feature = "verbose")]
+				if let Ok((cbor::MajorType::String, len)) = reader.read_header() {
+					if let Ok(reason) = reader.read_payload(len) {
+						verbose::read_failed(reason);
+					}
+				}
+				return Ok(None);
+			}
+			#[cfg(feature = "map-read")]
+			Ok((cbor::MajorType::Map, entries)) => {
+				// We got a map holding some file data and whether it is the end of the file.
+				let mut eof = false;
+				for _ in 0..entries {
+					let key = match reader.read_header() {
+						Ok((cbor::MajorType::String, len)) => reader.read_payload(len),
+						_ => return Ok(None),
+					};
+					match key {
+						Ok(b"data") => {
+							let Ok(data) = reader.read_bytes() else {
+								return Ok(None);
+							};
+							sink(data)?;
+						}
+						Ok(b"eof") => match reader.read_special() {
+							Ok(20) => eof = false,
+							Ok(21) => eof = true,
+							_ => return Ok(None),
+						},
+						Ok(_) if reader.skip().is_ok() => (),
+						_ => return Ok(None),
+					}
+				}
+				if eof {
+					return Ok(Some(true));
+				}
+			}
+			_ => {
+				// We got something unexpected.
+				return Ok(None);
+			}
+		}
+	}
+	Ok(Some(false))
+}
+
+/// The type of an unmanaged drive that may hold an image.
+#[cfg(feature = "drive-boot")]
+const DRIVE_COMPONENT_TYPE: &str = "drive";
+
+/// The bytes at the start of the first sector of an unmanaged drive, or the start of a tape, that
+/// holds an image.
+///
+/// They are followed by the length of the image in bytes, as a 32-bit integer with the most
+/// significant byte first, and then by the image itself, which continues into as many following
+/// sectors as needed.
+#[cfg(any(feature = "drive-boot", feature = "tape-boot"))]
+const IMAGE_SIGNATURE: &[u8; 4] = b"OCWB";
+
+/// Splits the header off the start of an unmanaged drive or tape.
+///
+/// If `data` starts with [`IMAGE_SIGNATURE`] and the image length, the length and the rest of
+/// `data` are returned. Otherwise, `None` is returned.
+#[cfg(any(feature = "drive-boot", feature = "tape-boot"))]
+fn split_image_header(data: &[u8]) -> Option<(usize, &[u8])> {
+	let (header, data) = data.split_at_checked(IMAGE_SIGNATURE.len() + 4)?;
+	let len = header.strip_prefix(IMAGE_SIGNATURE)?.try_into().ok()?;
+	// Cast is sound because usize and u32 are the same size on Wasm.
+	Some((u32::from_be_bytes(len) as usize, data))
+}
+
+/// Fetches the result of a method call that returns a byte array, such as `drive.readSector` or
+/// `tape_drive.read`.
+///
+/// The `buffer` parameter is where to store the call result. The byte array is returned, or
+/// `None` if the call failed, its result does not fit in `buffer`, or the result is malformed.
+#[cfg(any(feature = "drive-boot", feature = "tape-boot"))]
+fn end_read_bytes(buffer: &mut [u8]) -> Option<&[u8]> {
+	let rc = unsafe { component_sys::invoke_end(buffer.as_mut_ptr(), buffer.len()) };
+	if rc < 0 {
+		// The call failed or its result does not fit. Discard it, if it is still there.
+		// SAFETY: invoke_cancel is unconditionally safe.
+		unsafe { component_sys::invoke_cancel() };
+		return None;
+	}
+	let result = call_result(buffer, rc);
+	// We expect a 1-element array holding a byte array.
+	let mut reader = cbor::Reader::new(result);
+	reader.expect_array(1).ok()?;
+	reader.read_bytes().ok()
+}
+
+/// The size of the largest sector that can be read from an unmanaged drive.
+#[cfg(feature = "drive-boot")]
+const MAX_SECTOR_SIZE: usize = 4096;
+
+/// Starts reading a sector from an unmanaged drive.
+///
+/// The `address` parameter identifies the drive component by UUID. The `sector` parameter is the
+/// number of the sector, counting from 1.
+///
+/// `true` is returned if the call is complete now. `false` is returned if the call has started but
+/// will not be complete until the next timeslice.
+#[cfg(feature = "drive-boot")]
+fn invoke_read_sector(address: &Address, sector: u32) -> bool {
+	// Encode the parameters: an array holding the sector number. The buffer has room for the array
+	// header and an integer of up to 32 bits, so this only fails if the BIOS is buggy.
+	let mut buffer = [0_u8; 6];
+	let mut writer = cbor::Writer::new(&mut buffer);
+	if writer.write_array_header(1).is_err() || writer.write_uint(sector.into()).is_err() {
+		internal_error();
+	}
+	invoke_method(address, "readSector", Some(&buffer))
+}
+
+/// Tries the next unmanaged drive in a listing, reading its first sector.
+///
+/// With the `boot-prompt` feature, if there are no more drives, the user is asked for the UUID of
+/// a filesystem to boot from.
+///
+/// # Errors
+/// [`NoBootMedium`](BiosError::NoBootMedium) is returned if there are no more drives.
+#[cfg(feature = "drive-boot")]
+#[cfg_attr(feature = "boot-prompt", allow(clippy::unnecessary_wraps))]
+fn scan_drives(mut listing: component::Listing<'static>) -> Result<(RunResult, State), BiosError> {
+	let Some(uuid) = listing.next().map(|entry| *entry.address()) else {
+		#[cfg(feature = "boot-prompt")]
+		return Ok((RunResult::RunNext, State::StartPrompt));
+		#[cfg(not(feature = "boot-prompt"))]
+		return Err(BiosError::NoBootMedium);
+	};
+	let done = invoke_read_sector(&uuid, 1);
+	Ok((
+		if done {
+			RunResult::RunNext
+		} else {
+			RunResult::Return
+		},
+		State::OpeningDrive(DriveInfo {
+			listing,
+			uuid,
+			sector: 1,
+			remaining: 0,
+		}),
+	))
+}
+
+/// Adds image data read from a sector of an unmanaged drive to the execution buffer, then reads
+/// the next sector or, if the whole image has been read, moves on to executing it.
+///
+/// The `data` parameter is the part of the sector that follows any header. Any of it beyond the end
+/// of the image is ignored.
+///
+/// # Errors
+/// An error is returned if the image data cannot be added to the execution buffer.
+#[cfg(feature = "drive-boot")]
+fn load_sector(mut info: DriveInfo, data: &[u8]) -> Result<(RunResult, State), BiosError> {
+	let data = data.get(..info.remaining).unwrap_or(data);
+	add_image_data(data)?;
+	info.remaining -= data.len();
+	if info.remaining == 0 {
+		return Ok((RunResult::RunNext, State::ReadyToExecute));
+	}
+	info.sector += 1;
+	let done = invoke_read_sector(&info.uuid, info.sector);
+	Ok((
+		if done {
+			RunResult::RunNext
+		} else {
+			RunResult::Return
+		},
+		State::ReadingSectors(info),
+	))
+}
+
+/// The type of a tape drive that may hold an image.
+#[cfg(feature = "tape-boot")]
+const TAPE_COMPONENT_TYPE: &str = "tape_drive";
+
+/// The number of bytes to ask to read from a tape at a time.
+#[cfg(feature = "tape-boot")]
+const TAPE_CHUNK_SIZE: usize = 4096;
+
+/// Fetches the result of a `tape_drive.isReady` call.
+///
+/// `true` is returned if the tape drive holds a tape. `false` is returned if it does not, or if
+/// the call failed or its result is malformed.
+#[cfg(feature = "tape-boot")]
+fn end_tape_ready() -> bool {
+	// Fetch the call result, which is a 1-element array holding a boolean.
+	let mut result_buffer = [0_u8; 8];
+	let rc = unsafe { component_sys::invoke_end(result_buffer.as_mut_ptr(), result_buffer.len()) };
+	if rc < 0 {
+		// SAFETY: invoke_cancel is unconditionally safe.
+		unsafe { component_sys::invoke_cancel() };
+		return false;
+	}
+	let mut reader = cbor::Reader::new(call_result(&result_buffer, rc));
+	// True is special value 21.
+	reader.expect_array(1).is_ok() && matches!(reader.read_special(), Ok(21))
+}
+
+/// Starts moving the tape in a tape drive.
+///
+/// The `address` parameter identifies the tape drive by its UUID. The `amount` parameter is the
+/// number of bytes to move the tape by, which is negative to move it backward.
+///
+/// `true` is returned if the call is complete now. `false` is returned if the call has started but
+/// will not be complete until the next timeslice.
+#[cfg(feature = "tape-boot")]
+fn invoke_seek_tape(address: &Address, amount: i64) -> bool {
+	// Encode the parameters: an array holding the amount. The buffer has room for the array header
+	// and an integer of up to 32 bits, so this only fails if the BIOS is buggy.
+	let mut buffer = [0_u8; 6];
+	let mut writer = cbor::Writer::new(&mut buffer);
+	if writer.write_array_header(1).is_err() || writer.write_int(amount).is_err() {
+		internal_error();
+	}
+	invoke_method(address, "seek", Some(&buffer))
+}
+
+/// Fetches the result of a `tape_drive.seek` call that rewinds the tape.
+///
+/// `true` is returned if the tape moved backward or not at all. `false` is returned if the call
+/// failed or its result is malformed, for example because the tape was taken out.
+#[cfg(feature = "tape-boot")]
+fn end_rewind_tape() -> bool {
+	// Fetch the call result, which is a 1-element array holding how far the tape moved, which is
+	// negative when it moved backward.
+	let mut result_buffer = [0_u8; 16];
+	let rc = unsafe { component_sys::invoke_end(result_buffer.as_mut_ptr(), result_buffer.len()) };
+	if rc < 0 {
+		// SAFETY: invoke_cancel is unconditionally safe.
+		unsafe { component_sys::invoke_cancel() };
+		return false;
+	}
+	let mut reader = cbor::Reader::new(call_result(&result_buffer, rc));
+	reader.expect_array(1).is_ok()
+		&& matches!(cbor::read_int(reader.remaining()), Ok((moved, _)) if moved <= 0)
+}
+
+/// Starts reading the next [`TAPE_CHUNK_SIZE`] bytes from the tape in a tape drive.
+///
+/// The `address` parameter identifies the tape drive by its UUID.
+///
+/// `true` is returned if the call is complete now. `false` is returned if the call has started but
+/// will not be complete until the next timeslice.
+#[cfg(feature = "tape-boot")]
+fn invoke_read_tape(address: &Address) -> bool {
+	// Encode the parameters: an array holding the length. The buffer has room for the array header
+	// and an integer of up to 16 bits, so this only fails if the BIOS is buggy.
+	let mut buffer = [0_u8; 4];
+	let mut writer = cbor::Writer::new(&mut buffer);
+	if writer.write_array_header(1).is_err() || writer.write_uint(TAPE_CHUNK_SIZE as u64).is_err() {
+		internal_error();
+	}
+	invoke_method(address, "read", Some(&buffer))
+}
+
+/// Adds image data read from a tape to the execution buffer, then reads more of the tape or, if
+/// the whole image has been read, moves on to executing it.
+///
+/// The `data` parameter is the data read from the tape that follows any header. Any of it beyond
+/// the end of the image is ignored.
+///
+/// # Errors
+/// An error is returned if the image data cannot be added to the execution buffer.
+#[cfg(feature = "tape-boot")]
+fn load_tape(mut info: TapeInfo, data: &[u8]) -> Result<(RunResult, State), BiosError> {
+	let data = data.get(..info.remaining).unwrap_or(data);
+	add_image_data(data)?;
+	info.remaining -= data.len();
+	if info.remaining == 0 {
+		return Ok((RunResult::RunNext, State::ReadyToExecute));
+	}
+	let done = invoke_read_tape(&info.uuid);
+	Ok((
+		if done {
+			RunResult::RunNext
+		} else {
+			RunResult::Return
+		},
+		State::ReadingTape(info),
+	))
+}
+
+/// The number of bytes to ask to read from an HTTP response body at a time.
+#[cfg(feature = "http-boot")]
+const HTTP_CHUNK_SIZE: usize = 8192;
+
+/// The size of the parameters built by [`invoke_request`].
+#[cfg(feature = "http-boot")]
+const REQUEST_PARAMS_LEN: usize = 1 + 3 + config::MAX_URL_LEN;
+
+/// Starts an HTTP request on an Internet card.
+///
+/// The `address` parameter identifies the component by its UUID. The `url` parameter is the URL
+/// to request, which must be at most [`MAX_URL_LEN`](config::MAX_URL_LEN) bytes long.
+///
+/// `true` is returned if the call is complete now. `false` is returned if the call has started but
+/// will not be complete until the next timeslice.
+#[cfg(feature = "http-boot")]
+fn invoke_request(address: &Address, url: &str) -> bool {
+	// Encode the parameters: an array holding the URL. The buffer has room for the array header, a
+	// string header with a 2-byte count, and a URL of up to MAX_URL_LEN bytes, so this only fails
+	// if the BIOS is buggy.
+	let mut buffer = [0_u8; REQUEST_PARAMS_LEN];
+	let mut writer = cbor::Writer::new(&mut buffer);
+	if writer.write_array_header(1).is_err() || writer.write_string(url).is_err() {
+		internal_error();
+	}
+	invoke_method(address, "request", Some(&buffer))
+}
+
+/// Fetches the result of an `internet.request` call.
+///
+/// If the request was started, its descriptor is returned. If it could not be started (for
+/// example because HTTP is disabled or the URL is not allowed), `None` is returned.
+///
+/// # Errors
+/// [`HttpMalformed`](BiosError::HttpMalformed) is returned if the result is malformed.
+#[cfg(feature = "http-boot")]
+fn end_request() -> Result<Option<descriptor::Owned>, BiosError> {
+	let mut result_buffer = [0_u8; 16];
+	let rc = unsafe { component_sys::invoke_end(result_buffer.as_mut_ptr(), result_buffer.len()) };
+	if rc < 0 {
+		return Ok(None);
+	}
+	let result = call_result(&result_buffer, rc);
+	// We expect a 1-element array holding an Identifier (39) tagged integer.
+	let mut reader = cbor::Reader::new(result);
+	let raw = (|| {
+		reader.expect_array(1)?;
+		reader.read_descriptor()
+	})()
+	.map_err(|_| BiosError::HttpMalformed)?;
+	// SAFETY: OC-Wasm has just handed the descriptor over, so nothing else owns it.
+	Ok(Some(unsafe { descriptor::Owned::new(raw) }))
+}
+
+/// Starts a method call on an HTTP request.
+///
+/// The `descriptor` parameter identifies the request. The `params` parameter is the CBOR-encoded
+/// parameters, or `None` if there are none.
+///
+/// `true` is returned if the call is complete now. `false` is returned if the call has started but
+/// will not be complete until the next timeslice.
+#[cfg(feature = "http-boot")]
+fn invoke_http(descriptor: descriptor::Borrowed<'_>, method: &str, params: Option<&[u8]>) -> bool {
+	let rc = unsafe {
+		component_sys::invoke_value_method(
+			descriptor.as_raw(),
+			method.as_ptr(),
+			method.len(),
+			params.map_or(ptr::null(), <[u8]>::as_ptr),
+		)
+	};
+	// If this fails, it indicates a bug in the BIOS, not a problem with the user’s configuration.
+	if rc < 0 {
+		internal_error();
+	}
+	rc != 0
+}
+
+/// Starts reading from the body of an HTTP response.
+///
+/// The `descriptor` parameter identifies the request.
+///
+/// `true` is returned if the call is complete now. `false` is returned if the call has started but
+/// will not be complete until the next timeslice.
+#[cfg(feature = "http-boot")]
+fn invoke_http_read(descriptor: descriptor::Borrowed<'_>) -> bool {
+	// Encode the parameters: an array holding the byte count. The buffer has room for the array
+	// header and an integer of up to 32 bits, so this only fails if the BIOS is buggy.
+	let mut buffer = [0_u8; 6];
+	let mut writer = cbor::Writer::new(&mut buffer);
+	if writer.write_array_header(1).is_err() || writer.write_uint(HTTP_CHUNK_SIZE as u64).is_err() {
+		internal_error();
+	}
+	invoke_http(descriptor, "read", Some(&buffer))
+}
+
+/// Fetches the result of an HTTP request’s `response` call.
+///
+/// `None` is returned if the response has not arrived yet. `Some(true)` is returned if the server
+/// responded with status 200, or `Some(false)` if it responded with any other status or the
+/// request failed.
+///
+/// # Errors
+/// [`HttpMalformed`](BiosError::HttpMalformed) is returned if the result is malformed.
+#[cfg(feature = "http-boot")]
+fn end_response() -> Result<Option<bool>, BiosError> {
+	// Fetch the call result. This holds the response headers as well as the status, so allow
+	// plenty of room. If the headers do not fit, the request is treated as having failed.
+	let mut result_buffer = [0_u8; 4096];
+	let rc = unsafe { component_sys::invoke_end(result_buffer.as_mut_ptr(), result_buffer.len()) };
+	if rc < 0 {
+		// The connection failed, or the result did not fit. Discard the result, if any.
+		// SAFETY: invoke_cancel is unconditionally safe.
+		unsafe { component_sys::invoke_cancel() };
+		return Ok(Some(false));
+	}
+	let result = call_result(&result_buffer, rc);
+	// We expect an array starting with either the status code or null.
+	let mut reader = cbor::Reader::new(result);
+	(|| match reader.read_header()? {
+		(cbor::MajorType::Array, count) if count >= 1 => match reader.read_header()? {
+			(cbor::MajorType::UnsignedInteger, status) => Ok(Some(status == 200)),
+			(cbor::MajorType::Special, 22) => Ok(None),
+			_ => Err(error::Error::CborDecode),
+		},
+		_ => Err(error::Error::CborDecode),
+	})()
+	.map_err(|_| BiosError::HttpMalformed)
+}
+
+/// The modem port on which a network boot server is discovered and the image is received.
+#[cfg(feature = "net-boot")]
+const NET_BOOT_PORT: u64 = 2049;
+
+/// The message broadcast to discover a network boot server.
+#[cfg(feature = "net-boot")]
+const NET_DISCOVER_MESSAGE: &str = "oc-wasm-bios discover";
+
+/// The number of seconds to wait for a network boot server to reply, or to send the next part of
+/// the image.
+#[cfg(feature = "net-boot")]
+const NET_TIMEOUT: f64 = 5.0;
+
+/// The largest payload of a modem message, which is the default maximum network packet size.
+#[cfg(feature = "net-boot")]
+const MAX_NET_PAYLOAD_LEN: usize = 8192;
+
+/// The buffer into which signals are popped while receiving the image over the network.
+///
+/// It has room for a modem message with the largest payload, plus its other parameters. It is
+/// static, rather than on the stack, so that the stack frame of [`run_step`] does not grow by a
+/// whole message.
+#[cfg(feature = "net-boot")]
+static NET_BUFFER: SyncUnsafeCell<[u8; 256 + MAX_NET_PAYLOAD_LEN]> =
+	SyncUnsafeCell::new([0; 256 + MAX_NET_PAYLOAD_LEN]);
+
+/// The size of the parameters built by [`invoke_modem`].
+#[cfg(feature = "net-boot")]
+const MODEM_PARAMS_LEN: usize = 1 + 3 + 2 + NET_DISCOVER_MESSAGE.len();
+
+/// Starts a method call on a modem that acts on the network boot port.
+///
+/// The `address` parameter identifies the modem by its UUID. The `message` parameter, if present,
+/// is passed after the port, as for `broadcast`.
+///
+/// `true` is returned if the call is complete now. `false` is returned if the call has started but
+/// will not be complete until the next timeslice.
+#[cfg(feature = "net-boot")]
+fn invoke_modem(address: &Address, method: &'static str, message: Option<&str>) -> bool {
+	// Encode the parameters: an array holding the port and the message, if any. The buffer has
+	// room for the array header, an integer of up to 16 bits, a string header with a 1-byte count,
+	// and the discovery message, so this only fails if the BIOS is buggy.
+	let mut buffer = [0_u8; MODEM_PARAMS_LEN];
+	let mut writer = cbor::Writer::new(&mut buffer);
+	if writer
+		.write_array_header(1 + usize::from(message.is_some()))
+		.is_err()
+		|| writer.write_uint(NET_BOOT_PORT).is_err()
+		|| message.is_some_and(|message| writer.write_string(message).is_err())
+	{
+		internal_error();
+	}
+	invoke_method(address, method, Some(&buffer))
+}
+
+/// Returns the sender and payload of a signal popped from the signal queue, if it is a
+/// `modem_message` signal received on the network boot port.
+///
+/// The sender is the UUID of the sending modem, as text. The payload is the first value sent,
+/// which must be a string or byte array; any further values are ignored.
+#[cfg(feature = "net-boot")]
+fn net_message(signal: &[u8]) -> Option<(&[u8], &[u8])> {
+	// We expect the signal name, then an array holding the receiving modem’s address, the sending
+	// modem’s address, the port, the distance, and the values sent.
+	let mut reader = cbor::Reader::new(signal);
+	(|| {
+		match reader.read_header()? {
+			(cbor::MajorType::String, len) if reader.read_payload(len)? == b"modem_message" => (),
+			_ => return Err(error::Error::CborDecode),
+		}
+		match reader.read_header()? {
+			(cbor::MajorType::Array, count) if count >= 5 => (),
+			_ => return Err(error::Error::CborDecode),
+		}
+		match reader.read_header()? {
+			(cbor::MajorType::String, len) => reader.read_payload(len)?,
+			_ => return Err(error::Error::CborDecode),
+		};
+		let sender = match reader.read_header()? {
+			(cbor::MajorType::String, len) => reader.read_payload(len)?,
+			_ => return Err(error::Error::CborDecode),
+		};
+		if reader.read_uint()? != NET_BOOT_PORT {
+			return Err(error::Error::CborDecode);
+		}
+		// The distance is a number, which has no payload.
+		reader.read_header()?;
+		let payload = match reader.read_header()? {
+			(cbor::MajorType::String | cbor::MajorType::Bytes, len) => reader.read_payload(len)?,
+			_ => return Err(error::Error::CborDecode),
+		};
+		Ok((sender, payload))
+	})()
+	.ok()
+}
+
+/// Decodes the result of reading the EEPROM data area.
+///
+/// We expect a single byte array, which is returned. An empty array, with no byte array in it at
+/// all, is treated the same as an empty byte array. With the `encoded-config` feature, the byte
+/// array may be wrapped in an Encoded CBOR Data Item (24) tag. With the `string-config` feature, a
+/// string is accepted in place of the byte array, and its UTF-8 encoding is returned.
+///
+/// # Errors
+/// * [`CborDecode`](error::Error::CborDecode) is returned if the result is malformed.
+fn decode_config_data(result: &[u8]) -> error::Result<&[u8]> {
+	let mut reader = cbor::Reader::new(result);
+	let data = match reader.read_header()? {
+		(cbor::MajorType::Array, 0) => &[][..],
+		(cbor::MajorType::Array, 1) => match reader.read_header()? {
+			(cbor::MajorType::Bytes, len) => reader.read_payload(len)?,
+			#[cfg(feature = "string-config")]
+			(cbor::MajorType::String, len) => reader.read_payload(len)?,
+			#[cfg(feature = "encoded-config")]
+			(cbor::MajorType::Tag, 24) => {
+				// This is an Encoded CBOR Data Item tag. Its payload is a byte array which itself
+				// holds the encoding of the real data item.
+				let encoded = reader.read_bytes()?;
+				if !reader.remaining().is_empty() {
+					return Err(error::Error::CborDecode);
+				}
+				reader = cbor::Reader::new(encoded);
+				reader.read_bytes()?
+			}
+			_ => return Err(error::Error::CborDecode),
+		},
+		_ => return Err(error::Error::CborDecode),
+	};
+	// The byte array must be the whole result.
+	if reader.remaining().is_empty() {
+		Ok(data)
+	} else {
+		Err(error::Error::CborDecode)
+	}
+}
+
+/// The maximum number of filesystems offered in the boot menu, each chosen by a digit key.
+#[cfg(feature = "boot-menu")]
+const MAX_MENU_ENTRIES: usize = 9;
+
+/// The number of seconds to wait for a key to be pressed in the boot menu, unless the boot
+/// configuration says otherwise.
+#[cfg(feature = "boot-menu")]
+const MENU_TIMEOUT: f64 = 5.0;
+
+/// The number of seconds to wait for a key to be pressed in the boot menu.
+#[cfg(feature = "boot-menu")]
+static MENU_WAIT: SyncUnsafeCell<f64> = SyncUnsafeCell::new(MENU_TIMEOUT);
+
+/// Whether the boot menu has been shown, or skipped for lack of a keyboard or screen, already.
+#[cfg(feature = "boot-menu")]
+static MENU_DONE: SyncUnsafeCell<bool> = SyncUnsafeCell::new(false);
+
+/// Moves on to the next filesystem to survey for the boot menu.
+///
+/// If there is another filesystem and room for another candidate, checking whether `/init.wasm`
+/// exists on it is started. Otherwise, the survey is finished: with no candidates a scan is
+/// started, with one it is booted from straight away, and with more the menu is shown.
+#[cfg(feature = "boot-menu")]
+fn survey_next(mut info: MenuInfo) -> (RunResult, State) {
+	if info.len < MAX_MENU_ENTRIES {
+		if let Some(entry) = info.listing.next() {
+			info.current = *entry.address();
+			let done = invoke_exists(&info.current, boot_path());
+			return (
+				if done {
+					RunResult::RunNext
+				} else {
+					RunResult::Return
+				},
+				State::Surveying(info),
+			);
+		}
+	}
+	match info.len {
+		0 => (RunResult::RunNext, State::StartScan),
+		1 => boot_candidate(info.candidates[0]),
+		_ => {
+			// SAFETY: Wasm is single-threaded, and MENU_WAIT is only accessed in single statements.
+			info.deadline = computer::uptime().into_inner() + unsafe { *MENU_WAIT.get() };
+			(RunResult::Return, State::Menu(info))
+		}
+	}
+}
+
+/// Starts booting from a filesystem chosen in the boot menu or typed at the boot prompt.
+///
+/// If it turns out not to be bootable after all, a scan is performed as for a designated boot
+/// device.
+#[cfg(any(feature = "boot-menu", feature = "boot-prompt"))]
+fn boot_candidate(address: Address) -> (RunResult, State) {
+	let done = invoke_open(&address, first_image_filename());
+	(
+		if done {
+			RunResult::RunNext
+		} else {
+			RunResult::Return
+		},
+		State::OpeningFile(OpeningFileInfo {
+			uuid: address,
+			source: UuidSource::Eeprom,
+			filename: 0,
+			#[cfg(feature = "split-image")]
+			part: None,
+		}),
+	)
+}
+
+/// Returns the key code of a signal popped from the signal queue, if it is a `key_down` signal.
+#[cfg(any(feature = "boot-menu", feature = "halt-key", feature = "rescue-key"))]
+fn key_down_code(signal: &[u8]) -> Option<u64> {
+	key_down(signal).map(|(_, code)| code)
+}
+
+/// Returns the character and key code of a signal popped from the signal queue, if it is a
+/// `key_down` signal.
+///
+/// A key that does not type a character, or whose character is not given as an integer, has
+/// character 0.
+#[cfg(any(
+	feature = "boot-menu",
+	feature = "boot-prompt",
+	feature = "halt-key",
+	feature = "rescue-key"
+))]
+fn key_down(signal: &[u8]) -> Option<(u64, u64)> {
+	// We expect the signal name, then an array holding the keyboard’s address, the character, the
+	// key code, and the player name.
+	let mut reader = cbor::Reader::new(signal);
+	(|| {
+		match reader.read_header()? {
+			(cbor::MajorType::String, len) if reader.read_payload(len)? == b"key_down" => (),
+			_ => return Err(error::Error::CborDecode),
+		}
+		match reader.read_header()? {
+			(cbor::MajorType::Array, count) if count >= 3 => (),
+			_ => return Err(error::Error::CborDecode),
+		}
+		match reader.read_header()? {
+			(cbor::MajorType::String, len) => reader.read_payload(len)?,
+			_ => return Err(error::Error::CborDecode),
+		};
+		// The character is a number, which has no payload.
+		let character = match reader.read_header()? {
+			(cbor::MajorType::UnsignedInteger, character) => character,
+			_ => 0,
+		};
+		Ok((character, reader.read_uint()?))
+	})()
+	.ok()
+}
+
+/// Examines every signal in the signal queue, leaving those it keeps in their original order.
+///
+/// `keep` is called with each signal, as popped, in queue order, and returns whether to keep it.
+/// Signals can only be examined by popping them, so [`MARKER_SIGNAL`] is pushed first, each signal
+/// kept is pushed back straight after being examined, and popping the marker again ends the
+/// examination. If the queue is empty, is too full to take the marker, or starts with a signal too
+/// large for [`SIGNAL_BUFFER`], nothing is examined and no marker is pushed. A signal too large
+/// for the buffer that turns up later in the queue cannot be popped either, so examination stops
+/// there, leaving the signals examined before it behind the rest of the queue. The marker then
+/// stays queued, and the next examination carries on up to it instead of pushing another. A signal
+/// that cannot be pushed back because the queue has filled up in the meantime is lost; nothing can
+/// be done about that.
+#[cfg(any(feature = "boot-delay", feature = "halt-key", feature = "rescue-key"))]
+fn examine_signals(mut keep: impl FnMut(&[u8]) -> bool) {
+	// SAFETY: Wasm is single-threaded, so only one thread will be here touching SIGNAL_BUFFER at a
+	// time. This is the only place in which SIGNAL_BUFFER is touched, and the reference is dropped
+	// on return, so the same thread also cannot make a second mutable reference.
+	let buffer = unsafe { &mut *SIGNAL_BUFFER.get() };
+	// SAFETY: Wasm is single-threaded, and MARKER_QUEUED is only accessed in single statements.
+	if !unsafe { *MARKER_QUEUED.get() } {
+		let fits = computer::pull_signal_length()
+			.is_some_and(|len| len.get() <= buffer.len() - SIGNAL_HEADROOM);
+		if !fits || computer::push_signal(MARKER_SIGNAL).is_err() {
+			return;
+		}
+		// SAFETY: Wasm is single-threaded, and MARKER_QUEUED is only accessed in single
+		// statements.
+		unsafe { *MARKER_QUEUED.get() = true };
+	}
+	while let Ok(Some(signal)) = computer::pull_signal(&mut buffer[SIGNAL_HEADROOM..]) {
+		if signal.strip_suffix(&[0x80]) == Some(&MARKER_SIGNAL[1..]) {
+			// SAFETY: Wasm is single-threaded, and MARKER_QUEUED is only accessed in single
+			// statements.
+			unsafe { *MARKER_QUEUED.get() = false };
+			return;
+		}
+		if !keep(signal) {
+			continue;
+		}
+
+		let len = signal.len();
+		computer::push_signal(signal_to_push(buffer, len)).ok();
+	}
+}
+
+/// Re-encodes a signal popped from the signal queue so that it can be pushed back.
+///
+/// The signal is `len` bytes long and starts [`SIGNAL_HEADROOM`] bytes into `buffer`. It is
+/// re-encoded in place, and the re-encoded signal is returned.
+///
+/// A popped signal is the name followed by an array holding the parameters, whereas a signal to
+/// push is a single array holding the name and then the parameters. The name is moved up over the
+/// array header and the longer array header is written in front of it, leaving the parameters
+/// where they are. OC-Wasm always pops signals shaped like that, so anything else is a bug.
+#[cfg(any(feature = "boot-delay", feature = "halt-key", feature = "rescue-key"))]
+fn signal_to_push(buffer: &mut [u8], len: usize) -> &[u8] {
+	let mut reader = cbor::Reader::new(&buffer[SIGNAL_HEADROOM..SIGNAL_HEADROOM + len]);
+	let Ok((cbor::MajorType::String, name_len)) = reader.read_header() else {
+		internal_error();
+	};
+	if reader.read_payload(name_len).is_err() {
+		internal_error();
+	}
+	let name_len = len - reader.remaining().len();
+	let Ok((cbor::MajorType::Array, params)) = reader.read_header() else {
+		internal_error();
+	};
+	let header_len = len - reader.remaining().len() - name_len;
+	let mut header = [0_u8; SIGNAL_HEADROOM];
+	let mut writer = cbor::Writer::new(&mut header);
+	// Cast is sound because a signal has few parameters.
+	#[allow(clippy::cast_possible_truncation)]
+	if writer.write_array_header(params as usize + 1).is_err() {
+		internal_error();
+	}
+	let header_end = writer.position();
+	let start = SIGNAL_HEADROOM + header_len - header_end;
+	buffer.copy_within(
+		SIGNAL_HEADROOM..SIGNAL_HEADROOM + name_len,
+		SIGNAL_HEADROOM + header_len,
+	);
+	buffer[start..start + header_end].copy_from_slice(&header[..header_end]);
+	&buffer[start..SIGNAL_HEADROOM + len]
+}
+
+/// Returns whether the signal queue holds a press of a key, leaving the queue as it was.
+///
+/// The `key` parameter is the key code of the key. The signals are examined with
+/// [`examine_signals`].
+#[cfg(any(feature = "halt-key", feature = "rescue-key"))]
+fn key_pressed(key: u64) -> bool {
+	let mut pressed = false;
+	examine_signals(|signal| {
+		pressed |= key_down_code(signal) == Some(key);
+		true
+	});
+	pressed
+}
+
+/// Pops signals from the signal queue until one selects an entry in the boot menu.
+///
+/// Pressing a digit key from 1 to 9 selects the entry with that number, counting from 0 here. The
+/// `len` parameter is the number of entries. The selected entry is returned, or `None` if the queue
+/// is emptied without one being selected.
+#[cfg(feature = "boot-menu")]
+fn menu_key(len: usize) -> Option<usize> {
+	// If a signal is too large for the buffer, it cannot be popped, so no key press behind it can be
+	// seen and the menu times out.
+	let mut buffer = [0_u8; 256];
+	while let Some(signal) = computer::pull_signal(&mut buffer).ok()? {
+		// The key codes of the digit keys 1 to 9 are 2 to 10.
+		if let Some(code @ 2..=10) = key_down_code(signal) {
+			// Cast is sound because the code is small.
+			#[allow(clippy::cast_possible_truncation)]
+			let index = (code - 2) as usize;
+			if index < len {
+				return Some(index);
+			}
+		}
+	}
+	None
+}
+
+/// Pops signals from the signal queue until one types a character at the boot prompt.
+///
+/// The character is returned, or `None` if the queue is emptied without one being typed.
+#[cfg(feature = "boot-prompt")]
+fn prompt_key() -> Option<u64> {
+	// If a signal is too large for the buffer, it cannot be popped, so no key press behind it can be
+	// seen.
+	let mut buffer = [0_u8; 256];
+	while let Some(signal) = computer::pull_signal(&mut buffer).ok()? {
+		match key_down(signal) {
+			Some((0, _)) | None => (),
+			Some((character, _)) => return Some(character),
+		}
+	}
+	None
+}
+
+/// Starts booting according to a boot configuration, once any boot delay it asks for is over.
+///
+/// With the `boot-delay` feature, if the configuration gives a boot delay and a keyboard and a
+/// screen are attached, a notice is shown and the delay is waited out first. Otherwise, this is the
+/// same as [`boot`].
+#[cfg_attr(not(feature = "boot-delay"), allow(clippy::needless_pass_by_value))]
+fn start_boot(boot_config: Config, lister: &mut component::Lister) -> (RunResult, State) {
+	#[cfg(feature = "boot-delay")]
+	if let Some(delay) = boot_config.delay {
+		if display::bound() && lister.start(Some("keyboard")).next().is_some() {
+			display::delay_notice(true);
+			return (
+				RunResult::Return,
+				State::BootDelay(BootDelayInfo {
+					config: boot_config,
+					deadline: computer::uptime().into_inner() + f64::from(delay) / 1000.0,
+				}),
+			);
+		}
+	}
+	boot(&boot_config, lister)
+}
+
+/// Returns whether the signal queue holds a key press, removing any key presses from it.
+///
+/// The signals are examined with [`examine_signals`], so other signals are left as they were.
+#[cfg(feature = "boot-delay")]
+fn any_key_pressed() -> bool {
+	let mut pressed = false;
+	examine_signals(|signal| {
+		let key = key_down(signal).is_some();
+		pressed |= key;
+		!key
+	});
+	pressed
+}
+
+/// Starts booting from the designated boot device, if it is a filesystem.
+///
+/// Opening `/init.wasm` on it is started (with the `check-exists` feature, checking that
+/// `/init.wasm` exists on it is started first). `None` is returned if the component does not
+/// exist or is not a filesystem.
+fn try_boot_device(boot_device: Address) -> Option<(RunResult, State)> {
+	if !is_bootable(&boot_device) {
+		return None;
+	}
+	#[cfg(feature = "check-exists")]
+	{
+		// Check that the image file exists before trying to open it.
+		let done = invoke_exists(&boot_device, boot_path());
+		Some((
+			if done {
+				RunResult::RunNext
+			} else {
+				RunResult::Return
+			},
+			State::CheckingExists(boot_device),
+		))
+	}
+	#[cfg(not(feature = "check-exists"))]
+	{
+		// Try opening /init.wasm on it.
+		let done = invoke_open(&boot_device, first_image_filename());
+		Some((
+			if done {
+				RunResult::RunNext
+			} else {
+				RunResult::Return
+			},
+			State::OpeningFile(OpeningFileInfo {
+				uuid: boot_device,
+				source: UuidSource::Eeprom,
+				filename: 0,
+				#[cfg(feature = "split-image")]
+				part: None,
+			}),
+		))
+	}
+}
+
+/// The number of seconds to wait for a designated boot device that is not connected to appear.
+#[cfg(feature = "wait-device")]
+const DEVICE_WAIT_TIMEOUT: f64 = 10.0;
+
+/// Starts booting according to a boot configuration.
+///
+/// If the configuration designates a filesystem as the boot device, and does not ask for it to be
+/// ignored, opening `/init.wasm` on it is started (with the `check-exists` feature, checking that
+/// `/init.wasm` exists on it is started first). Otherwise, if it has a boot slot, the filesystem
+/// in that slot is booted from. Otherwise, a scan for bootable media is started.
+///
+/// With the `http-boot` feature, if the configuration gives a URL and there is an Internet card,
+/// downloading the image from the URL is started instead. With the `net-boot` feature, if the
+/// configuration asks for a network boot and there is a modem, discovering a network boot server is
+/// started instead. With the `tape-boot` feature, if the configuration asks for a tape boot and
+/// there is a tape drive, checking for a tape is started instead. With the `wait-device` feature,
+/// if the designated boot device is not connected, waiting for it to appear is started.
+///
+/// Every other setting the configuration gives, such as the boot path or the scan options, is
+/// remembered first, to be used from now on.
+#[cfg_attr(
+	not(any(feature = "http-boot", feature = "net-boot", feature = "tape-boot")),
+	allow(unused_variables)
+)]
+fn boot(boot_config: &Config, lister: &mut component::Lister) -> (RunResult, State) {
+	// Remember the boot path.
+	// SAFETY: Wasm is single-threaded, and no string returned from boot_path is in use here.
+	#[cfg(feature = "config-path")]
+	unsafe {
+		*BOOT_PATH.get() = boot_config.path;
+	}
+
+	// Remember whether to try the second-stage loader. The rescue image is booted directly, so that
+	// a broken second-stage loader can be bypassed too.
+	#[cfg(feature = "stage2")]
+	{
+		#[cfg(feature = "rescue-key")]
+		// SAFETY: Wasm is single-threaded, and RESCUE is only accessed in single statements.
+		let rescue = unsafe { *RESCUE.get() };
+		#[cfg(not(feature = "rescue-key"))]
+		let rescue = false;
+		// SAFETY: Wasm is single-threaded, and STAGE2 is only accessed in single statements.
+		unsafe { *STAGE2.get() = boot_config.flag(config::STAGE2) && !rescue };
+	}
+
+	// Remember the scan limit.
+	// SAFETY: Wasm is single-threaded, and SCAN_LIMIT is only accessed in single statements.
+	#[cfg(feature = "scan-limit")]
+	unsafe {
+		*SCAN_LIMIT.get() = boot_config.scan_limit.map_or(MAX_SCAN, usize::from);
+	}
+
+	// Remember how long to wait in the boot menu.
+	// SAFETY: Wasm is single-threaded, and MENU_WAIT is only accessed in single statements.
+	#[cfg(feature = "boot-menu")]
+	unsafe {
+		*MENU_WAIT.get() = boot_config.menu_timeout.map_or(MENU_TIMEOUT, f64::from);
+	}
+
+	// Remember the filesystems to skip.
+	// SAFETY: Wasm is single-threaded, and SKIP_LIST is only accessed in single statements.
+	#[cfg(feature = "skip-list")]
+	unsafe {
+		*SKIP_LIST.get() = boot_config.skip;
+	}
+
+	// Remember the arguments to pass to the image.
+	// SAFETY: Wasm is single-threaded, and KERNEL_ARGS is only accessed in single statements.
+	#[cfg(feature = "kernel-args")]
+	unsafe {
+		*KERNEL_ARGS.get() = boot_config.args;
+	}
+
+	// Remember whether to seek to the start of each image file before reading it.
+	// SAFETY: Wasm is single-threaded, and SEEK_FIRST is only accessed in single statements.
+	#[cfg(feature = "seek-first")]
+	unsafe {
+		*SEEK_FIRST.get() = boot_config.seek;
+	}
+
+	// Remember whether to look for split images.
+	// SAFETY: Wasm is single-threaded, and SPLIT_IMAGE is only accessed in single statements.
+	#[cfg(feature = "split-image")]
+	unsafe {
+		*SPLIT_IMAGE.get() = boot_config.split;
+	}
+
+	// Remember whether to reboot if booting fails.
+	// SAFETY: Wasm is single-threaded, and REBOOT is only accessed in single statements.
+	#[cfg(feature = "reboot-on-failure")]
+	unsafe {
+		*REBOOT.get() = boot_config.reboot;
+	}
+
+	// Remember whether to sort scans.
+	// SAFETY: Wasm is single-threaded, and SORTED_SCAN is only accessed in single statements.
+	#[cfg(feature = "sorted-scan")]
+	unsafe {
+		*SORTED_SCAN.get() = boot_config.flag(config::SORTED_SCAN);
+	}
+
+	// Remember whether to try removable media first.
+	// SAFETY: Wasm is single-threaded, and REMOVABLE_FIRST is only accessed in single statements.
+	#[cfg(feature = "removable-first")]
+	unsafe {
+		*REMOVABLE_FIRST.get() = boot_config.flag(config::REMOVABLE_FIRST);
+	}
+
+	// Start reporting progress, if asked to.
+	#[cfg(feature = "verbose")]
+	if boot_config.flag(config::VERBOSE) {
+		verbose::enable();
+	}
+
+	// Remember the label prefix.
+	// SAFETY: Wasm is single-threaded, and LABEL_PREFIX is only accessed in single statements.
+	#[cfg(feature = "prefer-label")]
+	unsafe {
+		*LABEL_PREFIX.get() = boot_config.label_prefix;
+	}
+
+	// Remember the checksum.
+	// SAFETY: Wasm is single-threaded, and EXPECTED_CHECKSUM is only accessed in single statements.
+	#[cfg(feature = "checksum")]
+	unsafe {
+		*EXPECTED_CHECKSUM.get() = boot_config.checksum;
+	}
+
+	// If the configuration gives a URL, download the image from it, if there is an Internet card.
+	#[cfg(feature = "http-boot")]
+	if let Some(url) = &boot_config.url {
+		if let Some(internet) = lister.start(Some("internet")).next() {
+			#[cfg(feature = "display")]
+			display::reading(url.as_str());
+			let done = invoke_request(internet.address(), url.as_str());
+			return (
+				if done {
+					RunResult::RunNext
+				} else {
+					RunResult::Return
+				},
+				State::StartingHttp,
+			);
+		}
+	}
+
+	// If the configuration asks for a network boot, discover a server, if there is a modem.
+	#[cfg(feature = "net-boot")]
+	if boot_config.flag(config::NET_BOOT) {
+		if let Some(modem) = lister.start(Some("modem")).next() {
+			let modem = *modem.address();
+			let done = invoke_modem(&modem, "open", None);
+			return (
+				if done {
+					RunResult::RunNext
+				} else {
+					RunResult::Return
+				},
+				State::NetDiscover(modem),
+			);
+		}
+	}
+
+	// If the configuration asks for a tape boot, check for a tape, if there is a tape drive.
+	#[cfg(feature = "tape-boot")]
+	if boot_config.flag(config::TAPE_BOOT) {
+		if let Some(drive) = lister.start(Some(TAPE_COMPONENT_TYPE)).next() {
+			let drive = *drive.address();
+			let done = invoke_method(&drive, "isReady", None);
+			return (
+				if done {
+					RunResult::RunNext
+				} else {
+					RunResult::Return
+				},
+				State::CheckingTape(drive),
+			);
+		}
+	}
+
+	// If the configuration holds a boot list, try each boot device in it in turn, unless asked to
+	// ignore them.
+	#[cfg(feature = "boot-list")]
+	if !boot_config.flag(config::ALWAYS_SCAN) {
+		if let Some(list) = &boot_config.boot_list {
+			return (RunResult::RunNext, State::TryingList(list.clone()));
+		}
+	}
+
+	// If the configuration doesn’t designate a boot device, or if it asks for the designated boot
+	// device to be ignored, don’t explode, just skip straight to scanning for a bootable medium.
+	let boot_device = if boot_config.flag(config::ALWAYS_SCAN) {
+		None
+	} else {
+		boot_config.boot_device
+	};
+	if let Some(boot_device) = boot_device {
+		if let Some(step) = try_boot_device(boot_device) {
+			return step;
+		}
+
+		// If the designated boot device is not there at all, it may just not have been
+		// connected yet. Wait for it for a while.
+		#[cfg(feature = "wait-device")]
+		if matches!(
+			component::component_type(&boot_device, &mut [0; TYPE_BUFFER_LEN]),
+			Err(error::Error::NoSuchComponent)
+		) {
+			return (
+				RunResult::Return,
+				State::WaitingForDevice(WaitingInfo {
+					address: boot_device,
+					deadline: computer::uptime().into_inner() + DEVICE_WAIT_TIMEOUT,
+					#[cfg(feature = "boot-slot")]
+					slot: boot_config.boot_slot,
+				}),
+			);
+		}
+	}
+
+	// We couldn’t a designated boot device (either there wasn’t one, or it was to be ignored, or
+	// it doesn’t exist, or it isn’t a filesystem). Try the boot slot, if there is one.
+	#[cfg(feature = "boot-slot")]
+	if let Some(slot) = boot_config.boot_slot {
+		if !boot_config.flag(config::ALWAYS_SCAN) {
+			return (RunResult::RunNext, State::FindingSlot(slot));
+		}
+	}
+
+	// Start a scan.
+	(RunResult::RunNext, State::StartScan)
+}
+
+/// Runs one step of the state machine.
+///
+/// The `lister` parameter is used to list components. Listings held across steps borrow it, so it
+/// must be the same lister on every step.
+fn run_step(
+	state: State,
+	lister: &'static mut component::Lister,
+) -> Result<(RunResult, State), BiosError> {
+	// Dispatch based on current state.
 	match state {
 		State::Init => {
+			// Find the component to log state transitions to.
+			#[cfg(any(feature = "trace", feature = "verbose"))]
+			trace::find(lister);
+
+			// Halt if the halt key is being pressed.
+			#[cfg(feature = "halt-key")]
+			if lister.start(Some("keyboard")).next().is_some() && key_pressed(HALT_KEY) {
+				return Err(BiosError::HaltedByUser);
+			}
+
+			// Boot the rescue image if the rescue key is being pressed.
+			#[cfg(feature = "rescue-key")]
+			if lister.start(Some("keyboard")).next().is_some() && key_pressed(RESCUE_KEY) {
+				// SAFETY: Wasm is single-threaded, and RESCUE is only accessed in single
+				// statements.
+				unsafe { *RESCUE.get() = true };
+			}
+
+			// Bind a GPU to a screen, if there are both and this hasn’t been done yet, so that
+			// progress can be shown.
+			#[cfg(feature = "display")]
+			if !display::bound() {
+				if let Some((gpu, screen)) = display::find_gpu(lister) {
+					let done = display::invoke_bind(&gpu, &screen);
+					return Ok((
+						if done {
+							RunResult::RunNext
+						} else {
+							RunResult::Return
+						},
+						State::BindingGpu(gpu),
+					));
+				}
+			}
+
 			// Find the UUID of the EEPROM.
+			#[cfg_attr(not(feature = "multi-eeprom"), allow(unused_mut))]
 			let mut listing = lister.start(Some("eeprom"));
-			let eeprom = listing
-				.next()
-				.unwrap_or_else(|| computer::error("BIOS: no EEPROM"));
-			let eeprom_uuid = eeprom.address();
+			let Some(eeprom) = listing.next() else {
+				return Err(BiosError::NoEeprom);
+			};
+			let eeprom_uuid = *eeprom.address();
+
+			// Call the EEPROM’s data-reading method to read the boot device UUID.
+			Ok((
+				invoke_read_config(&eeprom_uuid),
+				State::ReadingBootDeviceUuid(EepromInfo {
+					#[cfg(feature = "remember-device")]
+					address: eeprom_uuid,
+					#[cfg(feature = "multi-eeprom")]
+					listing,
+				}),
+			))
+		}
+		#[cfg(feature = "display")]
+		State::BindingGpu(gpu) => {
+			// Start using the GPU, then carry on from the beginning.
+			display::end_bind(gpu);
+			Ok((RunResult::RunNext, State::Init))
+		}
+		#[cfg_attr(
+			not(any(feature = "multi-eeprom", feature = "remember-device")),
+			allow(unused_variables)
+		)]
+		State::ReadingBootDeviceUuid(info) => {
+			// Fetch the call result.
+			// SAFETY: Wasm is single-threaded, so only one thread will be here touching
+			// CONFIG_RESULT_BUFFER at a time. This is the only place in which CONFIG_RESULT_BUFFER
+			// is touched, and the reference is dropped by the end of the step, so the same thread
+			// also cannot make a second mutable reference.
+			let result_buffer = unsafe { &mut *CONFIG_RESULT_BUFFER.get() };
+			let rc = unsafe {
+				component_sys::invoke_end(result_buffer.as_mut_ptr(), result_buffer.len())
+			};
+			if rc < 0 {
+				call_failed(rc);
+			}
+			let result = call_result(result_buffer, rc);
+
+			// Decode the returned CBOR sequence.
+			let data = decode_config_data(result).map_err(|_| BiosError::EepromDataMalformed)?;
+
+			// Keep any image embedded in the data area, to boot if no medium is bootable. An image
+			// too large to keep is ignored.
+			#[cfg(feature = "eeprom-image")]
+			if let Some(image) = config::embedded_image(data) {
+				if image.len() <= EMBEDDED_IMAGE_MAX {
+					let mut embedded = EmbeddedImage {
+						data: [0; EMBEDDED_IMAGE_MAX],
+						len: image.len(),
+					};
+					embedded.data[..image.len()].copy_from_slice(image);
+					// SAFETY: Wasm is single-threaded, and EMBEDDED_IMAGE is only accessed in
+					// single statements.
+					unsafe { *EMBEDDED_IMAGE.get() = Some(embedded) };
+				}
+			}
+
+			// Parse the boot configuration.
+			let boot_config = Config::parse(data);
+
+			// If it doesn’t say what to boot from, try the next EEPROM, if there is one.
+			#[cfg(feature = "multi-eeprom")]
+			if !boot_config.is_usable() {
+				let mut listing = info.listing;
+				if let Some(eeprom) = listing.next() {
+					let address = *eeprom.address();
+					return Ok((
+						invoke_read_config(&address),
+						State::ReadingBootDeviceUuid(EepromInfo {
+							#[cfg(feature = "remember-device")]
+							address,
+							listing,
+						}),
+					));
+				}
+			}
+
+			// Keep the data area, so that a filesystem found by a scan can be designated as the
+			// boot device later, unless the designated boot device is to be ignored or the data
+			// area holds an embedded image.
+			#[cfg(feature = "remember-device")]
+			if !boot_config.flag(config::ALWAYS_SCAN)
+				&& data.len() <= EEPROM_DATA_MAX
+				&& !config::is_map(data)
+				&& !config::is_embedded_image(data)
+			{
+				let mut eeprom = EepromData {
+					address: info.address,
+					data: [0; EEPROM_DATA_MAX],
+					len: data.len(),
+				};
+				eeprom.data[..data.len()].copy_from_slice(data);
+				// SAFETY: Wasm is single-threaded, and EEPROM_DATA is only accessed in single
+				// statements.
+				unsafe { *EEPROM_DATA.get() = Some(eeprom) };
+			}
+
+			// Look for a boot configuration file, which takes precedence over the EEPROM.
+			#[cfg(feature = "file-config")]
+			{
+				Ok((
+					RunResult::RunNext,
+					State::SearchingConfig(ConfigSearchInfo {
+						first: boot_config.boot_device.filter(is_bootable),
+						eeprom_config: boot_config,
+						listing: restart_scan(lister),
+					}),
+				))
+			}
+			#[cfg(not(feature = "file-config"))]
+			{
+				Ok(start_boot(boot_config, lister))
+			}
+		}
+		#[cfg(feature = "file-config")]
+		State::SearchingConfig(mut info) => {
+			// Search the designated boot device first, then the other filesystems in the list.
+			let next = if let Some(first) = info.first.take() {
+				Some(first)
+			} else {
+				loop {
+					match info.listing.next() {
+						Some(entry) if Some(*entry.address()) == info.eeprom_config.boot_device => {
+						}
+						entry => break entry.map(|entry| *entry.address()),
+					}
+				}
+			};
+			if let Some(address) = next {
+				// Try opening the boot configuration file on it.
+				let done = invoke_open(&address, CONFIG_FILENAME);
+				Ok((
+					if done {
+						RunResult::RunNext
+					} else {
+						RunResult::Return
+					},
+					State::OpeningConfig(address, info),
+				))
+			} else {
+				// No filesystem has a boot configuration file, so use the one from the EEPROM.
+				Ok(start_boot(info.eeprom_config, lister))
+			}
+		}
+		#[cfg(feature = "file-config")]
+		State::OpeningConfig(uuid, info) => {
+			#[cfg(feature = "error-uuid")]
+			note_device(&uuid);
+			if let Some(descriptor) = end_open()? {
+				// We got a file descriptor. Read the file. Ask for one more byte than can be
+				// stored, so that an oversized file is detected.
+				let done = invoke_read(&uuid, descriptor.as_descriptor(), CONFIG_FILE_MAX + 1);
+				Ok((
+					if done {
+						RunResult::RunNext
+					} else {
+						RunResult::Return
+					},
+					State::ReadingConfig(ReadingConfigInfo {
+						descriptor,
+						uuid,
+						data: [0; CONFIG_FILE_MAX],
+						len: 0,
+					}),
+				))
+			} else {
+				// This filesystem has no boot configuration file. Keep looking.
+				Ok((RunResult::RunNext, State::SearchingConfig(info)))
+			}
+		}
+		#[cfg(feature = "file-config")]
+		State::ReadingConfig(mut info) => {
+			// Fetch the call result, appending any file data.
+			let mut result_buffer = [0_u8; 32 + CONFIG_FILE_MAX + 1];
+			let Some(eof) = end_read(&mut result_buffer, |data| {
+				let len = info.len + data.len();
+				if len > CONFIG_FILE_MAX {
+					return Err(BiosError::ConfigTooLarge);
+				}
+				info.data[info.len..len].copy_from_slice(data);
+				info.len = len;
+				Ok(())
+			})?
+			else {
+				return Err(BiosError::ConfigIoError);
+			};
+			if eof {
+				// We got EOF. Boot according to the file’s contents.
+				let boot_config = Config::parse(&info.data[..info.len]);
+				drop(info);
+				Ok(start_boot(boot_config, lister))
+			} else {
+				// Try to get some more.
+				let done = invoke_read(
+					&info.uuid,
+					info.descriptor.as_descriptor(),
+					CONFIG_FILE_MAX + 1 - info.len,
+				);
+				Ok((
+					if done {
+						RunResult::RunNext
+					} else {
+						RunResult::Return
+					},
+					State::ReadingConfig(info),
+				))
+			}
+		}
+		#[cfg(feature = "boot-delay")]
+		State::BootDelay(mut info) => {
+			// Handle any key presses. A key press shows the boot menu, which means scanning as
+			// if the always scan flag were set.
+			if any_key_pressed() {
+				info.config.flags |= config::ALWAYS_SCAN;
+			} else if computer::uptime().into_inner() < info.deadline {
+				return Ok((RunResult::Return, State::BootDelay(info)));
+			}
+			display::delay_notice(false);
+			Ok(boot(&info.config, lister))
+		}
+		#[cfg(feature = "boot-list")]
+		State::TryingList(mut list) => {
+			// Find the next boot device in the list that exists and is a filesystem.
+			while let Some(address) = list.next() {
+				let mut type_buffer = [0_u8; TYPE_BUFFER_LEN];
+				if !component::component_type(&address, &mut type_buffer)
+					.is_ok_and(|t| is_bootable_type(t))
+				{
+					continue;
+				}
+				// Try opening /init.wasm on it. If that fails, move on to the next one.
+				let done = invoke_open(&address, first_image_filename());
+				return Ok((
+					if done {
+						RunResult::RunNext
+					} else {
+						RunResult::Return
+					},
+					State::OpeningFile(OpeningFileInfo {
+						uuid: address,
+						source: UuidSource::List(list),
+						filename: 0,
+						#[cfg(feature = "split-image")]
+						part: None,
+					}),
+				));
+			}
+			// None of the boot devices is bootable. Fall back to a scan.
+			Ok((RunResult::RunNext, State::StartScan))
+		}
+		#[cfg(feature = "wait-device")]
+		State::WaitingForDevice(info) => {
+			// Keep yielding timeslices until the designated boot device appears or the deadline
+			// passes.
+			if let Some(step) = try_boot_device(info.address) {
+				return Ok(step);
+			}
+			if computer::uptime().into_inner() < info.deadline
+				&& matches!(
+					component::component_type(&info.address, &mut [0; TYPE_BUFFER_LEN]),
+					Err(error::Error::NoSuchComponent)
+				) {
+				return Ok((RunResult::Return, State::WaitingForDevice(info)));
+			}
+			// Give up on it, and try the boot slot, if there is one, or else scan.
+			#[cfg(feature = "boot-slot")]
+			if let Some(slot) = info.slot {
+				return Ok((RunResult::RunNext, State::FindingSlot(slot)));
+			}
+			Ok((RunResult::RunNext, State::StartScan))
+		}
+		#[cfg(feature = "boot-slot")]
+		State::FindingSlot(slot) => {
+			// Count through the filesystems to the one in the slot. This uses the listing directly
+			// rather than a Scan, whose order may not be plain listing order.
+			let address = {
+				let mut listing = restart_scan(lister);
+				let mut address = None;
+				for _ in 0..slot {
+					address = listing.next().map(|entry| *entry.address());
+					if address.is_none() {
+						break;
+					}
+				}
+				address
+			};
+			if let Some(address) = address {
+				// Try opening /init.wasm on it. If that fails, fall back to a scan, as with a
+				// designated boot device.
+				let done = invoke_open(&address, first_image_filename());
+				Ok((
+					if done {
+						RunResult::RunNext
+					} else {
+						RunResult::Return
+					},
+					State::OpeningFile(OpeningFileInfo {
+						uuid: address,
+						source: UuidSource::Eeprom,
+						filename: 0,
+						#[cfg(feature = "split-image")]
+						part: None,
+					}),
+				))
+			} else {
+				// There aren’t that many filesystems. Fall back to a scan.
+				Ok((RunResult::RunNext, State::StartScan))
+			}
+		}
+		State::StartScan => {
+			// If there is nothing at all to scan, say so, to tell missing hardware apart from a
+			// missing image file.
+			let storage = lister.start(Some(BOOTABLE_COMPONENT_TYPE)).next().is_some();
+			#[cfg(feature = "drive-boot")]
+			let storage = storage || lister.start(Some(DRIVE_COMPONENT_TYPE)).next().is_some();
+			if !storage {
+				return Err(BiosError::NoStorage);
+			}
+
+			// If there are a keyboard and a screen to show a boot menu on, and it hasn’t been shown
+			// yet, survey the filesystems to offer in it.
+			#[cfg(feature = "boot-menu")]
+			// SAFETY: Wasm is single-threaded, and MENU_DONE is only accessed in single statements.
+			if !unsafe { replace(&mut *MENU_DONE.get(), true) }
+				&& display::bound()
+				&& lister.start(Some("keyboard")).next().is_some()
+			{
+				return Ok(survey_next(MenuInfo {
+					listing: restart_scan(lister),
+					current: Address::default(),
+					candidates: [Address::default(); MAX_MENU_ENTRIES],
+					len: 0,
+					deadline: 0.0,
+				}));
+			}
+
+			// List all components of the proper type and start opening init.wasm on the first one.
+			Ok((RunResult::RunNext, State::Scanning(Scan::start(lister))))
+		}
+		#[cfg(feature = "boot-menu")]
+		State::Surveying(info) => {
+			if end_exists()? {
+				// This filesystem is bootable. Fetch its label to show.
+				let done = invoke_get_label(&info.current);
+				Ok((
+					if done {
+						RunResult::RunNext
+					} else {
+						RunResult::Return
+					},
+					State::Labelling(info),
+				))
+			} else {
+				Ok(survey_next(info))
+			}
+		}
+		#[cfg(feature = "boot-menu")]
+		State::Labelling(mut info) => {
+			// Show the filesystem as a new menu entry and record it.
+			display::menu_entry(
+				info.len,
+				end_get_label(&mut [0; LABEL_BUFFER_LEN])?.unwrap_or(""),
+			);
+			info.candidates[info.len] = info.current;
+			info.len += 1;
+			Ok(survey_next(info))
+		}
+		#[cfg(feature = "boot-menu")]
+		State::Menu(info) => {
+			// Handle any key presses. Boot from the chosen filesystem, or the first one if no
+			// choice is made before the deadline.
+			if let Some(index) = menu_key(info.len) {
+				return Ok(boot_candidate(info.candidates[index]));
+			}
+			if computer::uptime().into_inner() < info.deadline {
+				Ok((RunResult::Return, State::Menu(info)))
+			} else {
+				Ok(boot_candidate(info.candidates[0]))
+			}
+		}
+		State::Scanning(mut scan) => {
+			// Fetch the next component in the list.
+			if let Some(address) = scan.next() {
+				// Move on to the next component if the boot configuration says to skip this one.
+				#[cfg(feature = "skip-list")]
+				if is_skipped(&address) {
+					return Ok((RunResult::RunNext, State::Scanning(scan)));
+				}
+
+				// Give up if the scan has already tried as many components as it is allowed to.
+				#[cfg(feature = "scan-limit")]
+				{
+					// SAFETY: Wasm is single-threaded, and SCAN_LIMIT is only accessed in single
+					// statements.
+					if scan.tried == unsafe { *SCAN_LIMIT.get() } {
+						return Err(BiosError::ScanLimitReached);
+					}
+					scan.tried += 1;
+				}
+
+				// Record the component’s label to report if no bootable medium is found.
+				#[cfg(feature = "scan-labels")]
+				labels::record(read_label(&address, &mut [0; LABEL_BUFFER_LEN]));
+
+				// Report the component, for power users.
+				#[cfg(feature = "verbose")]
+				verbose::scanning(&address);
+
+				// We found a component. Unless installers are allowed, check whether it is one
+				// before trying to boot from it.
+				#[cfg(feature = "install-once")]
+				if !scan.include_installers {
+					let done = invoke_get_label(&address);
+					return Ok((
+						if done {
+							RunResult::RunNext
+						} else {
+							RunResult::Return
+						},
+						State::CheckingLabel(address, scan),
+					));
+				}
+
+				// Try opening /init.wasm on it.
+				let done = invoke_open(&address, first_image_filename());
+				Ok((
+					if done {
+						RunResult::RunNext
+					} else {
+						RunResult::Return
+					},
+					State::OpeningFile(OpeningFileInfo {
+						uuid: address,
+						source: UuidSource::Scan(scan),
+						filename: 0,
+						#[cfg(feature = "split-image")]
+						part: None,
+					}),
+				))
+			} else {
+				// There are no more components. If installers were skipped, scan again, this time
+				// booting from them too.
+				#[cfg(feature = "install-once")]
+				if !scan.include_installers {
+					#[cfg(feature = "scan-limit")]
+					let tried = scan.tried;
+					let mut scan = Scan::start(lister);
+					scan.include_installers = true;
+					#[cfg(feature = "scan-limit")]
+					{
+						scan.tried = tried;
+					}
+					return Ok((RunResult::RunNext, State::Scanning(scan)));
+				}
+				// No filesystem is bootable. Look for an unmanaged drive holding an image.
+				#[cfg(feature = "drive-boot")]
+				return scan_drives(lister.start(Some(DRIVE_COMPONENT_TYPE)));
+				// No medium is bootable. Ask the user which filesystem to boot from.
+				#[cfg(all(feature = "boot-prompt", not(feature = "drive-boot")))]
+				return Ok((RunResult::RunNext, State::StartPrompt));
+				#[cfg(not(any(feature = "boot-prompt", feature = "drive-boot")))]
+				Err(BiosError::NoBootMedium)
+			}
+		}
+		#[cfg(feature = "install-once")]
+		State::CheckingLabel(address, scan) => {
+			if end_get_label(&mut [0; LABEL_BUFFER_LEN])? == Some(INSTALLER_LABEL) {
+				// This is an installer. Skip it for now.
+				Ok((RunResult::RunNext, State::Scanning(scan)))
+			} else {
+				// Try opening /init.wasm on it.
+				let done = invoke_open(&address, first_image_filename());
+				Ok((
+					if done {
+						RunResult::RunNext
+					} else {
+						RunResult::Return
+					},
+					State::OpeningFile(OpeningFileInfo {
+						uuid: address,
+						source: UuidSource::Scan(scan),
+						filename: 0,
+						#[cfg(feature = "split-image")]
+						part: None,
+					}),
+				))
+			}
+		}
+		#[cfg(feature = "check-exists")]
+		State::CheckingExists(address) => {
+			if end_exists()? {
+				// Try opening /init.wasm on it.
+				let done = invoke_open(&address, first_image_filename());
+				Ok((
+					if done {
+						RunResult::RunNext
+					} else {
+						RunResult::Return
+					},
+					State::OpeningFile(OpeningFileInfo {
+						uuid: address,
+						source: UuidSource::Eeprom,
+						filename: 0,
+						#[cfg(feature = "split-image")]
+						part: None,
+					}),
+				))
+			} else {
+				// The designated boot device has no image file. Fall back to a scan.
+				Ok((RunResult::RunNext, State::StartScan))
+			}
+		}
+		#[cfg(feature = "http-boot")]
+		State::StartingHttp => {
+			if let Some(descriptor) = end_request()? {
+				// The request has been sent. Wait for the response.
+				let done = invoke_http(descriptor.as_descriptor(), "response", None);
+				Ok((
+					if done {
+						RunResult::RunNext
+					} else {
+						RunResult::Return
+					},
+					State::AwaitingHttpStatus(descriptor),
+				))
+			} else {
+				// The request could not be made. Fall back to a scan.
+				Ok((RunResult::RunNext, State::StartScan))
+			}
+		}
+		#[cfg(feature = "http-boot")]
+		State::AwaitingHttpStatus(descriptor) => match end_response()? {
+			None => {
+				// The response has not arrived yet. Ask again in the next timeslice.
+				invoke_http(descriptor.as_descriptor(), "response", None);
+				Ok((RunResult::Return, State::AwaitingHttpStatus(descriptor)))
+			}
+			Some(true) => {
+				// The server is sending the image. Read it.
+				#[cfg(feature = "boot-record")]
+				note_source("http", None, None);
+				let done = invoke_http_read(descriptor.as_descriptor());
+				Ok((
+					if done {
+						RunResult::RunNext
+					} else {
+						RunResult::Return
+					},
+					State::ReadingHttpResponse(descriptor),
+				))
+			}
+			Some(false) => {
+				// The request failed. Fall back to a scan.
+				Ok((RunResult::RunNext, State::StartScan))
+			}
+		},
+		#[cfg(feature = "http-boot")]
+		State::ReadingHttpResponse(descriptor) => {
+			// Fetch the call result, adding any data to the execution buffer.
+			let mut result_buffer = [0_u8; 32 + HTTP_CHUNK_SIZE];
+			let mut empty = true;
+			let Some(eof) = end_read(&mut result_buffer, |data| {
+				empty &= data.is_empty();
+				add_image_data(data)
+			})?
+			else {
+				return Err(BiosError::HttpMalformed);
+			};
+			if eof {
+				// We got the whole body.
+				drop(descriptor);
+				Ok((RunResult::RunNext, State::ReadyToExecute))
+			} else {
+				// Try to get some more. If no data was available yet, wait for the next timeslice
+				// rather than asking again straight away.
+				let done = invoke_http_read(descriptor.as_descriptor());
+				Ok((
+					if done && !empty {
+						RunResult::RunNext
+					} else {
+						RunResult::Return
+					},
+					State::ReadingHttpResponse(descriptor),
+				))
+			}
+		}
+		#[cfg(feature = "net-boot")]
+		State::NetDiscover(modem) => {
+			// Discard the result. If the port was already open, the call returns false, which is
+			// harmless.
+			// SAFETY: invoke_cancel is unconditionally safe.
+			unsafe { component_sys::invoke_cancel() };
+			let done = invoke_modem(&modem, "broadcast", Some(NET_DISCOVER_MESSAGE));
+			Ok((
+				if done {
+					RunResult::RunNext
+				} else {
+					RunResult::Return
+				},
+				State::NetReceive(NetInfo {
+					server: None,
+					deadline: computer::uptime().into_inner() + NET_TIMEOUT,
+				}),
+			))
+		}
+		#[cfg(feature = "net-boot")]
+		State::NetReceive(mut info) => {
+			// Discard the result of the broadcast. Once it has been discarded, this does nothing.
+			// SAFETY: invoke_cancel is unconditionally safe.
+			unsafe { component_sys::invoke_cancel() };
+
+			// Pop modem messages, adding the data from the server to the execution buffer. The
+			// first modem to reply becomes the server, and messages from any other are ignored.
+			// SAFETY: Wasm is single-threaded, so only one thread will be here touching NET_BUFFER
+			// at a time. This is the only place in which NET_BUFFER is touched, and the reference
+			// is dropped by the end of the step, so the same thread also cannot make a second
+			// mutable reference.
+			let buffer = unsafe { &mut *NET_BUFFER.get() };
+			while let Some(signal) =
+				computer::pull_signal(buffer).map_err(|_| BiosError::NetSignalTooLarge)?
+			{
+				let Some((sender, payload)) = net_message(signal) else {
+					continue;
+				};
+				let Ok(sender) = <[u8; 36]>::try_from(sender) else {
+					continue;
+				};
+				#[cfg(feature = "boot-record")]
+				if info.server.is_none() {
+					note_source("net", config::parse_text_uuid(&sender), None);
+				}
+				if *info.server.get_or_insert(sender) != sender {
+					continue;
+				}
+				if payload.is_empty() {
+					// An empty message marks the end of the image.
+					#[cfg(feature = "gzip")]
+					check_decompressed()?;
+					return Ok((RunResult::RunNext, State::ReadyToExecute));
+				}
+				add_file_data(payload)?;
+				info.deadline = computer::uptime().into_inner() + NET_TIMEOUT;
+			}
+
+			// Wait for more messages until the deadline. If no server replied at all, fall back
+			// to a scan; if the server stopped part of the way through, the image is incomplete.
+			if computer::uptime().into_inner() < info.deadline {
+				Ok((RunResult::Return, State::NetReceive(info)))
+			} else if info.server.is_none() {
+				Ok((RunResult::RunNext, State::StartScan))
+			} else {
+				Err(BiosError::NetTimeout)
+			}
+		}
+		State::OpeningFile(info) => {
+			// Give up on a filesystem that takes too long to open the file.
+			#[cfg(feature = "call-timeout")]
+			match call_status() {
+				CallStatus::Done => (),
+				CallStatus::Pending => return Ok((RunResult::Return, State::OpeningFile(info))),
+				CallStatus::TimedOut => return Ok(abandon_medium(info.source)),
+			}
+
+			#[cfg(feature = "error-uuid")]
+			note_device(&info.uuid);
+			#[cfg(not(feature = "skip-bad-media"))]
+			let opened = end_open()?;
+			#[cfg(feature = "skip-bad-media")]
+			let Ok(opened) = end_open() else {
+				// The filesystem returned a malformed result. Skip it rather than halting.
+				return Ok(abandon_medium(info.source));
+			};
+			if let Some(descriptor) = opened {
+				// We got a file descriptor. Read the file or, with the size-check feature, fetch its
+				// size first, or with the seek-first feature, seek to its start first.
+				#[cfg(feature = "boot-record")]
+				note_source(
+					"filesystem",
+					Some(info.uuid),
+					Some(image_filename(info.filename).unwrap_or_else(|| internal_error())),
+				);
+				#[cfg(feature = "display")]
+				display::reading(image_filename(info.filename).unwrap_or_else(|| internal_error()));
+				#[cfg(not(any(feature = "seek-first", feature = "size-check")))]
+				let (done, next_state) = (
+					invoke_read(&info.uuid, descriptor.as_descriptor(), image_chunk_size()),
+					State::ReadingFile,
+				);
+				#[cfg(all(feature = "seek-first", not(feature = "size-check")))]
+				let (done, next_state) = invoke_seek_or_read(&info.uuid, descriptor.as_descriptor());
+				#[cfg(feature = "size-check")]
+				let (done, next_state) = {
+					let path = image_filename(info.filename).unwrap_or_else(|| internal_error());
+					#[cfg(feature = "split-image")]
+					let mut name = [0; PART_FILENAME_LEN];
+					#[cfg(feature = "split-image")]
+					let path = match info.part {
+						Some(part) => part_filename(path, part, &mut name),
+						None => path,
+					};
+					(
+						invoke_with_path(&info.uuid, "size", path),
+						State::SizingFile,
+					)
+				};
+				Ok((
+					if done {
+						RunResult::RunNext
+					} else {
+						RunResult::Return
+					},
+					next_state(ReadingFileInfo {
+						uuid: info.uuid,
+						descriptor,
+						#[cfg(any(
+							feature = "call-timeout",
+							feature = "remember-device",
+							feature = "seek-first",
+							feature = "skip-bad-media",
+							feature = "split-image"
+						))]
+						source: info.source,
+						#[cfg(feature = "split-image")]
+						filename: info.filename,
+						#[cfg(feature = "split-image")]
+						part: info.part,
+						#[cfg(feature = "empty-read-limit")]
+						empty_reads: 0,
+						#[cfg(feature = "size-check")]
+						size: None,
+						#[cfg(feature = "size-check")]
+						read: 0,
+					}),
+				))
+			} else {
+				// This probably means open failed. If it failed because the filesystem is busy,
+				// set it aside to retry at the end of the scan, discarding any of its image
+				// already loaded.
+				#[cfg(feature = "defer-busy")]
+				if open_busy() {
+					if let UuidSource::Scan(mut scan) = info.source {
+						scan.defer(info.uuid);
+						return Ok(abandon_medium(UuidSource::Scan(scan)));
+					}
+				}
+
+				#[cfg(feature = "split-image")]
+				match info.part {
+					// SAFETY: Wasm is single-threaded, and SPLIT_IMAGE is only accessed in single
+					// statements.
+					None if unsafe { *SPLIT_IMAGE.get() } => {
+						// The image file doesn’t exist. Look for the first part of a split
+						// image on the same filesystem instead.
+						let base =
+							image_filename(info.filename).unwrap_or_else(|| internal_error());
+						let done = invoke_open(
+							&info.uuid,
+							part_filename(base, 0, &mut [0; PART_FILENAME_LEN]),
+						);
+						return Ok((
+							if done {
+								RunResult::RunNext
+							} else {
+								RunResult::Return
+							},
+							State::OpeningFile(OpeningFileInfo {
+								part: Some(0),
+								..info
+							}),
+						));
+					}
+					None | Some(0) => (),
+					Some(_) => {
+						// The previous part was the last one. The whole image has been loaded.
+						// If it came from a scan, remember where it was found.
+						#[cfg(feature = "remember-device")]
+						if let UuidSource::Scan(_) = info.source {
+							if let Some(step) = remember_device(&info.uuid) {
+								return Ok(step);
+							}
+						}
+						return Ok((RunResult::RunNext, State::ReadyToExecute));
+					}
+				}
+
+				// Try the next image file on the same filesystem, if there is one.
+				let filename = info.filename + 1;
+				if let Some(path) = image_filename(filename) {
+					let done = invoke_open(&info.uuid, path);
+					return Ok((
+						if done {
+							RunResult::RunNext
+						} else {
+							RunResult::Return
+						},
+						State::OpeningFile(OpeningFileInfo {
+							filename,
+							#[cfg(feature = "split-image")]
+							part: None,
+							..info
+						}),
+					));
+				}
+
+				// Scan or continue scanning for other bootable media.
+				Ok((
+					RunResult::RunNext,
+					match info.source {
+						UuidSource::Eeprom => State::StartScan,
+						UuidSource::Scan(scan) => State::Scanning(scan),
+						#[cfg(feature = "boot-list")]
+						UuidSource::List(list) => State::TryingList(list),
+					},
+				))
+			}
+		}
+		#[cfg(feature = "size-check")]
+		State::SizingFile(mut info) => {
+			#[cfg(not(feature = "skip-bad-media"))]
+			let size = end_size()?;
+			#[cfg(feature = "skip-bad-media")]
+			let Ok(size) = end_size() else {
+				// The filesystem returned a malformed result. Skip it rather than halting.
+				return Ok(abandon_medium(info.source));
+			};
+
+			// Refuse to read a file that is too large. Cast from u64 to usize is sound because the
+			// size has just been checked to be small.
+			#[allow(clippy::cast_possible_truncation)]
+			if let Some(size) = size {
+				if size > MAX_IMAGE_SIZE as u64 {
+					return Err(BiosError::ImageTooLarge);
+				}
+				info.size = Some(size as usize);
+				#[cfg(feature = "display")]
+				display::sized(size as usize);
+			}
+
+			// Read the file or, with the seek-first feature, seek to its start first.
+			#[cfg(not(feature = "seek-first"))]
+			let (done, next_state) = (
+				invoke_read(
+					&info.uuid,
+					info.descriptor.as_descriptor(),
+					image_chunk_size(),
+				),
+				State::ReadingFile,
+			);
+			#[cfg(feature = "seek-first")]
+			let (done, next_state) = invoke_seek_or_read(&info.uuid, info.descriptor.as_descriptor());
+			Ok((
+				if done {
+					RunResult::RunNext
+				} else {
+					RunResult::Return
+				},
+				next_state(info),
+			))
+		}
+		#[cfg(feature = "seek-first")]
+		State::Seeking(info) => {
+			// Give up on a filesystem that takes too long to seek.
+			#[cfg(feature = "call-timeout")]
+			match call_status() {
+				CallStatus::Done => (),
+				CallStatus::Pending => return Ok((RunResult::Return, State::Seeking(info))),
+				CallStatus::TimedOut => return Ok(abandon_medium(info.source)),
+			}
+
+			if !end_seek() {
+				// The filesystem cannot be trusted to read the file from the start. Move on.
+				return Ok(abandon_medium(info.source));
+			}
+
+			// Read the file.
+			let done = invoke_read(
+				&info.uuid,
+				info.descriptor.as_descriptor(),
+				image_chunk_size(),
+			);
+			Ok((
+				if done {
+					RunResult::RunNext
+				} else {
+					RunResult::Return
+				},
+				State::ReadingFile(info),
+			))
+		}
+		State::ReadingFile(info) => {
+			// Give up on a filesystem that takes too long to read the file.
+			#[cfg(feature = "call-timeout")]
+			match call_status() {
+				CallStatus::Done => (),
+				CallStatus::Pending => return Ok((RunResult::Return, State::ReadingFile(info))),
+				CallStatus::TimedOut => return Ok(abandon_medium(info.source)),
+			}
+
+			// Fetch the call result, adding any file data to the execution buffer.
+			#[cfg(feature = "error-uuid")]
+			note_device(&info.uuid);
+			// SAFETY: Wasm is single-threaded, so only one thread will be here touching
+			// READ_BUFFER at a time. This is the only place in which READ_BUFFER is touched, and
+			// the reference is dropped by the end of the step, so the same thread also cannot make
+			// a second mutable reference. The file data passed to add_file_data borrows from it,
+			// but nothing reachable from there touches READ_BUFFER.
+			let result_buffer = unsafe { &mut *READ_BUFFER.get() };
+			#[cfg(not(any(feature = "empty-read-limit", feature = "size-check")))]
+			let Some(eof) = end_read(result_buffer, add_file_data)?
+			else {
+				// The filesystem returned a malformed result. With the skip-bad-media feature,
+				// skip it rather than halting.
+				#[cfg(feature = "skip-bad-media")]
+				return Ok(abandon_medium(info.source));
+				#[cfg(not(feature = "skip-bad-media"))]
+				return Err(BiosError::ReadIoError);
+			};
+			#[cfg(any(feature = "empty-read-limit", feature = "size-check"))]
+			let mut info = info;
+			#[cfg(any(feature = "empty-read-limit", feature = "size-check"))]
+			let mut len = 0;
+			#[cfg(any(feature = "empty-read-limit", feature = "size-check"))]
+			let Some(eof) = end_read(result_buffer, |data| {
+				len += data.len();
+				add_file_data(data)
+			})?
+			else {
+				// The filesystem returned a malformed result. With the skip-bad-media feature,
+				// skip it rather than halting.
+				#[cfg(feature = "skip-bad-media")]
+				return Ok(abandon_medium(info.source));
+				#[cfg(not(feature = "skip-bad-media"))]
+				return Err(BiosError::ReadIoError);
+			};
+			#[cfg(feature = "size-check")]
+			{
+				info.read += len;
+			}
+			if eof {
+				// We got EOF. If the filesystem reported the file’s size, check that all of it was
+				// read.
+				#[cfg(feature = "size-check")]
+				if info.size.is_some_and(|size| size != info.read) {
+					return Err(BiosError::ReadIoError);
+				}
+				#[cfg(feature = "split-image")]
+				if let Some(part) = info.part {
+					if part < MAX_PART {
+						// Close this part and look for the next one.
+						let ReadingFileInfo {
+							descriptor,
+							uuid,
+							source,
+							filename,
+							..
+						} = info;
+						drop(descriptor);
+						let part = part + 1;
+						let base = image_filename(filename).unwrap_or_else(|| internal_error());
+						let done = invoke_open(
+							&uuid,
+							part_filename(base, part, &mut [0; PART_FILENAME_LEN]),
+						);
+						return Ok((
+							if done {
+								RunResult::RunNext
+							} else {
+								RunResult::Return
+							},
+							State::OpeningFile(OpeningFileInfo {
+								uuid,
+								source,
+								filename,
+								part: Some(part),
+							}),
+						));
+					}
+				}
+				// A compressed image must not be cut short.
+				#[cfg(feature = "gzip")]
+				check_decompressed()?;
+				// If the image came from a scan, remember where it was found.
+				#[cfg(feature = "remember-device")]
+				if let UuidSource::Scan(_) = info.source {
+					if let Some(step) = remember_device(&info.uuid) {
+						return Ok(step);
+					}
+				}
+				drop(info);
+				Ok((RunResult::RunNext, State::ReadyToExecute))
+			} else {
+				// A filesystem that keeps returning no data without reaching EOF is broken. Give up
+				// rather than reading from it forever.
+				#[cfg(feature = "empty-read-limit")]
+				if len == 0 {
+					info.empty_reads += 1;
+					if info.empty_reads >= MAX_EMPTY_READS {
+						return Err(BiosError::ReadIoError);
+					}
+				} else {
+					info.empty_reads = 0;
+				}
+
+				// Try to get some more.
+				let done = invoke_read(
+					&info.uuid,
+					info.descriptor.as_descriptor(),
+					image_chunk_size(),
+				);
+				Ok((
+					if done {
+						RunResult::RunNext
+					} else {
+						RunResult::Return
+					},
+					State::ReadingFile(info),
+				))
+			}
+		}
+		#[cfg(feature = "drive-boot")]
+		State::OpeningDrive(info) => {
+			// Check whether the first sector starts with the signature and, if so, take the image
+			// length from it.
+			let mut buffer = [0_u8; 8 + MAX_SECTOR_SIZE];
+			let Some((len, data)) = end_read_bytes(&mut buffer).and_then(split_image_header) else {
+				// This drive does not hold an image. Try the next one.
+				return scan_drives(info.listing);
+			};
+			#[cfg(feature = "boot-record")]
+			note_source("drive", Some(info.uuid), None);
+			load_sector(
+				DriveInfo {
+					remaining: len,
+					..info
+				},
+				data,
+			)
+		}
+		#[cfg(feature = "drive-boot")]
+		State::ReadingSectors(info) => {
+			#[cfg(feature = "error-uuid")]
+			note_device(&info.uuid);
+			let mut buffer = [0_u8; 8 + MAX_SECTOR_SIZE];
+			let Some(data) = end_read_bytes(&mut buffer) else {
+				return Err(BiosError::ReadIoError);
+			};
+			load_sector(info, data)
+		}
+		#[cfg(feature = "tape-boot")]
+		State::CheckingTape(uuid) => {
+			if !end_tape_ready() {
+				// There is no tape in the drive. Boot as usual instead.
+				return Ok((RunResult::RunNext, State::StartScan));
+			}
+			// Seek back as far as possible, to the start of the tape.
+			let done = invoke_seek_tape(&uuid, -i64::from(i32::MAX));
+			Ok((
+				if done {
+					RunResult::RunNext
+				} else {
+					RunResult::Return
+				},
+				State::RewindingTape(uuid),
+			))
+		}
+		#[cfg(feature = "tape-boot")]
+		State::RewindingTape(uuid) => {
+			if !end_rewind_tape() {
+				// The tape could not be rewound. Boot as usual instead.
+				return Ok((RunResult::RunNext, State::StartScan));
+			}
+			let done = invoke_read_tape(&uuid);
+			Ok((
+				if done {
+					RunResult::RunNext
+				} else {
+					RunResult::Return
+				},
+				State::OpeningTape(uuid),
+			))
+		}
+		#[cfg(feature = "tape-boot")]
+		State::OpeningTape(uuid) => {
+			// Check whether the tape starts with the signature and, if so, take the image length
+			// from it.
+			let mut buffer = [0_u8; 8 + TAPE_CHUNK_SIZE];
+			let Some((len, data)) = end_read_bytes(&mut buffer).and_then(split_image_header) else {
+				// This tape does not hold an image. Boot as usual instead.
+				return Ok((RunResult::RunNext, State::StartScan));
+			};
+			#[cfg(feature = "boot-record")]
+			note_source("tape", Some(uuid), None);
+			load_tape(
+				TapeInfo {
+					uuid,
+					remaining: len,
+				},
+				data,
+			)
+		}
+		#[cfg(feature = "tape-boot")]
+		State::ReadingTape(info) => {
+			#[cfg(feature = "error-uuid")]
+			note_device(&info.uuid);
+			let mut buffer = [0_u8; 8 + TAPE_CHUNK_SIZE];
+			match end_read_bytes(&mut buffer) {
+				// Reading past the end of the tape yields nothing, which means the image was cut
+				// short.
+				Some(data) if !data.is_empty() => load_tape(info, data),
+				_ => Err(BiosError::ReadIoError),
+			}
+		}
+		#[cfg(feature = "boot-prompt")]
+		State::StartPrompt => {
+			// Without a keyboard and a screen, there is no way to ask.
+			if !display::bound() || lister.start(Some("keyboard")).next().is_none() {
+				return Err(BiosError::NoBootMedium);
+			}
+			display::prompt(Some(b""));
+			Ok((
+				RunResult::Return,
+				State::Prompt(PromptInfo {
+					text: [0; 36],
+					len: 0,
+				}),
+			))
+		}
+		#[cfg(feature = "boot-prompt")]
+		State::Prompt(mut info) => {
+			// Handle any key presses, booting once Enter is pressed after the UUID of a
+			// filesystem.
+			while let Some(character) = prompt_key() {
+				match character {
+					// Enter.
+					13 => {
+						let mut type_buffer = [0_u8; TYPE_BUFFER_LEN];
+						if let Some(address) = config::parse_text_uuid(&info.text[..info.len])
+							.filter(|address| {
+								component::component_type(address, &mut type_buffer)
+									.is_ok_and(|t| is_bootable_type(t))
+							}) {
+							display::prompt(None);
+							return Ok(boot_candidate(address));
+						}
+						// That is not the UUID of a filesystem. Start again.
+						info.len = 0;
+					}
+					// Backspace.
+					8 => info.len = info.len.saturating_sub(1),
+					// Cast is sound because the character is ASCII.
+					#[allow(clippy::cast_possible_truncation)]
+					0x20..=0x7E if info.len < info.text.len() => {
+						info.text[info.len] = character as u8;
+						info.len += 1;
+					}
+					_ => continue,
+				}
+				display::prompt(Some(&info.text[..info.len]));
+			}
+			Ok((RunResult::Return, State::Prompt(info)))
+		}
+		#[cfg(feature = "remember-device")]
+		State::RememberingDevice => {
+			// Discard the result. If the EEPROM is read-only, the call fails, but booting should
+			// go ahead anyway.
+			// SAFETY: invoke_cancel is unconditionally safe.
+			unsafe { component_sys::invoke_cancel() };
+			Ok((RunResult::RunNext, State::ReadyToExecute))
+		}
+		#[cfg(feature = "boot-record")]
+		State::RecordingBoot => {
+			// Discard the result. If the EEPROM has no volatile data area, the call fails, but
+			// booting should go ahead anyway.
+			// SAFETY: invoke_cancel is unconditionally safe.
+			unsafe { component_sys::invoke_cancel() };
+			Ok((RunResult::RunNext, State::ReadyToExecute))
+		}
+		State::ReadyToExecute => {
+			// Check that the image is intact before running it.
+			#[cfg(feature = "checksum")]
+			check_image();
+
+			// Check that there is room to run it, rather than letting execution fail obscurely.
+			#[cfg(feature = "ram-check")]
+			check_ram();
+
+			// An image too short to hold the whole Wasm header is not a Wasm module either.
+			// SAFETY: Wasm is single-threaded, and IMAGE_LEN is only accessed in single statements.
+			#[cfg(feature = "magic-check")]
+			if unsafe { *IMAGE_LEN.get() } < WASM_HEADER.len() {
+				return Err(BiosError::NotWasm);
+			}
+
+			// Tell the image where it was loaded from. This comes back here once it is done.
+			#[cfg(feature = "boot-record")]
+			if let Some(step) = record_boot(lister) {
+				return Ok(step);
+			}
+
+			// Report the length and checksum of the whole image, for power users.
+			// SAFETY: Wasm is single-threaded, and IMAGE_LEN and CHECKSUM are only accessed in
+			// single statements.
+			#[cfg(feature = "crc-report")]
+			verbose::summary(
+				unsafe { *IMAGE_LEN.get() },
+				unsafe { *CHECKSUM.get() }.finish(),
+			);
+
+			// Give the user a moment to read the screen, if there is one.
+			#[cfg(feature = "handoff-delay")]
+			if lister.start(Some("screen")).next().is_some() {
+				return Ok((
+					RunResult::Return,
+					State::Delaying(computer::uptime().into_inner() + HANDOFF_DELAY),
+				));
+			}
+
+			// The execution buffer keeps track of its own length as data is appended to it with
+			// execute::add, so no length needs to be passed when executing.
+			execute::execute()
+		}
+		#[cfg(feature = "handoff-delay")]
+		State::Delaying(deadline) => {
+			// Keep yielding timeslices until the deadline passes.
+			if computer::uptime().into_inner() < deadline {
+				Ok((RunResult::Return, State::Delaying(deadline)))
+			} else {
+				execute::execute()
+			}
+		}
+		#[cfg(feature = "error-codes")]
+		State::Failed(code) => Ok((RunResult::Return, State::Failed(code))),
+	}
+}
+
+/// The application entry point.
+///
+/// Zero is returned while booting is in progress. With the `error-codes` feature, if booting has
+/// failed in a way that has a [code](BiosError::code), that code is returned instead.
+#[no_mangle]
+pub extern "C" fn run(_: i32) -> i32 {
+	run_timeslice()
+}
+
+/// Runs the BIOS for one timeslice, returning what [`run`] returns.
+///
+/// This is separate from [`run`] so that the tests can call it, since a panic, which is how the
+/// simulated computer stops the BIOS, cannot unwind out of an `extern "C"` function.
+fn run_timeslice() -> i32 {
+	// Hold a State.
+	static STATE: SyncUnsafeCell<State> = SyncUnsafeCell::new(State::Init);
+	// Hold a Lister.
+	static LISTER: SyncUnsafeCell<Option<component::Lister>> = SyncUnsafeCell::new(None);
+	// SAFETY: Wasm is single-threaded, so only one thread will be here touching STATE at a time.
+	// This is the only place in which STATE is touched, so the same thread also cannot make a
+	// second mutable reference.
+	let state = unsafe { &mut *STATE.get() };
+
+	// Give up if booting has taken too long, whatever is holding it up. No time is counted while
+	// waiting for the user or once booting has finished, and the full time is allowed again after
+	// the user has answered.
+	#[cfg(feature = "boot-timeout")]
+	if state.stops_boot_clock() {
+		// SAFETY: Wasm is single-threaded, and BOOT_DEADLINE is only accessed in single
+		// statements.
+		*unsafe { &mut *BOOT_DEADLINE.get() } = None;
+	} else {
+		let now = computer::uptime().into_inner();
+		// SAFETY: Wasm is single-threaded, and BOOT_DEADLINE is only accessed in single
+		// statements.
+		let deadline = *unsafe { &mut *BOOT_DEADLINE.get() }.get_or_insert(now + BOOT_TIMEOUT);
+		if now >= deadline {
+			BiosError::BootTimeout.fail();
+		}
+	}
+
+	// Start any method call that could not be started in an earlier timeslice before carrying
+	// on, since the current state is waiting for its result.
+	#[cfg(feature = "busy-retry")]
+	if !retry_busy_call() {
+		return 0;
+	}
+
+	// Run continuously until asked to return.
+	loop {
+		let old_state = replace(state, State::Init);
+		#[cfg(any(feature = "trace", feature = "verbose"))]
+		let old_name = old_state.name();
+		// Listings of bootable media are started with restart_scan, which describes the invariant
+		// that keeps listings held across steps from being mixed up.
+		// SAFETY: Wasm is single-threaded, so only one thread will be here touching LISTER at a
+		// time. This is the only place in which LISTER is touched, and the reference from the
+		// previous step is not used again, so the same thread also cannot make a second mutable
+		// reference.
+		let lister = unsafe { &mut *LISTER.get() }
+			.get_or_insert_with(|| component::Lister::take().unwrap_or_else(|| internal_error()));
+		let rc: Result<(RunResult, State), BiosError> = run_step(old_state, lister);
+		// If no medium is bootable, or there are none at all, boot the image embedded in the
+		// EEPROM instead, if there is one.
+		#[cfg(feature = "eeprom-image")]
+		let rc = if let Err(BiosError::NoBootMedium | BiosError::NoStorage) = rc {
+			boot_embedded_image().unwrap_or(rc)
+		} else {
+			rc
+		};
+		#[cfg(any(feature = "trace", feature = "verbose"))]
+		if let Ok((_, next_state)) = &rc {
+			#[cfg(feature = "trace")]
+			trace::transition(old_name, next_state.name());
+			#[cfg(feature = "verbose")]
+			verbose::transition(old_name, next_state.name());
+		}
+		match rc {
+			Ok((result, next_state)) => {
+				*state = next_state;
+				match result {
+					RunResult::RunNext => (),
+					RunResult::Return => {
+						#[cfg(feature = "error-codes")]
+						if let State::Failed(code) = *state {
+							return code;
+						}
+						return 0;
+					}
+				}
+			}
+			Err(e) => {
+				// With the reboot-on-failure feature, if the boot configuration asks for a reboot,
+				// fail() reboots, which takes precedence over reporting a code to the host.
+				#[cfg(feature = "reboot-on-failure")]
+				// SAFETY: Wasm is single-threaded, and REBOOT is only accessed in single statements.
+				if unsafe { *REBOOT.get() } {
+					e.fail();
+				}
+				#[cfg(feature = "error-codes")]
+				if let Some(code) = e.code() {
+					#[cfg(feature = "beep-codes")]
+					beep_code(code);
+					*state = State::Failed(code.into());
+					return code.into();
+				}
+				e.fail()
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use sim::Value;
+
+	/// A small Wasm module.
+	const IMAGE: &[u8] = b"\0asm\x01\0\0\0";
+
+	/// The amounts of image data reported to [`on_progress`], in order.
+	#[cfg(feature = "progress-hook")]
+	pub static PROGRESS: std::sync::Mutex<Vec<usize>> = std::sync::Mutex::new(Vec::new());
+
+	/// Returns a small Wasm module that can be told apart from others by `id`.
+	fn image(id: u8) -> Vec<u8> {
+		let mut image = IMAGE.to_vec();
+		image.push(id);
+		image
+	}
+
+	/// Returns an EEPROM whose data area designates `device` as the boot device, with `flags`
+	/// set.
+	///
+	/// The volatile data area holds the same, so that the configuration is found with or without
+	/// the `volatile-config` feature.
+	fn config_eeprom(device: &Address, flags: u8) -> sim::Eeprom {
+		let mut data = device.as_bytes().to_vec();
+		data.push(flags);
+		sim::Eeprom::new(&data).volatile_data(&data)
+	}
+
+	/// Returns how booting ends when it fails with `message`, whose failure code is `code`.
+	///
+	/// With the `error-codes` feature, a failure with a code is reported by returning the code.
+	fn failure(message: &str, code: Option<i32>) -> sim::Outcome {
+		match code {
+			Some(code) if cfg!(feature = "error-codes") => sim::Outcome::Returned(code),
+			_ => sim::Outcome::Halted(message.into()),
+		}
+	}
+
+	#[test]
+	fn boot_scans_opens_reads_and_executes() {
+		if !sim::isolated() {
+			return;
+		}
+		sim::attach_eeprom(sim::Eeprom::new(&[]));
+		let empty = sim::attach_filesystem(sim::Filesystem::new());
+		// Make the image take several reads.
+		let image: Vec<u8> = IMAGE.iter().copied().cycle().take(5000).collect();
+		let bootable = sim::attach_filesystem(
+			sim::Filesystem::new()
+				.file("/init.wasm", &image)
+				.max_read(2048),
+		);
+		assert_eq!(sim::boot(), sim::Outcome::Executed(image));
+		// The first filesystem has no image file, so the second one is booted from.
+		let open = [Value::str("/init.wasm")];
+		let opened = |address| {
+			sim::calls_to(address)
+				.iter()
+				.any(|call| call.method == "open" && call.params.starts_with(&open))
+		};
+		assert!(opened(&empty));
+		assert!(opened(&bootable));
+		let reads = sim::methods_called(&bootable)
+			.iter()
+			.filter(|method| *method == "read")
+			.count();
+		// Three reads return data, and the fourth finds the end of the file.
+		assert_eq!(reads, 4);
+		// The image file is closed before the image is executed.
+		assert_eq!(sim::closed().len(), 1);
+		assert!(sim::open_descriptors().is_empty());
+	}
+
+	#[test]
+	fn designated_device_boots_first() {
+		if !sim::isolated() {
+			return;
+		}
+		// The designated boot device is bootable, but comes after another bootable filesystem in
+		// the listing.
+		let designated = sim::address(9);
+		sim::attach_eeprom(config_eeprom(&designated, 0));
+		sim::attach_filesystem(sim::Filesystem::new().file("/init.wasm", &image(1)));
+		sim::attach_at(
+			designated,
+			"filesystem",
+			sim::Filesystem::new().file("/init.wasm", &image(2)),
+		);
+		assert_eq!(sim::boot(), sim::Outcome::Executed(image(2)));
+	}
+
+	#[test]
+	fn always_scan_flag_scans_in_listing_order() {
+		if !sim::isolated() {
+			return;
+		}
+		let designated = sim::address(9);
+		sim::attach_eeprom(config_eeprom(&designated, config::ALWAYS_SCAN));
+		sim::attach_filesystem(sim::Filesystem::new().file("/init.wasm", &image(1)));
+		sim::attach_at(
+			designated,
+			"filesystem",
+			sim::Filesystem::new().file("/init.wasm", &image(2)),
+		);
+		assert_eq!(sim::boot(), sim::Outcome::Executed(image(1)));
+	}
+
+	/// Checks that a popped signal is re-encoded to be pushed back as `expected`.
+	#[cfg(any(feature = "boot-delay", feature = "halt-key", feature = "rescue-key"))]
+	fn check_signal_to_push(popped: &[u8], expected: &[u8]) {
+		let mut buffer = vec![0xEE; SIGNAL_HEADROOM + popped.len()];
+		buffer[SIGNAL_HEADROOM..].copy_from_slice(popped);
+		assert_eq!(signal_to_push(&mut buffer, popped.len()), expected);
+	}
+
+	#[cfg(any(feature = "boot-delay", feature = "halt-key", feature = "rescue-key"))]
+	#[test]
+	fn signal_to_push_without_parameters() {
+		// "x", [] becomes ["x"].
+		check_signal_to_push(&[0x61, b'x', 0x80], &[0x81, 0x61, b'x']);
+	}
+
+	#[cfg(any(feature = "boot-delay", feature = "halt-key", feature = "rescue-key"))]
+	#[test]
+	fn signal_to_push_key_down() {
+		// "key_down", ["kb", 114, 19, "me"] becomes ["key_down", "kb", 114, 19, "me"].
+		let mut popped = vec![0x68];
+		popped.extend_from_slice(b"key_down");
+		let params = [0x62, b'k', b'b', 0x18, 0x72, 0x13, 0x62, b'm', b'e'];
+		popped.push(0x84);
+		popped.extend_from_slice(&params);
+		let mut expected = vec![0x85, 0x68];
+		expected.extend_from_slice(b"key_down");
+		expected.extend_from_slice(&params);
+		check_signal_to_push(&popped, &expected);
+	}
+
+	#[cfg(any(feature = "boot-delay", feature = "halt-key", feature = "rescue-key"))]
+	#[test]
+	fn signal_to_push_lengthens_array_header() {
+		// With 23 parameters, the array header grows from one byte to two; with 255, from two to
+		// three.
+		for (count, popped_header, pushed_header) in [
+			(23, &[0x97][..], &[0x98, 0x18][..]),
+			(255, &[0x98, 0xFF], &[0x99, 0x01, 0x00]),
+		] {
+			// Parameters from 24 upwards take two bytes each.
+			let params: Vec<u8> = (0..count)
+				.flat_map(|value: u8| {
+					if value < 24 {
+						vec![value]
+					} else {
+						vec![0x18, value]
+					}
+				})
+				.collect();
+			let mut popped = vec![0x62, b'h', b'i'];
+			popped.extend_from_slice(popped_header);
+			popped.extend_from_slice(&params);
+			let mut expected = pushed_header.to_vec();
+			expected.extend_from_slice(&[0x62, b'h', b'i']);
+			expected.extend_from_slice(&params);
+			check_signal_to_push(&popped, &expected);
+		}
+	}
+
+	#[cfg(feature = "busy-retry")]
+	#[test]
+	fn busy_call_succeeds_on_third_attempt() {
+		if !sim::isolated() {
+			return;
+		}
+		let address = sim::attach_filesystem(sim::Filesystem::new().file("/abc", b""));
+		let params = [0x81, 0x64, b'/', b'a', b'b', b'c'];
+		sim::fill_call_queue(2);
+		// The first attempt finds the queue full, so the state machine waits for the next
+		// timeslice.
+		assert!(!invoke_method(&address, "open", Some(&params)));
+		// So does the first retry.
+		assert!(!retry_busy_call());
+		// The second retry starts the call, which completes at once.
+		assert!(retry_busy_call());
+		// Nothing is left to retry.
+		assert!(retry_busy_call());
+		let calls = sim::calls();
+		assert_eq!(calls.len(), 3);
+		assert!(calls
+			.iter()
+			.all(|call| call.method == "open" && call.params == [Value::str("/abc")]));
+	}
+
+	#[cfg(feature = "removable-first")]
+	#[test]
+	fn removable_first_reads_floating_point_space() {
+		if !sim::isolated() {
+			return;
+		}
+		sim::attach_eeprom(config_eeprom(
+			&sim::address(0),
+			config::ALWAYS_SCAN | config::REMOVABLE_FIRST,
+		));
+		// A filesystem with unlimited space, which reports infinity, is not removable, but a
+		// floppy disk reporting its capacity as a floating-point number is.
+		sim::attach_filesystem(
+			sim::Filesystem::new()
+				.file("/init.wasm", &image(1))
+				.space_total(Value::Float(f64::INFINITY)),
+		);
+		sim::attach_filesystem(
+			sim::Filesystem::new()
+				.file("/init.wasm", &image(2))
+				.space_total(Value::Float(524_288.0)),
+		);
+		assert_eq!(sim::boot(), sim::Outcome::Executed(image(2)));
+	}
+
+	/// Returns the start of a tape holding `image`.
+	#[cfg(feature = "tape-boot")]
+	fn tape_image(image: &[u8]) -> Vec<u8> {
+		let mut tape = IMAGE_SIGNATURE.to_vec();
+		tape.extend_from_slice(&u32::try_from(image.len()).unwrap().to_be_bytes());
+		tape.extend_from_slice(image);
+		tape
+	}
+
+	/// Returns a tape drive holding a tape that starts with an image header followed by `image`,
+	/// whose `seek` method returns `moved`.
+	#[cfg(feature = "tape-boot")]
+	fn tape_drive(image: &[u8], moved: Value) -> impl sim::Device {
+		tape_drive_holding(tape_image(image), moved)
+	}
+
+	/// Returns a tape drive holding a tape that starts with `tape`, whose `seek` method returns
+	/// `moved`.
+	#[cfg(feature = "tape-boot")]
+	fn tape_drive_holding(tape: Vec<u8>, moved: Value) -> impl sim::Device {
+		let mut position = 0;
+		move |method: &str, params: &[Value]| match method {
+			"isReady" => sim::Reply::values([Value::Bool(true)]),
+			"seek" => {
+				position = 0;
+				sim::Reply::values([moved.clone()])
+			}
+			"read" => {
+				let Some(&Value::Int(len)) = params.first() else {
+					return sim::Reply::exception("bad argument");
+				};
+				let end = tape.len().min(position + usize::try_from(len).unwrap());
+				let data = tape[position..end].to_vec();
+				position = end;
+				sim::Reply::values([Value::Bytes(data)])
+			}
+			_ => sim::Reply::exception("no such method"),
+		}
+	}
+
+	#[cfg(feature = "tape-boot")]
+	#[test]
+	fn tape_boot_rewinds_and_reads_image() {
+		if !sim::isolated() {
+			return;
+		}
+		sim::attach_eeprom(config_eeprom(&sim::address(0), config::TAPE_BOOT));
+		sim::attach_filesystem(sim::Filesystem::new().file("/init.wasm", &image(1)));
+		let drive = sim::attach(TAPE_COMPONENT_TYPE, tape_drive(&image(2), Value::Int(-100)));
+		assert_eq!(sim::boot(), sim::Outcome::Executed(image(2)));
+		let seek = sim::calls_to(&drive)
+			.into_iter()
+			.find(|call| call.method == "seek")
+			.unwrap();
+		assert_eq!(seek.params, [Value::Int(-i64::from(i32::MAX))]);
+	}
+
+	#[cfg(feature = "tape-boot")]
+	#[test]
+	fn tape_boot_scans_if_rewind_fails() {
+		if !sim::isolated() {
+			return;
+		}
+		sim::attach_eeprom(config_eeprom(&sim::address(0), config::TAPE_BOOT));
+		sim::attach_filesystem(sim::Filesystem::new().file("/init.wasm", &image(1)));
+		let drive = sim::attach(TAPE_COMPONENT_TYPE, tape_drive(&image(2), Value::Null));
+		assert_eq!(sim::boot(), sim::Outcome::Executed(image(1)));
+		assert!(!sim::methods_called(&drive).contains(&"read".to_owned()));
+	}
+
+	/// Checks the BIOS with `path` as `BIOS_INIT_PATH` and `features` enabled, returning whether it
+	/// built and what the compiler printed.
+	///
+	/// This runs a separate build, in a directory of its own so as not to wait for or disturb the
+	/// one running the tests.
+	fn check_with_init_path(path: &str, features: &str) -> (bool, String) {
+		let manifest_dir = env!("CARGO_MANIFEST_DIR");
+		let output = std::process::Command::new(env!("CARGO"))
+			.current_dir(manifest_dir)
+			.args(["check", "--offline", "--bin", env!("CARGO_PKG_NAME")])
+			.args(["--features", features])
+			.arg("--target-dir")
+			.arg(std::path::Path::new(manifest_dir).join("target/init-path"))
+			.env("BIOS_INIT_PATH", path)
+			.output()
+			.unwrap();
+		(
+			output.status.success(),
+			String::from_utf8_lossy(&output.stderr).into_owned(),
+		)
+	}
+
+	#[test]
+	fn overlong_init_path_fails_to_build() {
+		let path = format!("/{}", "a".repeat(MAX_FILENAME_LEN));
+		let (built, messages) = check_with_init_path(&path, "");
+		assert!(!built);
+		assert!(
+			messages.contains("BIOS_INIT_PATH must be at most 251 bytes long"),
+			"{messages}"
+		);
+	}
+
+	#[test]
+	fn longest_init_path_builds_with_split_config_path() {
+		// The path and the suffixes of the parts of a split image just fit, even though the path
+		// is longer than any path that the boot configuration can give.
+		let path = format!("/{}", "a".repeat(MAX_FILENAME_LEN - 1));
+		let (built, messages) = check_with_init_path(&path, "config-path,split-image");
+		assert!(built, "{messages}");
+	}
+
+	/// Returns how many copies of [`MARKER_SIGNAL`] the signal queue holds.
+	#[cfg(any(feature = "boot-delay", feature = "halt-key", feature = "rescue-key"))]
+	fn markers_queued() -> usize {
+		let mut marker = MARKER_SIGNAL[1..].to_vec();
+		marker.push(0x80);
+		sim::signals()
+			.iter()
+			.filter(|signal| **signal == marker)
+			.count()
+	}
+
+	#[cfg(any(feature = "boot-delay", feature = "halt-key", feature = "rescue-key"))]
+	#[test]
+	fn examine_signals_skips_queue_starting_with_oversized_signal() {
+		if !sim::isolated() {
+			return;
+		}
+		sim::queue_signal("big", &[Value::Bytes(vec![0; 9000])]);
+		sim::queue_signal("small", &[]);
+		let before = sim::signals();
+		let mut examined = 0;
+		examine_signals(|_| {
+			examined += 1;
+			true
+		});
+		assert_eq!(examined, 0);
+		assert_eq!(sim::signals(), before);
+	}
+
+	#[cfg(any(feature = "boot-delay", feature = "halt-key", feature = "rescue-key"))]
+	#[test]
+	fn examine_signals_reuses_marker_left_behind_oversized_signal() {
+		if !sim::isolated() {
+			return;
+		}
+		sim::queue_signal("a", &[]);
+		sim::queue_signal("big", &[Value::Bytes(vec![0; 9000])]);
+		sim::queue_signal("b", &[]);
+		let mut examined = Vec::new();
+		examine_signals(|signal| {
+			examined.push(signal.to_vec());
+			true
+		});
+		// The examination stops at the oversized signal, leaving the marker behind it.
+		let mut first = Value::str("a").to_cbor();
+		first.push(0x80);
+		assert_eq!(examined, [first]);
+		assert_eq!(markers_queued(), 1);
+		examine_signals(|_| true);
+		assert_eq!(markers_queued(), 1);
+
+		// Once something else pops the oversized signal, the next examination carries on up to
+		// the same marker and removes it.
+		let mut buffer = vec![0; 10000];
+		computer::pull_signal(&mut buffer).unwrap();
+		let mut examined = 0;
+		examine_signals(|_| {
+			examined += 1;
+			true
+		});
+		assert_eq!(examined, 1);
+		assert_eq!(markers_queued(), 0);
+		let names: Vec<_> = sim::signals()
+			.iter()
+			.map(|signal| Value::decode(signal).unwrap().0)
+			.collect();
+		assert_eq!(names, [Value::str("a"), Value::str("b")]);
+	}
+
+	#[cfg(feature = "error-uuid")]
+	#[test]
+	fn fmt_uuid_prefix_writes_first_four_bytes() {
+		let address = Address::from_bytes([
+			0x01, 0xAB, 0xCD, 0xEF, 0x10, 0x32, 0x54, 0x76, 0x98, 0xBA, 0xDC, 0xFE, 0x00, 0x00,
+			0x00, 0x00,
+		]);
+		let mut buffer = [0_u8; 8];
+		fmt_uuid_prefix(&address, &mut buffer);
+		assert_eq!(&buffer, b"01abcdef");
+		fmt_uuid_prefix(&Address::from_bytes([0xFF; 16]), &mut buffer);
+		assert_eq!(&buffer, b"ffffffff");
+
+		let mut buffer = [0_u8; DEVICE_MESSAGE_LEN];
+		assert_eq!(
+			message_with_device("Read failed", &address, &mut buffer),
+			"Read failed (01abcdef)"
+		);
+	}
+
+	/// Returns a filesystem that answers as `filesystem` does, except that the results of `open`
+	/// and `read` calls that return a single value hold that value on its own.
+	#[cfg(feature = "bare-results")]
+	fn unwrapping(mut filesystem: sim::Filesystem) -> impl sim::Device {
+		use sim::Device as _;
+		move |method: &str, params: &[Value]| match filesystem.call(method, params) {
+			sim::Reply::Values(values)
+				if values.len() == 1 && matches!(method, "open" | "read") =>
+			{
+				sim::Reply::Raw(values[0].to_cbor())
+			}
+			reply => reply,
+		}
+	}
+
+	#[cfg(feature = "bare-results")]
+	#[test]
+	fn bare_results_accepts_unwrapped_open_and_read() {
+		if !sim::isolated() {
+			return;
+		}
+		sim::attach_eeprom(sim::Eeprom::new(&[]));
+		let image: Vec<u8> = IMAGE.iter().copied().cycle().take(5000).collect();
+		sim::attach(
+			"filesystem",
+			unwrapping(
+				sim::Filesystem::new()
+					.file("/init.wasm", &image)
+					.max_read(2048),
+			),
+		);
+		assert_eq!(sim::boot(), sim::Outcome::Executed(image));
+		assert!(sim::open_descriptors().is_empty());
+	}
+
+	#[cfg(feature = "bare-results")]
+	#[test]
+	fn bare_results_decodes_single_values() {
+		if !sim::isolated() {
+			return;
+		}
+		// A bare descriptor and a wrapped one.
+		let address = sim::attach("filesystem", |_: &str, _: &[Value]| {
+			sim::Reply::Raw(Value::Descriptor(sim::new_descriptor()).to_cbor())
+		});
+		let descriptor = sim::new_descriptor();
+		assert!(invoke_open(&address, "/init.wasm"));
+		assert!(end_open().ok().flatten().is_some());
+		sim::detach(&address);
+		let address = sim::attach("filesystem", move |_: &str, _: &[Value]| {
+			sim::Reply::values([Value::Descriptor(descriptor)])
+		});
+		assert!(invoke_open(&address, "/init.wasm"));
+		assert_eq!(
+			end_open()
+				.ok()
+				.flatten()
+				.map(|descriptor| descriptor.as_descriptor().as_raw()),
+			Some(descriptor)
+		);
+
+		// Bare and wrapped data, null, and something unexpected.
+		for (reply, expected, data) in [
+			(Value::bytes(b"abc").to_cbor(), Some(false), &b"abc"[..]),
+			(
+				Value::Array(vec![Value::bytes(b"abc")]).to_cbor(),
+				Some(false),
+				b"abc",
+			),
+			(Value::Null.to_cbor(), Some(true), b""),
+			(Value::Array(vec![Value::Null]).to_cbor(), Some(true), b""),
+			(Value::str("abc").to_cbor(), None, b""),
+		] {
+			let address = sim::attach("filesystem", move |_: &str, _: &[Value]| {
+				sim::Reply::Raw(reply.clone())
+			});
+			assert!(invoke_method(&address, "read", None));
+			let mut buffer = [0_u8; 16];
+			let mut read = Vec::new();
+			let result = end_read(&mut buffer, |chunk| {
+				read.extend_from_slice(chunk);
+				Ok(())
+			});
+			assert_eq!(result.ok(), Some(expected));
+			assert_eq!(read, data);
+			sim::detach(&address);
+		}
+	}
+
+	/// Returns an EEPROM holding a map configuration that sets `split` to `split`.
+	#[cfg(feature = "split-image")]
+	fn split_eeprom(split: bool) -> sim::Eeprom {
+		let data = Value::Map(vec![(Value::str("split"), Value::Bool(split))]).to_cbor();
+		sim::Eeprom::new(&data).volatile_data(&data)
+	}
+
+	#[cfg(feature = "split-image")]
+	#[test]
+	fn part_filename_has_no_leading_zeros() {
+		let mut name = [0_u8; PART_FILENAME_LEN];
+		for (part, expected) in [
+			(0, "/init.wasm.0"),
+			(9, "/init.wasm.9"),
+			(10, "/init.wasm.10"),
+			(100, "/init.wasm.100"),
+			(999, "/init.wasm.999"),
+		] {
+			assert_eq!(part_filename("/init.wasm", part, &mut name), expected);
+		}
+	}
+
+	#[cfg(feature = "split-image")]
+	#[test]
+	fn split_image_parts_are_concatenated() {
+		if !sim::isolated() {
+			return;
+		}
+		let (first, second) = IMAGE.split_at(3);
+		sim::attach_eeprom(split_eeprom(true));
+		sim::attach_filesystem(
+			sim::Filesystem::new()
+				.file("/init.wasm.0", first)
+				.file("/init.wasm.1", second),
+		);
+		assert_eq!(sim::boot(), sim::Outcome::Executed(IMAGE.to_vec()));
+		assert!(sim::open_descriptors().is_empty());
+	}
+
+	#[cfg(feature = "split-image")]
+	#[test]
+	fn split_image_parts_are_ignored_unless_configured() {
+		if !sim::isolated() {
+			return;
+		}
+		let (first, second) = IMAGE.split_at(3);
+		sim::attach_eeprom(split_eeprom(false));
+		let split = sim::attach_filesystem(
+			sim::Filesystem::new()
+				.file("/init.wasm.0", first)
+				.file("/init.wasm.1", second),
+		);
+		sim::attach_filesystem(sim::Filesystem::new().file("/init.wasm", &image(1)));
+		assert_eq!(sim::boot(), sim::Outcome::Executed(image(1)));
+		let open = [Value::str("/init.wasm.0")];
+		assert!(!sim::calls_to(&split)
+			.iter()
+			.any(|call| call.method == "open" && call.params.starts_with(&open)));
+	}
+
+	#[cfg(feature = "skip-bad-media")]
+	#[test]
+	fn abandoned_read_closes_descriptor_before_scan_continues() {
+		if !sim::isolated() {
+			return;
+		}
+		sim::attach_eeprom(sim::Eeprom::new(&[]));
+		// The first filesystem opens the image file but returns garbage from read.
+		let bad = sim::attach_filesystem(sim::Filesystem::new().file("/init.wasm", IMAGE).hook(
+			|method, _| (method == "read").then(|| sim::Reply::values([Value::str("garbage")])),
+		));
+		// By the time the second filesystem is asked to open its image file, nothing may be open.
+		let good =
+			sim::attach_filesystem(sim::Filesystem::new().file("/init.wasm", &image(1)).hook(
+				|method, _| {
+					if method == "open" {
+						assert!(sim::open_descriptors().is_empty());
+					}
+					None
+				},
+			));
+		assert_eq!(sim::boot(), sim::Outcome::Executed(image(1)));
+		assert!(sim::methods_called(&bad)
+			.iter()
+			.any(|method| method == "read"));
+		assert!(sim::methods_called(&good)
+			.iter()
+			.any(|method| method == "open"));
+		// Both image files were closed.
+		assert_eq!(sim::closed().len(), 2);
+		assert!(sim::open_descriptors().is_empty());
+	}
+
+	/// Returns an EEPROM that designates `device` as the boot device and gives `checksum` as the
+	/// image’s checksum.
+	#[cfg(feature = "checksum")]
+	fn checksum_eeprom(device: &Address, checksum: u32) -> sim::Eeprom {
+		let mut data = device.as_bytes().to_vec();
+		data.push(config::CHECKSUM);
+		data.extend_from_slice(&checksum.to_be_bytes());
+		sim::Eeprom::new(&data).volatile_data(&data)
+	}
+
+	/// Returns the CRC-32 checksum of `data`.
+	#[cfg(any(feature = "checksum", feature = "crc-report"))]
+	fn crc32_of(data: &[u8]) -> u32 {
+		let mut crc = crc32::Crc32::new();
+		crc.update(data);
+		crc.finish()
+	}
+
+	#[cfg(feature = "checksum")]
+	#[test]
+	fn matching_checksum_executes() {
+		if !sim::isolated() {
+			return;
+		}
+		let device = sim::attach_filesystem(sim::Filesystem::new().file("/init.wasm", IMAGE));
+		sim::attach_eeprom(checksum_eeprom(&device, crc32_of(IMAGE)));
+		assert_eq!(sim::boot(), sim::Outcome::Executed(IMAGE.to_vec()));
+	}
+
+	#[cfg(feature = "checksum")]
+	#[test]
+	fn corrupted_checksum_halts() {
+		if !sim::isolated() {
+			return;
+		}
+		let device = sim::attach_filesystem(sim::Filesystem::new().file("/init.wasm", IMAGE));
+		sim::attach_eeprom(checksum_eeprom(&device, crc32_of(IMAGE) ^ 1));
+		assert_eq!(
+			sim::boot(),
+			sim::Outcome::Halted("BIOS: image checksum mismatch".into())
+		);
+	}
+
+	#[cfg(feature = "beep-codes")]
+	#[test]
+	fn beep_code_plays_pattern_for_code() {
+		if !sim::isolated() {
+			return;
+		}
+		for code in 0..=4 {
+			beep_code(code);
+		}
+		assert_eq!(
+			sim::beeps(),
+			["-", "..", "..."].map(|pattern| sim::Beep::Pattern(pattern.into()))
+		);
+	}
+
+	#[cfg(feature = "beep-codes")]
+	#[test]
+	fn missing_eeprom_beeps_one_long_beep() {
+		if !sim::isolated() {
+			return;
+		}
+		sim::attach_filesystem(sim::Filesystem::new().file("/init.wasm", IMAGE));
+		assert_eq!(sim::boot(), failure("BIOS: no EEPROM", Some(1)));
+		assert_eq!(sim::beeps(), [sim::Beep::Pattern("-".into())]);
+	}
+
+	#[cfg(feature = "beep-codes")]
+	#[test]
+	fn missing_boot_medium_beeps_two_short_beeps() {
+		if !sim::isolated() {
+			return;
+		}
+		sim::attach_eeprom(sim::Eeprom::new(&[]));
+		sim::attach_filesystem(sim::Filesystem::new());
+		sim::boot();
+		assert_eq!(sim::beeps(), [sim::Beep::Pattern("..".into())]);
+	}
+
+	#[cfg(any(feature = "prefer-label", feature = "scan-labels"))]
+	#[test]
+	fn read_label_decodes_string_results_only() {
+		if !sim::isolated() {
+			return;
+		}
+		for (reply, expected) in [
+			(
+				Value::Array(vec![Value::str("boot")]).to_cbor(),
+				Some("boot"),
+			),
+			(Value::Array(vec![Value::Null]).to_cbor(), None),
+			(Value::Array(vec![Value::Int(7)]).to_cbor(), None),
+			(Value::Array(vec![Value::bytes(b"boot")]).to_cbor(), None),
+			// The string claims four bytes but only two follow.
+			(vec![0x81, 0x64, b'b', b'o'], None),
+		] {
+			let address = sim::attach("filesystem", move |_: &str, _: &[Value]| {
+				sim::Reply::Raw(reply.clone())
+			});
+			assert_eq!(
+				read_label(&address, &mut [0; LABEL_BUFFER_LEN]),
+				expected,
+				"{expected:?}"
+			);
+			sim::detach(&address);
+		}
+	}
+
+	#[test]
+	fn scan_steps_pick_first_filesystem_with_image() {
+		if !sim::isolated() {
+			return;
+		}
+		let empty = sim::attach_filesystem(sim::Filesystem::new());
+		let bootable = sim::attach_filesystem(sim::Filesystem::new().file("/init.wasm", IMAGE));
+		let later = sim::attach_filesystem(sim::Filesystem::new().file("/init.wasm", &image(1)));
+		let lister: *mut component::Lister =
+			Box::leak(Box::new(component::Lister::take().unwrap()));
+		let mut state = State::StartScan;
+		let mut opened = Vec::new();
+		for _ in 0..100 {
+			if let State::OpeningFile(info) = &state {
+				if opened.last() != Some(&info.uuid) {
+					opened.push(info.uuid);
+				}
+			}
+			if let State::ReadingFile(info) = &state {
+				// The scan reads the image from the first filesystem that has one.
+				assert_eq!(info.uuid, bootable);
+				assert_eq!(opened, [empty, bootable]);
+				assert!(sim::calls_to(&later).is_empty());
+				return;
+			}
+			// SAFETY: As in run_timeslice, the reference from the previous step is not used again.
+			state = match run_step(state, unsafe { &mut *lister }) {
+				Ok((_, state)) => state,
+				Err(e) => panic!("{}", e.message()),
+			};
+		}
+		panic!("the scan did not start reading an image file");
+	}
+
+	/// Returns the paths, other than that of the boot configuration file, that were opened on
+	/// `address`, in order.
+	#[cfg(feature = "fallback-filenames")]
+	fn opened_paths(address: &Address) -> Vec<String> {
+		sim::calls_to(address)
+			.into_iter()
+			.filter(|call| call.method == "open")
+			.filter_map(|call| match call.params.first() {
+				Some(Value::Str(path)) => Some(path.clone()),
+				_ => None,
+			})
+			.filter(|path| !cfg!(feature = "file-config") || path != "/boot/bios.cfg")
+			.collect()
+	}
+
+	#[cfg(feature = "fallback-filenames")]
+	#[test]
+	fn fallback_filename_is_tried_when_image_file_is_missing() {
+		if !sim::isolated() {
+			return;
+		}
+		sim::attach_eeprom(sim::Eeprom::new(&[]));
+		let fallback =
+			sim::attach_filesystem(sim::Filesystem::new().file("/boot/init.wasm", &image(1)));
+		assert_eq!(sim::boot(), sim::Outcome::Executed(image(1)));
+		// The last fallback is not needed.
+		assert_eq!(opened_paths(&fallback), ["/init.wasm", "/boot/init.wasm"]);
+	}
+
+	#[cfg(feature = "fallback-filenames")]
+	#[test]
+	fn fallback_filenames_are_tried_before_next_filesystem() {
+		if !sim::isolated() {
+			return;
+		}
+		sim::attach_eeprom(sim::Eeprom::new(&[]));
+		let fallback = sim::attach_filesystem(sim::Filesystem::new().file("/boot.wasm", &image(1)));
+		sim::attach_filesystem(sim::Filesystem::new().file("/init.wasm", &image(2)));
+		assert_eq!(sim::boot(), sim::Outcome::Executed(image(1)));
+		assert_eq!(
+			opened_paths(&fallback),
+			["/init.wasm", "/boot/init.wasm", "/boot.wasm"]
+		);
+	}
+
+	/// Returns the contents written to the boot configuration’s data area of `eeprom`, in order.
+	#[cfg(feature = "remember-device")]
+	fn config_writes(eeprom: &Address) -> Vec<Vec<u8>> {
+		sim::calls_to(eeprom)
+			.into_iter()
+			.filter(|call| call.method == CONFIG_WRITE_METHOD)
+			.map(|call| match &call.params[..] {
+				[Value::Bytes(data)] => data.clone(),
+				params => panic!("unexpected parameters {params:?}"),
+			})
+			.collect()
+	}
+
+	#[cfg(feature = "remember-device")]
+	#[test]
+	fn remember_device_writes_scanned_uuid() {
+		if !sim::isolated() {
+			return;
+		}
+		// The designated boot device is gone, so a scan finds the image.
+		let mut data = [0x77; 16].to_vec();
+		data.extend_from_slice(&[0x00]);
+		let eeprom = sim::attach_eeprom(sim::Eeprom::new(&data).volatile_data(&data));
+		sim::attach_filesystem(sim::Filesystem::new());
+		let bootable = sim::attach_filesystem(sim::Filesystem::new().file("/init.wasm", IMAGE));
+		assert_eq!(sim::boot(), sim::Outcome::Executed(IMAGE.to_vec()));
+		// The UUID is replaced and the flags are kept.
+		let mut expected = bootable.as_bytes().to_vec();
+		expected.push(0x00);
+		assert_eq!(config_writes(&eeprom), [expected]);
+	}
+
+	#[cfg(feature = "remember-device")]
+	#[test]
+	fn remember_device_fills_empty_data_area() {
+		if !sim::isolated() {
+			return;
+		}
+		let eeprom = sim::attach_eeprom(sim::Eeprom::new(&[]));
+		let bootable = sim::attach_filesystem(sim::Filesystem::new().file("/init.wasm", IMAGE));
+		assert_eq!(sim::boot(), sim::Outcome::Executed(IMAGE.to_vec()));
+		assert_eq!(config_writes(&eeprom), [bootable.as_bytes().to_vec()]);
+	}
+
+	#[cfg(feature = "remember-device")]
+	#[test]
+	fn remember_device_leaves_designated_device_alone() {
+		if !sim::isolated() {
+			return;
+		}
+		// Booting from the designated device writes nothing.
+		let bootable = sim::address(2);
+		let eeprom = sim::attach_eeprom(config_eeprom(&bootable, 0));
+		sim::attach_filesystem(sim::Filesystem::new().file("/init.wasm", IMAGE));
+		assert_eq!(sim::boot(), sim::Outcome::Executed(IMAGE.to_vec()));
+		assert!(config_writes(&eeprom).is_empty());
+	}
+
+	#[cfg(feature = "remember-device")]
+	#[test]
+	fn remember_device_honours_always_scan() {
+		if !sim::isolated() {
+			return;
+		}
+		let eeprom = sim::attach_eeprom(config_eeprom(&sim::address(9), config::ALWAYS_SCAN));
+		sim::attach_filesystem(sim::Filesystem::new().file("/init.wasm", IMAGE));
+		assert_eq!(sim::boot(), sim::Outcome::Executed(IMAGE.to_vec()));
+		assert!(config_writes(&eeprom).is_empty());
+	}
+
+	/// Returns the methods called on `address` with the default image path as their first
+	/// parameter, in order.
+	#[cfg(feature = "check-exists")]
+	fn image_path_methods(address: &Address) -> Vec<String> {
+		let path = [Value::str(FILENAME)];
+		sim::calls_to(address)
+			.into_iter()
+			.filter(|call| call.params.starts_with(&path))
+			.map(|call| call.method)
+			.collect()
+	}
+
+	#[cfg(feature = "check-exists")]
+	#[test]
+	fn check_exists_scans_when_designated_device_lacks_image() {
+		if !sim::isolated() {
+			return;
+		}
+		let designated = sim::address(2);
+		sim::attach_eeprom(config_eeprom(&designated, 0));
+		sim::attach_filesystem(sim::Filesystem::new());
+		sim::attach_filesystem(sim::Filesystem::new().file("/init.wasm", &image(2)));
+		assert_eq!(sim::boot(), sim::Outcome::Executed(image(2)));
+		// The designated device is not asked to open the image file until the scan reaches it.
+		assert_eq!(image_path_methods(&designated), ["exists", "open"]);
+	}
+
+	#[cfg(feature = "check-exists")]
+	#[test]
+	fn check_exists_opens_existing_image() {
+		if !sim::isolated() {
+			return;
+		}
+		let designated = sim::address(2);
+		sim::attach_eeprom(config_eeprom(&designated, 0));
+		sim::attach_filesystem(sim::Filesystem::new().file("/init.wasm", &image(1)));
+		assert_eq!(sim::boot(), sim::Outcome::Executed(image(1)));
+		assert_eq!(image_path_methods(&designated)[..2], ["exists", "open"]);
+	}
+
+	/// Returns an Internet card whose requests get `status` in response, after `pending` calls to
+	/// `response` that find it has not arrived yet, and then `body` in chunks of `chunk` bytes.
+	#[cfg(feature = "http-boot")]
+	fn internet(status: u16, pending: usize, body: Vec<u8>, chunk: usize) -> impl sim::Device {
+		move |method: &str, _: &[Value]| {
+			assert_eq!(method, "request");
+			let mut pending = pending;
+			let mut chunks: Vec<Vec<u8>> = body.chunks(chunk).map(<[u8]>::to_vec).collect();
+			chunks.reverse();
+			let descriptor = sim::new_value(move |method: &str, _: &[Value]| match method {
+				"response" if pending > 0 => {
+					pending -= 1;
+					sim::Reply::values([Value::Null])
+				}
+				"response" => sim::Reply::values([
+					Value::Int(status.into()),
+					Value::str("OK"),
+					Value::Map(Vec::new()),
+				]),
+				"read" => sim::Reply::values([chunks.pop().map_or(Value::Null, Value::Bytes)]),
+				_ => sim::Reply::exception("no such method"),
+			});
+			sim::Reply::values([Value::Descriptor(descriptor)])
+		}
+	}
+
+	#[cfg(feature = "http-boot")]
+	#[test]
+	fn http_boot_downloads_image() {
+		if !sim::isolated() {
+			return;
+		}
+		let url = b"http://example.com/init.wasm";
+		sim::attach_eeprom(sim::Eeprom::new(url).volatile_data(url));
+		// Make the image take several reads.
+		let download: Vec<u8> = IMAGE.iter().copied().cycle().take(20000).collect();
+		let card = sim::attach("internet", internet(200, 2, download.clone(), 8192));
+		sim::attach_filesystem(sim::Filesystem::new().file("/init.wasm", &image(1)));
+		assert_eq!(sim::boot(), sim::Outcome::Executed(download));
+		let request = &sim::calls_to(&card)[0];
+		assert_eq!(request.params, [Value::str("http://example.com/init.wasm")]);
+		assert!(sim::open_descriptors().is_empty());
+	}
+
+	#[cfg(feature = "http-boot")]
+	#[test]
+	fn http_boot_scans_if_request_fails() {
+		if !sim::isolated() {
+			return;
+		}
+		let url = b"http://example.com/init.wasm";
+		sim::attach_eeprom(sim::Eeprom::new(url).volatile_data(url));
+		sim::attach("internet", internet(404, 0, IMAGE.to_vec(), 8192));
+		sim::attach_filesystem(sim::Filesystem::new().file("/init.wasm", &image(1)));
+		assert_eq!(sim::boot(), sim::Outcome::Executed(image(1)));
+		assert!(sim::open_descriptors().is_empty());
+	}
+
+	/// Returns the byte counts asked for by the `read` calls made on `address`, in order.
+	#[cfg(any(feature = "fit-chunks", feature = "low-memory-chunks"))]
+	fn read_counts(address: &Address) -> Vec<i64> {
+		sim::calls_to(address)
+			.into_iter()
+			.filter(|call| call.method == "read")
+			.map(|call| match call.params[..] {
+				[_, Value::Int(count)] => count,
+				_ => panic!("unexpected parameters {:?}", call.params),
+			})
+			.collect()
+	}
+
+	#[cfg(feature = "fit-chunks")]
+	#[test]
+	fn fit_chunks_reads_no_more_than_fits() {
+		if !sim::isolated() {
+			return;
+		}
+		// The image exactly fills the execution buffer.
+		let image: Vec<u8> = IMAGE.iter().copied().cycle().take(20).collect();
+		sim::set_ram(20, 3 * 1024 * 1024);
+		sim::attach_eeprom(sim::Eeprom::new(&[]));
+		let bootable = sim::attach_filesystem(
+			sim::Filesystem::new()
+				.file("/init.wasm", &image)
+				.max_read(8),
+		);
+		assert_eq!(sim::boot(), sim::Outcome::Executed(image));
+		// Each read asks for the space left, and the last asks for one byte to find the end of the
+		// file.
+		assert_eq!(read_counts(&bootable), [20, 12, 4, 1]);
+	}
+
+	#[cfg(feature = "fit-chunks")]
+	#[test]
+	fn fit_chunks_halts_once_image_is_found_too_large() {
+		if !sim::isolated() {
+			return;
+		}
+		let image: Vec<u8> = IMAGE.iter().copied().cycle().take(21).collect();
+		sim::set_ram(20, 3 * 1024 * 1024);
+		sim::attach_eeprom(sim::Eeprom::new(&[]));
+		let bootable = sim::attach_filesystem(sim::Filesystem::new().file("/init.wasm", &image));
+		assert_eq!(
+			sim::boot(),
+			sim::Outcome::Halted("BIOS: image too large".into())
+		);
+		assert_eq!(read_counts(&bootable), [20, 1]);
+	}
+
+	#[cfg(feature = "size-check")]
+	#[test]
+	fn size_check_refuses_oversized_image() {
+		if !sim::isolated() {
+			return;
+		}
+		sim::attach_eeprom(sim::Eeprom::new(&[]));
+		let bootable =
+			sim::attach_filesystem(sim::Filesystem::new().file("/init.wasm", IMAGE).hook(
+				|method, _| {
+					(method == "size").then(|| {
+						sim::Reply::values([Value::Int((MAX_IMAGE_SIZE + 1).try_into().unwrap())])
+					})
+				},
+			));
+		assert_eq!(sim::boot(), failure("BIOS: image too large", None));
+		assert!(!sim::methods_called(&bootable)
+			.iter()
+			.any(|method| method == "read"));
+	}
+
+	#[cfg(feature = "size-check")]
+	#[test]
+	fn size_check_detects_short_read() {
+		if !sim::isolated() {
+			return;
+		}
+		sim::attach_eeprom(sim::Eeprom::new(&[]));
+		sim::attach_filesystem(
+			sim::Filesystem::new()
+				.file("/init.wasm", IMAGE)
+				.hook(|method, _| {
+					(method == "size").then(|| sim::Reply::values([Value::Int(100)]))
+				}),
+		);
+		assert_eq!(
+			sim::boot(),
+			failure("BIOS: I/O error reading /init.wasm", Some(3))
+		);
+	}
+
+	#[cfg(feature = "size-check")]
+	#[test]
+	fn size_check_accepts_matching_size() {
+		if !sim::isolated() {
+			return;
+		}
+		sim::attach_eeprom(sim::Eeprom::new(&[]));
+		sim::attach_filesystem(sim::Filesystem::new().file("/init.wasm", IMAGE));
+		assert_eq!(sim::boot(), sim::Outcome::Executed(IMAGE.to_vec()));
+	}
+
+	#[cfg(feature = "size-check")]
+	#[test]
+	fn size_check_accepts_unknown_size() {
+		if !sim::isolated() {
+			return;
+		}
+		sim::attach_eeprom(sim::Eeprom::new(&[]));
+		sim::attach_filesystem(
+			sim::Filesystem::new()
+				.file("/init.wasm", IMAGE)
+				.hook(|method, _| (method == "size").then(|| sim::Reply::values([Value::Null]))),
+		);
+		assert_eq!(sim::boot(), sim::Outcome::Executed(IMAGE.to_vec()));
+	}
+
+	/// Attaches a GPU, a screen, and a keyboard, returning the UUIDs of the GPU and the keyboard.
+	#[cfg(any(feature = "boot-menu", feature = "boot-prompt"))]
+	fn attach_console() -> (Address, Address) {
+		let gpu = sim::attach_inert("gpu");
+		sim::attach_inert("screen");
+		let keyboard = sim::attach_inert("keyboard");
+		(gpu, keyboard)
+	}
+
+	/// Returns the text shown on the screen by `gpu`, in order.
+	#[cfg(any(feature = "boot-menu", feature = "boot-prompt", feature = "verbose"))]
+	fn shown(gpu: &Address) -> Vec<String> {
+		sim::calls_to(gpu)
+			.into_iter()
+			.filter(|call| call.method == "set")
+			.filter_map(|call| match call.params.get(2) {
+				Some(Value::Str(text)) => Some(text.trim().to_owned()),
+				_ => None,
+			})
+			.collect()
+	}
+
+	/// Attaches the filesystems offered in the boot menu tests: two bootable ones, labelled
+	/// `alpha` and `gamma`, with one that is not bootable between them.
+	#[cfg(feature = "boot-menu")]
+	fn attach_menu_filesystems() {
+		sim::attach_eeprom(sim::Eeprom::new(&[]));
+		sim::attach_filesystem(
+			sim::Filesystem::new()
+				.file("/init.wasm", &image(1))
+				.label("alpha"),
+		);
+		sim::attach_filesystem(sim::Filesystem::new().label("beta"));
+		sim::attach_filesystem(
+			sim::Filesystem::new()
+				.file("/init.wasm", &image(2))
+				.label("gamma"),
+		);
+	}
+
+	#[cfg(feature = "boot-menu")]
+	#[test]
+	fn boot_menu_boots_chosen_filesystem() {
+		if !sim::isolated() {
+			return;
+		}
+		let (gpu, keyboard) = attach_console();
+		attach_menu_filesystems();
+		// Press 2.
+		sim::queue_key_down(&keyboard, i64::from(b'2'), 3);
+		assert_eq!(sim::boot(), sim::Outcome::Executed(image(2)));
+		let shown = shown(&gpu);
+		for entry in ["1", "alpha", "2", "gamma"] {
+			assert!(shown.iter().any(|text| text == entry), "{shown:?}");
+		}
+		assert!(!shown.iter().any(|text| text == "beta"), "{shown:?}");
+	}
+
+	#[cfg(feature = "boot-menu")]
+	#[test]
+	fn boot_menu_ignores_keys_beyond_last_entry() {
+		if !sim::isolated() {
+			return;
+		}
+		let (_, keyboard) = attach_console();
+		attach_menu_filesystems();
+		// Press 3, which has no entry, and then 2.
+		sim::queue_key_down(&keyboard, i64::from(b'3'), 4);
+		sim::queue_key_down(&keyboard, i64::from(b'2'), 3);
+		assert_eq!(sim::boot(), sim::Outcome::Executed(image(2)));
+	}
+
+	#[cfg(feature = "boot-menu")]
+	#[test]
+	fn boot_menu_times_out_to_first_entry() {
+		if !sim::isolated() {
+			return;
+		}
+		attach_console();
+		attach_menu_filesystems();
+		assert_eq!(sim::boot(), sim::Outcome::Executed(image(1)));
+		// The menu waited for its timeout.
+		assert!(computer::uptime().into_inner() >= MENU_TIMEOUT);
+	}
+
+	/// Returns a filesystem holding `image` whose first `empty` reads return no data.
+	#[cfg(feature = "empty-read-limit")]
+	fn stalling_filesystem(image: &[u8], mut empty: u8) -> sim::Filesystem {
+		sim::Filesystem::new()
+			.file("/init.wasm", image)
+			.hook(move |method, _| {
+				(method == "read" && empty > 0).then(|| {
+					empty -= 1;
+					sim::Reply::values([Value::bytes(b"")])
+				})
+			})
+	}
+
+	#[cfg(feature = "empty-read-limit")]
+	#[test]
+	fn empty_reads_below_limit_are_retried() {
+		if !sim::isolated() {
+			return;
+		}
+		sim::attach_eeprom(sim::Eeprom::new(&[]));
+		sim::attach_filesystem(stalling_filesystem(IMAGE, MAX_EMPTY_READS - 1));
+		assert_eq!(sim::boot(), sim::Outcome::Executed(IMAGE.to_vec()));
+	}
+
+	#[cfg(feature = "empty-read-limit")]
+	#[test]
+	fn empty_reads_up_to_limit_fail() {
+		if !sim::isolated() {
+			return;
+		}
+		sim::attach_eeprom(sim::Eeprom::new(&[]));
+		let stalling = sim::attach_filesystem(stalling_filesystem(IMAGE, MAX_EMPTY_READS));
+		assert_eq!(
+			sim::boot(),
+			failure("BIOS: I/O error reading /init.wasm", Some(3))
+		);
+		let reads = sim::methods_called(&stalling)
+			.iter()
+			.filter(|method| *method == "read")
+			.count();
+		assert_eq!(reads, usize::from(MAX_EMPTY_READS));
+	}
+
+	#[cfg(feature = "multi-eeprom")]
+	#[test]
+	fn multi_eeprom_uses_first_configuration_that_says_what_to_boot() {
+		if !sim::isolated() {
+			return;
+		}
+		// The second EEPROM designates the last filesystem, and the third another one.
+		let designated = sim::address(5);
+		sim::attach_eeprom(sim::Eeprom::new(&[]));
+		sim::attach_eeprom(config_eeprom(&designated, 0));
+		sim::attach_eeprom(config_eeprom(&sim::address(4), 0));
+		sim::attach_filesystem(sim::Filesystem::new().file("/init.wasm", &image(1)));
+		sim::attach_filesystem(sim::Filesystem::new().file("/init.wasm", &image(2)));
+		assert_eq!(sim::boot(), sim::Outcome::Executed(image(2)));
+	}
+
+	#[cfg(feature = "multi-eeprom")]
+	#[test]
+	fn multi_eeprom_scans_if_no_configuration_says_what_to_boot() {
+		if !sim::isolated() {
+			return;
+		}
+		let first = sim::attach_eeprom(sim::Eeprom::new(&[]));
+		let last = sim::attach_eeprom(sim::Eeprom::new(&[]));
+		sim::attach_filesystem(sim::Filesystem::new().file("/init.wasm", &image(1)));
+		assert_eq!(sim::boot(), sim::Outcome::Executed(image(1)));
+		// Both configurations were read.
+		for eeprom in [first, last] {
+			assert!(sim::methods_called(&eeprom)
+				.iter()
+				.any(|method| method == CONFIG_METHOD));
+		}
+		// With remember-device, the filesystem is designated in the last EEPROM.
+		#[cfg(feature = "remember-device")]
+		{
+			assert!(config_writes(&first).is_empty());
+			assert_eq!(config_writes(&last).len(), 1);
+		}
+	}
+
+	#[cfg(feature = "call-timeout")]
+	#[test]
+	fn call_timeout_abandons_stuck_open() {
+		if !sim::isolated() {
+			return;
+		}
+		sim::attach_eeprom(sim::Eeprom::new(&[]));
+		sim::attach_filesystem(sim::Filesystem::new().file("/init.wasm", &image(1)).hook(
+			|method, params| {
+				(method == "open" && params.starts_with(&[Value::str("/init.wasm")]))
+					.then_some(sim::Reply::Never)
+			},
+		));
+		sim::attach_filesystem(sim::Filesystem::new().file("/init.wasm", &image(2)));
+		assert_eq!(sim::boot(), sim::Outcome::Executed(image(2)));
+		assert!(sim::timeslices() > u64::from(MAX_PENDING_STEPS));
+	}
+
+	#[cfg(feature = "call-timeout")]
+	#[test]
+	fn call_timeout_abandons_stuck_read_and_discards_its_data() {
+		if !sim::isolated() {
+			return;
+		}
+		sim::attach_eeprom(sim::Eeprom::new(&[]));
+		// The first read returns some data, and the second never completes.
+		let mut reads = 0;
+		sim::attach_filesystem(
+			sim::Filesystem::new()
+				.file("/init.wasm", &image(1))
+				.max_read(4)
+				.hook(move |method, _| {
+					reads += u32::from(method == "read");
+					(method == "read" && reads == 2).then_some(sim::Reply::Never)
+				}),
+		);
+		sim::attach_filesystem(sim::Filesystem::new().file("/init.wasm", &image(2)));
+		// Nothing from the first filesystem is left in the execution buffer.
+		assert_eq!(sim::boot(), sim::Outcome::Executed(image(2)));
+		assert!(sim::open_descriptors().is_empty());
+	}
+
+	#[cfg(feature = "call-timeout")]
+	#[test]
+	fn call_timeout_waits_for_slow_calls() {
+		if !sim::isolated() {
+			return;
+		}
+		sim::attach_eeprom(sim::Eeprom::new(&[]));
+		// Every call that is answered by the filesystem takes a timeslice.
+		let mut inner = sim::Filesystem::new().file("/init.wasm", &image(1));
+		sim::attach("filesystem", move |method: &str, params: &[Value]| {
+			use sim::Device as _;
+			sim::Reply::Later(Box::new(inner.call(method, params)))
+		});
+		sim::attach_filesystem(sim::Filesystem::new().file("/init.wasm", &image(2)));
+		assert_eq!(sim::boot(), sim::Outcome::Executed(image(1)));
+	}
+
+	#[cfg(feature = "call-timeout")]
+	#[test]
+	fn call_timeout_moves_on_when_open_throws() {
+		if !sim::isolated() {
+			return;
+		}
+		sim::attach_eeprom(sim::Eeprom::new(&[]));
+		sim::attach_filesystem(sim::Filesystem::new().file("/init.wasm", &image(1)).hook(
+			|method, params| {
+				(method == "open" && params.first() == Some(&Value::str("/init.wasm")))
+					.then(|| sim::Reply::exception("no such file"))
+			},
+		));
+		sim::attach_filesystem(sim::Filesystem::new().file("/init.wasm", &image(2)));
+		assert_eq!(sim::boot(), sim::Outcome::Executed(image(2)));
+	}
+
+	/// Returns an unmanaged drive with `sector_size`-byte sectors holding `contents` from the
+	/// start of its first sector.
+	#[cfg(feature = "drive-boot")]
+	fn drive(contents: &[u8], sector_size: usize) -> impl sim::Device {
+		let contents = contents.to_vec();
+		move |method: &str, params: &[Value]| match (method, params) {
+			("readSector", [Value::Int(sector)]) => {
+				let start = usize::try_from(sector - 1).unwrap() * sector_size;
+				let mut data = contents.get(start..).unwrap_or_default().to_vec();
+				data.resize(sector_size, 0);
+				sim::Reply::values([Value::Bytes(data)])
+			}
+			_ => sim::Reply::exception("no such method"),
+		}
+	}
+
+	/// Returns the first sectors of a drive holding `image`.
+	#[cfg(feature = "drive-boot")]
+	fn drive_image(image: &[u8]) -> Vec<u8> {
+		let mut contents = b"OCWB".to_vec();
+		contents.extend_from_slice(&u32::try_from(image.len()).unwrap().to_be_bytes());
+		contents.extend_from_slice(image);
+		contents
+	}
+
+	#[cfg(feature = "drive-boot")]
+	#[test]
+	fn drive_boot_reads_image_across_sectors() {
+		if !sim::isolated() {
+			return;
+		}
+		sim::attach_eeprom(sim::Eeprom::new(&[]));
+		sim::attach_filesystem(sim::Filesystem::new());
+		let image: Vec<u8> = IMAGE.iter().copied().cycle().take(40).collect();
+		let drive = sim::attach("drive", drive(&drive_image(&image), 16));
+		assert_eq!(sim::boot(), sim::Outcome::Executed(image));
+		// The header and the image take 48 bytes, which is three sectors.
+		let sectors: Vec<_> = sim::calls_to(&drive)
+			.into_iter()
+			.map(|call| call.params)
+			.collect();
+		assert_eq!(sectors, [1, 2, 3].map(|sector| vec![Value::Int(sector)]));
+	}
+
+	#[cfg(feature = "drive-boot")]
+	#[test]
+	fn drive_boot_skips_drives_without_image() {
+		if !sim::isolated() {
+			return;
+		}
+		sim::attach_eeprom(sim::Eeprom::new(&[]));
+		let blank = sim::attach("drive", drive(&[], 512));
+		sim::attach("drive", drive(&drive_image(&image(1)), 512));
+		assert_eq!(sim::boot(), sim::Outcome::Executed(image(1)));
+		assert_eq!(sim::calls_to(&blank).len(), 1);
+	}
+
+	#[cfg(feature = "drive-boot")]
+	#[test]
+	fn drive_boot_comes_after_filesystems() {
+		if !sim::isolated() {
+			return;
+		}
+		sim::attach_eeprom(sim::Eeprom::new(&[]));
+		let drive = sim::attach("drive", drive(&drive_image(&image(1)), 512));
+		sim::attach_filesystem(sim::Filesystem::new().file("/init.wasm", &image(2)));
+		assert_eq!(sim::boot(), sim::Outcome::Executed(image(2)));
+		assert!(sim::calls_to(&drive).is_empty());
+	}
+
+	#[cfg(feature = "trace")]
+	#[test]
+	fn trace_logs_transitions_to_debug_card() {
+		if !sim::isolated() {
+			return;
+		}
+		let debug = sim::attach_inert("ocelot");
+		sim::attach_eeprom(sim::Eeprom::new(&[]));
+		sim::attach_filesystem(sim::Filesystem::new().file("/init.wasm", IMAGE));
+		assert_eq!(sim::boot(), sim::Outcome::Executed(IMAGE.to_vec()));
+		let messages: Vec<String> = sim::calls_to(&debug)
+			.into_iter()
+			.map(|call| {
+				assert_eq!(call.method, "log");
+				let [Value::Str(message)] = &call.params[..] else {
+					panic!("bad log parameters {:?}", call.params);
+				};
+				message.clone()
+			})
+			.collect();
+		assert!(messages[0].starts_with("Init -> "));
+		for message in &messages {
+			let (from, to) = message.split_once(" -> ").unwrap();
+			assert_ne!(from, to);
+		}
+		// Each message starts from the state the previous one ended in.
+		for pair in messages.windows(2) {
+			assert_eq!(
+				pair[0].split_once(" -> ").unwrap().1,
+				pair[1].split_once(" -> ").unwrap().0
+			);
+		}
+	}
+
+	#[cfg(feature = "trace")]
+	#[test]
+	fn trace_truncates_long_messages() {
+		if !sim::isolated() {
+			return;
+		}
+		let debug = sim::attach_inert("ocelot");
+		trace::find(&mut component::Lister::take().unwrap());
+		let long = "x".repeat(trace::MAX_MESSAGE_LEN);
+		trace::transition("Init", &long);
+		trace::transition("Same", "Same");
+		let calls = sim::calls_to(&debug);
+		assert_eq!(calls.len(), 1);
+		let expected = format!("Init -> {long}");
+		assert_eq!(
+			calls[0].params,
+			[Value::str(&expected[..trace::MAX_MESSAGE_LEN])]
+		);
+	}
+
+	#[test]
+	fn reading_to_end_of_file_leads_to_ready_to_execute() {
+		if !sim::isolated() {
+			return;
+		}
+		let filesystem = sim::attach_filesystem(sim::Filesystem::new().file("/init.wasm", IMAGE));
+		let lister: *mut component::Lister =
+			Box::leak(Box::new(component::Lister::take().unwrap()));
+		let mut state = State::StartScan;
+		let mut read = false;
+		for _ in 0..100 {
+			match state {
+				State::ReadingFile(..) => read = true,
+				State::ReadyToExecute => {
+					// The whole image has been read, but nothing has been executed yet; that is
+					// left to the next step.
+					assert!(read);
+					assert_eq!(sim::methods_called(&filesystem).last().unwrap(), "read");
+					return;
+				}
+				_ => (),
+			}
+			// SAFETY: As in run_timeslice, the reference from the previous step is not used again.
+			state = match run_step(state, unsafe { &mut *lister }) {
+				Ok((_, state)) => state,
+				Err(e) => panic!("{}", e.message()),
+			};
+		}
+		panic!("reading the image did not finish");
+	}
+
+	#[test]
+	fn open_returning_false_or_null_moves_on() {
+		if !sim::isolated() {
+			return;
+		}
+		sim::attach_eeprom(sim::Eeprom::new(&[]));
+		let refusing = [Value::Bool(false), Value::Null].map(|result| {
+			sim::attach_filesystem(sim::Filesystem::new().file("/init.wasm", IMAGE).hook(
+				move |method, params| {
+					(method == "open" && params.first() == Some(&Value::str("/init.wasm")))
+						.then(|| sim::Reply::values([result.clone()]))
+				},
+			))
+		});
+		sim::attach_filesystem(sim::Filesystem::new().file("/init.wasm", &image(1)));
+		assert_eq!(sim::boot(), sim::Outcome::Executed(image(1)));
+		// Neither refusal was taken for an open file.
+		for address in refusing {
+			assert!(!sim::methods_called(&address)
+				.iter()
+				.any(|method| method == "read"));
+		}
+	}
+
+	#[test]
+	fn chunks_of_large_image_are_concatenated() {
+		if !sim::isolated() {
+			return;
+		}
+		sim::attach_eeprom(sim::Eeprom::new(&[]));
+		// Each byte differs from those a chunk earlier, so a stale buffer would show.
+		let mut image = IMAGE.to_vec();
+		image.extend((0..CHUNK_SIZE * 5 / 2).map(|i| u8::try_from(i % 251).unwrap()));
+		let filesystem = sim::attach_filesystem(sim::Filesystem::new().file("/init.wasm", &image));
+		assert_eq!(sim::boot(), sim::Outcome::Executed(image));
+		let reads = sim::methods_called(&filesystem)
+			.iter()
+			.filter(|method| *method == "read")
+			.count();
+		assert!(reads > 3, "only {reads} reads");
+	}
+
+	/// Returns the message reporting that a scan is examining `address`.
+	#[cfg(feature = "verbose")]
+	fn scan_message(address: &Address) -> String {
+		format!("Scan {}", display::format_address(address, &mut [0; 36]))
+	}
+
+	#[cfg(feature = "verbose")]
+	#[test]
+	fn verbose_reports_on_screen() {
+		if !sim::isolated() {
+			return;
+		}
+		let gpu = sim::attach_inert("gpu");
+		sim::attach_inert("screen");
+		// The boot device is missing, so the BIOS scans for another.
+		sim::attach_eeprom(config_eeprom(&sim::address(9), config::VERBOSE));
+		let filesystem = sim::attach_filesystem(sim::Filesystem::new().file("/init.wasm", IMAGE));
+		assert_eq!(sim::boot(), sim::Outcome::Executed(IMAGE.to_vec()));
+		let shown = shown(&gpu);
+		assert!(shown.contains(&scan_message(&filesystem)), "{shown:?}");
+		assert!(shown.iter().any(|text| text.contains(" -> ")));
+	}
+
+	#[cfg(feature = "verbose")]
+	#[test]
+	fn verbose_reports_to_debug_card_without_screen() {
+		if !sim::isolated() {
+			return;
+		}
+		let debug = sim::attach_inert("ocelot");
+		sim::attach_eeprom(config_eeprom(&sim::address(9), config::VERBOSE));
+		let filesystem = sim::attach_filesystem(sim::Filesystem::new().file("/init.wasm", IMAGE));
+		assert_eq!(sim::boot(), sim::Outcome::Executed(IMAGE.to_vec()));
+		assert!(sim::calls_to(&debug)
+			.iter()
+			.any(|call| call.params == [Value::Str(scan_message(&filesystem))]));
+	}
+
+	#[cfg(feature = "verbose")]
+	#[test]
+	fn verbose_is_silent_without_flag() {
+		if !sim::isolated() {
+			return;
+		}
+		let gpu = sim::attach_inert("gpu");
+		sim::attach_inert("screen");
+		sim::attach_eeprom(config_eeprom(&sim::address(9), 0));
+		sim::attach_filesystem(sim::Filesystem::new().file("/init.wasm", IMAGE));
+		assert_eq!(sim::boot(), sim::Outcome::Executed(IMAGE.to_vec()));
+		let shown = shown(&gpu);
+		assert!(
+			!shown
+				.iter()
+				.any(|text| text.contains(" -> ") || text.starts_with("Scan ")),
+			"{shown:?}"
+		);
+	}
+
+	/// Queues a `modem_message` signal received by `modem` from `sender` on `port`, holding
+	/// `payload`.
+	#[cfg(feature = "net-boot")]
+	fn queue_modem_message(modem: &Address, sender: &Address, port: i64, payload: &[u8]) {
+		sim::queue_signal(
+			"modem_message",
+			&[
+				Value::str(&modem.to_string()),
+				Value::str(&sender.to_string()),
+				Value::Int(port),
+				Value::Float(0.0),
+				Value::bytes(payload),
+			],
+		);
+	}
+
+	#[cfg(feature = "net-boot")]
+	#[test]
+	fn net_boot_discovers_server_and_receives_image() {
+		if !sim::isolated() {
+			return;
+		}
+		sim::attach_eeprom(config_eeprom(&sim::address(9), config::NET_BOOT));
+		let modem = sim::attach("modem", |_: &str, _: &[Value]| {
+			sim::Reply::values([Value::Bool(true)])
+		});
+		let server = sim::address(7);
+		let other = sim::address(8);
+		queue_modem_message(&modem, &server, 2049, &IMAGE[..3]);
+		// Messages on other ports, and from modems other than the first to reply, are ignored.
+		queue_modem_message(&modem, &server, 2050, b"port");
+		queue_modem_message(&modem, &other, 2049, b"other");
+		queue_modem_message(&modem, &server, 2049, &IMAGE[3..]);
+		queue_modem_message(&modem, &server, 2049, b"");
+		assert_eq!(sim::boot(), sim::Outcome::Executed(IMAGE.to_vec()));
+		let calls: Vec<_> = sim::calls_to(&modem)
+			.into_iter()
+			.map(|call| (call.method, call.params))
+			.collect();
+		assert_eq!(
+			calls,
+			[
+				("open".to_owned(), vec![Value::Int(2049)]),
+				(
+					"broadcast".to_owned(),
+					vec![Value::Int(2049), Value::str("oc-wasm-bios discover")]
+				),
+			]
+		);
+	}
+
+	#[cfg(feature = "net-boot")]
+	#[test]
+	fn net_boot_falls_back_to_scan_without_server() {
+		if !sim::isolated() {
+			return;
+		}
+		sim::attach_eeprom(config_eeprom(&sim::address(9), config::NET_BOOT));
+		sim::attach_inert("modem");
+		sim::attach_filesystem(sim::Filesystem::new().file("/init.wasm", IMAGE));
+		assert_eq!(sim::boot(), sim::Outcome::Executed(IMAGE.to_vec()));
+		assert!(computer::uptime().into_inner() >= 5.0);
+	}
+
+	#[cfg(feature = "net-boot")]
+	#[test]
+	fn net_boot_times_out_without_end_marker() {
+		if !sim::isolated() {
+			return;
+		}
+		sim::attach_eeprom(config_eeprom(&sim::address(9), config::NET_BOOT));
+		let modem = sim::attach_inert("modem");
+		sim::attach_filesystem(sim::Filesystem::new().file("/init.wasm", IMAGE));
+		queue_modem_message(&modem, &sim::address(7), 2049, IMAGE);
+		assert_eq!(
+			sim::boot(),
+			sim::Outcome::Halted("BIOS: network boot timed out".into())
+		);
+	}
+
+	#[test]
+	fn restart_scan_lists_bootable_media_each_time() {
+		if !sim::isolated() {
+			return;
+		}
+		let first = sim::attach_filesystem(sim::Filesystem::new());
+		sim::attach_inert("gpu");
+		let second = sim::attach_filesystem(sim::Filesystem::new());
+		let lister: *mut component::Lister =
+			Box::leak(Box::new(component::Lister::take().unwrap()));
+		let addresses = |listing: &mut component::Listing<'_>| {
+			core::iter::from_fn(|| listing.next().map(|entry| *entry.address())).collect::<Vec<_>>()
+		};
+		// A scan abandoned part of the way through does not affect the next.
+		// SAFETY: As in run_timeslice, each listing is left behind once the next is started.
+		let mut abandoned = restart_scan(unsafe { &mut *lister });
+		assert_eq!(abandoned.next().map(|entry| *entry.address()), Some(first));
+		for _ in 0..2 {
+			// SAFETY: As above.
+			assert_eq!(
+				addresses(&mut restart_scan(unsafe { &mut *lister })),
+				[first, second]
+			);
+		}
+	}
+
+	#[cfg(feature = "ram-check")]
+	#[test]
+	fn ram_check_halts_when_image_does_not_fit() {
+		if !sim::isolated() {
+			return;
+		}
+		sim::set_ram(
+			4 * 1024 * 1024,
+			u32::try_from(IMAGE.len() + RAM_MARGIN - 1).unwrap(),
+		);
+		sim::attach_eeprom(sim::Eeprom::new(&[]));
+		sim::attach_filesystem(sim::Filesystem::new().file("/init.wasm", IMAGE));
+		assert_eq!(
+			sim::boot(),
+			sim::Outcome::Halted("BIOS: kernel too large for RAM".into())
+		);
+	}
+
+	#[cfg(feature = "ram-check")]
+	#[test]
+	fn ram_check_passes_image_that_just_fits() {
+		if !sim::isolated() {
+			return;
+		}
+		sim::set_ram(
+			4 * 1024 * 1024,
+			u32::try_from(IMAGE.len() + RAM_MARGIN).unwrap(),
+		);
+		sim::attach_eeprom(sim::Eeprom::new(&[]));
+		sim::attach_filesystem(sim::Filesystem::new().file("/init.wasm", IMAGE));
+		assert_eq!(sim::boot(), sim::Outcome::Executed(IMAGE.to_vec()));
+	}
+
+	/// Returns the boot records written to `eeprom`.
+	#[cfg(feature = "boot-record")]
+	fn boot_records(eeprom: &Address) -> Vec<Vec<Value>> {
+		sim::calls_to(eeprom)
+			.into_iter()
+			.filter(|call| call.method == "setVolatileData")
+			.map(|call| call.params)
+			.collect()
+	}
+
+	#[cfg(feature = "boot-record")]
+	#[test]
+	fn boot_record_names_filesystem_and_path() {
+		if !sim::isolated() {
+			return;
+		}
+		let eeprom = sim::attach_eeprom(sim::Eeprom::new(&[]));
+		let filesystem = sim::attach_filesystem(sim::Filesystem::new().file("/init.wasm", IMAGE));
+		assert_eq!(sim::boot(), sim::Outcome::Executed(IMAGE.to_vec()));
+		let record = Value::Map(vec![
+			(Value::str("source"), Value::str("filesystem")),
+			(Value::str("uuid"), Value::bytes(filesystem.as_bytes())),
+			(Value::str("path"), Value::str("/init.wasm")),
+		]);
+		assert_eq!(
+			boot_records(&eeprom),
+			[vec![Value::Bytes(record.to_cbor())]]
+		);
+	}
+
+	#[cfg(feature = "boot-record")]
+	#[test]
+	fn boot_record_failure_is_ignored() {
+		if !sim::isolated() {
+			return;
+		}
+		// The EEPROM has no volatile data area to write to.
+		let mut inner = sim::Eeprom::new(&[]);
+		let eeprom = sim::attach("eeprom", move |method: &str, params: &[Value]| {
+			use sim::Device as _;
+			if method == "setVolatileData" {
+				sim::Reply::exception("no such method")
+			} else {
+				inner.call(method, params)
+			}
+		});
+		sim::attach_filesystem(sim::Filesystem::new().file("/init.wasm", IMAGE));
+		assert_eq!(sim::boot(), sim::Outcome::Executed(IMAGE.to_vec()));
+		assert_eq!(boot_records(&eeprom).len(), 1);
+	}
+
+	#[test]
+	fn full_execution_buffer_reports_image_too_large() {
+		if !sim::isolated() {
+			return;
+		}
+		// The execution buffer holds no more than the installed RAM.
+		sim::set_ram(u32::try_from(IMAGE.len() - 1).unwrap(), 1024 * 1024);
+		sim::attach_eeprom(sim::Eeprom::new(&[]));
+		sim::attach_filesystem(sim::Filesystem::new().file("/init.wasm", IMAGE));
+		assert_eq!(
+			sim::boot(),
+			sim::Outcome::Halted("BIOS: image too large".into())
+		);
+	}
+
+	#[cfg(feature = "boot-list")]
+	#[test]
+	fn boot_list_skips_missing_device() {
+		if !sim::isolated() {
+			return;
+		}
+		// The list names a missing device, then the second of two filesystems.
+		let list = Value::Array(vec![
+			Value::bytes(sim::address(9).as_bytes()),
+			Value::bytes(sim::address(3).as_bytes()),
+		]);
+		sim::attach_eeprom(sim::Eeprom::new(&list.to_cbor()));
+		let first = sim::attach_filesystem(sim::Filesystem::new().file("/init.wasm", &image(1)));
+		sim::attach_filesystem(sim::Filesystem::new().file("/init.wasm", &image(2)));
+		assert_eq!(sim::boot(), sim::Outcome::Executed(image(2)));
+		// The list was followed, so there was no scan to reach the first filesystem.
+		assert!(sim::calls_to(&first)
+			.iter()
+			.all(|call| call.params.first() != Some(&Value::str("/init.wasm"))));
+	}
+
+	#[cfg(feature = "boot-list")]
+	#[test]
+	fn empty_boot_list_scans() {
+		if !sim::isolated() {
+			return;
+		}
+		sim::attach_eeprom(sim::Eeprom::new(&Value::Array(Vec::new()).to_cbor()));
+		sim::attach_filesystem(sim::Filesystem::new().file("/init.wasm", &image(1)));
+		assert_eq!(sim::boot(), sim::Outcome::Executed(image(1)));
+	}
+
+	#[test]
+	fn no_storage_devices_is_reported_without_scanning() {
+		if !sim::isolated() {
+			return;
+		}
+		sim::attach_eeprom(sim::Eeprom::new(&[]));
+		sim::attach_inert("gpu");
+		assert_eq!(sim::boot(), failure("BIOS: no storage devices", Some(2)));
+	}
+
+	#[test]
+	fn storage_without_image_has_no_bootable_medium() {
+		if !sim::isolated() {
+			return;
+		}
+		sim::attach_eeprom(sim::Eeprom::new(&[]));
+		sim::attach_filesystem(sim::Filesystem::new());
+		// With the scan-labels feature, the labels of the filesystems scanned are reported too.
+		let message = if cfg!(feature = "scan-labels") {
+			"BIOS: no bootable medium (unlabelled)"
+		} else {
+			"BIOS: no bootable medium"
+		};
+		assert_eq!(sim::boot(), failure(message, Some(2)));
+	}
+
+	#[cfg(feature = "stage2")]
+	#[test]
+	fn stage2_loader_is_booted_when_present() {
+		if !sim::isolated() {
+			return;
+		}
+		let filesystem = sim::address(2);
+		sim::attach_eeprom(config_eeprom(&filesystem, config::STAGE2));
+		sim::attach_filesystem(
+			sim::Filesystem::new()
+				.file("/boot/stage2.wasm", &image(1))
+				.file("/init.wasm", &image(2)),
+		);
+		assert_eq!(sim::boot(), sim::Outcome::Executed(image(1)));
+	}
+
+	#[cfg(feature = "stage2")]
+	#[test]
+	fn stage2_absent_falls_through_to_image() {
+		if !sim::isolated() {
+			return;
+		}
+		let filesystem = sim::address(2);
+		sim::attach_eeprom(config_eeprom(&filesystem, config::STAGE2));
+		sim::attach_filesystem(sim::Filesystem::new().file("/init.wasm", &image(2)));
+		assert_eq!(sim::boot(), sim::Outcome::Executed(image(2)));
+		let opened: Vec<_> = sim::calls_to(&filesystem)
+			.into_iter()
+			.filter(|call| call.method == "open")
+			.filter_map(|call| call.params.first().cloned())
+			.filter(|path| *path != Value::str("/boot/bios.cfg"))
+			.collect();
+		assert_eq!(
+			opened[..2],
+			[Value::str("/boot/stage2.wasm"), Value::str("/init.wasm")]
+		);
+	}
+
+	#[cfg(feature = "stage2")]
+	#[test]
+	fn stage2_loader_is_ignored_without_flag() {
+		if !sim::isolated() {
+			return;
+		}
+		sim::attach_eeprom(config_eeprom(&sim::address(2), 0));
+		sim::attach_filesystem(
+			sim::Filesystem::new()
+				.file("/boot/stage2.wasm", &image(1))
+				.file("/init.wasm", &image(2)),
+		);
+		assert_eq!(sim::boot(), sim::Outcome::Executed(image(2)));
+	}
+
+	/// Returns the boot record naming `source` as the source, and `uuid` as the component the
+	/// image was loaded from, if any.
+	#[cfg(all(
+		feature = "boot-record",
+		any(
+			feature = "drive-boot",
+			feature = "eeprom-image",
+			feature = "http-boot",
+			feature = "net-boot",
+			feature = "tape-boot"
+		)
+	))]
+	fn boot_record(source: &str, uuid: Option<&Address>) -> Vec<Vec<Value>> {
+		let mut record = vec![(Value::str("source"), Value::str(source))];
+		record.extend(uuid.map(|uuid| (Value::str("uuid"), Value::bytes(uuid.as_bytes()))));
+		vec![vec![Value::Bytes(Value::Map(record).to_cbor())]]
+	}
+
+	#[cfg(all(feature = "boot-record", feature = "drive-boot"))]
+	#[test]
+	fn boot_record_names_drive() {
+		if !sim::isolated() {
+			return;
+		}
+		let eeprom = sim::attach_eeprom(sim::Eeprom::new(&[]));
+		let drive = sim::attach("drive", drive(&drive_image(IMAGE), 512));
+		assert_eq!(sim::boot(), sim::Outcome::Executed(IMAGE.to_vec()));
+		assert_eq!(boot_records(&eeprom), boot_record("drive", Some(&drive)));
+	}
+
+	#[cfg(all(feature = "boot-record", feature = "tape-boot"))]
+	#[test]
+	fn boot_record_names_tape_drive() {
+		if !sim::isolated() {
+			return;
+		}
+		let eeprom = sim::attach_eeprom(config_eeprom(&sim::address(0), config::TAPE_BOOT));
+		let drive = sim::attach(TAPE_COMPONENT_TYPE, tape_drive(IMAGE, Value::Int(0)));
+		assert_eq!(sim::boot(), sim::Outcome::Executed(IMAGE.to_vec()));
+		assert_eq!(boot_records(&eeprom), boot_record("tape", Some(&drive)));
+	}
+
+	#[cfg(all(feature = "boot-record", feature = "http-boot"))]
+	#[test]
+	fn boot_record_names_http() {
+		if !sim::isolated() {
+			return;
+		}
+		let eeprom = sim::attach_eeprom(sim::Eeprom::new(b"http://example.com/init.wasm"));
+		sim::attach("internet", internet(200, 0, IMAGE.to_vec(), 8192));
+		assert_eq!(sim::boot(), sim::Outcome::Executed(IMAGE.to_vec()));
+		assert_eq!(boot_records(&eeprom), boot_record("http", None));
+	}
+
+	#[cfg(all(feature = "boot-record", feature = "net-boot"))]
+	#[test]
+	fn boot_record_names_network_boot_server() {
+		if !sim::isolated() {
+			return;
+		}
+		let eeprom = sim::attach_eeprom(config_eeprom(&sim::address(9), config::NET_BOOT));
+		let modem = sim::attach_inert("modem");
+		let server = sim::address(7);
+		queue_modem_message(&modem, &server, 2049, IMAGE);
+		queue_modem_message(&modem, &server, 2049, b"");
+		assert_eq!(sim::boot(), sim::Outcome::Executed(IMAGE.to_vec()));
+		assert_eq!(boot_records(&eeprom), boot_record("net", Some(&server)));
+	}
+
+	#[cfg(all(feature = "boot-record", feature = "eeprom-image"))]
+	#[test]
+	fn boot_record_names_embedded_image() {
+		if !sim::isolated() {
+			return;
+		}
+		let mut data = vec![0xFF];
+		data.extend_from_slice(IMAGE);
+		let eeprom = sim::attach_eeprom(sim::Eeprom::new(&data));
+		sim::attach_filesystem(sim::Filesystem::new());
+		assert_eq!(sim::boot(), sim::Outcome::Executed(IMAGE.to_vec()));
+		assert_eq!(boot_records(&eeprom), boot_record("eeprom", None));
+	}
+
+	#[test]
+	fn call_result_rejects_length_past_buffer() {
+		if !sim::isolated() {
+			return;
+		}
+		let buffer = [1, 2, 3];
+		assert_eq!(call_result(&buffer, 2), [1, 2]);
+		assert_eq!(call_result(&buffer, 3), buffer);
+		for rc in [4, isize::MAX] {
+			assert_eq!(
+				sim::stopped(|| {
+					call_result(&buffer, rc);
+				}),
+				Some(sim::Outcome::Halted("BIOS: internal error".into()))
+			);
+		}
+	}
+
+	/// Returns a filesystem holding [`IMAGE`] whose `open` method refuses to open it, giving
+	/// `reason`.
+	fn refusing_filesystem(reason: &'static str) -> sim::Filesystem {
+		sim::Filesystem::new()
+			.file("/init.wasm", IMAGE)
+			.hook(move |method, params| {
+				(method == "open" && params.first() == Some(&Value::str("/init.wasm")))
+					.then(|| sim::Reply::values([Value::Null, Value::str(reason)]))
+			})
+	}
 
-			// Call the EEPROM’s “getData” method to read the boot device UUID.
-			let method = "getData";
-			let rc = unsafe {
-				component_sys::invoke_component_method(
-					eeprom_uuid.as_bytes().as_ptr(),
-					method.as_ptr(),
-					method.len(),
-					ptr::null(),
-				)
-			};
-			if rc < 0 {
-				internal_error();
-			}
-			Ok((
-				if rc == 0 {
-					RunResult::Return
-				} else {
-					RunResult::RunNext
-				},
-				State::ReadingBootDeviceUuid,
-			))
+	#[test]
+	fn open_failure_with_reason_moves_on() {
+		if !sim::isolated() {
+			return;
 		}
-		State::ReadingBootDeviceUuid => {
-			// Fetch the call result. An EEPROM’s data area is 256 bytes so 300 should be plenty
-			// for the CBOR overhead.
-			let mut result_buffer = [0_u8; 300];
-			let rc = unsafe {
-				component_sys::invoke_end(result_buffer.as_mut_ptr(), result_buffer.len())
-			};
-			if rc < 0 {
-				internal_error();
-			}
-			// Cast from isize to usize is sound because we just verified rc ≥ 0.
-			#[allow(clippy::cast_sign_loss)]
-			let result = unsafe { result_buffer.get_unchecked(0..(rc as usize)) };
-
-			// Decode the returned CBOR sequence. We expect a single byte array.
-			let (major_type, count, rest) = cbor_decode_header(result)?;
-			if major_type != CborMajorType::Array || count != 1 {
-				computer::error("BIOS: eeprom.getData bad");
-			}
-			let (major_type, count, rest) = cbor_decode_header(rest)?;
-			if major_type != CborMajorType::Bytes {
-				computer::error("BIOS: eeprom.getData bad");
-			}
-			if rest.len() as u64 != count {
-				computer::error("BIOS: eeprom.getData bad");
-			}
-
-			// Check if it’s a binary UUID address. If not, don’t explode, just skip straight to
-			// scanning for a bootable medium.
-			if let Ok(boot_device) = rest.try_into().map(Address::from_bytes) {
-				// Check whether the specified component exists and, if so, is of type
-				// filesystem.
-				let mut boot_device_type_buffer = [0_u8; BOOTABLE_COMPONENT_TYPE.len()];
-				// component_type can fail for reasons BufferTooShort or NoSuchComponent. The
-				// buffer is long enough to hold the component type we care about,so either of
-				// those means the boot device is either not found or is not a filesystem. In
-				// those cases, skip to scanning.
-				if let Ok(candidate_type) =
-					component::component_type(&boot_device, &mut boot_device_type_buffer)
-				{
-					if candidate_type == BOOTABLE_COMPONENT_TYPE {
-						let done = invoke_open_init(&boot_device);
-						return Ok((
-							if done {
-								RunResult::RunNext
-							} else {
-								RunResult::Return
-							},
-							State::OpeningFile(OpeningFileInfo {
-								uuid: boot_device,
-								source: UuidSource::Eeprom,
-							}),
-						));
-					}
-				}
-			}
+		sim::attach_eeprom(sim::Eeprom::new(&[]));
+		sim::attach_filesystem(refusing_filesystem("not found"));
+		sim::attach_filesystem(sim::Filesystem::new().file("/init.wasm", &image(1)));
+		assert_eq!(sim::boot(), sim::Outcome::Executed(image(1)));
+	}
 
-			// We couldn’t a designated boot device (either there wasn’t one, or it doesn’t exist,
-			// or it isn’t a filesystem). Start a scan.
-			Ok((RunResult::RunNext, State::StartScan))
+	#[cfg(feature = "verbose")]
+	#[test]
+	fn open_failure_reason_is_reported() {
+		if !sim::isolated() {
+			return;
 		}
-		State::StartScan => {
-			// List all components of the proper type and start opening init.wasm on the first one.
-			let listing = lister.start(Some(BOOTABLE_COMPONENT_TYPE));
-			Ok((RunResult::RunNext, State::Scanning(listing)))
+		let debug = sim::attach_inert("ocelot");
+		sim::attach_eeprom(config_eeprom(&sim::address(9), config::VERBOSE));
+		sim::attach_filesystem(refusing_filesystem("not found"));
+		sim::attach_filesystem(sim::Filesystem::new().file("/init.wasm", &image(1)));
+		assert_eq!(sim::boot(), sim::Outcome::Executed(image(1)));
+		assert!(sim::calls_to(&debug)
+			.iter()
+			.any(|call| call.params == [Value::str("Open failed: not found")]));
+	}
+
+	/// Returns whether the BIOS tried to open the image file on the filesystem `address`.
+	#[cfg(feature = "scan-limit")]
+	fn opened_image(address: &Address) -> bool {
+		sim::calls_to(address).iter().any(|call| {
+			call.method == "open" && call.params.first() == Some(&Value::str("/init.wasm"))
+		})
+	}
+
+	// A buffered scan holds fewer candidates than the default limit.
+	#[cfg(all(
+		feature = "scan-limit",
+		not(any(
+			feature = "prefer-label",
+			feature = "removable-first",
+			feature = "reverse-scan",
+			feature = "sorted-scan"
+		))
+	))]
+	#[test]
+	fn scan_stops_at_limit() {
+		if !sim::isolated() {
+			return;
 		}
-		State::Scanning(mut listing) => {
-			// Fetch the next component in the list.
-			if let Some(entry) = listing.next() {
-				// We found a component. Try opening /init.wasm on it.
-				let done = invoke_open_init(entry.address());
-				Ok((
-					if done {
-						RunResult::RunNext
-					} else {
-						RunResult::Return
-					},
-					State::OpeningFile(OpeningFileInfo {
-						uuid: *entry.address(),
-						source: UuidSource::Scan(listing),
-					}),
-				))
-			} else {
-				// There are no more components.
-				computer::error("BIOS: no bootable medium")
-			}
+		sim::attach_eeprom(sim::Eeprom::new(&[]));
+		let filesystems: Vec<_> = (0..MAX_SCAN)
+			.map(|_| sim::attach_filesystem(sim::Filesystem::new()))
+			.collect();
+		let beyond = sim::attach_filesystem(sim::Filesystem::new().file("/init.wasm", IMAGE));
+		assert_eq!(
+			sim::boot(),
+			sim::Outcome::Halted("BIOS: scan limit reached".into())
+		);
+		assert!(filesystems.iter().all(opened_image));
+		assert!(!opened_image(&beyond));
+	}
+
+	// A buffered scan holds fewer candidates than the default limit.
+	#[cfg(all(
+		feature = "scan-limit",
+		not(any(
+			feature = "prefer-label",
+			feature = "removable-first",
+			feature = "reverse-scan",
+			feature = "sorted-scan"
+		))
+	))]
+	#[test]
+	fn scan_boots_from_last_filesystem_within_limit() {
+		if !sim::isolated() {
+			return;
 		}
-		State::OpeningFile(info) => {
-			// Fetch the call result. An open call returns either a handle or else a null followed
-			// by the filename you tried to open, so make a buffer large enough to hold either of
-			// those.
-			let mut result_buffer = [0_u8; 32 + FILENAME.len()];
-			let rc = unsafe {
-				component_sys::invoke_end(result_buffer.as_mut_ptr(), result_buffer.len())
-			};
-			if rc >= 0 {
-				// Decode the first data item.
-				// Cast from isize to usize is sound because we just verified rc ≥ 0.
-				#[allow(clippy::cast_sign_loss)]
-				let result = unsafe { result_buffer.get_unchecked(0..(rc as usize)) };
-				let (major, count, rest) = cbor_decode_header(result)?;
-				if major == CborMajorType::Array && count == 1 {
-					let (major, count, rest) = cbor_decode_header(rest)?;
-					if major == CborMajorType::Tag && count == 39 {
-						// This is an Identifier tag. Its payload remains, and is the tagged data item.
-						let (major, count, _) = cbor_decode_header(rest)?;
-						if major == CborMajorType::UnsignedInteger {
-							// We got a file descriptor. Read the file.
-							// Cast from u64 to u32 is sound because descriptors are always small.
-							#[allow(clippy::cast_possible_truncation)]
-							let descriptor = count as u32;
-							// SAFETY: We just saw an Identifier (39) tagged integer in CBOR data
-							// provided by OC-Wasm. That can only appear when handing over a fresh
-							// descriptor.
-							let descriptor = unsafe { descriptor::Owned::new(descriptor) };
-							let done = invoke_read(&info.uuid, descriptor.as_descriptor());
-							Ok((
-								if done {
-									RunResult::RunNext
-								} else {
-									RunResult::Return
-								},
-								State::ReadingFile(ReadingFileInfo {
-									uuid: info.uuid,
-									descriptor,
-								}),
-							))
-						} else {
-							computer::error("BIOS: filesystem.open bad")
-						}
-					} else {
-						computer::error("BIOS: filesystem.open bad")
-					}
+		sim::attach_eeprom(sim::Eeprom::new(&[]));
+		for _ in 1..MAX_SCAN {
+			sim::attach_filesystem(sim::Filesystem::new());
+		}
+		sim::attach_filesystem(sim::Filesystem::new().file("/init.wasm", IMAGE));
+		assert_eq!(sim::boot(), sim::Outcome::Executed(IMAGE.to_vec()));
+	}
+
+	#[cfg(all(feature = "scan-limit", feature = "map-config"))]
+	#[test]
+	fn scan_limit_is_configurable() {
+		if !sim::isolated() {
+			return;
+		}
+		let data = Value::Map(vec![(Value::str("scan-limit"), Value::Int(2))]).to_cbor();
+		sim::attach_eeprom(sim::Eeprom::new(&data).volatile_data(&data));
+		sim::attach_filesystem(sim::Filesystem::new());
+		sim::attach_filesystem(sim::Filesystem::new());
+		let beyond = sim::attach_filesystem(sim::Filesystem::new().file("/init.wasm", IMAGE));
+		assert_eq!(
+			sim::boot(),
+			sim::Outcome::Halted("BIOS: scan limit reached".into())
+		);
+		assert!(!opened_image(&beyond));
+	}
+
+	#[cfg(feature = "tape-boot")]
+	#[test]
+	fn tape_boot_stops_at_end_of_image() {
+		if !sim::isolated() {
+			return;
+		}
+		sim::attach_eeprom(config_eeprom(&sim::address(0), config::TAPE_BOOT));
+		let mut tape = tape_image(&image(2));
+		tape.extend_from_slice(b"trailing data");
+		sim::attach(TAPE_COMPONENT_TYPE, tape_drive_holding(tape, Value::Int(0)));
+		assert_eq!(sim::boot(), sim::Outcome::Executed(image(2)));
+	}
+
+	#[cfg(feature = "tape-boot")]
+	#[test]
+	fn tape_boot_scans_without_header() {
+		if !sim::isolated() {
+			return;
+		}
+		sim::attach_eeprom(config_eeprom(&sim::address(0), config::TAPE_BOOT));
+		sim::attach_filesystem(sim::Filesystem::new().file("/init.wasm", &image(1)));
+		let mut tape = tape_image(&image(2));
+		tape[0] = b'X';
+		sim::attach(TAPE_COMPONENT_TYPE, tape_drive_holding(tape, Value::Int(0)));
+		assert_eq!(sim::boot(), sim::Outcome::Executed(image(1)));
+	}
+
+	#[cfg(feature = "tape-boot")]
+	#[test]
+	fn tape_boot_scans_without_tape() {
+		if !sim::isolated() {
+			return;
+		}
+		sim::attach_eeprom(config_eeprom(&sim::address(0), config::TAPE_BOOT));
+		sim::attach_filesystem(sim::Filesystem::new().file("/init.wasm", &image(1)));
+		let mut inner = tape_drive(&image(2), Value::Int(0));
+		let drive = sim::attach(
+			TAPE_COMPONENT_TYPE,
+			move |method: &str, params: &[Value]| {
+				use sim::Device as _;
+				if method == "isReady" {
+					sim::Reply::values([Value::Bool(false)])
 				} else {
-					computer::error("BIOS: filesystem.open bad")
+					inner.call(method, params)
 				}
-			} else if rc == -12
-			/* Other error */
-			{
-				// This probably means open failed. Scan or continue scanning for other
-				// bootable media.
-				Ok((
-					RunResult::RunNext,
-					match info.source {
-						UuidSource::Eeprom => State::StartScan,
-						UuidSource::Scan(listing) => State::Scanning(listing),
-					},
-				))
-			} else {
-				computer::error("BIOS: filesystem.open bad")
-			}
+			},
+		);
+		assert_eq!(sim::boot(), sim::Outcome::Executed(image(1)));
+		assert_eq!(sim::methods_called(&drive), ["isReady"]);
+	}
+
+	#[cfg(feature = "skip-bad-media")]
+	#[test]
+	fn malformed_read_from_boot_device_falls_back_to_scan() {
+		if !sim::isolated() {
+			return;
 		}
-		State::ReadingFile(info) => {
-			// Fetch the call result.
-			let mut result_buffer = [0_u8; 32 + CHUNK_SIZE];
-			let rc = unsafe {
-				component_sys::invoke_end(result_buffer.as_mut_ptr(), result_buffer.len())
+		let designated = sim::address(2);
+		sim::attach_eeprom(config_eeprom(&designated, 0));
+		sim::attach_filesystem(
+			sim::Filesystem::new()
+				.file("/init.wasm", IMAGE)
+				.hook(|method, _| (method == "read").then(|| sim::Reply::Raw(vec![0x81, 0x5F]))),
+		);
+		sim::attach_filesystem(sim::Filesystem::new().file("/init.wasm", &image(1)));
+		assert_eq!(sim::boot(), sim::Outcome::Executed(image(1)));
+		assert!(sim::methods_called(&designated)
+			.iter()
+			.any(|method| method == "read"));
+	}
+
+	#[cfg(feature = "skip-bad-media")]
+	#[test]
+	fn malformed_open_result_falls_back_to_scan() {
+		if !sim::isolated() {
+			return;
+		}
+		sim::attach_eeprom(config_eeprom(&sim::address(2), 0));
+		sim::attach_filesystem(sim::Filesystem::new().file("/init.wasm", IMAGE).hook(
+			|method, params| {
+				(method == "open" && params.first() == Some(&Value::str("/init.wasm")))
+					.then(|| sim::Reply::values([Value::Int(7)]))
+			},
+		));
+		sim::attach_filesystem(sim::Filesystem::new().file("/init.wasm", &image(1)));
+		assert_eq!(sim::boot(), sim::Outcome::Executed(image(1)));
+	}
+
+	#[cfg(not(feature = "skip-bad-media"))]
+	#[test]
+	fn malformed_read_halts_without_skipping_bad_media() {
+		if !sim::isolated() {
+			return;
+		}
+		sim::attach_eeprom(config_eeprom(&sim::address(2), 0));
+		sim::attach_filesystem(
+			sim::Filesystem::new()
+				.file("/init.wasm", IMAGE)
+				.hook(|method, _| (method == "read").then(|| sim::Reply::Raw(vec![0x81, 0x5F]))),
+		);
+		sim::attach_filesystem(sim::Filesystem::new().file("/init.wasm", &image(1)));
+		// With the error-uuid feature, the failing filesystem is named too.
+		let message = if cfg!(feature = "error-uuid") {
+			"BIOS: I/O error reading /init.wasm (02020202)"
+		} else {
+			"BIOS: I/O error reading /init.wasm"
+		};
+		assert_eq!(sim::boot(), failure(message, Some(3)));
+	}
+
+	/// Types `text` on `keyboard`, where `\n` stands for Enter and `\u{8}` for Backspace.
+	#[cfg(feature = "boot-prompt")]
+	fn type_text(keyboard: &Address, text: &str) {
+		for character in text.chars() {
+			let (character, code) = match character {
+				'\n' => (13, 28),
+				'\u{8}' => (8, 14),
+				_ => (i64::from(u32::from(character)), 0),
 			};
-			if rc < 0 {
-				internal_error();
-			}
-			// Cast from isize to usize is sound because we just verified rc ≥ 0.
-			#[allow(clippy::cast_sign_loss)]
-			let result = unsafe { result_buffer.get_unchecked(0..(rc as usize)) };
-			// Decode the first data item.
-			let (major, count, rest) = cbor_decode_header(result)?;
-			if major == CborMajorType::Array && count == 1 {
-				let (major, count, rest) = cbor_decode_header(rest)?;
-				if major == CborMajorType::Bytes && count <= rest.len() as u64 {
-					// We got some file data. Add it to the execution buffer and try to get some more.
-					// SAFETY: we just checked that count ≤ rest.len()
-					// Cast from u64 to usize is sound because count ≤ rest.len().
-					#[allow(clippy::cast_possible_truncation)]
-					execute::add(unsafe { rest.get_unchecked(0..count as usize) })?;
-					let done = invoke_read(&info.uuid, info.descriptor.as_descriptor());
-					Ok((
-						if done {
-							RunResult::RunNext
-						} else {
-							RunResult::Return
-						},
-						State::ReadingFile(info),
-					))
-				} else if major == CborMajorType::Special && count == 22 {
-					// We got null, indicating EOF.
-					drop(info);
-					execute::execute()
-				} else {
-					// We got something unexpected.
-					computer::error("BIOS: I/O error reading /init.wasm")
-				}
-			} else {
-				// We did not get a 1-element array.
-				computer::error("BIOS: I/O error reading /init.wasm")
+			sim::queue_key_down(keyboard, character, code);
+		}
+	}
+
+	/// Runs the BIOS until it shows the boot prompt on `gpu` with nothing typed.
+	#[cfg(feature = "boot-prompt")]
+	fn run_until_prompt(gpu: &Address) {
+		for _ in 0..1000 {
+			if shown(gpu).last().map(String::as_str) == Some("Boot UUID: _") {
+				return;
 			}
+			sim::steps(1);
 		}
+		panic!("the boot prompt was not shown");
 	}
-}
 
-/// The application entry point.
-#[no_mangle]
-pub extern "C" fn run(_: i32) -> i32 {
-	// Hold a State.
-	static STATE: SyncUnsafeCell<State> = SyncUnsafeCell::new(State::Init);
-	// SAFETY: Wasm is single-threaded, so only one thread will be here touching STATE at a time.
-	// This is the only place in which STATE is touched, so the same thread also cannot make a
-	// second mutable reference.
-	let state = unsafe { &mut *STATE.get() };
+	#[cfg(feature = "boot-prompt")]
+	#[test]
+	fn boot_prompt_boots_typed_uuid() {
+		if !sim::isolated() {
+			return;
+		}
+		let (gpu, keyboard) = attach_console();
+		sim::attach_eeprom(sim::Eeprom::new(&[]));
+		let filesystem = sim::attach_filesystem(sim::Filesystem::new());
+		run_until_prompt(&gpu);
+		// The image appears on the filesystem while the prompt is shown.
+		sim::detach(&filesystem);
+		sim::attach_at(
+			filesystem,
+			"filesystem",
+			sim::Filesystem::new().file("/init.wasm", IMAGE),
+		);
+		// A malformed UUID is ignored and the prompt starts again, and Backspace erases a mistake.
+		type_text(&keyboard, "nonsense\n");
+		let uuid = filesystem.to_string();
+		type_text(
+			&keyboard,
+			&format!("{}x\u{8}{}\n", &uuid[..10], &uuid[10..]),
+		);
+		assert_eq!(sim::boot(), sim::Outcome::Executed(IMAGE.to_vec()));
+		assert!(shown(&gpu).contains(&"Boot UUID: nonsense_".to_owned()));
+	}
 
-	// Run continuously until asked to return.
-	loop {
-		let old_state = replace(state, State::Init);
-		let rc: error::Result<(RunResult, State)> = run_step(old_state);
-		match rc {
-			Ok((result, next_state)) => {
-				*state = next_state;
-				match result {
-					RunResult::RunNext => (),
-					RunResult::Return => return 0,
-				}
-			}
-			Err(_) => computer::error("BIOS: internal error"),
+	#[cfg(feature = "boot-prompt")]
+	#[test]
+	fn boot_prompt_needs_keyboard() {
+		if !sim::isolated() {
+			return;
+		}
+		sim::attach_inert("gpu");
+		sim::attach_inert("screen");
+		sim::attach_eeprom(sim::Eeprom::new(&[]));
+		sim::attach_filesystem(sim::Filesystem::new());
+		assert_eq!(sim::boot(), failure("BIOS: no bootable medium", Some(2)));
+	}
+
+	#[cfg(feature = "progress-hook")]
+	#[test]
+	fn progress_is_reported_after_each_chunk() {
+		if !sim::isolated() {
+			return;
+		}
+		sim::attach_eeprom(sim::Eeprom::new(&[]));
+		let image: Vec<u8> = IMAGE.iter().copied().cycle().take(20).collect();
+		sim::attach_filesystem(
+			sim::Filesystem::new()
+				.file("/init.wasm", &image)
+				.max_read(8),
+		);
+		assert_eq!(sim::boot(), sim::Outcome::Executed(image));
+		assert_eq!(*PROGRESS.lock().unwrap(), [8, 16, 20]);
+	}
+
+	#[cfg(all(feature = "progress-hook", feature = "skip-bad-media"))]
+	#[test]
+	fn progress_starts_again_for_next_filesystem() {
+		if !sim::isolated() {
+			return;
+		}
+		sim::attach_eeprom(sim::Eeprom::new(&[]));
+		// The first filesystem fails part of the way through the image.
+		let mut reads = 0;
+		sim::attach_filesystem(
+			sim::Filesystem::new()
+				.file("/init.wasm", &image(1))
+				.max_read(4)
+				.hook(move |method, _| {
+					(method == "read")
+						.then(|| {
+							reads += 1;
+							(reads == 2).then(|| sim::Reply::Raw(vec![0x81, 0x5F]))
+						})
+						.flatten()
+				}),
+		);
+		sim::attach_filesystem(sim::Filesystem::new().file("/init.wasm", &image(2)));
+		assert_eq!(sim::boot(), sim::Outcome::Executed(image(2)));
+		assert_eq!(*PROGRESS.lock().unwrap(), [4, 9]);
+	}
+
+	#[cfg(feature = "magic-check")]
+	#[test]
+	fn magic_check_accepts_header_split_across_reads() {
+		if !sim::isolated() {
+			return;
+		}
+		sim::attach_eeprom(sim::Eeprom::new(&[]));
+		sim::attach_filesystem(
+			sim::Filesystem::new()
+				.file("/init.wasm", &image(1))
+				.max_read(3),
+		);
+		assert_eq!(sim::boot(), sim::Outcome::Executed(image(1)));
+	}
+
+	#[cfg(feature = "magic-check")]
+	#[test]
+	fn magic_check_rejects_text_after_first_read() {
+		if !sim::isolated() {
+			return;
+		}
+		sim::attach_eeprom(sim::Eeprom::new(&[]));
+		let text = "This is not a Wasm module, but a long text file.".repeat(10);
+		let filesystem = sim::attach_filesystem(
+			sim::Filesystem::new()
+				.file("/init.wasm", text.as_bytes())
+				.max_read(16),
+		);
+		assert_eq!(
+			sim::boot(),
+			sim::Outcome::Halted("BIOS: init.wasm is not a Wasm module".into())
+		);
+		let reads = sim::methods_called(&filesystem)
+			.iter()
+			.filter(|method| *method == "read")
+			.count();
+		assert_eq!(reads, 1);
+	}
+
+	#[cfg(feature = "magic-check")]
+	#[test]
+	fn magic_check_rejects_mismatch_in_second_read() {
+		if !sim::isolated() {
+			return;
+		}
+		sim::attach_eeprom(sim::Eeprom::new(&[]));
+		sim::attach_filesystem(
+			sim::Filesystem::new()
+				.file("/init.wasm", b"\0asm\x02\0\0\0 version 2")
+				.max_read(3),
+		);
+		assert_eq!(
+			sim::boot(),
+			sim::Outcome::Halted("BIOS: init.wasm is not a Wasm module".into())
+		);
+	}
+
+	#[cfg(feature = "magic-check")]
+	#[test]
+	fn magic_check_rejects_short_image() {
+		if !sim::isolated() {
+			return;
+		}
+		sim::attach_eeprom(sim::Eeprom::new(&[]));
+		sim::attach_filesystem(sim::Filesystem::new().file("/init.wasm", &IMAGE[..5]));
+		assert_eq!(
+			sim::boot(),
+			sim::Outcome::Halted("BIOS: init.wasm is not a Wasm module".into())
+		);
+	}
+
+	#[cfg(feature = "string-config")]
+	#[test]
+	fn decode_config_data_accepts_string() {
+		assert_eq!(decode_config_data(b"\x81\x63abc"), Ok(&b"abc"[..]));
+		assert_eq!(decode_config_data(b"\x81\x43abc"), Ok(&b"abc"[..]));
+		assert_eq!(
+			decode_config_data(b"\x81\x63abcd"),
+			Err(error::Error::CborDecode)
+		);
+	}
+
+	#[cfg(all(
+		feature = "string-config",
+		feature = "text-uuid",
+		not(feature = "volatile-config")
+	))]
+	#[test]
+	fn string_config_boots_from_dashed_uuid() {
+		if !sim::isolated() {
+			return;
+		}
+		sim::attach_eeprom(
+			sim::Eeprom::new(&[]).data(Value::str("03030303-0303-0303-0303-030303030303")),
+		);
+		sim::attach_filesystem(sim::Filesystem::new().file("/init.wasm", &image(1)));
+		let designated =
+			sim::attach_filesystem(sim::Filesystem::new().file("/init.wasm", &image(2)));
+		assert_eq!(designated, sim::address(3));
+		assert_eq!(sim::boot(), sim::Outcome::Executed(image(2)));
+	}
+
+	#[cfg(feature = "string-config")]
+	#[test]
+	fn string_config_boots_from_binary_uuid() {
+		if !sim::isolated() {
+			return;
+		}
+		sim::attach_eeprom(config_eeprom(&sim::address(3), 0));
+		sim::attach_filesystem(sim::Filesystem::new().file("/init.wasm", &image(1)));
+		sim::attach_filesystem(sim::Filesystem::new().file("/init.wasm", &image(2)));
+		assert_eq!(sim::boot(), sim::Outcome::Executed(image(2)));
+	}
+
+	#[cfg(feature = "reboot-on-failure")]
+	#[test]
+	fn reboot_on_failure_beeps_and_reboots() {
+		if !sim::isolated() {
+			return;
+		}
+		let data = Value::Map(vec![(Value::str("reboot"), Value::Bool(true))]).to_cbor();
+		sim::attach_eeprom(sim::Eeprom::new(&data).volatile_data(&data));
+		sim::attach_filesystem(sim::Filesystem::new());
+		assert_eq!(sim::boot(), sim::Outcome::Rebooted);
+		assert!(sim::beeps().contains(&sim::Beep::Tone(
+			REBOOT_BEEP_FREQUENCY,
+			REBOOT_BEEP_DURATION
+		)));
+	}
+
+	#[cfg(feature = "reboot-on-failure")]
+	#[test]
+	fn reboot_on_failure_halts_without_flag() {
+		if !sim::isolated() {
+			return;
+		}
+		let data = Value::Map(vec![(Value::str("reboot"), Value::Bool(false))]).to_cbor();
+		sim::attach_eeprom(sim::Eeprom::new(&data).volatile_data(&data));
+		sim::attach_filesystem(sim::Filesystem::new());
+		assert_ne!(sim::boot(), sim::Outcome::Rebooted);
+		assert!(!sim::beeps().contains(&sim::Beep::Tone(
+			REBOOT_BEEP_FREQUENCY,
+			REBOOT_BEEP_DURATION
+		)));
+	}
+
+	#[cfg(feature = "low-memory-chunks")]
+	#[test]
+	fn low_memory_chunks_are_full_size_with_free_ram() {
+		if !sim::isolated() {
+			return;
+		}
+		sim::attach_eeprom(sim::Eeprom::new(&[]));
+		let image: Vec<u8> = IMAGE.iter().copied().cycle().take(20000).collect();
+		let filesystem = sim::attach_filesystem(sim::Filesystem::new().file("/init.wasm", &image));
+		assert_eq!(sim::boot(), sim::Outcome::Executed(image));
+		assert_eq!(
+			read_counts(&filesystem)[0],
+			i64::try_from(CHUNK_SIZE).unwrap()
+		);
+	}
+
+	// With the ram-check feature, no image fits in so little free RAM.
+	#[cfg(all(feature = "low-memory-chunks", not(feature = "ram-check")))]
+	#[test]
+	fn low_memory_chunks_shrink_when_free_ram_is_low() {
+		if !sim::isolated() {
+			return;
+		}
+		sim::set_ram(
+			4 * 1024 * 1024,
+			u32::try_from(LOW_MEMORY_THRESHOLD - 1).unwrap(),
+		);
+		sim::attach_eeprom(sim::Eeprom::new(&[]));
+		let image: Vec<u8> = IMAGE.iter().copied().cycle().take(20000).collect();
+		let filesystem = sim::attach_filesystem(sim::Filesystem::new().file("/init.wasm", &image));
+		assert_eq!(sim::boot(), sim::Outcome::Executed(image));
+		// 20000 bytes take five reads of 4096 bytes, and a sixth to see the end of the file.
+		assert_eq!(
+			read_counts(&filesystem),
+			[i64::try_from(LOW_MEMORY_CHUNK_SIZE).unwrap(); 6]
+		);
+	}
+
+	/// Calls `read` on a filesystem that returns `reply`, and returns what [`end_read`] makes of it
+	/// and the data it passes on.
+	fn read_reply(reply: Vec<u8>) -> (Option<bool>, Vec<u8>) {
+		let address = sim::attach("filesystem", move |_: &str, _: &[Value]| {
+			sim::Reply::Raw(reply.clone())
+		});
+		assert!(invoke_method(&address, "read", None));
+		let mut buffer = [0_u8; 64];
+		let mut read = Vec::new();
+		let Ok(result) = end_read(&mut buffer, |chunk| {
+			read.extend_from_slice(chunk);
+			Ok(())
+		}) else {
+			panic!("end_read failed");
+		};
+		sim::detach(&address);
+		(result, read)
+	}
+
+	#[test]
+	fn end_read_tells_end_of_file_from_failure() {
+		if !sim::isolated() {
+			return;
+		}
+		assert_eq!(
+			read_reply(Value::Array(vec![Value::Null]).to_cbor()),
+			(Some(true), Vec::new())
+		);
+		assert_eq!(
+			read_reply(Value::Array(vec![Value::Null, Value::str("device error")]).to_cbor()),
+			(None, Vec::new())
+		);
+	}
+
+	#[cfg(feature = "chunked-read")]
+	#[test]
+	fn chunked_read_tells_end_of_file_from_failure() {
+		if !sim::isolated() {
+			return;
+		}
+		assert_eq!(
+			read_reply(Value::Array(vec![Value::bytes(b"abc"), Value::Null]).to_cbor()),
+			(Some(true), b"abc".to_vec())
+		);
+		let (result, _) = read_reply(
+			Value::Array(vec![
+				Value::bytes(b"abc"),
+				Value::Null,
+				Value::str("device error"),
+			])
+			.to_cbor(),
+		);
+		assert_eq!(result, None);
+	}
+
+	// Without the chunked-read feature, a 2-element result is rejected before the reason is seen.
+	#[cfg(all(feature = "verbose", feature = "chunked-read"))]
+	#[test]
+	fn verbose_reports_read_failure_reason() {
+		if !sim::isolated() {
+			return;
+		}
+		let debug = sim::attach_inert("ocelot");
+		sim::attach_eeprom(config_eeprom(&sim::address(9), config::VERBOSE));
+		sim::attach_filesystem(sim::Filesystem::new().file("/init.wasm", IMAGE).hook(
+			|method, _| {
+				(method == "read")
+					.then(|| sim::Reply::values([Value::Null, Value::str("device error")]))
+			},
+		));
+		assert_ne!(sim::boot(), sim::Outcome::Executed(IMAGE.to_vec()));
+		assert!(sim::calls_to(&debug)
+			.iter()
+			.any(|call| call.params == [Value::str("Read failed: device error")]));
+	}
+
+	#[cfg(feature = "wait-device")]
+	#[test]
+	fn wait_device_boots_from_device_that_appears_late() {
+		if !sim::isolated() {
+			return;
+		}
+		sim::attach_eeprom(config_eeprom(&sim::address(9), 0));
+		sim::attach_filesystem(sim::Filesystem::new().file("/init.wasm", &image(1)));
+		sim::steps(20);
+		sim::attach_at(
+			sim::address(9),
+			"filesystem",
+			sim::Filesystem::new().file("/init.wasm", &image(2)),
+		);
+		assert_eq!(sim::boot(), sim::Outcome::Executed(image(2)));
+		assert!(computer::uptime().into_inner() < DEVICE_WAIT_TIMEOUT);
+	}
+
+	#[cfg(feature = "wait-device")]
+	#[test]
+	fn wait_device_scans_once_timeout_passes() {
+		if !sim::isolated() {
+			return;
+		}
+		sim::attach_eeprom(config_eeprom(&sim::address(9), 0));
+		sim::attach_filesystem(sim::Filesystem::new().file("/init.wasm", &image(1)));
+		assert_eq!(sim::boot(), sim::Outcome::Executed(image(1)));
+		assert!(computer::uptime().into_inner() >= DEVICE_WAIT_TIMEOUT);
+	}
+
+	#[cfg(feature = "boot-timeout")]
+	#[test]
+	fn boot_timeout_halts_when_loading_is_too_slow() {
+		if !sim::isolated() {
+			return;
+		}
+		sim::attach_eeprom(sim::Eeprom::new(&[]));
+		// The image is read a byte at a time, and every call takes a timeslice, so loading it
+		// would take far longer than allowed.
+		let image: Vec<u8> = IMAGE.iter().copied().cycle().take(4000).collect();
+		let mut inner = sim::Filesystem::new()
+			.file("/init.wasm", &image)
+			.max_read(1);
+		sim::attach("filesystem", move |method: &str, params: &[Value]| {
+			use sim::Device as _;
+			sim::Reply::Later(Box::new(inner.call(method, params)))
+		});
+		assert_eq!(
+			sim::boot(),
+			sim::Outcome::Halted("BIOS: boot timeout".into())
+		);
+		let uptime = computer::uptime().into_inner();
+		assert!(
+			(BOOT_TIMEOUT..BOOT_TIMEOUT + 1.0).contains(&uptime),
+			"{uptime}"
+		);
+	}
+
+	#[cfg(feature = "kernel-args")]
+	#[test]
+	fn kernel_args_are_passed_in_boot_record() {
+		if !sim::isolated() {
+			return;
+		}
+		let data =
+			Value::Map(vec![(Value::str("args"), Value::str("root=/dev/a quiet"))]).to_cbor();
+		let eeprom = sim::attach_eeprom(sim::Eeprom::new(&data));
+		let filesystem = sim::attach_filesystem(sim::Filesystem::new().file("/init.wasm", IMAGE));
+		assert_eq!(sim::boot(), sim::Outcome::Executed(IMAGE.to_vec()));
+		let record = Value::Map(vec![
+			(Value::str("source"), Value::str("filesystem")),
+			(Value::str("uuid"), Value::bytes(filesystem.as_bytes())),
+			(Value::str("path"), Value::str("/init.wasm")),
+			(Value::str("args"), Value::str("root=/dev/a quiet")),
+		]);
+		assert_eq!(
+			boot_records(&eeprom),
+			[vec![Value::Bytes(record.to_cbor())]]
+		);
+	}
+
+	/// Returns a filesystem holding `image` that is too busy to open `/init.wasm` the first `busy`
+	/// times it is asked.
+	#[cfg(feature = "defer-busy")]
+	fn busy_filesystem(image: &[u8], mut busy: u32) -> sim::Filesystem {
+		sim::Filesystem::new()
+			.file("/init.wasm", image)
+			.hook(move |method, params| {
+				(method == "open" && params.first() == Some(&Value::str("/init.wasm")) && busy != 0)
+					.then(|| {
+						busy -= 1;
+						sim::Reply::exception("too many open handles")
+					})
+			})
+	}
+
+	/// Returns the number of times the BIOS tried to open `/init.wasm` on the filesystem
+	/// `address`.
+	#[cfg(feature = "defer-busy")]
+	fn image_opens(address: &Address) -> usize {
+		sim::calls_to(address)
+			.iter()
+			.filter(|call| {
+				call.method == "open" && call.params.first() == Some(&Value::str("/init.wasm"))
+			})
+			.count()
+	}
+
+	#[cfg(feature = "defer-busy")]
+	#[test]
+	fn defer_busy_retries_busy_filesystem_at_end_of_scan() {
+		if !sim::isolated() {
+			return;
+		}
+		sim::attach_eeprom(sim::Eeprom::new(&[]));
+		let busy = sim::attach_filesystem(busy_filesystem(&image(1), 1));
+		let empty = sim::attach_filesystem(sim::Filesystem::new());
+		assert_eq!(sim::boot(), sim::Outcome::Executed(image(1)));
+		assert_eq!(image_opens(&busy), 2);
+		assert_ne!(image_opens(&empty), 0);
+	}
+
+	#[cfg(feature = "defer-busy")]
+	#[test]
+	fn defer_busy_retries_only_once() {
+		if !sim::isolated() {
+			return;
+		}
+		sim::attach_eeprom(sim::Eeprom::new(&[]));
+		let busy = sim::attach_filesystem(busy_filesystem(&image(1), u32::MAX));
+		assert!(matches!(
+			sim::boot(),
+			sim::Outcome::Halted(_) | sim::Outcome::Returned(_)
+		));
+		// With the reverse-scan feature, the second pass tries it again, and with the
+		// install-once feature, so does the second scan.
+		if !cfg!(any(feature = "reverse-scan", feature = "install-once")) {
+			assert_eq!(image_opens(&busy), 2);
+		}
+	}
+
+	/// Returns an EEPROM whose data area holds `image` as an embedded image.
+	#[cfg(feature = "eeprom-image")]
+	fn image_eeprom(image: &[u8]) -> sim::Eeprom {
+		let mut data = vec![0xFF];
+		data.extend_from_slice(image);
+		sim::Eeprom::new(&data).volatile_data(&data)
+	}
+
+	#[cfg(feature = "eeprom-image")]
+	#[test]
+	fn eeprom_image_boots_when_no_medium_is_bootable() {
+		if !sim::isolated() {
+			return;
+		}
+		sim::attach_eeprom(image_eeprom(&image(1)));
+		sim::attach_filesystem(sim::Filesystem::new());
+		assert_eq!(sim::boot(), sim::Outcome::Executed(image(1)));
+	}
+
+	#[cfg(feature = "eeprom-image")]
+	#[test]
+	fn eeprom_image_boots_without_storage() {
+		if !sim::isolated() {
+			return;
+		}
+		sim::attach_eeprom(image_eeprom(&image(1)));
+		assert_eq!(sim::boot(), sim::Outcome::Executed(image(1)));
+	}
+
+	#[cfg(feature = "eeprom-image")]
+	#[test]
+	fn eeprom_image_comes_after_bootable_medium() {
+		if !sim::isolated() {
+			return;
+		}
+		sim::attach_eeprom(image_eeprom(&image(1)));
+		sim::attach_filesystem(sim::Filesystem::new().file("/init.wasm", &image(2)));
+		assert_eq!(sim::boot(), sim::Outcome::Executed(image(2)));
+	}
+
+	#[cfg(feature = "eeprom-image")]
+	#[test]
+	fn eeprom_image_too_large_is_ignored() {
+		if !sim::isolated() {
+			return;
+		}
+		let image: Vec<u8> = IMAGE
+			.iter()
+			.copied()
+			.cycle()
+			.take(EMBEDDED_IMAGE_MAX + 1)
+			.collect();
+		sim::attach_eeprom(image_eeprom(&image));
+		sim::attach_filesystem(sim::Filesystem::new());
+		assert_ne!(sim::boot(), sim::Outcome::Executed(image));
+	}
+
+	#[cfg(feature = "map-read")]
+	#[test]
+	fn map_read_decodes_data_and_eof() {
+		if !sim::isolated() {
+			return;
+		}
+		let entry = |key: &str, value: Value| (Value::str(key), value);
+		let reply =
+			|entries: Vec<(Value, Value)>| Value::Array(vec![Value::Map(entries)]).to_cbor();
+		// The legacy shape is still understood.
+		assert_eq!(
+			read_reply(Value::Array(vec![Value::bytes(b"abc")]).to_cbor()),
+			(Some(false), b"abc".to_vec())
+		);
+		for (entries, expected, data) in [
+			(
+				vec![entry("data", Value::bytes(b"abc"))],
+				Some(false),
+				&b"abc"[..],
+			),
+			(
+				vec![
+					entry("data", Value::bytes(b"abc")),
+					entry("eof", Value::Bool(true)),
+				],
+				Some(true),
+				b"abc",
+			),
+			(vec![entry("eof", Value::Bool(true))], Some(true), b""),
+			(vec![entry("eof", Value::Bool(false))], Some(false), b""),
+			(
+				vec![
+					entry("mode", Value::Array(vec![Value::Int(1), Value::str("x")])),
+					entry("data", Value::bytes(b"abc")),
+				],
+				Some(false),
+				b"abc",
+			),
+			(vec![entry("data", Value::str("abc"))], None, b""),
+			(vec![entry("eof", Value::Int(1))], None, b""),
+			(vec![(Value::Int(1), Value::bytes(b"abc"))], None, b""),
+		] {
+			assert_eq!(read_reply(reply(entries)), (expected, data.to_vec()));
+		}
+	}
+
+	#[cfg(feature = "map-read")]
+	#[test]
+	fn map_read_boots_from_map_shaped_results() {
+		if !sim::isolated() {
+			return;
+		}
+		sim::attach_eeprom(sim::Eeprom::new(&[]));
+		sim::attach_filesystem(sim::Filesystem::new().file("/init.wasm", &image(2)).hook(
+			|method, _| {
+				(method == "read").then(|| {
+					sim::Reply::values([Value::Map(vec![
+						(Value::str("data"), Value::bytes(&image(1))),
+						(Value::str("eof"), Value::Bool(true)),
+					])])
+				})
+			},
+		));
+		assert_eq!(sim::boot(), sim::Outcome::Executed(image(1)));
+	}
+
+	#[cfg(feature = "panic-message")]
+	#[test]
+	fn panic_message_gives_location() {
+		let mut buffer = [0; PANIC_MESSAGE_LEN];
+		assert_eq!(panic_message(None, &mut buffer), "BIOS: panic");
+		assert_eq!(
+			panic_message(Some(("src/main.rs", 123)), &mut buffer),
+			"BIOS: panic at main.rs:123"
+		);
+		assert_eq!(
+			panic_message(Some(("cbor.rs", 0)), &mut buffer),
+			"BIOS: panic at cbor.rs:0"
+		);
+		assert_eq!(
+			panic_message(Some(("src/sixteen_bytes.rs", u32::MAX)), &mut buffer),
+			"BIOS: panic at sixteen_bytes.rs:4294967295"
+		);
+		// A location that does not fit is left out.
+		assert_eq!(
+			panic_message(Some(("src/seventeen_byte.rs", u32::MAX)), &mut buffer),
+			"BIOS: panic"
+		);
+	}
+
+	#[cfg(feature = "panic-message")]
+	#[test]
+	fn panic_halts_with_message() {
+		if !sim::isolated() {
+			return;
+		}
+		assert_eq!(
+			sim::stopped(|| report_panic(Some(("src/main.rs", 42)))),
+			Some(sim::Outcome::Halted("BIOS: panic at main.rs:42".into()))
+		);
+	}
+
+	/// Returns an EEPROM holding a map configuration that sets `seek` to `seek`.
+	#[cfg(feature = "seek-first")]
+	fn seek_eeprom(seek: bool) -> sim::Eeprom {
+		let data = Value::Map(vec![(Value::str("seek"), Value::Bool(seek))]).to_cbor();
+		sim::Eeprom::new(&data).volatile_data(&data)
+	}
+
+	/// Returns the methods called on the filesystem `address` with the image file’s descriptor.
+	#[cfg(feature = "seek-first")]
+	fn image_file_methods(address: &Address) -> Vec<String> {
+		sim::calls_to(address)
+			.into_iter()
+			.filter(|call| matches!(call.params.first(), Some(Value::Descriptor(_))))
+			.map(|call| call.method)
+			.collect()
+	}
+
+	#[cfg(feature = "seek-first")]
+	#[test]
+	fn seek_first_seeks_to_start_before_reading() {
+		if !sim::isolated() {
+			return;
+		}
+		sim::attach_eeprom(seek_eeprom(true));
+		let filesystem =
+			sim::attach_filesystem(sim::Filesystem::new().file("/init.wasm", &image(1)));
+		assert_eq!(sim::boot(), sim::Outcome::Executed(image(1)));
+		let methods = image_file_methods(&filesystem);
+		assert_eq!(methods.first().map(String::as_str), Some("seek"));
+		assert!(methods[1..].iter().all(|method| method != "seek"));
+		let seek = sim::calls_to(&filesystem)
+			.into_iter()
+			.find(|call| call.method == "seek")
+			.unwrap();
+		assert!(matches!(
+			&seek.params[..],
+			[Value::Descriptor(_), Value::Str(whence), Value::Int(0)] if whence == "set"
+		));
+	}
+
+	#[cfg(feature = "seek-first")]
+	#[test]
+	fn seek_first_moves_on_when_seek_fails() {
+		if !sim::isolated() {
+			return;
+		}
+		sim::attach_eeprom(seek_eeprom(true));
+		let failing =
+			sim::attach_filesystem(sim::Filesystem::new().file("/init.wasm", &image(1)).hook(
+				|method, _| {
+					(method == "seek")
+						.then(|| sim::Reply::values([Value::Null, Value::str("not supported")]))
+				},
+			));
+		let throwing =
+			sim::attach_filesystem(sim::Filesystem::new().file("/init.wasm", &image(2)).hook(
+				|method, _| (method == "seek").then(|| sim::Reply::exception("not supported")),
+			));
+		sim::attach_filesystem(sim::Filesystem::new().file("/init.wasm", &image(3)));
+		assert_eq!(sim::boot(), sim::Outcome::Executed(image(3)));
+		for address in [failing, throwing] {
+			assert!(!sim::methods_called(&address).contains(&"read".to_string()));
+		}
+		assert!(sim::open_descriptors().is_empty());
+	}
+
+	#[cfg(feature = "seek-first")]
+	#[test]
+	fn seek_first_is_off_without_flag() {
+		if !sim::isolated() {
+			return;
+		}
+		sim::attach_eeprom(seek_eeprom(false));
+		let filesystem =
+			sim::attach_filesystem(sim::Filesystem::new().file("/init.wasm", &image(1)));
+		assert_eq!(sim::boot(), sim::Outcome::Executed(image(1)));
+		assert!(!image_file_methods(&filesystem).contains(&"seek".to_string()));
+	}
+
+	#[cfg(feature = "crc-report")]
+	#[test]
+	fn crc_report_logs_running_checksum_and_summary() {
+		if !sim::isolated() {
+			return;
+		}
+		let debug = sim::attach_inert("ocelot");
+		sim::attach_eeprom(config_eeprom(&sim::address(9), config::VERBOSE));
+		let image: Vec<u8> = IMAGE.iter().copied().cycle().take(20).collect();
+		sim::attach_filesystem(
+			sim::Filesystem::new()
+				.file("/init.wasm", &image)
+				.max_read(12),
+		);
+		assert_eq!(sim::boot(), sim::Outcome::Executed(image.clone()));
+		let logged: Vec<Value> = sim::calls_to(&debug)
+			.into_iter()
+			.flat_map(|call| call.params)
+			.collect();
+		let crc = |len| Value::Str(format!("CRC {:08x}", crc32_of(&image[..len])));
+		let summary = Value::Str(format!("Loaded 20 bytes, CRC {:08x}", crc32_of(&image)));
+		let position = |message: &Value| logged.iter().position(|logged| logged == message);
+		let first = position(&crc(12)).unwrap();
+		let second = position(&crc(20)).unwrap();
+		let last = position(&summary).unwrap();
+		assert!(first < second && second < last, "{logged:?}");
+	}
+
+	#[test]
+	fn call_failed_reports_result_too_large() {
+		if !sim::isolated() {
+			return;
+		}
+		let too_large = if cfg!(feature = "result-check") {
+			"BIOS: call result too large"
+		} else {
+			"BIOS: internal error"
+		};
+		assert_eq!(
+			sim::stopped(|| call_failed(-4)),
+			Some(sim::Outcome::Halted(too_large.into()))
+		);
+		assert_eq!(
+			sim::stopped(|| call_failed(-12)),
+			Some(sim::Outcome::Halted("BIOS: internal error".into()))
+		);
+	}
+
+	#[cfg(feature = "result-check")]
+	#[test]
+	fn result_check_halts_on_read_result_too_large() {
+		if !sim::isolated() {
+			return;
+		}
+		sim::attach_eeprom(sim::Eeprom::new(&[]));
+		sim::attach_filesystem(sim::Filesystem::new().file("/init.wasm", IMAGE).hook(
+			|method, _| {
+				(method == "read")
+					.then(|| sim::Reply::values([Value::bytes(&vec![0; 32 + CHUNK_SIZE])]))
+			},
+		));
+		assert_eq!(
+			sim::boot(),
+			sim::Outcome::Halted("BIOS: call result too large".into())
+		);
+	}
+
+	#[cfg(feature = "skip-list")]
+	#[test]
+	fn skip_list_skips_filesystem_first_in_listing() {
+		if !sim::isolated() {
+			return;
 		}
+		let skipped = sim::address(2);
+		let data = Value::Map(vec![(
+			Value::str("skip"),
+			Value::Array(vec![Value::bytes(skipped.as_bytes())]),
+		)])
+		.to_cbor();
+		sim::attach_eeprom(sim::Eeprom::new(&data).volatile_data(&data));
+		assert_eq!(
+			sim::attach_filesystem(sim::Filesystem::new().file("/init.wasm", &image(1))),
+			skipped
+		);
+		sim::attach_filesystem(sim::Filesystem::new().file("/init.wasm", &image(2)));
+		assert_eq!(sim::boot(), sim::Outcome::Executed(image(2)));
+		let open = [Value::str("/init.wasm")];
+		assert!(!sim::calls_to(&skipped)
+			.iter()
+			.any(|call| call.method == "open" && call.params.starts_with(&open)));
 	}
 }
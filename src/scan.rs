@@ -0,0 +1,471 @@
+//! Enumeration of candidate bootable media.
+
+#[cfg(feature = "removable-first")]
+use super::boot_priority;
+use super::component;
+#[cfg(feature = "prefer-label")]
+use super::is_preferred;
+use super::restart_scan;
+#[cfg(feature = "sorted-scan")]
+use super::sort_scan;
+use oc_wasm_safe::Address;
+
+/// The maximum number of candidates that can be collected when the candidate list is buffered.
+///
+/// Any bootable media beyond this many are ignored.
+#[cfg(any(
+	feature = "prefer-label",
+	feature = "removable-first",
+	feature = "reverse-scan",
+	feature = "sorted-scan"
+))]
+const MAX_CANDIDATES: usize = 32;
+
+/// The maximum number of busy candidates that can be set aside to retry at the end of a scan.
+///
+/// Any further busy candidates are skipped like any other unbootable medium.
+#[cfg(feature = "defer-busy")]
+const MAX_DEFERRED: usize = 4;
+
+/// An in-progress scan over all bootable media.
+///
+/// By default, the component listing is streamed, so each candidate is visited exactly once in
+/// listing order.
+#[cfg(not(any(
+	feature = "prefer-label",
+	feature = "removable-first",
+	feature = "reverse-scan",
+	feature = "sorted-scan"
+)))]
+pub struct Scan {
+	/// The listing of bootable media that have not been visited yet.
+	listing: component::Listing<'static>,
+
+	/// Whether media labelled as installers may be booted from in this scan.
+	#[cfg(feature = "install-once")]
+	pub include_installers: bool,
+
+	/// The number of candidates tried so far.
+	#[cfg(feature = "scan-limit")]
+	pub tried: usize,
+
+	/// The candidates set aside because they were busy, in the order in which they are retried.
+	#[cfg(feature = "defer-busy")]
+	deferred: [Address; MAX_DEFERRED],
+
+	/// The number of valid elements in `deferred`.
+	#[cfg(feature = "defer-busy")]
+	deferred_len: usize,
+
+	/// The number of elements of `deferred` returned so far.
+	#[cfg(feature = "defer-busy")]
+	retried: usize,
+}
+
+#[cfg(not(any(
+	feature = "prefer-label",
+	feature = "removable-first",
+	feature = "reverse-scan",
+	feature = "sorted-scan"
+)))]
+impl Scan {
+	/// Starts a scan.
+	pub fn start(lister: &'static mut component::Lister) -> Self {
+		Self {
+			listing: restart_scan(lister),
+			#[cfg(feature = "install-once")]
+			include_installers: false,
+			#[cfg(feature = "scan-limit")]
+			tried: 0,
+			#[cfg(feature = "defer-busy")]
+			deferred: [Address::default(); MAX_DEFERRED],
+			#[cfg(feature = "defer-busy")]
+			deferred_len: 0,
+			#[cfg(feature = "defer-busy")]
+			retried: 0,
+		}
+	}
+}
+
+#[cfg(not(any(
+	feature = "prefer-label",
+	feature = "removable-first",
+	feature = "reverse-scan",
+	feature = "sorted-scan"
+)))]
+impl Iterator for Scan {
+	type Item = Address;
+
+	fn next(&mut self) -> Option<Address> {
+		let address = self.listing.next().map(|entry| *entry.address());
+		#[cfg(feature = "defer-busy")]
+		let address = address.or_else(|| self.next_deferred());
+		address
+	}
+}
+
+/// An in-progress scan over all bootable media.
+///
+/// With the `prefer-label`, `removable-first`, `reverse-scan`, or `sorted-scan` feature, the
+/// candidates are collected up front. With `sorted-scan`, if the boot configuration asks for it,
+/// they are sorted by UUID, comparing the raw bytes, in place of listing order. With
+/// `prefer-label`, those whose labels start with the preferred prefix are moved to the front,
+/// keeping listing order within each group. With `removable-first`, the candidates are then ordered
+/// by boot priority, highest first, keeping the existing order among candidates of equal priority.
+/// The candidates are then visited once in that order and, with `reverse-scan`, if none of them is
+/// bootable, a second time in reverse order.
+#[cfg(any(
+	feature = "prefer-label",
+	feature = "removable-first",
+	feature = "reverse-scan",
+	feature = "sorted-scan"
+))]
+pub struct Scan {
+	/// The addresses of the candidates, in the order in which they are first visited.
+	candidates: [Address; MAX_CANDIDATES],
+
+	/// The number of valid elements in `candidates`.
+	len: usize,
+
+	/// The number of candidates returned so far, across all passes.
+	position: usize,
+
+	/// Whether media labelled as installers may be booted from in this scan.
+	#[cfg(feature = "install-once")]
+	pub include_installers: bool,
+
+	/// The number of candidates tried so far.
+	#[cfg(feature = "scan-limit")]
+	pub tried: usize,
+
+	/// The candidates set aside because they were busy, in the order in which they are retried.
+	#[cfg(feature = "defer-busy")]
+	deferred: [Address; MAX_DEFERRED],
+
+	/// The number of valid elements in `deferred`.
+	#[cfg(feature = "defer-busy")]
+	deferred_len: usize,
+
+	/// The number of elements of `deferred` returned so far.
+	#[cfg(feature = "defer-busy")]
+	retried: usize,
+}
+
+#[cfg(any(
+	feature = "prefer-label",
+	feature = "removable-first",
+	feature = "reverse-scan",
+	feature = "sorted-scan"
+))]
+impl Scan {
+	/// Starts a scan.
+	pub fn start(lister: &'static mut component::Lister) -> Self {
+		let mut listing = restart_scan(lister);
+		let mut scan = Self::empty();
+		while scan.len < MAX_CANDIDATES {
+			if let Some(entry) = listing.next() {
+				scan.candidates[scan.len] = *entry.address();
+				scan.len += 1;
+			} else {
+				break;
+			}
+		}
+		#[cfg(feature = "sorted-scan")]
+		if sort_scan() {
+			sort_by_uuid(&mut scan.candidates[..scan.len]);
+		}
+		#[cfg(feature = "prefer-label")]
+		move_to_front(&mut scan.candidates[..scan.len], is_preferred);
+		#[cfg(feature = "removable-first")]
+		order_by_priority(&mut scan.candidates[..scan.len], boot_priority);
+		scan
+	}
+
+	/// Returns a scan with no candidates.
+	fn empty() -> Self {
+		Self {
+			candidates: [Address::default(); MAX_CANDIDATES],
+			len: 0,
+			position: 0,
+			#[cfg(feature = "install-once")]
+			include_installers: false,
+			#[cfg(feature = "scan-limit")]
+			tried: 0,
+			#[cfg(feature = "defer-busy")]
+			deferred: [Address::default(); MAX_DEFERRED],
+			#[cfg(feature = "defer-busy")]
+			deferred_len: 0,
+			#[cfg(feature = "defer-busy")]
+			retried: 0,
+		}
+	}
+}
+
+/// Orders candidates by `priority`, highest first, keeping the existing order among candidates of
+/// equal priority.
+///
+/// Each candidate’s priority is computed once. Insertion sort is small, and there are few enough
+/// candidates. At most [`MAX_CANDIDATES`] candidates may be passed.
+#[cfg(feature = "removable-first")]
+fn order_by_priority(candidates: &mut [Address], mut priority: impl FnMut(&Address) -> u8) {
+	let mut priorities = [0_u8; MAX_CANDIDATES];
+	for index in 0..candidates.len() {
+		priorities[index] = priority(&candidates[index]);
+		let mut position = index;
+		while position > 0 && priorities[position - 1] < priorities[position] {
+			priorities.swap(position - 1, position);
+			candidates.swap(position - 1, position);
+			position -= 1;
+		}
+	}
+}
+
+/// Sorts candidates by UUID, comparing the raw bytes.
+///
+/// Insertion sort is small, and there are few enough candidates.
+#[cfg(feature = "sorted-scan")]
+fn sort_by_uuid(candidates: &mut [Address]) {
+	for index in 1..candidates.len() {
+		let mut position = index;
+		while position > 0 && candidates[position - 1].as_bytes() > candidates[position].as_bytes()
+		{
+			candidates.swap(position - 1, position);
+			position -= 1;
+		}
+	}
+}
+
+/// Moves the candidates for which `preferred` returns `true` to the front, keeping the existing
+/// order within each group.
+#[cfg(feature = "prefer-label")]
+fn move_to_front(candidates: &mut [Address], mut preferred: impl FnMut(&Address) -> bool) {
+	let mut front = 0;
+	for index in 0..candidates.len() {
+		if preferred(&candidates[index]) {
+			candidates[front..=index].rotate_right(1);
+			front += 1;
+		}
+	}
+}
+
+#[cfg(any(
+	feature = "prefer-label",
+	feature = "removable-first",
+	feature = "reverse-scan",
+	feature = "sorted-scan"
+))]
+impl Iterator for Scan {
+	type Item = Address;
+
+	fn next(&mut self) -> Option<Address> {
+		let index = if self.position < self.len {
+			// First pass, forward.
+			self.position
+		} else if cfg!(feature = "reverse-scan") && self.position < 2 * self.len {
+			// Second pass, backward.
+			2 * self.len - 1 - self.position
+		} else {
+			#[cfg(feature = "defer-busy")]
+			return self.next_deferred();
+			#[cfg(not(feature = "defer-busy"))]
+			return None;
+		};
+		self.position += 1;
+		Some(self.candidates[index])
+	}
+}
+
+#[cfg(feature = "defer-busy")]
+impl Scan {
+	/// Sets aside a candidate that was busy, to retry once every other candidate has been visited.
+	///
+	/// Each candidate is retried at most once per scan, so a candidate that has already been set
+	/// aside, or that does not fit, is dropped.
+	pub fn defer(&mut self, address: Address) {
+		if self.deferred_len < MAX_DEFERRED
+			&& !self.deferred[..self.deferred_len].contains(&address)
+		{
+			self.deferred[self.deferred_len] = address;
+			self.deferred_len += 1;
+		}
+	}
+
+	/// Returns the next candidate set aside to retry, if any.
+	fn next_deferred(&mut self) -> Option<Address> {
+		let address = self.deferred[..self.deferred_len]
+			.get(self.retried)
+			.copied();
+		self.retried += usize::from(address.is_some());
+		address
+	}
+}
+
+#[cfg(all(
+	test,
+	any(
+		feature = "prefer-label",
+		feature = "removable-first",
+		feature = "reverse-scan",
+		feature = "sorted-scan"
+	)
+))]
+mod tests {
+	use super::*;
+
+	/// Returns candidates with the given first address bytes.
+	fn candidates(ids: &[u8]) -> Vec<Address> {
+		ids.iter()
+			.map(|&id| Address::from_bytes([id; 16]))
+			.collect()
+	}
+
+	/// Returns a scan over candidates with the given first address bytes, in that order.
+	fn scan_over(ids: &[u8]) -> Scan {
+		let mut scan = Scan::empty();
+		scan.len = ids.len();
+		scan.candidates[..scan.len].copy_from_slice(&candidates(ids));
+		scan
+	}
+
+	/// Returns the candidates a scan over `ids` visits when nothing is deferred.
+	fn visits(ids: &[u8]) -> Vec<Address> {
+		let mut visits = candidates(ids);
+		if cfg!(feature = "reverse-scan") {
+			visits.extend(candidates(ids).into_iter().rev());
+		}
+		visits
+	}
+
+	#[test]
+	fn scan_visits_candidates_in_order() {
+		for ids in [&[][..], &[1], &[3, 1, 2]] {
+			assert_eq!(scan_over(ids).collect::<Vec<_>>(), visits(ids), "{ids:?}");
+		}
+	}
+
+	#[test]
+	fn scan_stays_finished() {
+		let mut scan = scan_over(&[1, 2]);
+		assert_eq!(scan.by_ref().count(), visits(&[1, 2]).len());
+		for _ in 0..3 {
+			assert_eq!(scan.next(), None);
+		}
+	}
+
+	#[cfg(feature = "defer-busy")]
+	#[test]
+	fn deferred_candidates_come_after_every_pass() {
+		let [first, second, third] = candidates(&[1, 2, 3])[..] else {
+			unreachable!()
+		};
+		let mut scan = scan_over(&[1, 2, 3]);
+		assert_eq!(scan.next(), Some(first));
+		scan.defer(first);
+		assert_eq!(scan.next(), Some(second));
+		scan.defer(second);
+		// A candidate already set aside is not set aside again.
+		scan.defer(first);
+		let mut expected = visits(&[1, 2, 3]).split_off(2);
+		expected.extend([first, second]);
+		assert_eq!(scan.by_ref().collect::<Vec<_>>(), expected);
+		assert_eq!(scan.next(), None);
+
+		// Each candidate is retried only once, but one deferred late is still retried.
+		let mut scan = scan_over(&[1, 2]);
+		let mut visited = Vec::new();
+		while let Some(address) = scan.next() {
+			visited.push(address);
+			scan.defer(address);
+		}
+		let mut expected = visits(&[1, 2]);
+		expected.extend(candidates(&[1, 2]));
+		assert_eq!(visited, expected);
+		scan.defer(third);
+		assert_eq!(scan.next(), Some(third));
+		assert_eq!(scan.next(), None);
+	}
+
+	#[cfg(feature = "defer-busy")]
+	#[test]
+	fn deferred_candidates_beyond_capacity_are_dropped() {
+		let ids: Vec<u8> = (1..=u8::try_from(MAX_DEFERRED).unwrap() + 2).collect();
+		let mut scan = scan_over(&ids);
+		for address in candidates(&ids) {
+			scan.defer(address);
+		}
+		let mut expected = visits(&ids);
+		expected.extend(candidates(&ids[..MAX_DEFERRED]));
+		assert_eq!(scan.collect::<Vec<_>>(), expected);
+	}
+
+	#[cfg(feature = "prefer-label")]
+	#[test]
+	fn move_to_front_keeps_order_within_groups() {
+		// Odd IDs are preferred.
+		let preferred = |address: &Address| address.as_bytes()[0] % 2 == 1;
+		for (ids, expected) in [
+			(&[][..], &[][..]),
+			(&[2, 4, 6], &[2, 4, 6]),
+			(&[1, 3, 5], &[1, 3, 5]),
+			(&[2, 1, 4, 3, 6, 5], &[1, 3, 5, 2, 4, 6]),
+			(&[1, 2, 3], &[1, 3, 2]),
+			(&[6, 4, 2, 5], &[5, 6, 4, 2]),
+		] {
+			let mut list = candidates(ids);
+			move_to_front(&mut list, preferred);
+			assert_eq!(list, candidates(expected), "{ids:?}");
+		}
+	}
+
+	#[cfg(feature = "sorted-scan")]
+	#[test]
+	fn sort_by_uuid_orders_by_raw_bytes() {
+		for (ids, expected) in [
+			(&[][..], &[][..]),
+			(&[7], &[7]),
+			(&[1, 2, 3], &[1, 2, 3]),
+			(&[3, 2, 1], &[1, 2, 3]),
+			(&[0xFF, 0x00, 0x80, 0x7F], &[0x00, 0x7F, 0x80, 0xFF]),
+			// Duplicate UUIDs are kept.
+			(&[2, 1, 2, 1], &[1, 1, 2, 2]),
+			(&[5, 5, 5], &[5, 5, 5]),
+		] {
+			let mut list = candidates(ids);
+			sort_by_uuid(&mut list);
+			assert_eq!(list, candidates(expected), "{ids:?}");
+		}
+
+		// Only the first differing byte matters, not the later ones.
+		let mut low = [0x10; 16];
+		low[15] = 0xFF;
+		let mut high = [0x10; 16];
+		high[0] = 0x11;
+		high[15] = 0x00;
+		let mut list = [Address::from_bytes(high), Address::from_bytes(low)];
+		sort_by_uuid(&mut list);
+		assert_eq!(list, [Address::from_bytes(low), Address::from_bytes(high)]);
+	}
+
+	#[cfg(feature = "removable-first")]
+	#[test]
+	fn order_by_priority_is_stable() {
+		// The priority is the tens digit.
+		for (ids, expected) in [
+			(&[][..], &[][..]),
+			(&[1, 2, 3], &[1, 2, 3]),
+			(&[1, 12, 5, 13, 20], &[20, 12, 13, 1, 5]),
+			(&[21, 22, 11, 23, 1], &[21, 22, 23, 11, 1]),
+			(&[1, 11, 1, 11], &[11, 11, 1, 1]),
+		] {
+			let mut list = candidates(ids);
+			let mut calls = 0;
+			order_by_priority(&mut list, |address| {
+				calls += 1;
+				address.as_bytes()[0] / 10
+			});
+			assert_eq!(list, candidates(expected), "{ids:?}");
+			// Each candidate's priority is computed once.
+			assert_eq!(calls, ids.len());
+		}
+	}
+}
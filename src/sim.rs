@@ -0,0 +1,1362 @@
+//! A simulated computer for the tests to run the BIOS on.
+//!
+//! The [`component`], [`component_sys`], [`computer`], [`descriptor`], and [`execute`] modules
+//! stand in for those of OC-Wasm-safe and OC-Wasm-sys, with the same signatures, so that the BIOS
+//! runs on top of them unchanged. A test adds components to the computer, then calls [`boot`] to
+//! run the BIOS one timeslice at a time until it executes an image, halts, or reboots. Method calls
+//! are answered by each component’s [`Device`] and recorded, so that the test can check them
+//! afterwards.
+//!
+//! The BIOS keeps its state in statics, which live as long as the process, so each test that runs
+//! the BIOS does so in a process of its own; see [`isolated`].
+
+// Each build configuration, and the tests written for it, use only some of the simulation.
+#![allow(dead_code)]
+
+use super::run_timeslice;
+use crate::cbor;
+use oc_wasm_safe::Address;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::{env, panic, process, slice, thread};
+
+/// A CBOR data item passed to or returned from a method call, or held in a signal.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+	/// Null.
+	Null,
+
+	/// A boolean.
+	Bool(bool),
+
+	/// An integer.
+	Int(i64),
+
+	/// A floating-point number.
+	Float(f64),
+
+	/// A byte array.
+	Bytes(Vec<u8>),
+
+	/// A string.
+	Str(String),
+
+	/// An array.
+	Array(Vec<Value>),
+
+	/// A map, as a list of key/value pairs.
+	Map(Vec<(Value, Value)>),
+
+	/// An opaque value descriptor, which OC-Wasm encodes as an Identifier tagged integer.
+	Descriptor(u32),
+}
+
+impl Value {
+	/// Returns a string value.
+	pub fn str(value: &str) -> Self {
+		Self::Str(value.into())
+	}
+
+	/// Returns a byte array value.
+	pub fn bytes(value: &[u8]) -> Self {
+		Self::Bytes(value.into())
+	}
+
+	/// Appends the CBOR encoding of the value to `out`.
+	pub fn encode(&self, out: &mut Vec<u8>) {
+		match self {
+			Self::Null => out.push(0xF6),
+			Self::Bool(value) => out.push(0xF4 | u8::from(*value)),
+			Self::Int(value) => {
+				if *value < 0 {
+					encode_head(1, value.unsigned_abs() - 1, out);
+				} else {
+					encode_head(0, value.unsigned_abs(), out);
+				}
+			}
+			Self::Float(value) => {
+				out.push(0xFB);
+				out.extend(value.to_bits().to_be_bytes());
+			}
+			Self::Bytes(value) => {
+				encode_head(2, value.len() as u64, out);
+				out.extend(value);
+			}
+			Self::Str(value) => {
+				encode_head(3, value.len() as u64, out);
+				out.extend(value.as_bytes());
+			}
+			Self::Array(items) => {
+				encode_head(4, items.len() as u64, out);
+				for item in items {
+					item.encode(out);
+				}
+			}
+			Self::Map(pairs) => {
+				encode_head(5, pairs.len() as u64, out);
+				for (key, value) in pairs {
+					key.encode(out);
+					value.encode(out);
+				}
+			}
+			Self::Descriptor(value) => {
+				encode_head(6, 39, out);
+				encode_head(0, (*value).into(), out);
+			}
+		}
+	}
+
+	/// Returns the CBOR encoding of the value.
+	pub fn to_cbor(&self) -> Vec<u8> {
+		let mut out = Vec::new();
+		self.encode(&mut out);
+		out
+	}
+
+	/// Decodes a data item from the start of `input`, returning it and the rest of the input.
+	///
+	/// `None` is returned if `input` does not start with a whole data item of a kind that OC-Wasm
+	/// uses.
+	pub fn decode(input: &[u8]) -> Option<(Self, &[u8])> {
+		if let Ok((value, rest)) = cbor::read_float(input) {
+			return Some((Self::Float(value), rest));
+		}
+		let (major, count, rest) = cbor::decode_header(input).ok()?;
+		match major {
+			cbor::MajorType::UnsignedInteger => Some((Self::Int(i64::try_from(count).ok()?), rest)),
+			cbor::MajorType::NegativeInteger => {
+				Some((Self::Int(-1 - i64::try_from(count).ok()?), rest))
+			}
+			cbor::MajorType::Bytes | cbor::MajorType::String => {
+				let len = usize::try_from(count).ok()?;
+				let payload = rest.get(..len)?;
+				let value = if major == cbor::MajorType::Bytes {
+					Self::Bytes(payload.into())
+				} else {
+					Self::Str(String::from_utf8(payload.into()).ok()?)
+				};
+				Some((value, &rest[len..]))
+			}
+			cbor::MajorType::Array => {
+				let mut rest = rest;
+				let mut items = Vec::new();
+				for _ in 0..count {
+					let (item, after) = Self::decode(rest)?;
+					items.push(item);
+					rest = after;
+				}
+				Some((Self::Array(items), rest))
+			}
+			cbor::MajorType::Map => {
+				let mut rest = rest;
+				let mut pairs = Vec::new();
+				for _ in 0..count {
+					let (key, after) = Self::decode(rest)?;
+					let (value, after) = Self::decode(after)?;
+					pairs.push((key, value));
+					rest = after;
+				}
+				Some((Self::Map(pairs), rest))
+			}
+			cbor::MajorType::Tag if count == 39 => {
+				let (cbor::MajorType::UnsignedInteger, value, rest) =
+					cbor::decode_header(rest).ok()?
+				else {
+					return None;
+				};
+				Some((Self::Descriptor(u32::try_from(value).ok()?), rest))
+			}
+			cbor::MajorType::Special => match count {
+				20 => Some((Self::Bool(false), rest)),
+				21 => Some((Self::Bool(true), rest)),
+				22 => Some((Self::Null, rest)),
+				_ => None,
+			},
+			_ => None,
+		}
+	}
+}
+
+/// Appends a CBOR data item header with major type `major` and count `count` to `out`.
+fn encode_head(major: u8, count: u64, out: &mut Vec<u8>) {
+	let major = major << 5;
+	if let Ok(small @ 0..=23) = u8::try_from(count) {
+		out.push(major | small);
+	} else if let Ok(count) = u8::try_from(count) {
+		out.extend([major | 0x18, count]);
+	} else if let Ok(count) = u16::try_from(count) {
+		out.push(major | 0x19);
+		out.extend(count.to_be_bytes());
+	} else if let Ok(count) = u32::try_from(count) {
+		out.push(major | 0x1A);
+		out.extend(count.to_be_bytes());
+	} else {
+		out.push(major | 0x1B);
+		out.extend(count.to_be_bytes());
+	}
+}
+
+/// Returns the CBOR encoding of an array of values, as a method call returns them.
+pub fn encode_values(values: &[Value]) -> Vec<u8> {
+	Value::Array(values.into()).to_cbor()
+}
+
+/// How a component answers a method call.
+pub enum Reply {
+	/// The call completes at once, returning these values.
+	Values(Vec<Value>),
+
+	/// The call completes at once, returning this CBOR encoding, which need not be well formed.
+	Raw(Vec<u8>),
+
+	/// The call throws an exception with this message.
+	Exception(String),
+
+	/// The call completes as the reply says, but only in the next timeslice, as an indirect call
+	/// does.
+	Later(Box<Reply>),
+
+	/// The call never completes.
+	Never,
+}
+
+impl Reply {
+	/// Returns a reply with which the call completes at once, returning `values`.
+	pub fn values<const N: usize>(values: [Value; N]) -> Self {
+		Self::Values(values.into())
+	}
+
+	/// Returns a reply with which the call throws an exception with `message`.
+	pub fn exception(message: &str) -> Self {
+		Self::Exception(message.into())
+	}
+}
+
+/// The behaviour of a component or of an opaque value, answering the method calls made on it.
+pub trait Device {
+	/// Answers a call of `method` with `params`.
+	fn call(&mut self, method: &str, params: &[Value]) -> Reply;
+}
+
+impl<F: FnMut(&str, &[Value]) -> Reply> Device for F {
+	fn call(&mut self, method: &str, params: &[Value]) -> Reply {
+		self(method, params)
+	}
+}
+
+/// A method call made by the BIOS.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Call {
+	/// What the method was called on.
+	pub target: Target,
+
+	/// The name of the method.
+	pub method: String,
+
+	/// The parameters.
+	pub params: Vec<Value>,
+}
+
+/// What a method is called on.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Target {
+	/// A component, identified by its UUID.
+	Component(Address),
+
+	/// An opaque value, identified by its descriptor.
+	Value(u32),
+}
+
+/// A beep played by the BIOS.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Beep {
+	/// A tone of a frequency, in hertz, and a duration, in milliseconds.
+	Tone(u16, u16),
+
+	/// A Morse code pattern.
+	Pattern(String),
+}
+
+/// How running the BIOS ended.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Outcome {
+	/// The BIOS executed the image in the execution buffer, which is given.
+	Executed(Vec<u8>),
+
+	/// The BIOS halted the computer with an error message.
+	Halted(String),
+
+	/// The BIOS rebooted the computer.
+	Rebooted,
+
+	/// [`run`](super::run) returned a nonzero code.
+	Returned(i32),
+}
+
+/// A component attached to the computer.
+struct Component {
+	/// The UUID.
+	address: Address,
+
+	/// The component type.
+	kind: String,
+
+	/// The behaviour, which is taken out while it is answering a call so that it can use the
+	/// simulation itself.
+	device: Option<Box<dyn Device>>,
+}
+
+/// A method call in progress.
+struct Pending {
+	/// The CBOR-encoded result, or the message of the exception thrown.
+	result: Result<Vec<u8>, String>,
+
+	/// The timeslice from which the call is complete, or `None` if it never is.
+	ready: Option<u64>,
+}
+
+/// The state of the simulated computer.
+struct Machine {
+	/// The attached components, in the order in which they are listed.
+	components: Vec<Component>,
+
+	/// The UUIDs of the components that the listing in progress has not returned yet.
+	listing: VecDeque<Address>,
+
+	/// The method call in progress, if any.
+	pending: Option<Pending>,
+
+	/// The message of the exception thrown by the last method call that threw one.
+	exception: Option<String>,
+
+	/// The method calls made so far.
+	calls: Vec<Call>,
+
+	/// The behaviours of the opaque values handed out, by descriptor.
+	values: HashMap<u32, Option<Box<dyn Device>>>,
+
+	/// The next descriptor to hand out.
+	next_descriptor: u32,
+
+	/// The descriptors that have been handed out and not closed yet.
+	open: BTreeSet<u32>,
+
+	/// The descriptors that have been closed, in order.
+	closed: Vec<u32>,
+
+	/// The signal queue, with each signal encoded as it is popped.
+	signals: VecDeque<Vec<u8>>,
+
+	/// The beeps played so far.
+	beeps: Vec<Beep>,
+
+	/// The execution buffer.
+	image: Vec<u8>,
+
+	/// The RAM installed, in bytes, which is also the capacity of the execution buffer.
+	installed_ram: u32,
+
+	/// The RAM free, in bytes.
+	free_ram: u32,
+
+	/// The number of timeslices started so far.
+	timeslice: u64,
+
+	/// The number of method calls still to be refused because the call queue is full.
+	queue_full: u32,
+
+	/// How running the BIOS ended, once it has.
+	outcome: Option<Outcome>,
+}
+
+impl Default for Machine {
+	fn default() -> Self {
+		Self {
+			components: Vec::new(),
+			listing: VecDeque::new(),
+			pending: None,
+			exception: None,
+			calls: Vec::new(),
+			values: HashMap::new(),
+			next_descriptor: 1,
+			open: BTreeSet::new(),
+			closed: Vec::new(),
+			signals: VecDeque::new(),
+			beeps: Vec::new(),
+			image: Vec::new(),
+			installed_ram: 4 * 1024 * 1024,
+			free_ram: 3 * 1024 * 1024,
+			timeslice: 0,
+			queue_full: 0,
+			outcome: None,
+		}
+	}
+}
+
+std::thread_local! {
+	/// The simulated computer.
+	static MACHINE: RefCell<Machine> = RefCell::new(Machine::default());
+}
+
+/// Runs `f` on the simulated computer.
+///
+/// `f` must not panic or call back into the simulation.
+fn with<R>(f: impl FnOnce(&mut Machine) -> R) -> R {
+	MACHINE.with_borrow_mut(f)
+}
+
+/// The number of seconds that a timeslice lasts.
+const TIMESLICE: f64 = 0.05;
+
+/// The number of timeslices for which [`boot`] runs the BIOS before giving up on it.
+const MAX_TIMESLICES: u32 = 5000;
+
+/// The environment variable that marks a process as running a single test in isolation.
+const ISOLATED_VAR: &str = "OC_WASM_BIOS_ISOLATED_TEST";
+
+/// Runs the calling test in a process of its own.
+///
+/// In the test’s own process, `true` is returned, and the test should go on to run the BIOS. In
+/// the test harness’s process, the test is run again in a new process, which is checked to have
+/// passed, and `false` is returned, in which case the test should do nothing more.
+///
+/// # Panics
+/// This function panics if the test fails in its own process.
+pub fn isolated() -> bool {
+	if env::var_os(ISOLATED_VAR).is_some() {
+		// Stopping the BIOS unwinds with a Stop payload, which is not a failure worth reporting.
+		let report = panic::take_hook();
+		panic::set_hook(Box::new(move |info| {
+			if !info.payload().is::<Stop>() {
+				report(info);
+			}
+		}));
+		return true;
+	}
+	// The test harness runs each test in a thread named after it.
+	let name = thread::current()
+		.name()
+		.expect("tests run in named threads")
+		.to_owned();
+	let output = process::Command::new(env::current_exe().unwrap())
+		.args([&name, "--exact", "--test-threads=1", "--nocapture"])
+		.env(ISOLATED_VAR, "1")
+		.output()
+		.unwrap();
+	let stdout = String::from_utf8_lossy(&output.stdout);
+	assert!(
+		output.status.success() && stdout.contains("1 passed"),
+		"{name} failed in its own process:\n{stdout}{}",
+		String::from_utf8_lossy(&output.stderr)
+	);
+	false
+}
+
+/// The payload of the panic with which the simulated computer stops the BIOS.
+struct Stop;
+
+/// Stops the BIOS, recording how it ended.
+fn stop(outcome: Outcome) -> ! {
+	with(|machine| machine.outcome = Some(outcome));
+	panic::panic_any(Stop)
+}
+
+/// Calls `f`, a part of the BIOS, returning how it stopped the BIOS, or `None` if it returned.
+///
+/// # Panics
+/// This function panics if `f` panics other than by stopping the BIOS.
+pub fn stopped(f: impl FnOnce() + panic::UnwindSafe) -> Option<Outcome> {
+	match panic::catch_unwind(f) {
+		Ok(()) => None,
+		Err(payload) if payload.is::<Stop>() => with(|machine| machine.outcome.take()),
+		Err(payload) => panic::resume_unwind(payload),
+	}
+}
+
+/// Runs the BIOS for one timeslice.
+///
+/// How running the BIOS ended is returned if it did, or `None` if it is still booting.
+///
+/// # Panics
+/// This function panics if the BIOS panics, or if the test is not running in a process of its own.
+pub fn step() -> Option<Outcome> {
+	assert!(
+		env::var_os(ISOLATED_VAR).is_some(),
+		"a test that runs the BIOS must call isolated first"
+	);
+	with(|machine| machine.timeslice += 1);
+	match panic::catch_unwind(run_timeslice) {
+		Ok(0) => None,
+		Ok(code) => Some(Outcome::Returned(code)),
+		Err(payload) if payload.is::<Stop>() => with(|machine| machine.outcome.take()),
+		Err(payload) => panic::resume_unwind(payload),
+	}
+}
+
+/// Runs the BIOS until it executes an image, halts, reboots, or returns a nonzero code.
+///
+/// # Panics
+/// This function panics if the BIOS panics or keeps running for [`MAX_TIMESLICES`] timeslices.
+pub fn boot() -> Outcome {
+	for _ in 0..MAX_TIMESLICES {
+		if let Some(outcome) = step() {
+			return outcome;
+		}
+	}
+	panic!("the BIOS is still running after {MAX_TIMESLICES} timeslices");
+}
+
+/// Runs the BIOS for `count` timeslices, checking that it is still running afterwards.
+///
+/// # Panics
+/// This function panics if the BIOS stops or panics.
+pub fn steps(count: u32) {
+	for _ in 0..count {
+		if let Some(outcome) = step() {
+			panic!("the BIOS stopped early: {outcome:?}");
+		}
+	}
+}
+
+/// Returns the address that the `index`th component added is given by default.
+pub fn address(index: u8) -> Address {
+	Address::from_bytes([index; 16])
+}
+
+/// Attaches a component of type `kind` with UUID `address`, which behaves as `device`.
+pub fn attach_at(address: Address, kind: &str, device: impl Device + 'static) {
+	with(|machine| {
+		machine.components.push(Component {
+			address,
+			kind: kind.into(),
+			device: Some(Box::new(device)),
+		});
+	});
+}
+
+/// Attaches a component of type `kind`, which behaves as `device`.
+///
+/// The component’s UUID is returned. The first component is given [`address(1)`](address), the
+/// second `address(2)`, and so on.
+pub fn attach(kind: &str, device: impl Device + 'static) -> Address {
+	let index = with(|machine| machine.components.len() + 1);
+	let address = address(u8::try_from(index).unwrap());
+	attach_at(address, kind, device);
+	address
+}
+
+/// Attaches a filesystem component, returning its UUID.
+pub fn attach_filesystem(filesystem: Filesystem) -> Address {
+	attach("filesystem", filesystem)
+}
+
+/// Attaches an EEPROM component, returning its UUID.
+pub fn attach_eeprom(eeprom: Eeprom) -> Address {
+	attach("eeprom", eeprom)
+}
+
+/// Attaches a component of type `kind` whose methods all return nothing, returning its UUID.
+pub fn attach_inert(kind: &str) -> Address {
+	attach(kind, |_: &str, _: &[Value]| Reply::Values(Vec::new()))
+}
+
+/// Detaches the component with UUID `address`.
+pub fn detach(address: &Address) {
+	with(|machine| {
+		machine
+			.components
+			.retain(|component| component.address != *address);
+	});
+}
+
+/// Hands out a new opaque value, which behaves as `device`, returning its descriptor.
+///
+/// This is meant to be called from a device answering a method call.
+pub fn new_value(device: impl Device + 'static) -> u32 {
+	let descriptor = new_descriptor();
+	with(|machine| machine.values.insert(descriptor, Some(Box::new(device))));
+	descriptor
+}
+
+/// Hands out a new descriptor, returning it.
+///
+/// This is meant to be called from a device answering a method call.
+pub fn new_descriptor() -> u32 {
+	with(|machine| {
+		let descriptor = machine.next_descriptor;
+		machine.next_descriptor += 1;
+		machine.open.insert(descriptor);
+		descriptor
+	})
+}
+
+/// Returns whether `descriptor` has been handed out and not closed.
+pub fn is_open(descriptor: u32) -> bool {
+	with(|machine| machine.open.contains(&descriptor))
+}
+
+/// Returns the descriptors that have been closed, in order.
+pub fn closed() -> Vec<u32> {
+	with(|machine| machine.closed.clone())
+}
+
+/// Returns the descriptors that have been handed out and not closed.
+pub fn open_descriptors() -> Vec<u32> {
+	with(|machine| machine.open.iter().copied().collect())
+}
+
+/// Returns the method calls made so far.
+pub fn calls() -> Vec<Call> {
+	with(|machine| machine.calls.clone())
+}
+
+/// Returns the method calls made so far on the component with UUID `address`.
+pub fn calls_to(address: &Address) -> Vec<Call> {
+	let target = Target::Component(*address);
+	with(|machine| {
+		machine
+			.calls
+			.iter()
+			.filter(|call| call.target == target)
+			.cloned()
+			.collect()
+	})
+}
+
+/// Returns the names of the methods called so far on the component with UUID `address`, in
+/// order.
+pub fn methods_called(address: &Address) -> Vec<String> {
+	calls_to(address)
+		.into_iter()
+		.map(|call| call.method)
+		.collect()
+}
+
+/// Returns the beeps played so far.
+pub fn beeps() -> Vec<Beep> {
+	with(|machine| machine.beeps.clone())
+}
+
+/// Makes the next `count` method calls fail because the call queue is full.
+pub fn fill_call_queue(count: u32) {
+	with(|machine| machine.queue_full = count);
+}
+
+/// Sets the RAM installed and free, in bytes.
+pub fn set_ram(installed: u32, free: u32) {
+	with(|machine| {
+		machine.installed_ram = installed;
+		machine.free_ram = free;
+	});
+}
+
+/// Adds a signal with name `name` and parameters `params` to the end of the signal queue.
+pub fn queue_signal(name: &str, params: &[Value]) {
+	let mut signal = Value::str(name).to_cbor();
+	Value::Array(params.into()).encode(&mut signal);
+	with(|machine| machine.signals.push_back(signal));
+}
+
+/// Adds a `key_down` signal for a key with character `character` and key code `code`, pressed on
+/// the keyboard with UUID `keyboard`, to the end of the signal queue.
+pub fn queue_key_down(keyboard: &Address, character: i64, code: i64) {
+	queue_signal(
+		"key_down",
+		&[
+			Value::str(&keyboard.to_string()),
+			Value::Int(character),
+			Value::Int(code),
+			Value::str("player"),
+		],
+	);
+}
+
+/// Returns the signal queue, with each signal encoded as it is popped.
+pub fn signals() -> Vec<Vec<u8>> {
+	with(|machine| machine.signals.iter().cloned().collect())
+}
+
+/// Returns the number of timeslices that the BIOS has been run for.
+pub fn timeslices() -> u64 {
+	with(|machine| machine.timeslice)
+}
+
+/// Makes a method call on `target` with CBOR-encoded parameters at `params`.
+///
+/// The return value is as for OC-Wasm’s `invoke_component_method`.
+///
+/// # Safety
+/// `params` must be null or point at a valid CBOR-encoded array.
+unsafe fn invoke(target: Target, method: &str, params: *const u8) -> i32 {
+	let params = decode_params(params);
+	let device = with(|machine| {
+		machine.calls.push(Call {
+			target,
+			method: method.into(),
+			params: params.clone(),
+		});
+		// Only one call can be in progress at a time, including one whose result has not been
+		// fetched yet.
+		if machine.pending.is_some() {
+			return Err(-8 /* QueueFull */);
+		}
+		if machine.queue_full != 0 {
+			machine.queue_full -= 1;
+			return Err(-8 /* QueueFull */);
+		}
+		let device = match target {
+			Target::Component(address) => machine
+				.components
+				.iter_mut()
+				.find(|component| component.address == address)
+				.map(|component| &mut component.device),
+			Target::Value(descriptor) => machine
+				.values
+				.get_mut(&descriptor)
+				.filter(|_| machine.open.contains(&descriptor)),
+		};
+		match device {
+			Some(device) => Ok(device.take().expect("device called while answering a call")),
+			None if matches!(target, Target::Component(_)) => Err(-5 /* NoSuchComponent */),
+			None => Err(-10 /* BadDescriptor */),
+		}
+	});
+	let mut device = match device {
+		Ok(device) => device,
+		Err(rc) => return rc,
+	};
+	let mut reply = device.call(method, &params);
+	with(|machine| {
+		let slot = match target {
+			Target::Component(address) => machine
+				.components
+				.iter_mut()
+				.find(|component| component.address == address)
+				.map(|component| &mut component.device),
+			Target::Value(descriptor) => machine.values.get_mut(&descriptor),
+		};
+		// The device may have detached itself.
+		if let Some(slot) = slot {
+			*slot = Some(device);
+		}
+		let mut ready = Some(machine.timeslice);
+		let result = loop {
+			match reply {
+				Reply::Values(values) => break Ok(encode_values(&values)),
+				Reply::Raw(bytes) => break Ok(bytes),
+				Reply::Exception(message) => break Err(message),
+				Reply::Later(later) => {
+					ready = ready.map(|ready| ready + 1);
+					reply = *later;
+				}
+				Reply::Never => {
+					ready = None;
+					break Ok(Vec::new());
+				}
+			}
+		};
+		let done = ready == Some(machine.timeslice);
+		machine.pending = Some(Pending { result, ready });
+		i32::from(done)
+	})
+}
+
+/// Decodes the parameters of a method call from a pointer to their CBOR encoding.
+///
+/// # Safety
+/// `params` must be null or point at a valid CBOR-encoded array.
+unsafe fn decode_params(params: *const u8) -> Vec<Value> {
+	if params.is_null() {
+		return Vec::new();
+	}
+	// The length of the encoding is not passed along with it, so decode longer and longer
+	// prefixes of it until one holds the whole array. Each prefix is part of the encoding until
+	// then.
+	for len in 1..=0x1_0000 {
+		// SAFETY: The caller promises that params points at a valid encoding, which is at least
+		// len bytes long since no shorter prefix of it decoded.
+		let bytes = unsafe { slice::from_raw_parts(params, len) };
+		if let Some((Value::Array(params), [])) = Value::decode(bytes) {
+			return params;
+		}
+	}
+	panic!("method call parameters are not a CBOR array");
+}
+
+/// Stands in for OC-Wasm-sys’s `component` module.
+pub mod component_sys {
+	use super::{invoke, with, Address, Target};
+	use std::{ptr, slice, str};
+
+	/// Starts calling a method on a component.
+	///
+	/// # Safety
+	/// As for OC-Wasm-sys’s `invoke_component_method`.
+	pub unsafe fn invoke_component_method(
+		address: *const u8,
+		method: *const u8,
+		method_length: usize,
+		params: *const u8,
+	) -> i32 {
+		let address = Address::from_bytes(slice::from_raw_parts(address, 16).try_into().unwrap());
+		let method = str::from_utf8(slice::from_raw_parts(method, method_length)).unwrap();
+		invoke(Target::Component(address), method, params)
+	}
+
+	/// Starts calling a method on an opaque value.
+	///
+	/// # Safety
+	/// As for OC-Wasm-sys’s `invoke_value_method`.
+	pub unsafe fn invoke_value_method(
+		descriptor: u32,
+		method: *const u8,
+		method_length: usize,
+		params: *const u8,
+	) -> i32 {
+		let method = str::from_utf8(slice::from_raw_parts(method, method_length)).unwrap();
+		invoke(Target::Value(descriptor), method, params)
+	}
+
+	/// Fetches the result of the method call in progress.
+	///
+	/// # Safety
+	/// As for OC-Wasm-sys’s `invoke_end`.
+	pub unsafe fn invoke_end(buffer: *mut u8, length: usize) -> isize {
+		with(|machine| {
+			let timeslice = machine.timeslice;
+			let Some(result) = machine
+				.pending
+				.as_ref()
+				.filter(|pending| pending.ready.is_some_and(|ready| ready <= timeslice))
+				.map(|pending| pending.result.clone())
+			else {
+				return -9; // QueueEmpty
+			};
+			match result {
+				Err(message) => {
+					machine.exception = Some(message);
+					machine.pending = None;
+					-12 // Other
+				}
+				Ok(result) if buffer.is_null() => result.len().try_into().unwrap(),
+				Ok(result) if result.len() > length => -4, // BufferTooShort
+				Ok(result) => {
+					ptr::copy_nonoverlapping(result.as_ptr(), buffer, result.len());
+					let len = result.len().try_into().unwrap();
+					machine.pending = None;
+					len
+				}
+			}
+		})
+	}
+
+	/// Cancels the method call in progress.
+	///
+	/// # Safety
+	/// As for OC-Wasm-sys’s `invoke_cancel`.
+	pub unsafe fn invoke_cancel() {
+		with(|machine| machine.pending = None);
+	}
+
+	/// Fetches the message of the exception thrown by the last method call that threw one.
+	///
+	/// # Safety
+	/// As for OC-Wasm-sys’s `last_exception_message`.
+	pub unsafe fn last_exception_message(buffer: *mut u8, buffer_length: usize) -> isize {
+		with(|machine| match &machine.exception {
+			None => -9,                                           // QueueEmpty
+			Some(message) if message.len() > buffer_length => -4, // BufferTooShort
+			Some(message) => {
+				ptr::copy_nonoverlapping(message.as_ptr(), buffer, message.len());
+				message.len().try_into().unwrap()
+			}
+		})
+	}
+}
+
+/// Stands in for OC-Wasm-safe’s `component` module.
+pub mod component {
+	use super::{with, Address};
+	use oc_wasm_safe::error;
+	use std::marker::PhantomData;
+
+	/// Lists the components attached to the computer.
+	pub struct Lister(());
+
+	impl Lister {
+		/// Returns a lister.
+		#[allow(clippy::unnecessary_wraps)] // The same signature as OC-Wasm-safe’s.
+		pub fn take() -> Option<Self> {
+			Some(Self(()))
+		}
+
+		/// Starts listing the components of type `component_type`, or all of them if it is
+		/// `None`.
+		///
+		/// As with OC-Wasm, only one listing is in progress at a time, so starting one abandons the
+		/// last.
+		#[allow(clippy::unused_self)]
+		pub fn start(&mut self, component_type: Option<&str>) -> Listing<'_> {
+			with(|machine| {
+				machine.listing = machine
+					.components
+					.iter()
+					.filter(|component| component_type.is_none_or(|kind| component.kind == kind))
+					.map(|component| component.address)
+					.collect();
+			});
+			Listing(PhantomData)
+		}
+	}
+
+	/// A listing in progress.
+	pub struct Listing<'lister>(PhantomData<&'lister mut Lister>);
+
+	impl Listing<'_> {
+		/// Returns the next component listed, if any.
+		#[allow(clippy::should_implement_trait, clippy::unused_self)]
+		pub fn next(&mut self) -> Option<ListEntry> {
+			with(|machine| machine.listing.pop_front()).map(|address| ListEntry { address })
+		}
+	}
+
+	/// A component listed.
+	pub struct ListEntry {
+		/// The UUID of the component.
+		address: Address,
+	}
+
+	impl ListEntry {
+		/// Returns the UUID of the component.
+		pub fn address(&self) -> &Address {
+			&self.address
+		}
+	}
+
+	/// Writes the type of the component with UUID `address` to `buffer`, returning it.
+	///
+	/// # Errors
+	/// * [`BufferTooShort`](error::Error::BufferTooShort) is returned if the type does not fit.
+	/// * [`NoSuchComponent`](error::Error::NoSuchComponent) is returned if there is no such
+	///   component.
+	pub fn component_type<'buf>(
+		address: &Address,
+		buffer: &'buf mut [u8],
+	) -> error::Result<&'buf mut str> {
+		let kind = with(|machine| {
+			machine
+				.components
+				.iter()
+				.find(|component| component.address == *address)
+				.map(|component| component.kind.clone())
+		})
+		.ok_or(error::Error::NoSuchComponent)?;
+		let buffer = buffer
+			.get_mut(..kind.len())
+			.ok_or(error::Error::BufferTooShort)?;
+		buffer.copy_from_slice(kind.as_bytes());
+		Ok(std::str::from_utf8_mut(buffer).unwrap())
+	}
+}
+
+/// Stands in for OC-Wasm-safe’s `computer` module.
+pub mod computer {
+	use super::{stop, with, Beep, Outcome, Value, TIMESLICE};
+	use oc_wasm_safe::error;
+	use std::num::NonZeroUsize;
+
+	/// A number that is not NaN, as OC-Wasm-safe returns the uptime.
+	pub struct NotNan(f64);
+
+	impl NotNan {
+		/// Returns the number.
+		pub fn into_inner(self) -> f64 {
+			self.0
+		}
+	}
+
+	/// Returns the number of seconds since the computer started, which advances by one timeslice
+	/// each time the BIOS is run.
+	pub fn uptime() -> NotNan {
+		// Cast is sound because the tests run for far fewer than 2⁵³ timeslices.
+		#[allow(clippy::cast_precision_loss)]
+		with(|machine| NotNan(machine.timeslice as f64 * TIMESLICE))
+	}
+
+	/// Returns the RAM installed, in bytes.
+	pub fn installed_ram() -> u32 {
+		with(|machine| machine.installed_ram)
+	}
+
+	/// Returns the RAM free, in bytes.
+	pub fn free_ram() -> u32 {
+		with(|machine| machine.free_ram)
+	}
+
+	/// The number of signals the signal queue holds at most.
+	const MAX_SIGNALS: usize = 256;
+
+	/// Pushes a signal, encoded as an array holding the name and then the parameters, to the end
+	/// of the signal queue.
+	///
+	/// # Errors
+	/// * [`CborDecode`](error::Error::CborDecode) is returned if the signal is malformed.
+	/// * [`QueueFull`](error::Error::QueueFull) is returned if the signal queue is full.
+	pub fn push_signal(signal: &[u8]) -> error::Result<()> {
+		let Some((Value::Array(mut items), [])) = Value::decode(signal) else {
+			return Err(error::Error::CborDecode);
+		};
+		if !matches!(items.first(), Some(Value::Str(_))) {
+			return Err(error::Error::CborDecode);
+		}
+		let params = items.split_off(1);
+		let mut popped = items[0].to_cbor();
+		Value::Array(params).encode(&mut popped);
+		with(|machine| {
+			if machine.signals.len() == MAX_SIGNALS {
+				return Err(error::Error::QueueFull);
+			}
+			machine.signals.push_back(popped);
+			Ok(())
+		})
+	}
+
+	/// Returns the length of the signal at the front of the signal queue, if any.
+	pub fn pull_signal_length() -> Option<NonZeroUsize> {
+		with(|machine| NonZeroUsize::new(machine.signals.front().map_or(0, Vec::len)))
+	}
+
+	/// Pops the signal at the front of the signal queue, if any, into `buffer`.
+	///
+	/// # Errors
+	/// * [`BufferTooShort`](error::Error::BufferTooShort) is returned, leaving the signal in the
+	///   queue, if it does not fit in `buffer`.
+	pub fn pull_signal(buffer: &mut [u8]) -> error::Result<Option<&mut [u8]>> {
+		with(|machine| {
+			let Some(signal) = machine.signals.front() else {
+				return Ok(None);
+			};
+			let buffer = buffer
+				.get_mut(..signal.len())
+				.ok_or(error::Error::BufferTooShort)?;
+			buffer.copy_from_slice(signal);
+			machine.signals.pop_front();
+			Ok(Some(buffer))
+		})
+	}
+
+	/// Plays a tone.
+	pub fn beep(frequency: u16, duration: u16) {
+		with(|machine| machine.beeps.push(Beep::Tone(frequency, duration)));
+	}
+
+	/// Plays a Morse code pattern.
+	pub fn beep_pattern(pattern: &str) {
+		with(|machine| machine.beeps.push(Beep::Pattern(pattern.into())));
+	}
+
+	/// Reboots the computer.
+	pub fn reboot() -> ! {
+		stop(Outcome::Rebooted)
+	}
+
+	/// Halts the computer with an error message.
+	pub fn error(message: &str) -> ! {
+		stop(Outcome::Halted(message.into()))
+	}
+}
+
+/// Stands in for OC-Wasm-safe’s `descriptor` module.
+pub mod descriptor {
+	use super::with;
+	use oc_wasm_safe::descriptor::AsRaw;
+	use std::marker::PhantomData;
+
+	/// Gives access to a descriptor without owning it.
+	pub trait AsDescriptor {
+		/// Borrows the descriptor.
+		fn as_descriptor(&self) -> Borrowed<'_>;
+	}
+
+	/// An owned descriptor, which is closed when dropped.
+	pub struct Owned(u32);
+
+	impl Owned {
+		/// Takes ownership of descriptor `raw`.
+		///
+		/// # Safety
+		/// As for OC-Wasm-safe’s `Owned::new`.
+		pub const unsafe fn new(raw: u32) -> Self {
+			Self(raw)
+		}
+	}
+
+	impl AsRaw for Owned {
+		fn as_raw(&self) -> u32 {
+			self.0
+		}
+	}
+
+	impl AsDescriptor for Owned {
+		fn as_descriptor(&self) -> Borrowed<'_> {
+			Borrowed(self.0, PhantomData)
+		}
+	}
+
+	impl Drop for Owned {
+		fn drop(&mut self) {
+			let raw = self.0;
+			let was_open = with(|machine| {
+				machine.closed.push(raw);
+				machine.open.remove(&raw)
+			});
+			assert!(was_open, "descriptor {raw} closed twice");
+		}
+	}
+
+	/// A borrowed descriptor.
+	#[derive(Clone, Copy)]
+	pub struct Borrowed<'a>(u32, PhantomData<&'a Owned>);
+
+	impl Borrowed<'_> {
+		/// Returns the raw descriptor.
+		pub fn as_raw(self) -> u32 {
+			self.0
+		}
+	}
+
+	impl AsRaw for Borrowed<'_> {
+		fn as_raw(&self) -> u32 {
+			self.0
+		}
+	}
+}
+
+/// Stands in for OC-Wasm-safe’s `execute` module.
+pub mod execute {
+	use super::{stop, with, Outcome};
+	use oc_wasm_safe::error;
+
+	/// Empties the execution buffer.
+	pub fn clear() {
+		with(|machine| machine.image.clear());
+	}
+
+	/// Appends `data` to the execution buffer.
+	///
+	/// # Errors
+	/// * [`Other`](error::Error::Other) is returned if the execution buffer would hold more than
+	///   the computer’s installed RAM.
+	pub fn add(data: &[u8]) -> error::Result<()> {
+		with(|machine| {
+			if machine.image.len() + data.len() > machine.installed_ram as usize {
+				return Err(error::Error::Other);
+			}
+			machine.image.extend_from_slice(data);
+			Ok(())
+		})
+	}
+
+	/// Executes the image in the execution buffer.
+	pub fn execute() -> ! {
+		let image = with(|machine| machine.image.clone());
+		stop(Outcome::Executed(image))
+	}
+}
+
+/// A filesystem component.
+pub struct Filesystem {
+	/// The files, by path.
+	files: BTreeMap<String, Vec<u8>>,
+
+	/// The label, if any.
+	label: Option<String>,
+
+	/// What `spaceTotal` returns.
+	space_total: Value,
+
+	/// The number of bytes that a read returns at most.
+	max_read: usize,
+
+	/// The path and position of each file open, by descriptor.
+	handles: HashMap<u32, (String, usize)>,
+
+	/// Answers method calls before the filesystem itself does, if it returns a reply.
+	hook: Option<Hook>,
+}
+
+/// A hook that answers some of the method calls made on a [`Filesystem`].
+type Hook = Box<dyn FnMut(&str, &[Value]) -> Option<Reply>>;
+
+impl Filesystem {
+	/// Returns an empty, unlabelled filesystem with a megabyte of space.
+	pub fn new() -> Self {
+		Self {
+			files: BTreeMap::new(),
+			label: None,
+			space_total: Value::Int(1024 * 1024),
+			max_read: usize::MAX,
+			handles: HashMap::new(),
+			hook: None,
+		}
+	}
+
+	/// Adds a file at `path` holding `contents`.
+	#[must_use]
+	pub fn file(mut self, path: &str, contents: &[u8]) -> Self {
+		self.files.insert(path.into(), contents.into());
+		self
+	}
+
+	/// Sets the label.
+	#[must_use]
+	pub fn label(mut self, label: &str) -> Self {
+		self.label = Some(label.into());
+		self
+	}
+
+	/// Sets what `spaceTotal` returns.
+	#[must_use]
+	pub fn space_total(mut self, space_total: Value) -> Self {
+		self.space_total = space_total;
+		self
+	}
+
+	/// Sets the number of bytes that a read returns at most.
+	#[must_use]
+	pub fn max_read(mut self, max_read: usize) -> Self {
+		self.max_read = max_read;
+		self
+	}
+
+	/// Sets a hook that answers method calls before the filesystem itself does, if it returns a
+	/// reply.
+	#[must_use]
+	pub fn hook(mut self, hook: impl FnMut(&str, &[Value]) -> Option<Reply> + 'static) -> Self {
+		self.hook = Some(Box::new(hook));
+		self
+	}
+}
+
+impl Device for Filesystem {
+	fn call(&mut self, method: &str, params: &[Value]) -> Reply {
+		if let Some(reply) = self.hook.as_mut().and_then(|hook| hook(method, params)) {
+			return reply;
+		}
+		let path = match params.first() {
+			Some(Value::Str(path)) => Some(path.as_str()),
+			_ => None,
+		};
+		let handle = match params.first() {
+			Some(Value::Descriptor(descriptor)) if is_open(*descriptor) => {
+				self.handles.get_mut(descriptor)
+			}
+			_ => None,
+		};
+		match (method, path, handle) {
+			("open", Some(path), _) => {
+				if self.files.contains_key(path) {
+					let descriptor = new_descriptor();
+					self.handles.insert(descriptor, (path.into(), 0));
+					Reply::values([Value::Descriptor(descriptor)])
+				} else {
+					Reply::values([Value::Null, Value::str(path)])
+				}
+			}
+			("read", _, Some((path, position))) => {
+				let Some(Value::Int(count)) = params.get(1) else {
+					return Reply::exception("bad arguments");
+				};
+				let count = usize::try_from(*count).unwrap().min(self.max_read);
+				let contents = &self.files[path.as_str()];
+				let start = (*position).min(contents.len());
+				let end = contents.len().min(start + count);
+				*position = end;
+				if start == end {
+					Reply::values([Value::Null])
+				} else {
+					Reply::values([Value::bytes(&contents[start..end])])
+				}
+			}
+			("seek", _, Some((path, position))) => {
+				let (Some(Value::Str(whence)), Some(Value::Int(offset))) =
+					(params.get(1), params.get(2))
+				else {
+					return Reply::exception("bad arguments");
+				};
+				let base = match whence.as_str() {
+					"set" => 0,
+					"cur" => *position,
+					_ => self.files[path.as_str()].len(),
+				};
+				*position = base.saturating_add_signed(isize::try_from(*offset).unwrap());
+				Reply::values([Value::Int((*position).try_into().unwrap())])
+			}
+			("read" | "seek", _, None) => Reply::exception("bad file descriptor"),
+			("exists", Some(path), _) => {
+				Reply::values([Value::Bool(self.files.contains_key(path))])
+			}
+			("size", Some(path), _) => Reply::values([Value::Int(
+				self.files
+					.get(path)
+					.map_or(0, |contents| contents.len().try_into().unwrap()),
+			)]),
+			("getLabel", _, _) => {
+				Reply::values([self.label.as_deref().map_or(Value::Null, Value::str)])
+			}
+			("spaceTotal", _, _) => Reply::values([self.space_total.clone()]),
+			_ => Reply::exception("no such method"),
+		}
+	}
+}
+
+/// An EEPROM component.
+pub struct Eeprom {
+	/// What `getData` returns.
+	data: Value,
+
+	/// What `getVolatileData` returns.
+	volatile_data: Value,
+}
+
+impl Eeprom {
+	/// Returns an EEPROM whose data area holds `data` and whose volatile data area is empty.
+	pub fn new(data: &[u8]) -> Self {
+		Self {
+			data: Value::bytes(data),
+			volatile_data: Value::bytes(&[]),
+		}
+	}
+
+	/// Sets what `getData` returns.
+	#[must_use]
+	pub fn data(mut self, data: Value) -> Self {
+		self.data = data;
+		self
+	}
+
+	/// Sets the volatile data area’s contents.
+	#[must_use]
+	pub fn volatile_data(mut self, data: &[u8]) -> Self {
+		self.volatile_data = Value::bytes(data);
+		self
+	}
+}
+
+impl Device for Eeprom {
+	fn call(&mut self, method: &str, params: &[Value]) -> Reply {
+		match (method, params) {
+			("getData", _) => Reply::values([self.data.clone()]),
+			("getVolatileData", _) => Reply::values([self.volatile_data.clone()]),
+			("setData", [data]) => {
+				self.data = data.clone();
+				Reply::Values(Vec::new())
+			}
+			("setVolatileData", [data]) => {
+				self.volatile_data = data.clone();
+				Reply::Values(Vec::new())
+			}
+			_ => Reply::exception("no such method"),
+		}
+	}
+}
@@ -0,0 +1,77 @@
+//! Logging of state machine transitions to a debug component, to help diagnose boot problems.
+//!
+//! Logging is best-effort: if there is no debug component, or if a call to log a message cannot
+//! complete immediately, the message is simply dropped.
+
+use super::{cbor, component, component_sys, internal_error, start_best_effort};
+use oc_wasm_safe::Address;
+use sync_unsafe_cell::SyncUnsafeCell;
+
+/// The type of the component to log to.
+///
+/// This is the debug card of the Ocelot emulator, whose `log` method writes a message to the
+/// emulator’s log.
+const COMPONENT_TYPE: &str = "ocelot";
+
+/// The longest message that is logged; longer messages are truncated.
+pub const MAX_MESSAGE_LEN: usize = 64;
+
+/// The component to log to, once it has been found.
+static DEBUG: SyncUnsafeCell<Option<Address>> = SyncUnsafeCell::new(None);
+
+/// Finds the component to log to, if there is one.
+pub fn find(lister: &mut component::Lister) {
+	let debug = lister
+		.start(Some(COMPONENT_TYPE))
+		.next()
+		.map(|entry| *entry.address());
+	// SAFETY: Wasm is single-threaded, and DEBUG is only accessed in this module, without holding
+	// any references beyond a single statement.
+	unsafe { *DEBUG.get() = debug };
+}
+
+/// Logs a transition between two states, unless both have the same name.
+///
+/// The `from` and `to` parameters are the names of the old and new states.
+#[cfg(feature = "trace")]
+pub fn transition(from: &str, to: &str) {
+	if from != to {
+		let mut buffer = [0_u8; MAX_MESSAGE_LEN];
+		let mut len = 0;
+		for part in [from, " -> ", to] {
+			let end = (len + part.len()).min(MAX_MESSAGE_LEN);
+			buffer[len..end].copy_from_slice(&part.as_bytes()[..end - len]);
+			len = end;
+		}
+		// SAFETY: The state names are ASCII, so truncating them anywhere leaves valid UTF-8.
+		trace(unsafe { core::str::from_utf8_unchecked(&buffer[..len]) });
+	}
+}
+
+/// Logs a message.
+///
+/// The `message` parameter must be at most [`MAX_MESSAGE_LEN`] bytes long.
+pub fn trace(message: &str) {
+	// SAFETY: Wasm is single-threaded, and DEBUG is only accessed in this module, without holding
+	// any references beyond a single statement.
+	let Some(debug) = (unsafe { *DEBUG.get() }) else {
+		return;
+	};
+	// Encode the parameters: an array holding the message. The buffer has room for the array
+	// header, a string header with a 1-byte count, and the message, so this only fails if the BIOS
+	// is buggy.
+	let mut buffer = [0_u8; 3 + MAX_MESSAGE_LEN];
+	let mut writer = cbor::Writer::new(&mut buffer);
+	if writer.write_array_header(1).is_err() || writer.write_string(message).is_err() {
+		internal_error();
+	}
+	// Transitions are logged after the step that made them, which may have left a call in
+	// progress. The call queue is then full, and the message is lost.
+	if start_best_effort(&debug, "log", Some(&buffer)).is_none() {
+		return;
+	}
+	// Discard the result or, if the call did not complete immediately, abandon it, losing the
+	// message.
+	// SAFETY: invoke_cancel is unconditionally safe.
+	unsafe { component_sys::invoke_cancel() };
+}
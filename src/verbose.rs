@@ -0,0 +1,186 @@
+//! Reporting of boot progress for power users, enabled by a flag in the boot configuration.
+//!
+//! Each message is drawn on the screen, if a GPU has been bound to one, or else logged to the
+//! Ocelot emulator’s debug card, if there is one. Like the rest of the display and logging,
+//! reporting is best-effort: a message that cannot be shown is simply dropped.
+
+use super::{display, trace};
+use oc_wasm_safe::Address;
+use sync_unsafe_cell::SyncUnsafeCell;
+
+/// The first row of the screen used for messages.
+///
+/// The rows above it show the image file being read and the amount loaded so far.
+const FIRST_ROW: u32 = 3;
+
+/// The number of rows of the screen used for messages.
+///
+/// This fills the smallest screen. Once every row has been used, messages start again from the
+/// first, overwriting the oldest.
+const ROWS: u32 = 14;
+
+/// The width to which messages drawn on the screen are padded, so that a shorter message entirely
+/// covers a longer one drawn earlier on the same row.
+const WIDTH: usize = trace::MAX_MESSAGE_LEN;
+
+/// Whether reporting is enabled.
+static ENABLED: SyncUnsafeCell<bool> = SyncUnsafeCell::new(false);
+
+/// The position, counting from 0, of the row on which the next message is drawn.
+static ROW: SyncUnsafeCell<u32> = SyncUnsafeCell::new(0);
+
+/// Enables reporting.
+pub fn enable() {
+	// SAFETY: Wasm is single-threaded, and ENABLED is only accessed in this module, without
+	// holding any references beyond a single statement.
+	unsafe { *ENABLED.get() = true };
+}
+
+/// Reports a transition between two states, unless both have the same name.
+///
+/// The `from` and `to` parameters are the names of the old and new states.
+pub fn transition(from: &str, to: &str) {
+	// With the trace feature, transitions are already logged to the debug card.
+	if from != to && (display::bound() || !cfg!(feature = "trace")) {
+		report(&[from, " -> ", to]);
+	}
+}
+
+/// Reports that a filesystem found by a scan is about to be examined.
+pub fn scanning(address: &Address) {
+	let mut buffer = [0_u8; 36];
+	report(&["Scan ", display::format_address(address, &mut buffer)]);
+}
+
+/// Reports that a filesystem could not open the image file.
+///
+/// The `reason` parameter is the reason the filesystem gave, which is usually the path it could
+/// not find. A reason that is not ASCII is left out.
+pub fn open_failed(reason: &[u8]) {
+	report(&["Open failed: ", ascii(reason)]);
+}
+
+/// Reports that a filesystem could not read the image file.
+///
+/// The `reason` parameter is the reason the filesystem gave. A reason that is not ASCII is left
+/// out.
+pub fn read_failed(reason: &[u8]) {
+	report(&["Read failed: ", ascii(reason)]);
+}
+
+/// Reports the checksum of the image data loaded so far.
+#[cfg(feature = "crc-report")]
+pub fn checksum(crc: u32) {
+	report(&["CRC ", hex(crc, &mut [0; 8])]);
+}
+
+/// Reports the length and checksum of the whole image, once it has been loaded.
+#[cfg(feature = "crc-report")]
+pub fn summary(len: usize, crc: u32) {
+	report(&[
+		"Loaded ",
+		decimal(len, &mut [0; 10]),
+		" bytes, CRC ",
+		hex(crc, &mut [0; 8]),
+	]);
+}
+
+/// Formats `value` as eight lowercase hexadecimal digits in `buffer`.
+#[cfg(feature = "crc-report")]
+fn hex(value: u32, buffer: &mut [u8; 8]) -> &str {
+	const HEX_DIGITS: &[u8; 16] = b"0123456789abcdef";
+	for (digits, byte) in buffer.chunks_exact_mut(2).zip(value.to_be_bytes()) {
+		digits[0] = HEX_DIGITS[usize::from(byte >> 4)];
+		digits[1] = HEX_DIGITS[usize::from(byte & 0x0F)];
+	}
+	// SAFETY: Every byte written is an ASCII character, so the text is valid UTF-8.
+	unsafe { core::str::from_utf8_unchecked(buffer) }
+}
+
+/// Formats `value` in decimal at the end of `buffer`, returning the digits.
+#[cfg(feature = "crc-report")]
+fn decimal(value: usize, buffer: &mut [u8; 10]) -> &str {
+	let mut start = buffer.len();
+	let mut value = value;
+	loop {
+		start -= 1;
+		// Cast is sound because the digit is less than ten.
+		#[allow(clippy::cast_possible_truncation)]
+		{
+			buffer[start] = b'0' + (value % 10) as u8;
+		}
+		value /= 10;
+		if value == 0 {
+			break;
+		}
+	}
+	// SAFETY: Every byte written is an ASCII character, so the text is valid UTF-8.
+	unsafe { core::str::from_utf8_unchecked(&buffer[start..]) }
+}
+
+/// Returns `text` as a string if it is ASCII, or else an empty string.
+fn ascii(text: &[u8]) -> &str {
+	core::str::from_utf8(text)
+		.ok()
+		.filter(|text| text.is_ascii())
+		.unwrap_or("")
+}
+
+/// Reports a message made up of several parts, if reporting is enabled.
+///
+/// The parts must be ASCII. Messages longer than [`WIDTH`] bytes are truncated.
+fn report(parts: &[&str]) {
+	// SAFETY: Wasm is single-threaded, and ENABLED is only accessed in this module, without
+	// holding any references beyond a single statement.
+	if !unsafe { *ENABLED.get() } {
+		return;
+	}
+	let mut buffer = [b' '; WIDTH];
+	let mut len = 0;
+	for part in parts {
+		let end = (len + part.len()).min(WIDTH);
+		buffer[len..end].copy_from_slice(&part.as_bytes()[..end - len]);
+		len = end;
+	}
+	if display::bound() {
+		// SAFETY: Wasm is single-threaded, and ROW is only accessed in this module, without
+		// holding any references beyond a single statement.
+		let row = unsafe {
+			let row = &mut *ROW.get();
+			let current = *row;
+			*row = (current + 1) % ROWS;
+			current
+		};
+		// SAFETY: The parts are ASCII and the padding is spaces, so the text is valid UTF-8.
+		display::gpu_set(1, FIRST_ROW + row, unsafe {
+			core::str::from_utf8_unchecked(&buffer)
+		});
+	} else {
+		// SAFETY: The parts are ASCII, so truncating them anywhere leaves valid UTF-8.
+		trace::trace(unsafe { core::str::from_utf8_unchecked(&buffer[..len]) });
+	}
+}
+
+#[cfg(all(test, feature = "crc-report"))]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn hex_writes_eight_digits() {
+		assert_eq!(hex(0, &mut [0; 8]), "00000000");
+		assert_eq!(hex(0xCBF4_3926, &mut [0; 8]), "cbf43926");
+		assert_eq!(hex(0x0123_4567, &mut [0; 8]), "01234567");
+		assert_eq!(hex(u32::MAX, &mut [0; 8]), "ffffffff");
+	}
+
+	#[test]
+	fn decimal_writes_digits_without_leading_zeros() {
+		assert_eq!(decimal(0, &mut [0; 10]), "0");
+		assert_eq!(decimal(9, &mut [0; 10]), "9");
+		assert_eq!(decimal(16384, &mut [0; 10]), "16384");
+		assert_eq!(
+			decimal(u32::MAX.try_into().unwrap(), &mut [0; 10]),
+			"4294967295"
+		);
+	}
+}